@@ -0,0 +1,109 @@
+use crate::error::ApiError;
+use axum::extract::{Request, State};
+use axum::http::header::AUTHORIZATION;
+use axum::middleware::Next;
+use axum::response::Response;
+use common::config::ManagementOperator;
+use std::sync::Arc;
+
+/// The authenticated operator of a management request, recorded as the actor of audit trails.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Operator(pub String);
+
+/// Bearer tokens of the operators allowed on the management api.
+#[derive(Clone)]
+pub struct ManagementAuth {
+    operators: Arc<Vec<ManagementOperator>>,
+}
+
+impl ManagementAuth {
+    pub fn new(operators: Vec<ManagementOperator>) -> Self {
+        Self {
+            operators: Arc::new(operators),
+        }
+    }
+
+    /// Every token is compared in full, the time taken does not tell how much of one matched.
+    pub fn operator(&self, token: &str) -> Option<Operator> {
+        self.operators
+            .iter()
+            .fold(None, |found, o| match constant_time_eq(&o.token, token) {
+                true => Some(Operator(o.actor.clone())),
+                false => found,
+            })
+    }
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+/// Rejects requests without the bearer token of an operator, handlers extract the
+/// [`Operator`] from the request extensions.
+pub async fn require_operator(
+    State(auth): State<ManagementAuth>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let operator = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .and_then(|token| auth.operator(token.trim()))
+        .ok_or(ApiError::Unauthorized)?;
+    request.extensions_mut().insert(operator);
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::{Extension, Router};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        let auth = ManagementAuth::new(vec![ManagementOperator {
+            actor: "support".to_string(),
+            token: "s3cret".to_string(),
+        }]);
+        Router::new()
+            .route(
+                "/whoami",
+                get(async |Extension(operator): Extension<Operator>| operator.0),
+            )
+            .layer(axum::middleware::from_fn_with_state(auth, require_operator))
+    }
+
+    async fn status(authorization: Option<&str>) -> StatusCode {
+        let mut request = Request::builder().uri("/whoami");
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
+        app()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_require_operator() {
+        assert_eq!(status(Some("Bearer s3cret")).await, StatusCode::OK);
+        assert_eq!(status(None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(Some("Bearer s3cre")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status(Some("s3cret")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            ManagementAuth::new(vec![]).operator("s3cret"),
+            None,
+            "without operators nobody is let in"
+        );
+    }
+}
@@ -1,4 +1,7 @@
-use serde::Serialize;
+use crate::error::ApiError;
+use crate::images::ImageUrls;
+use common::persistence::repo::{LotImageDetails, LotVehicleDetails};
+use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 #[derive(Serialize, ToSchema)]
@@ -7,11 +10,72 @@ pub struct LotVehicleWithImages {
     #[serde(flatten)]
     pub lot_vehicle: LotVehicle,
     pub lot_images: Vec<LotImage>,
+    /// Latest sale outcome from the live auction, absent until the lot was auctioned
+    pub auction_result: Option<AuctionResult>,
+}
+
+impl LotVehicleWithImages {
+    pub async fn with_urls(value: LotVehicleDetails, urls: &ImageUrls) -> Result<Self, ApiError> {
+        let mut lot_images = Vec::with_capacity(value.lot_images.len());
+        for lot_image in value.lot_images {
+            lot_images.push(LotImage::with_urls(lot_image, urls).await?);
+        }
+        Ok(Self {
+            lot_vehicle: value.lot_vehicle.into(),
+            lot_images,
+            auction_result: value.auction_result.map(|x| x.into()),
+        })
+    }
+
+    pub async fn all_with_urls(
+        values: Vec<LotVehicleDetails>,
+        urls: &ImageUrls,
+    ) -> Result<Vec<Self>, ApiError> {
+        let mut vehicles = Vec::with_capacity(values.len());
+        for value in values {
+            vehicles.push(Self::with_urls(value, urls).await?);
+        }
+        Ok(vehicles)
+    }
+}
+
+/// Every lot of a vin across auction houses.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct VinHistory {
+    pub vin: String,
+    /// Ordered by sale date, lots without one last
+    pub lots: Vec<LotVehicleWithImages>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LotVehicleSearchHit {
+    #[serde(flatten)]
+    pub lot_vehicle: LotVehicle,
+    pub rank: f32,
+    /// Make, model, trim, damage, color and yard with matched words wrapped in `<mark>` tags
+    pub highlight: String,
+}
+
+/// A lot sharing near-identical photos with the requested one, e.g. the same car relisted
+/// under a new lot number or at another yard.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RelatedLotVehicle {
+    #[serde(flatten)]
+    pub lot_vehicle: LotVehicle,
+    /// Photos of the requested lot with a near-identical photo in this lot
+    pub shared_images: i32,
+    /// Smallest number of differing perceptual hash bits between the photos of both lots
+    pub distance: i32,
 }
 
 #[derive(Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct LotVehicle {
+    /// Auction house of the lot, lot numbers are unique per source only
+    pub source: String,
     pub lot_number: i32,
     pub make: String,
     pub model: String,
@@ -36,11 +100,29 @@ pub struct LotVehicle {
     pub fuel_type: Option<String>,
     pub drive_type: Option<String>,
     pub keys_status: Option<String>,
+    pub trim: Option<String>,
+    pub yard: Option<String>,
+    /// Every listed photo of the lot has its variants, failed ones are retried
+    pub images_complete: bool,
+    pub vin_decode: Option<VinDecode>,
+}
+
+/// Offline decode of the vin, absent when the lot has no vin
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct VinDecode {
+    pub valid: bool,
+    pub check_digit_valid: Option<bool>,
+    pub manufacturer: Option<String>,
+    pub country: Option<String>,
+    pub model_year: Option<i32>,
+    pub plant_code: Option<String>,
 }
 
 impl From<common::persistence::models::copart::LotVehicle> for LotVehicle {
     fn from(value: common::persistence::models::copart::LotVehicle) -> Self {
         Self {
+            source: value.source,
             lot_number: value.lot_number,
             make: value.make,
             model: value.model,
@@ -64,6 +146,17 @@ impl From<common::persistence::models::copart::LotVehicle> for LotVehicle {
             fuel_type: value.fuel_type,
             drive_type: value.drive_type,
             keys_status: value.keys_status,
+            trim: value.trim,
+            yard: value.yard,
+            images_complete: value.images_complete_at.is_some(),
+            vin_decode: value.vin_valid.map(|valid| VinDecode {
+                valid,
+                check_digit_valid: value.vin_check_digit_valid,
+                manufacturer: value.vin_manufacturer,
+                country: value.vin_country,
+                model_year: value.vin_model_year,
+                plant_code: value.vin_plant_code,
+            }),
         }
     }
 }
@@ -71,28 +164,231 @@ impl From<common::persistence::models::copart::LotVehicle> for LotVehicle {
 #[derive(Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct LotImage {
-    pub standard_bucket_key: Option<String>,
-    pub standard_mime_type: Option<String>,
+    pub sequence_number: i32,
+    /// Smallest first
+    pub variants: Vec<LotImageVariant>,
+}
 
-    pub thumbnail_bucket_key: Option<String>,
-    pub thumbnail_mime_type: Option<String>,
+impl LotImage {
+    async fn with_urls(value: LotImageDetails, urls: &ImageUrls) -> Result<Self, ApiError> {
+        let mut variants = Vec::with_capacity(value.variants.len());
+        for variant in value.variants {
+            variants.push(LotImageVariant {
+                url: urls.url(&variant.bucket_key).await?,
+                name: variant.name,
+                format: variant.format,
+                mime_type: variant.mime_type,
+                width: variant.width,
+                height: variant.height,
+                size: variant.size,
+            });
+        }
+        Ok(Self {
+            sequence_number: value.lot_image.sequence_number,
+            variants,
+        })
+    }
+}
 
-    pub high_res_bucket_key: Option<String>,
-    pub high_res_mime_type: Option<String>,
+/// A resized copy of a lot image, images synced before resizing have variants
+/// named `standard`, `thumbnail` and `high_res` without dimensions.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LotImageVariant {
+    #[schema(example = "w800")]
+    pub name: String,
+    #[schema(example = "webp")]
+    pub format: String,
+    pub mime_type: String,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    /// Bytes
+    pub size: Option<i64>,
+    /// Ready to fetch, either presigned and expiring or public depending on the deployment
+    pub url: String,
+}
 
-    pub sequence_number: i32,
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AuctionResult {
+    pub final_bid: f64,
+    pub buyer_country: Option<String>,
+    pub buyer_state: Option<String>,
+    pub min_met: bool,
+    #[schema(value_type = String, example = "2025-10-13T15:30:00")]
+    pub sold_at: chrono::NaiveDateTime,
+}
+
+impl From<common::persistence::models::copart::AuctionResult> for AuctionResult {
+    fn from(value: common::persistence::models::copart::AuctionResult) -> Self {
+        Self {
+            final_bid: value.final_bid,
+            buyer_country: value.buyer_country,
+            buyer_state: value.buyer_state,
+            min_met: value.min_met,
+            sold_at: value.sold_at,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LotVehicleChange {
+    pub field: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    #[schema(value_type = String, example = "2025-10-13T15:30:00")]
+    pub changed_at: chrono::NaiveDateTime,
+}
+
+impl From<common::persistence::models::copart::LotVehicleHistory> for LotVehicleChange {
+    fn from(value: common::persistence::models::copart::LotVehicleHistory) -> Self {
+        Self {
+            field: value.field,
+            old_value: value.old_value,
+            new_value: value.new_value,
+            changed_at: value.changed_at,
+        }
+    }
+}
+
+/// Bids of a lot as seen by a search, taken only when they changed since the previous one
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LotPriceSnapshot {
+    pub current_bid: f64,
+    pub buy_today_bid: f64,
+    pub high_bid: f64,
+    pub sale_status: String,
+    #[schema(value_type = String, example = "2025-10-13T15:30:00")]
+    pub seen_at: chrono::NaiveDateTime,
+}
+
+impl From<common::persistence::models::copart::LotPriceSnapshot> for LotPriceSnapshot {
+    fn from(value: common::persistence::models::copart::LotPriceSnapshot) -> Self {
+        Self {
+            current_bid: value.current_bid,
+            buy_today_bid: value.buy_today_bid,
+            high_bid: value.high_bid,
+            sale_status: value.sale_status,
+            seen_at: value.seen_at,
+        }
+    }
+}
+
+/// Lifecycle of a vin takedown, only an active takedown hides the vin from public responses
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum TakedownState {
+    Requested,
+    Verified,
+    Active,
+    Revoked,
+}
+
+impl From<TakedownState> for common::persistence::models::takedown::TakedownState {
+    fn from(value: TakedownState) -> Self {
+        match value {
+            TakedownState::Requested => Self::Requested,
+            TakedownState::Verified => Self::Verified,
+            TakedownState::Active => Self::Active,
+            TakedownState::Revoked => Self::Revoked,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NewVinTakedown {
+    pub vin: String,
+    pub contact_email: String,
+    pub contact_name: Option<String>,
+    pub reason: String,
+}
+
+/// Acknowledges a submission without revealing details of an already open takedown
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct VinTakedownReceipt {
+    pub id: i32,
+    pub state: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct VinTakedownTransition {
+    pub state: TakedownState,
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct VinTakedown {
+    pub id: i32,
+    pub vin: String,
+    pub state: String,
+    pub contact_email: String,
+    pub contact_name: Option<String>,
+    pub reason: String,
+    #[schema(value_type = String, example = "2025-10-13T15:30:00")]
+    pub requested_at: chrono::NaiveDateTime,
+    #[schema(value_type = Option<String>, example = "2025-10-13T15:30:00")]
+    pub verified_at: Option<chrono::NaiveDateTime>,
+    #[schema(value_type = Option<String>, example = "2025-10-13T15:30:00")]
+    pub activated_at: Option<chrono::NaiveDateTime>,
+    #[schema(value_type = Option<String>, example = "2025-10-13T15:30:00")]
+    pub revoked_at: Option<chrono::NaiveDateTime>,
+    /// Set once images of the vin's lots were erased from the database and the bucket
+    #[schema(value_type = Option<String>, example = "2025-10-13T15:30:00")]
+    pub erased_at: Option<chrono::NaiveDateTime>,
+}
+
+impl From<common::persistence::models::takedown::VinTakedown> for VinTakedown {
+    fn from(value: common::persistence::models::takedown::VinTakedown) -> Self {
+        Self {
+            id: value.id,
+            vin: value.vin,
+            state: value.state,
+            contact_email: value.contact_email,
+            contact_name: value.contact_name,
+            reason: value.reason,
+            requested_at: value.requested_at,
+            verified_at: value.verified_at,
+            activated_at: value.activated_at,
+            revoked_at: value.revoked_at,
+            erased_at: value.erased_at,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct VinTakedownWithAudit {
+    #[serde(flatten)]
+    pub vin_takedown: VinTakedown,
+    /// State changes oldest first, the first one is the submission
+    pub audit: Vec<VinTakedownAuditEntry>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct VinTakedownAuditEntry {
+    pub from_state: Option<String>,
+    pub to_state: String,
+    pub actor: String,
+    pub note: Option<String>,
+    #[schema(value_type = String, example = "2025-10-13T15:30:00")]
+    pub changed_at: chrono::NaiveDateTime,
 }
 
-impl From<common::persistence::models::copart::LotImage> for LotImage {
-    fn from(value: common::persistence::models::copart::LotImage) -> Self {
+impl From<common::persistence::models::takedown::VinTakedownAudit> for VinTakedownAuditEntry {
+    fn from(value: common::persistence::models::takedown::VinTakedownAudit) -> Self {
         Self {
-            standard_bucket_key: value.standard_bucket_key,
-            standard_mime_type: value.standard_mime_type,
-            thumbnail_bucket_key: value.thumbnail_bucket_key,
-            thumbnail_mime_type: value.thumbnail_mime_type,
-            high_res_bucket_key: value.high_res_bucket_key,
-            high_res_mime_type: value.high_res_mime_type,
-            sequence_number: value.sequence_number,
+            from_state: value.from_state,
+            to_state: value.to_state,
+            actor: value.actor,
+            note: value.note,
+            changed_at: value.changed_at,
         }
     }
 }
@@ -1,6 +1,8 @@
 use axum::extract::FromRequest;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
+use common::io::error::GeneralError;
+use common::io::provider::LotId;
 use serde::Serialize;
 use thiserror::Error;
 use utoipa::ToSchema;
@@ -11,23 +13,67 @@ pub enum ApiError {
     Diesel(#[from] diesel::result::Error),
     #[error("postgres pool error: `{0}`")]
     PgPool(#[from] diesel_async::pooled_connection::deadpool::PoolError),
+    #[error("repository error: `{0}`")]
+    Repo(#[from] GeneralError),
     #[error("lot vehicle with given ln not found: `{0}`")]
-    LotVehicleNotFoundLn(i32),
+    LotVehicleNotFoundLn(LotId),
+    #[error("unknown lot source: `{0}`")]
+    UnknownSource(String),
     #[error("lot vehicle with given vin not found: `{0}`")]
     LotVehicleNotFoundVin(String),
+    #[error("invalid vin `{0}`: `{1}`")]
+    InvalidVin(String, common::vin::VinError),
+    #[error("vin takedown not found: `{0}`")]
+    TakedownNotFound(i32),
+    #[error("invalid vin takedown: `{0}`")]
+    InvalidTakedown(String),
+    #[error("image url error: `{0}`")]
+    ImageUrl(String),
+    #[error("missing or unknown operator token")]
+    Unauthorized,
 }
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         let (status, message) = match self {
-            Self::LotVehicleNotFoundLn(ln) => (
+            Self::LotVehicleNotFoundLn(id) => (
                 StatusCode::NOT_FOUND,
-                format!("lot vehicle with lot number not found: `{ln}`"),
+                format!(
+                    "lot vehicle with lot number not found: `{}` at `{}`",
+                    id.lot_number, id.provider
+                ),
+            ),
+            Self::UnknownSource(source) => (
+                StatusCode::BAD_REQUEST,
+                format!("unknown lot source: `{source}`"),
             ),
             Self::LotVehicleNotFoundVin(vin) => (
                 StatusCode::NOT_FOUND,
                 format!("lot vehicle with vin number not found: `{vin}`"),
             ),
+            Self::InvalidVin(vin, e @ common::vin::VinError::CheckDigitMismatch { .. }) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("lot vehicle with vin number not found, possibly mistyped `{vin}`: {e}"),
+            ),
+            Self::InvalidVin(vin, e) => (
+                StatusCode::BAD_REQUEST,
+                format!("invalid vin number `{vin}`: {e}"),
+            ),
+            Self::TakedownNotFound(id) => (
+                StatusCode::NOT_FOUND,
+                format!("vin takedown not found: `{id}`"),
+            ),
+            Self::InvalidTakedown(reason) => (
+                StatusCode::BAD_REQUEST,
+                format!("invalid vin takedown: {reason}"),
+            ),
+            Self::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                "missing or unknown operator token".to_string(),
+            ),
+            Self::Repo(e @ GeneralError::TakedownTransition { .. }) => {
+                (StatusCode::CONFLICT, e.to_string())
+            }
             _ => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "Something went wrong".to_string(),
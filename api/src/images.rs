@@ -0,0 +1,83 @@
+use crate::error::ApiError;
+use common::bucket::store::ObjectStore;
+use common::config::ImageUrls as ImageUrlsConfig;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Turns bucket keys into urls clients fetch images from, so responses never expose
+/// the bucket layout. The strategy is configured per environment.
+#[derive(Clone)]
+pub enum ImageUrls {
+    Presigned {
+        store: Arc<dyn ObjectStore>,
+        expires_in: Duration,
+    },
+    Public {
+        base_url: Arc<str>,
+    },
+}
+
+impl ImageUrls {
+    pub fn new(config: &ImageUrlsConfig, store: Arc<dyn ObjectStore>) -> Self {
+        match config {
+            ImageUrlsConfig::Presigned { expiry_secs } => Self::Presigned {
+                store,
+                expires_in: Duration::from_secs(*expiry_secs),
+            },
+            ImageUrlsConfig::Public { base_url } => Self::Public {
+                base_url: base_url.trim_end_matches('/').into(),
+            },
+        }
+    }
+
+    pub async fn url(&self, bucket_key: &str) -> Result<String, ApiError> {
+        match self {
+            Self::Presigned { store, expires_in } => store
+                .presign(bucket_key, *expires_in)
+                .await
+                .map_err(|e| ApiError::ImageUrl(e.to_string())),
+            Self::Public { base_url } => Ok(format!("{base_url}/{bucket_key}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::bucket::fs::FsStore;
+    use common::bucket::testing::presigning_s3_store;
+
+    fn store() -> Arc<dyn ObjectStore> {
+        Arc::new(FsStore::new("/var/lib/lot-images"))
+    }
+
+    #[tokio::test]
+    async fn test_public_url() -> Result<(), Box<dyn std::error::Error>> {
+        let urls = ImageUrls::new(
+            &ImageUrlsConfig::Public {
+                base_url: "https://cdn.example.com/lot-images/".to_string(),
+            },
+            store(),
+        );
+
+        assert_eq!(
+            urls.url("sha256/abc").await?,
+            "https://cdn.example.com/lot-images/sha256/abc"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_presigned_url() -> Result<(), Box<dyn std::error::Error>> {
+        let urls = ImageUrls::new(
+            &ImageUrlsConfig::Presigned { expiry_secs: 60 },
+            Arc::new(presigning_s3_store()),
+        );
+
+        let url = urls.url("sha256/abc").await?;
+
+        assert!(url.starts_with("http://localhost:9000/lot-images/sha256/abc?"));
+        assert!(url.contains("X-Amz-Expires=60"));
+        Ok(())
+    }
+}
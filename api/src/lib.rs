@@ -1,9 +1,48 @@
-use utoipa::OpenApi;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
 
+pub mod auth;
 pub mod domain;
 pub mod error;
+pub mod images;
 pub mod routes;
+pub mod state;
 
 #[derive(OpenApi)]
-#[openapi(paths(crate::routes::lot_vehicle::all, crate::routes::lot_vehicle::by_ln))]
+#[openapi(paths(
+    crate::routes::lot_vehicle::all,
+    crate::routes::lot_vehicle::by_ln,
+    crate::routes::lot_vehicle::history_by_ln,
+    crate::routes::lot_vehicle::history_by_vin,
+    crate::routes::lot_vehicle::prices_by_ln,
+    crate::routes::lot_vehicle::related_by_ln,
+    crate::routes::lot_vehicle::search,
+    crate::routes::vin_takedown::submit
+))]
 pub struct Docs;
+
+/// Served on the management port only, next to the routes it describes.
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        crate::routes::vin_takedown::all,
+        crate::routes::vin_takedown::by_id,
+        crate::routes::vin_takedown::transition
+    ),
+    modifiers(&OperatorToken)
+)]
+pub struct ManagementDocs;
+
+struct OperatorToken;
+
+impl Modify for OperatorToken {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        openapi
+            .components
+            .get_or_insert_with(Default::default)
+            .add_security_scheme(
+                "operator_token",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+    }
+}
@@ -1,74 +1,123 @@
-use axum::routing::get;
-use common::logging::setup_logging;
+use api::auth::{require_operator, ManagementAuth};
+use api::images::ImageUrls;
+use api::state::AppState;
+use axum::routing::{get, post, put};
+use axum::Json;
+use common::bucket::OBJECT_STORE;
+use common::config::CONFIG;
 use common::persistence::init_pg_pool;
-use std::sync::Arc;
-use tokio::sync::Notify;
+use common::persistence::migrate::run_pending_migrations;
+use common::persistence::repo::PgRepo;
+use common::service::{Service, Stage};
+use tokio::sync::oneshot;
 use tokio_util::sync::CancellationToken;
-use tracing::info;
+use tracing::{info, warn};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
-#[cfg(not(target_env = "msvc"))]
-use tikv_jemallocator::Jemalloc;
-
-#[cfg(not(target_env = "msvc"))]
-#[global_allocator]
-static GLOBAL: Jemalloc = Jemalloc;
+common::jemalloc!();
 
 #[tokio::main]
 async fn main() {
-    setup_logging("api");
-    info!("starting app");
-    let cancellation_token = CancellationToken::new();
+    let mut service = Service::new("api").admin("0.0.0.0:6972");
 
     let pool = init_pg_pool();
+    if CONFIG.postgres.migrate_on_startup {
+        run_pending_migrations(&pool)
+            .await
+            .expect("failed to run pending migrations");
+    }
+    let repo = PgRepo::new(pool);
+    let state = AppState {
+        repo: repo.clone(),
+        image_urls: ImageUrls::new(&CONFIG.images.urls, OBJECT_STORE.clone()),
+    };
     let app = axum::Router::new()
         .route("/lot_vehicle", get(api::routes::lot_vehicle::all))
+        .route("/lot_vehicle/search", get(api::routes::lot_vehicle::search))
         .route(
             "/lot_vehicle/{lot_number}",
             get(api::routes::lot_vehicle::by_ln),
         )
+        .route(
+            "/lot_vehicle/{lot_number}/history",
+            get(api::routes::lot_vehicle::history_by_ln),
+        )
+        .route(
+            "/lot_vehicle/{lot_number}/prices",
+            get(api::routes::lot_vehicle::prices_by_ln),
+        )
+        .route(
+            "/lot_vehicle/{lot_number}/related",
+            get(api::routes::lot_vehicle::related_by_ln),
+        )
         .route(
             "/lot_vehicle/vin/{vin}",
             get(api::routes::lot_vehicle::by_vin),
         )
-        .with_state(pool)
+        .route(
+            "/lot_vehicle/vin/{vin}/history",
+            get(api::routes::lot_vehicle::history_by_vin),
+        )
+        .route("/vin_takedown", post(api::routes::vin_takedown::submit))
+        .with_state(state)
         .merge(SwaggerUi::new("/docs").url("/api-doc/openapi.json", api::Docs::openapi()));
 
+    // takedown management exposes requester contacts, it is served on an internal address
+    // to operators authenticated by their token only
+    let management_app = axum::Router::new()
+        .route("/vin_takedown", get(api::routes::vin_takedown::all))
+        .route("/vin_takedown/{id}", get(api::routes::vin_takedown::by_id))
+        .route(
+            "/vin_takedown/{id}/state",
+            put(api::routes::vin_takedown::transition),
+        )
+        .route(
+            "/api-doc/openapi.json",
+            get(async || Json(api::ManagementDocs::openapi())),
+        )
+        .with_state(repo)
+        .layer(axum::middleware::from_fn_with_state(
+            ManagementAuth::new(CONFIG.management.operators.clone()),
+            require_operator,
+        ));
+
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8081")
         .await
         .expect("failed to bind");
-    let app_done = serve(listener, app, cancellation_token.clone());
+    service.register("http server", Stage::Ingress, |token| {
+        serve(listener, app, token)
+    });
 
-    info!("app started");
-    tokio::signal::ctrl_c()
+    if CONFIG.management.operators.is_empty() {
+        warn!("`MANAGEMENT_TOKENS` is not set, every management request is rejected");
+    }
+    let management_listener = tokio::net::TcpListener::bind(&CONFIG.management.bind)
         .await
-        .expect("failed to listen for ctrl c event");
-    info!("exiting");
-    cancellation_token.cancel();
-    app_done.notified().await;
-    info!("exited");
+        .expect("failed to bind management address");
+    service.register("management http server", Stage::Ingress, |token| {
+        serve(management_listener, management_app, token)
+    });
+
+    service.run().await;
 }
 
 fn serve(
     listener: tokio::net::TcpListener,
     app: axum::Router,
     cancellation_token: CancellationToken,
-) -> Arc<Notify> {
-    let done = Arc::new(Notify::new());
+) -> oneshot::Receiver<()> {
+    let (done_sender, done) = oneshot::channel();
 
-    tokio::spawn({
-        let done = done.clone();
-        async move {
-            axum::serve(listener, app)
-                .with_graceful_shutdown(async move {
-                    cancellation_token.cancelled().await;
-                    info!("gracefully shutting down app");
-                    done.notify_waiters();
-                })
-                .await
-                .expect("failed to serve");
-        }
+    tokio::spawn(async move {
+        axum::serve(listener, app)
+            .with_graceful_shutdown(async move {
+                cancellation_token.cancelled().await;
+                info!("gracefully shutting down app");
+            })
+            .await
+            .expect("failed to serve");
+        let _ = done_sender.send(());
     });
 
     done
@@ -1,16 +1,20 @@
-use crate::domain::LotVehicleWithImages;
+use crate::domain::{
+    LotPriceSnapshot, LotVehicleChange, LotVehicleSearchHit, LotVehicleWithImages,
+    RelatedLotVehicle, VinHistory,
+};
 use crate::error::{ApiError, ErrorResponse};
-use axum::extract::{Path, State};
+use crate::images::ImageUrls;
+use axum::extract::{Path, Query, State};
 use axum::Json;
-use common::persistence::models::copart::{LotImage, LotVehicle};
-use common::persistence::schema::lot_image::sequence_number;
-use common::persistence::schema::lot_vehicle::dsl::lot_vehicle;
-use common::persistence::schema::lot_vehicle::vin;
-use common::persistence::PgPool;
-use diesel::{
-    BelongingToDsl, ExpressionMethods, GroupedBy, OptionalExtension, QueryDsl, SelectableHelper,
-};
-use diesel_async::RunQueryDsl;
+use common::io::provider::{LotId, Provider};
+use common::persistence::related::{DEFAULT_MAX_DISTANCE, DEFAULT_RELATED_LIMIT};
+use common::persistence::repo::{LotPriceRepo, LotVehicleRepo, PgRepo};
+use common::persistence::search::DEFAULT_SEARCH_LIMIT;
+use common::vin::Vin;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+const ALL_LIMIT: i64 = 500;
 
 #[utoipa::path(
     get,
@@ -20,30 +24,31 @@ use diesel_async::RunQueryDsl;
         (status = 200, description = "Returns all lot vehicles with images", body = [LotVehicleWithImages])
     )
 )]
-pub async fn all(State(pool): State<PgPool>) -> Result<Json<Vec<LotVehicleWithImages>>, ApiError> {
-    let mut conn = pool.get().await?;
-    let all_vehicles = lot_vehicle
-        .select(LotVehicle::as_select())
-        .limit(500)
-        .load(&mut conn)
-        .await?;
+pub async fn all(
+    State(repo): State<PgRepo>,
+    State(urls): State<ImageUrls>,
+) -> Result<Json<Vec<LotVehicleWithImages>>, ApiError> {
+    let all_vehicles = repo.lot_vehicles(ALL_LIMIT).await?;
+    Ok(Json(
+        LotVehicleWithImages::all_with_urls(all_vehicles, &urls).await?,
+    ))
+}
 
-    let all_images = LotImage::belonging_to(&all_vehicles)
-        .select(LotImage::as_select())
-        .load(&mut conn)
-        .await?;
+#[derive(Default, Deserialize, IntoParams)]
+pub struct SourceParams {
+    /// Auction house of the lot, `copart` when absent
+    source: Option<String>,
+}
 
-    let lot_vehicles_with_images = all_images
-        .grouped_by(&all_vehicles)
-        .into_iter()
-        .zip(all_vehicles)
-        .map(|(images, vehicle)| LotVehicleWithImages {
-            lot_vehicle: vehicle.into(),
-            lot_images: images.into_iter().map(|x| x.into()).collect(),
-        })
-        .collect::<Vec<LotVehicleWithImages>>();
-
-    Ok(Json(lot_vehicles_with_images))
+impl SourceParams {
+    fn lot_id(&self, ln: i32) -> Result<LotId, ApiError> {
+        let provider = match self.source.as_deref() {
+            Some(source) => Provider::parse(source)
+                .ok_or_else(|| ApiError::UnknownSource(source.to_string()))?,
+            None => Provider::default(),
+        };
+        Ok(LotId::new(provider, ln))
+    }
 }
 
 #[utoipa::path(
@@ -51,37 +56,27 @@ pub async fn all(State(pool): State<PgPool>) -> Result<Json<Vec<LotVehicleWithIm
     path = "/lot-vehicle/{ln}",
     tag = "lot vehicle by lot number",
     params(
-        ("ln" = i32, Path, description = "The lot number of the vehicle")
+        ("ln" = i32, Path, description = "The lot number of the vehicle"),
+        SourceParams
     ),
     responses(
         (status = 200, description = "Returns a lot vehicle with images", body = LotVehicleWithImages),
+        (status = 400, description = "Returns a error when the source is unknown", body = ErrorResponse),
         (status = 404, description = "Returns a error when lot number does not exist", body = ErrorResponse)
     )
 )]
 pub async fn by_ln(
     Path(ln): Path<i32>,
-    State(pool): State<PgPool>,
+    Query(params): Query<SourceParams>,
+    State(repo): State<PgRepo>,
+    State(urls): State<ImageUrls>,
 ) -> Result<Json<LotVehicleWithImages>, ApiError> {
-    let mut conn = pool.get().await?;
-    let vehicle = lot_vehicle
-        .find(&ln)
-        .select(LotVehicle::as_select())
-        .first(&mut conn)
-        .await
-        .optional()?
-        .ok_or(ApiError::LotVehicleNotFoundLn(ln))?;
-
-    let all_images = LotImage::belonging_to(&vehicle)
-        .order(sequence_number.asc())
-        .select(LotImage::as_select())
-        .load(&mut conn)
-        .await?;
-
-    let lot_vehicle_with_images = LotVehicleWithImages {
-        lot_vehicle: vehicle.into(),
-        lot_images: all_images.into_iter().map(|x| x.into()).collect(),
-    };
-    Ok(Json(lot_vehicle_with_images))
+    let id = params.lot_id(ln)?;
+    let vehicle = repo
+        .lot_vehicle_by_id(id)
+        .await?
+        .ok_or(ApiError::LotVehicleNotFoundLn(id))?;
+    Ok(Json(LotVehicleWithImages::with_urls(vehicle, &urls).await?))
 }
 
 #[utoipa::path(
@@ -89,35 +84,483 @@ pub async fn by_ln(
     path = "/lot-vehicle/vin/{vin}",
     tag = "lot vehicle by vin number",
     params(
-        ("vin" = String, Path, description = "The vin number of the vehicle")
+        ("vin" = String, Path, description = "The vin number of the vehicle, case, whitespace and I/O/Q confusions are tolerated")
     ),
     responses(
         (status = 200, description = "Returns a lot vehicle with images", body = LotVehicleWithImages),
-        (status = 404, description = "Returns a error when lot number does not exist", body = ErrorResponse)
+        (status = 400, description = "Returns a error when vin does not exist and its format is invalid", body = ErrorResponse),
+        (status = 404, description = "Returns a error when vin does not exist", body = ErrorResponse),
+        (status = 422, description = "Returns a error when vin does not exist and its check digit does not match", body = ErrorResponse)
     )
 )]
 pub async fn by_vin(
     Path(v): Path<String>,
-    State(pool): State<PgPool>,
+    State(repo): State<PgRepo>,
+    State(urls): State<ImageUrls>,
 ) -> Result<Json<LotVehicleWithImages>, ApiError> {
-    let mut conn = pool.get().await?;
-    let vehicle = lot_vehicle
-        .filter(vin.eq(&v))
-        .select(LotVehicle::as_select())
-        .first(&mut conn)
-        .await
-        .optional()?
-        .ok_or(ApiError::LotVehicleNotFoundVin(v))?;
-
-    let all_images = LotImage::belonging_to(&vehicle)
-        .order(sequence_number.asc())
-        .select(LotImage::as_select())
-        .load(&mut conn)
+    let v = common::vin::normalize(&v);
+    let vehicle = repo
+        .lot_vehicle_by_vin(&v)
+        .await?
+        .ok_or_else(|| vin_not_found(v))?;
+    Ok(Json(LotVehicleWithImages::with_urls(vehicle, &urls).await?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/lot-vehicle/vin/{vin}/history",
+    tag = "lot vehicle history by vin number",
+    params(
+        ("vin" = String, Path, description = "The vin number of the vehicle, case, whitespace and I/O/Q confusions are tolerated")
+    ),
+    responses(
+        (status = 200, description = "Returns every lot of the vin across auction houses, ordered by sale date", body = VinHistory),
+        (status = 400, description = "Returns a error when vin does not exist and its format is invalid", body = ErrorResponse),
+        (status = 404, description = "Returns a error when vin does not exist", body = ErrorResponse),
+        (status = 422, description = "Returns a error when vin does not exist and its check digit does not match", body = ErrorResponse)
+    )
+)]
+pub async fn history_by_vin(
+    Path(v): Path<String>,
+    State(repo): State<PgRepo>,
+    State(urls): State<ImageUrls>,
+) -> Result<Json<VinHistory>, ApiError> {
+    let v = common::vin::normalize(&v);
+    let lots = repo.lot_vehicles_by_vin(&v).await?;
+    if lots.is_empty() {
+        return Err(vin_not_found(v));
+    }
+    Ok(Json(VinHistory {
+        vin: v,
+        lots: LotVehicleWithImages::all_with_urls(lots, &urls).await?,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/lot-vehicle/{ln}/history",
+    tag = "lot vehicle history by lot number",
+    params(
+        ("ln" = i32, Path, description = "The lot number of the vehicle"),
+        SourceParams
+    ),
+    responses(
+        (status = 200, description = "Returns changed fields of a lot vehicle, oldest first", body = [LotVehicleChange]),
+        (status = 400, description = "Returns a error when the source is unknown", body = ErrorResponse),
+        (status = 404, description = "Returns a error when lot number does not exist", body = ErrorResponse)
+    )
+)]
+pub async fn history_by_ln(
+    Path(ln): Path<i32>,
+    Query(params): Query<SourceParams>,
+    State(repo): State<PgRepo>,
+) -> Result<Json<Vec<LotVehicleChange>>, ApiError> {
+    let id = params.lot_id(ln)?;
+    let history = repo
+        .lot_vehicle_history(id)
+        .await?
+        .ok_or(ApiError::LotVehicleNotFoundLn(id))?;
+    Ok(Json(history.into_iter().map(|x| x.into()).collect()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/lot-vehicle/{ln}/prices",
+    tag = "lot vehicle prices by lot number",
+    params(
+        ("ln" = i32, Path, description = "The lot number of the vehicle"),
+        SourceParams
+    ),
+    responses(
+        (status = 200, description = "Returns bid snapshots of a lot vehicle, oldest first", body = [LotPriceSnapshot]),
+        (status = 400, description = "Returns a error when the source is unknown", body = ErrorResponse),
+        (status = 404, description = "Returns a error when lot number does not exist", body = ErrorResponse)
+    )
+)]
+pub async fn prices_by_ln(
+    Path(ln): Path<i32>,
+    Query(params): Query<SourceParams>,
+    State(repo): State<PgRepo>,
+) -> Result<Json<Vec<LotPriceSnapshot>>, ApiError> {
+    let id = params.lot_id(ln)?;
+    let prices = repo
+        .price_snapshots(id)
+        .await?
+        .ok_or(ApiError::LotVehicleNotFoundLn(id))?;
+    Ok(Json(prices.into_iter().map(|x| x.into()).collect()))
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct SearchParams {
+    /// Free text over make, model, trim, damage, color and yard, or the last 6+ characters of a vin
+    q: String,
+    /// Maximum number of hits, at most 100
+    limit: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/lot-vehicle/search",
+    tag = "lot vehicle search",
+    params(SearchParams),
+    responses(
+        (status = 200, description = "Returns matching lot vehicles, best match first", body = [LotVehicleSearchHit])
+    )
+)]
+pub async fn search(
+    Query(params): Query<SearchParams>,
+    State(repo): State<PgRepo>,
+) -> Result<Json<Vec<LotVehicleSearchHit>>, ApiError> {
+    let hits = repo
+        .search_lot_vehicles(&params.q, params.limit.unwrap_or(DEFAULT_SEARCH_LIMIT))
         .await?;
+    Ok(Json(
+        hits.into_iter()
+            .map(|(hit, vehicle)| LotVehicleSearchHit {
+                lot_vehicle: vehicle.into(),
+                rank: hit.rank,
+                highlight: hit.highlight,
+            })
+            .collect(),
+    ))
+}
 
-    let lot_vehicle_with_images = LotVehicleWithImages {
-        lot_vehicle: vehicle.into(),
-        lot_images: all_images.into_iter().map(|x| x.into()).collect(),
+#[derive(Deserialize, IntoParams)]
+pub struct RelatedParams {
+    /// Differing bits up to which two photos count as the same, at most 12
+    max_distance: Option<i32>,
+    /// Maximum number of lots, at most 100
+    limit: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/lot-vehicle/{ln}/related",
+    tag = "related lot vehicles by lot number",
+    params(
+        ("ln" = i32, Path, description = "The lot number of the vehicle"),
+        SourceParams,
+        RelatedParams
+    ),
+    responses(
+        (status = 200, description = "Returns lots sharing near-identical photos, most shared photos first", body = [RelatedLotVehicle]),
+        (status = 400, description = "Returns a error when the source is unknown", body = ErrorResponse),
+        (status = 404, description = "Returns a error when lot number does not exist", body = ErrorResponse)
+    )
+)]
+pub async fn related_by_ln(
+    Path(ln): Path<i32>,
+    Query(params): Query<SourceParams>,
+    Query(related): Query<RelatedParams>,
+    State(repo): State<PgRepo>,
+) -> Result<Json<Vec<RelatedLotVehicle>>, ApiError> {
+    let id = params.lot_id(ln)?;
+    let lots = repo
+        .related_lot_vehicles(
+            id,
+            related.max_distance.unwrap_or(DEFAULT_MAX_DISTANCE),
+            related.limit.unwrap_or(DEFAULT_RELATED_LIMIT),
+        )
+        .await?
+        .ok_or(ApiError::LotVehicleNotFoundLn(id))?;
+    Ok(Json(
+        lots.into_iter()
+            .map(|(lot, vehicle)| RelatedLotVehicle {
+                lot_vehicle: vehicle.into(),
+                shared_images: lot.shared_images,
+                distance: lot.distance,
+            })
+            .collect(),
+    ))
+}
+
+/// Lots may be listed with malformed vins, so a vin is validated only when nothing matched
+/// and a mistyped one is reported instead of a plain not found.
+fn vin_not_found(v: String) -> ApiError {
+    match Vin::parse(&v).and_then(|parsed| parsed.validate_check_digit()) {
+        Ok(()) => ApiError::LotVehicleNotFoundVin(v),
+        Err(e) => ApiError::InvalidVin(v, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::persistence::models::copart::{
+        NewLotImage, NewLotImages, NewLotPriceSnapshot, NewLotVehicle, NewLotVehicles,
     };
-    Ok(Json(lot_vehicle_with_images))
+    use common::persistence::repo::LotImageRepo;
+    use common::persistence::testing::{fixtures, TestDb};
+
+    async fn seeded() -> Result<TestDb, Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        let repo = db.repo();
+        repo.upsert_lot_vehicles(NewLotVehicles(vec![
+            fixtures::lot_vehicle(1),
+            fixtures::lot_vehicle(2),
+        ]))
+        .await?;
+        repo.upsert_lot_vehicles(NewLotVehicles(vec![NewLotVehicle {
+            odometer: 60000.0,
+            ..fixtures::lot_vehicle(1)
+        }]))
+        .await?;
+        repo.upsert_lot_images(
+            LotId::copart(1),
+            NewLotImages(vec![fixtures::lot_image(1, 2), fixtures::lot_image(1, 1)]),
+        )
+        .await?;
+        repo.save_auction_result(fixtures::auction_result(1))
+            .await?;
+        Ok(db)
+    }
+
+    fn urls() -> ImageUrls {
+        ImageUrls::Public {
+            base_url: "http://cdn.test/lot-images".into(),
+        }
+    }
+
+    fn copart() -> Query<SourceParams> {
+        Query(SourceParams { source: None })
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_all_and_by_ln() -> Result<(), Box<dyn std::error::Error>> {
+        let db = seeded().await?;
+
+        let Json(vehicles) = all(State(db.repo()), State(urls())).await?;
+        assert_eq!(vehicles.len(), 2);
+
+        let Json(vehicle) = by_ln(Path(1), copart(), State(db.repo()), State(urls())).await?;
+        assert_eq!(vehicle.lot_vehicle.lot_number, 1);
+        assert_eq!(vehicle.lot_vehicle.odometer, 60000.0);
+        assert_eq!(
+            vehicle
+                .lot_images
+                .iter()
+                .map(|i| i.sequence_number)
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(
+            vehicle.lot_images[0]
+                .variants
+                .iter()
+                .map(|v| v.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["w320", "w800", "w1600"]
+        );
+        assert_eq!(
+            vehicle.lot_images[0].variants[0].url,
+            "http://cdn.test/lot-images/sha256/1/1/w320"
+        );
+        assert!(vehicle.auction_result.is_some());
+
+        assert!(matches!(
+            by_ln(Path(3), copart(), State(db.repo()), State(urls())).await,
+            Err(ApiError::LotVehicleNotFoundLn(id)) if id == LotId::copart(3)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_by_vin() -> Result<(), Box<dyn std::error::Error>> {
+        let db = seeded().await?;
+        let stored_vin = fixtures::lot_vehicle(2).vin.unwrap();
+        // stored as the provider spelled it
+        let raw_vin = fixtures::lot_vehicle(3).vin.unwrap();
+        db.repo()
+            .upsert_lot_vehicles(NewLotVehicles(vec![NewLotVehicle {
+                vin: Some(format!("{}-{}", raw_vin[..8].to_lowercase(), &raw_vin[8..])),
+                ..fixtures::lot_vehicle(3)
+            }]))
+            .await?;
+
+        let Json(vehicle) = by_vin(
+            Path(stored_vin.to_lowercase()),
+            State(db.repo()),
+            State(urls()),
+        )
+        .await?;
+        assert_eq!(vehicle.lot_vehicle.lot_number, 2);
+        assert!(vehicle.auction_result.is_none());
+        let Json(vehicle) = by_vin(Path(raw_vin.clone()), State(db.repo()), State(urls())).await?;
+        assert_eq!(vehicle.lot_vehicle.lot_number, 3);
+        let Json(history) = history_by_vin(Path(raw_vin), State(db.repo()), State(urls())).await?;
+        assert_eq!(history.lots.len(), 1);
+
+        assert!(matches!(
+            by_vin(
+                Path("1HGCM82633A004352".to_string()),
+                State(db.repo()),
+                State(urls())
+            )
+            .await,
+            Err(ApiError::LotVehicleNotFoundVin(_))
+        ));
+        assert!(matches!(
+            by_vin(Path("1HGCM".to_string()), State(db.repo()), State(urls())).await,
+            Err(ApiError::InvalidVin(..))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_history_by_ln() -> Result<(), Box<dyn std::error::Error>> {
+        let db = seeded().await?;
+
+        let Json(history) = history_by_ln(Path(1), copart(), State(db.repo())).await?;
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].field, "odometer");
+
+        let Json(history) = history_by_ln(Path(2), copart(), State(db.repo())).await?;
+        assert!(history.is_empty());
+
+        assert!(matches!(
+            history_by_ln(Path(3), copart(), State(db.repo())).await,
+            Err(ApiError::LotVehicleNotFoundLn(id)) if id == LotId::copart(3)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_prices_by_ln() -> Result<(), Box<dyn std::error::Error>> {
+        let db = seeded().await?;
+        for current_bid in [100.0, 300.0] {
+            db.repo()
+                .save_price_snapshots(vec![NewLotPriceSnapshot {
+                    current_bid,
+                    ..fixtures::price_snapshot(1)
+                }])
+                .await?;
+        }
+
+        let Json(prices) = prices_by_ln(Path(1), copart(), State(db.repo())).await?;
+        assert_eq!(
+            prices.iter().map(|p| p.current_bid).collect::<Vec<_>>(),
+            vec![100.0, 300.0]
+        );
+        let Json(prices) = prices_by_ln(Path(2), copart(), State(db.repo())).await?;
+        assert!(prices.is_empty());
+        assert!(matches!(
+            prices_by_ln(Path(3), copart(), State(db.repo())).await,
+            Err(ApiError::LotVehicleNotFoundLn(id)) if id == LotId::copart(3)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_search() -> Result<(), Box<dyn std::error::Error>> {
+        let db = seeded().await?;
+
+        let params = |q: &str, limit| SearchParams {
+            q: q.to_string(),
+            limit,
+        };
+        let Json(hits) = search(Query(params("toyota camry", None)), State(db.repo())).await?;
+        assert_eq!(hits.len(), 2);
+        assert!(hits[0].highlight.contains("<mark>CAMRY</mark>"));
+
+        let Json(hits) = search(Query(params("toyota", Some(1))), State(db.repo())).await?;
+        assert_eq!(hits.len(), 1);
+
+        let Json(hits) = search(Query(params("nothing", None)), State(db.repo())).await?;
+        assert!(hits.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_related_by_ln() -> Result<(), Box<dyn std::error::Error>> {
+        let db = seeded().await?;
+        // lot 2 is a relisting showing the first photo of lot 1, re-encoded
+        for (ln, perceptual_hash) in [(1, 0x0F0F), (2, 0x0F0E)] {
+            db.repo()
+                .upsert_lot_images(
+                    LotId::copart(ln),
+                    NewLotImages(vec![NewLotImage {
+                        perceptual_hash: Some(perceptual_hash),
+                        ..fixtures::lot_image(ln, 1)
+                    }]),
+                )
+                .await?;
+        }
+        let related_params = |max_distance| {
+            Query(RelatedParams {
+                max_distance,
+                limit: None,
+            })
+        };
+
+        let Json(related) =
+            related_by_ln(Path(1), copart(), related_params(None), State(db.repo())).await?;
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].lot_vehicle.lot_number, 2);
+        assert_eq!((related[0].shared_images, related[0].distance), (1, 1));
+
+        let Json(related) =
+            related_by_ln(Path(1), copart(), related_params(Some(0)), State(db.repo())).await?;
+        assert!(related.is_empty());
+        assert!(matches!(
+            related_by_ln(Path(3), copart(), related_params(None), State(db.repo())).await,
+            Err(ApiError::LotVehicleNotFoundLn(id)) if id == LotId::copart(3)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_source_scoped_lots() -> Result<(), Box<dyn std::error::Error>> {
+        let db = seeded().await?;
+        let vin = fixtures::lot_vehicle(1).vin.unwrap();
+        db.repo()
+            .upsert_lot_vehicles(NewLotVehicles(vec![NewLotVehicle {
+                source: Provider::Stub.to_string(),
+                sale_date: chrono::NaiveDate::from_ymd_opt(2025, 10, 1)
+                    .and_then(|d| d.and_hms_opt(12, 0, 0)),
+                ..fixtures::lot_vehicle(1)
+            }]))
+            .await?;
+        let stub = || {
+            Query(SourceParams {
+                source: Some("stub".to_string()),
+            })
+        };
+
+        let Json(vehicle) = by_ln(Path(1), stub(), State(db.repo()), State(urls())).await?;
+        assert_eq!(vehicle.lot_vehicle.source, "stub");
+        assert!(vehicle.lot_images.is_empty());
+        assert!(matches!(
+            by_ln(Path(2), stub(), State(db.repo()), State(urls())).await,
+            Err(ApiError::LotVehicleNotFoundLn(id)) if id == LotId::new(Provider::Stub, 2)
+        ));
+        let unknown = Query(SourceParams {
+            source: Some("iaai".to_string()),
+        });
+        assert!(matches!(
+            history_by_ln(Path(1), unknown, State(db.repo())).await,
+            Err(ApiError::UnknownSource(_))
+        ));
+
+        let Json(history) =
+            history_by_vin(Path(vin.to_lowercase()), State(db.repo()), State(urls())).await?;
+        assert_eq!(history.vin, vin);
+        assert_eq!(
+            history
+                .lots
+                .iter()
+                .map(|l| l.lot_vehicle.source.as_str())
+                .collect::<Vec<_>>(),
+            vec!["stub", "copart"]
+        );
+        assert!(matches!(
+            history_by_vin(
+                Path("1HGCM82633A004352".to_string()),
+                State(db.repo()),
+                State(urls())
+            )
+            .await,
+            Err(ApiError::LotVehicleNotFoundVin(_))
+        ));
+        Ok(())
+    }
 }
@@ -1 +1,2 @@
 pub mod lot_vehicle;
+pub mod vin_takedown;
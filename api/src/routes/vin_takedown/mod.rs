@@ -0,0 +1,290 @@
+use crate::auth::Operator;
+use crate::domain::{
+    NewVinTakedown, TakedownState, VinTakedown, VinTakedownReceipt, VinTakedownTransition,
+    VinTakedownWithAudit,
+};
+use crate::error::{ApiError, ErrorResponse};
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use common::persistence::models::takedown;
+use common::persistence::repo::{PgRepo, TakedownRepo};
+use common::vin::Vin;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+#[utoipa::path(
+    post,
+    path = "/vin-takedown",
+    tag = "vin takedown",
+    request_body = NewVinTakedown,
+    responses(
+        (status = 202, description = "Takedown received, the vin is hidden once it is verified and activated", body = VinTakedownReceipt),
+        (status = 400, description = "Returns a error when the vin or the contact is invalid", body = ErrorResponse)
+    )
+)]
+pub async fn submit(
+    State(repo): State<PgRepo>,
+    Json(request): Json<NewVinTakedown>,
+) -> Result<(StatusCode, Json<VinTakedownReceipt>), ApiError> {
+    let vin = common::vin::normalize(&request.vin);
+    if let Err(e) = Vin::parse(&vin) {
+        return Err(ApiError::InvalidVin(vin, e));
+    }
+    let contact_email = request.contact_email.trim();
+    if !contact_email.contains('@') {
+        return Err(ApiError::InvalidTakedown(
+            "contact email is not valid".to_string(),
+        ));
+    }
+    let reason = request.reason.trim();
+    if reason.is_empty() {
+        return Err(ApiError::InvalidTakedown("reason is missing".to_string()));
+    }
+
+    let takedown = repo
+        .submit_takedown(takedown::NewVinTakedown {
+            vin,
+            contact_email: contact_email.to_string(),
+            contact_name: request.contact_name,
+            reason: reason.to_string(),
+        })
+        .await?;
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(VinTakedownReceipt {
+            id: takedown.id,
+            state: takedown.state,
+        }),
+    ))
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct TakedownParams {
+    /// Only takedowns in this state
+    state: Option<TakedownState>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/vin-takedown",
+    tag = "vin takedown management",
+    params(TakedownParams),
+    security(("operator_token" = [])),
+    responses(
+        (status = 200, description = "Returns takedowns newest first", body = [VinTakedown]),
+        (status = 401, description = "Returns a error without an operator token", body = ErrorResponse)
+    )
+)]
+pub async fn all(
+    Query(params): Query<TakedownParams>,
+    State(repo): State<PgRepo>,
+) -> Result<Json<Vec<VinTakedown>>, ApiError> {
+    let takedowns = repo.takedowns(params.state.map(|s| s.into())).await?;
+    Ok(Json(takedowns.into_iter().map(|x| x.into()).collect()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/vin-takedown/{id}",
+    tag = "vin takedown management",
+    params(
+        ("id" = i32, Path, description = "The id of the takedown")
+    ),
+    security(("operator_token" = [])),
+    responses(
+        (status = 200, description = "Returns a takedown with its audit trail", body = VinTakedownWithAudit),
+        (status = 401, description = "Returns a error without an operator token", body = ErrorResponse),
+        (status = 404, description = "Returns a error when the takedown does not exist", body = ErrorResponse)
+    )
+)]
+pub async fn by_id(
+    Path(id): Path<i32>,
+    State(repo): State<PgRepo>,
+) -> Result<Json<VinTakedownWithAudit>, ApiError> {
+    let (takedown, audit) = repo
+        .takedown(id)
+        .await?
+        .ok_or(ApiError::TakedownNotFound(id))?;
+    Ok(Json(VinTakedownWithAudit {
+        vin_takedown: takedown.into(),
+        audit: audit.into_iter().map(|x| x.into()).collect(),
+    }))
+}
+
+#[utoipa::path(
+    put,
+    path = "/vin-takedown/{id}/state",
+    tag = "vin takedown management",
+    params(
+        ("id" = i32, Path, description = "The id of the takedown")
+    ),
+    request_body = VinTakedownTransition,
+    security(("operator_token" = [])),
+    responses(
+        (status = 200, description = "Returns the takedown in its new state, the operator of the token is recorded as its actor", body = VinTakedown),
+        (status = 401, description = "Returns a error without an operator token", body = ErrorResponse),
+        (status = 404, description = "Returns a error when the takedown does not exist", body = ErrorResponse),
+        (status = 409, description = "Returns a error when the takedown cannot move to the state", body = ErrorResponse)
+    )
+)]
+pub async fn transition(
+    Path(id): Path<i32>,
+    State(repo): State<PgRepo>,
+    Extension(operator): Extension<Operator>,
+    Json(request): Json<VinTakedownTransition>,
+) -> Result<Json<VinTakedown>, ApiError> {
+    let takedown = repo
+        .transition_takedown(
+            id,
+            request.state.into(),
+            &operator.0,
+            request.note.as_deref(),
+        )
+        .await?
+        .ok_or(ApiError::TakedownNotFound(id))?;
+    Ok(Json(takedown.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::images::ImageUrls;
+    use crate::routes::lot_vehicle;
+    use common::bucket::fs::FsStore;
+    use common::config::ImageUrls as ImageUrlsConfig;
+    use common::persistence::testing::{fixtures, TestDb};
+    use std::sync::Arc;
+
+    fn transition_to(state: TakedownState) -> Json<VinTakedownTransition> {
+        Json(VinTakedownTransition { state, note: None })
+    }
+
+    fn support() -> Extension<Operator> {
+        Extension(Operator("support".to_string()))
+    }
+
+    fn urls() -> State<ImageUrls> {
+        State(ImageUrls::new(
+            &ImageUrlsConfig::Public {
+                base_url: "https://cdn.example.com/lot-images/".to_string(),
+            },
+            Arc::new(FsStore::new("/var/lib/lot-images")),
+        ))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_takedown_workflow() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        db.seed(vec![fixtures::lot_vehicle(1), fixtures::lot_vehicle(2)])
+            .await?;
+        db.seed_images(vec![fixtures::lot_image(1, 1)]).await?;
+        let vin = fixtures::lot_vehicle(1).vin.unwrap();
+
+        let (status, Json(receipt)) = submit(
+            State(db.repo()),
+            Json(NewVinTakedown {
+                vin: vin.to_lowercase(),
+                contact_email: "owner@example.com".to_string(),
+                contact_name: None,
+                reason: "sold privately".to_string(),
+            }),
+        )
+        .await?;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(receipt.state, "requested");
+
+        // activating an unverified takedown is rejected
+        assert!(matches!(
+            transition(
+                Path(receipt.id),
+                State(db.repo()),
+                support(),
+                transition_to(TakedownState::Active)
+            )
+            .await,
+            Err(ApiError::Repo(_))
+        ));
+        transition(
+            Path(receipt.id),
+            State(db.repo()),
+            support(),
+            transition_to(TakedownState::Verified),
+        )
+        .await?;
+        let Json(active) = transition(
+            Path(receipt.id),
+            State(db.repo()),
+            support(),
+            transition_to(TakedownState::Active),
+        )
+        .await?;
+        assert_eq!(active.state, "active");
+        assert_eq!(active.vin, vin);
+
+        assert!(matches!(
+            lot_vehicle::by_vin(Path(vin.clone()), State(db.repo()), urls()).await,
+            Err(ApiError::LotVehicleNotFoundVin(_))
+        ));
+        let Json(redacted) = lot_vehicle::by_ln(
+            Path(1),
+            Query(lot_vehicle::SourceParams::default()),
+            State(db.repo()),
+            urls(),
+        )
+        .await?;
+        assert!(redacted.lot_vehicle.vin.is_none());
+        assert!(redacted.lot_images.is_empty());
+        let Json(all_vehicles) = lot_vehicle::all(State(db.repo()), urls()).await?;
+        assert_eq!(all_vehicles.len(), 1);
+
+        let Json(listed) = all(
+            Query(TakedownParams {
+                state: Some(TakedownState::Active),
+            }),
+            State(db.repo()),
+        )
+        .await?;
+        assert_eq!(listed.len(), 1);
+        let Json(details) = by_id(Path(receipt.id), State(db.repo())).await?;
+        assert_eq!(details.audit.len(), 3);
+        assert_eq!(details.audit[0].actor, "owner@example.com");
+        assert_eq!(details.audit[2].actor, "support");
+        assert!(matches!(
+            by_id(Path(0), State(db.repo())).await,
+            Err(ApiError::TakedownNotFound(0))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_submit_validation() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        let request = |vin: &str, contact_email: &str, reason: &str| {
+            Json(NewVinTakedown {
+                vin: vin.to_string(),
+                contact_email: contact_email.to_string(),
+                contact_name: None,
+                reason: reason.to_string(),
+            })
+        };
+
+        assert!(matches!(
+            submit(State(db.repo()), request("1HGCM", "owner@example.com", "x")).await,
+            Err(ApiError::InvalidVin(..))
+        ));
+        assert!(matches!(
+            submit(State(db.repo()), request("1HGCM82633A004352", "owner", "x")).await,
+            Err(ApiError::InvalidTakedown(_))
+        ));
+        assert!(matches!(
+            submit(
+                State(db.repo()),
+                request("1HGCM82633A004352", "owner@example.com", " ")
+            )
+            .await,
+            Err(ApiError::InvalidTakedown(_))
+        ));
+        Ok(())
+    }
+}
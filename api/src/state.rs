@@ -0,0 +1,21 @@
+use crate::images::ImageUrls;
+use axum::extract::FromRef;
+use common::persistence::repo::PgRepo;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub repo: PgRepo,
+    pub image_urls: ImageUrls,
+}
+
+impl FromRef<AppState> for PgRepo {
+    fn from_ref(input: &AppState) -> Self {
+        input.repo.clone()
+    }
+}
+
+impl FromRef<AppState> for ImageUrls {
+    fn from_ref(input: &AppState) -> Self {
+        input.image_urls.clone()
+    }
+}
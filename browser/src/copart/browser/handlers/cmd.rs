@@ -1,4 +1,4 @@
-use crate::copart::browser::CmdReceiver;
+use crate::copart::browser::{CmdReceiver, ForcedLotImages};
 use chromiumoxide::cdp::browser_protocol::page::NavigateParams;
 use chromiumoxide::Page;
 use common::io::copart::{AuctionId, CopartCmd, DateTimeRfc3339, LotNumber, LotYear, PageNumber};
@@ -15,12 +15,13 @@ pub struct CmdsHandler {
 
 pub struct Navigator {
     page: Arc<Page>,
+    forced: ForcedLotImages,
 }
 
 impl CmdsHandler {
-    pub fn new(page: Arc<Page>, cmd_receiver: CmdReceiver) -> Self {
+    pub fn new(page: Arc<Page>, cmd_receiver: CmdReceiver, forced: ForcedLotImages) -> Self {
         Self {
-            navigator: Navigator { page },
+            navigator: Navigator { page, forced },
             cmd_receiver,
         }
     }
@@ -42,7 +43,8 @@ impl CmdsHandler {
                         .await
                 }
                 CopartCmd::LoginRefresh => self.navigator.login().await,
-                CopartCmd::LotImages(ln) => self.navigator.lot_images(ln).await,
+                CopartCmd::LotImages(ln) => self.navigator.lot_images(ln, false).await,
+                CopartCmd::ForceLotImages(ln) => self.navigator.lot_images(ln, true).await,
                 CopartCmd::Auction(aid) => self.navigator.auction(aid).await,
             }
         }
@@ -88,8 +90,16 @@ impl Navigator {
         }
     }
 
+    /// `lotNumber` is read back by `ResponseHandler` from the intercepted request, `force`
+    /// is handed over in [`ForcedLotImages`].
     #[instrument(skip(self))]
-    pub async fn lot_images(&self, lot_number: LotNumber) {
+    pub async fn lot_images(&self, lot_number: LotNumber, force: bool) {
+        if force {
+            self.forced
+                .lock()
+                .expect("forced lot images lock poisoned")
+                .insert(lot_number);
+        }
         let url = format!(
             "https://www.copart.ca/public/data/lotdetails/solr/lotImages/{lot_number}?lotNumber={lot_number}"
         );
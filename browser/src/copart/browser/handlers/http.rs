@@ -1,4 +1,4 @@
-use crate::copart::browser::ResponseSender;
+use crate::copart::browser::{ForcedLotImages, ResponseSender};
 use crate::copart::{request, response};
 use base64::Engine;
 use chromiumoxide::cdp::browser_protocol::fetch::{
@@ -39,16 +39,18 @@ struct RequestHandler {
 struct ResponseHandler {
     page: Arc<Page>,
     response_sender: ResponseSender,
+    forced: ForcedLotImages,
 }
 
 impl HttpHandler {
-    pub fn new(page: Arc<Page>, response_sender: ResponseSender) -> Self {
+    pub fn new(page: Arc<Page>, response_sender: ResponseSender, forced: ForcedLotImages) -> Self {
         Self {
             page: page.clone(),
             request_handler: RequestHandler { page: page.clone() },
             response_handler: ResponseHandler {
                 page: page.clone(),
                 response_sender,
+                forced,
             },
         }
     }
@@ -246,6 +248,12 @@ impl ResponseHandler {
             .get("lotNumber")
             .ok_or(GeneralError::LotNumberNotFound)?
             .parse::<LotNumber>()?;
+        // taken before the body, a failed response does not leave the lot forced
+        let force = self
+            .forced
+            .lock()
+            .expect("forced lot images lock poisoned")
+            .remove(&lot_number);
 
         let base64_body = self
             .get_browser_response_body(event.request_id.clone())
@@ -255,6 +263,7 @@ impl ResponseHandler {
         let response = LotImagesResponse {
             response: unmarshalled.into(),
             lot_number,
+            force,
         };
         Ok(response)
     }
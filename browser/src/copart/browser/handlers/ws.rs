@@ -4,6 +4,7 @@ use crate::copart::response::auction::{plain, solace};
 use base64::Engine;
 use chromiumoxide::cdp::browser_protocol::network::EventWebSocketFrameReceived;
 use chromiumoxide::Page;
+use common::io::copart::{AuctionResultResponse, CopartResponse};
 use common::io::error::GeneralError;
 use futures::StreamExt;
 use std::io::{Cursor, Read, Seek, SeekFrom};
@@ -17,14 +18,14 @@ const SMF_FOOTER_SIZE: usize = 40;
 
 pub struct WsHandler {
     page: Arc<Page>,
-    _response_sender: ResponseSender,
+    response_sender: ResponseSender,
 }
 
 impl WsHandler {
-    pub fn new(page: Arc<Page>, _response_sender: ResponseSender) -> Self {
+    pub fn new(page: Arc<Page>, response_sender: ResponseSender) -> Self {
         Self {
             page,
-            _response_sender,
+            response_sender,
         }
     }
 
@@ -86,13 +87,23 @@ impl WsHandler {
     async fn handle_plaintext(&self, payload: Vec<u8>) -> Result<(), GeneralError> {
         let msg = serde_json::from_slice::<plain::SoldMessage>(&payload)?;
         info!("ws event plain: {:?}", msg);
-        Ok(())
+        self.process_auction_result(msg.try_into()).await
     }
 
     async fn handle_solace(&self, payload: Vec<u8>) -> Result<(), GeneralError> {
         let decoded = decode_smf(payload)?;
         let msg = serde_json::from_slice::<solace::SoldMessage>(&decoded)?;
         info!("ws event solace: {:?}", msg);
+        self.process_auction_result(msg.try_into()).await
+    }
+
+    async fn process_auction_result(
+        &self,
+        maybe_result: Result<AuctionResultResponse, GeneralError>,
+    ) -> Result<(), GeneralError> {
+        self.response_sender
+            .send(CopartResponse::AuctionResult(maybe_result))
+            .await?;
         Ok(())
     }
 }
@@ -5,12 +5,13 @@ use crate::copart::browser::handlers::ws::WsHandler;
 use chromiumoxide::cdp::browser_protocol::fetch::{EnableParams, RequestPattern, RequestStage};
 use chromiumoxide::cdp::browser_protocol::network::ResourceType;
 use chromiumoxide::{Browser, BrowserConfig, Handler, Page};
-use common::io::copart::{CopartCmd, CopartResponse};
+use common::io::copart::{CopartCmd, CopartResponse, LotNumber};
 use common::io::error::GeneralError;
+use std::collections::HashSet;
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc::{Receiver, Sender};
-use tokio::sync::Notify;
+use tokio::sync::oneshot;
 use tokio_util::sync::CancellationToken;
 use tracing::debug;
 
@@ -21,6 +22,9 @@ pub type CmdSender = Sender<CopartCmd>;
 pub type CmdReceiver = Receiver<CopartCmd>;
 pub type ResponseReceiver = Receiver<CopartResponse>;
 pub type ResponseSender = Sender<CopartResponse>;
+/// Lots whose images were requested with a forced resync, set by `Navigator` and taken by
+/// `ResponseHandler` as the flag cannot travel in copart's url.
+pub type ForcedLotImages = Arc<Mutex<HashSet<LotNumber>>>;
 
 pub struct CopartBrowser;
 
@@ -29,7 +33,7 @@ impl CopartBrowser {
         host: Option<String>,
         port: Option<u16>,
         cancellation_token: CancellationToken,
-    ) -> Result<((CmdSender, ResponseReceiver), Arc<Notify>), GeneralError> {
+    ) -> Result<((CmdSender, ResponseReceiver), oneshot::Receiver<()>), GeneralError> {
         debug!("running browser on proxy: {:?}:{:?}", host, port);
         let mut args = vec![
             "--no-sandbox".to_string(),
@@ -73,21 +77,21 @@ impl CopartBrowser {
         cmd_receiver: CmdReceiver,
         resp_sender: ResponseSender,
         cancellation_token: &CancellationToken,
-    ) -> Arc<Notify> {
+    ) -> oneshot::Receiver<()> {
         let engine_task = BrowserEngineHandler::new(handler).handle();
         // setup_page must be called after the browser engine handler is started
         let page = Self::setup_page(&browser)
             .await
             .expect("failed to setup page");
 
-        let cmds_task = CmdsHandler::new(page.clone(), cmd_receiver).handle();
-        let http_task = HttpHandler::new(page.clone(), resp_sender.clone()).handle();
+        let forced = ForcedLotImages::default();
+        let cmds_task = CmdsHandler::new(page.clone(), cmd_receiver, forced.clone()).handle();
+        let http_task = HttpHandler::new(page.clone(), resp_sender.clone(), forced).handle();
         let ws_task = WsHandler::new(page.clone(), resp_sender.clone()).handle();
 
-        let done = Arc::new(Notify::new());
+        let (done_sender, done) = oneshot::channel();
         tokio::spawn({
             let cancellation_token = cancellation_token.clone();
-            let done = done.clone();
             async move {
                 cancellation_token.cancelled().await;
                 browser.close().await.expect("failed to close browser");
@@ -95,7 +99,7 @@ impl CopartBrowser {
                     .await
                     .expect("failed to await browser engine handler");
                 let _ = tokio::join!(cmds_task, http_task, ws_task);
-                done.notify_waiters();
+                let _ = done_sender.send(());
             }
         });
 
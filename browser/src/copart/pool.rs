@@ -5,8 +5,7 @@ use common::io::copart::{CopartCmd, CopartResponse};
 use common::io::error::GeneralError;
 use futures::StreamExt;
 use std::collections::VecDeque;
-use std::sync::Arc;
-use tokio::sync::Notify;
+use tokio::sync::oneshot;
 use tokio::task::AbortHandle;
 use tokio_util::sync::CancellationToken;
 use tracing::{error, info};
@@ -48,19 +47,19 @@ impl CopartBrowserPool {
         (pool, external_signaling)
     }
 
-    pub async fn run(self, num_workers: usize) -> Arc<Notify> {
-        let global_done = Arc::new(Notify::new());
+    pub async fn run(self, num_workers: usize) -> oneshot::Receiver<()> {
+        let (global_done_sender, global_done) = oneshot::channel();
 
         let (cmd_senders, browsers_done, mut aborts) = self.spawn_browsers(num_workers).await;
 
         let abort_cmd_receive = self.cmd_receive_handler(cmd_senders);
         aborts.push(abort_cmd_receive);
 
-        Self::done_handler(Arc::clone(&global_done), browsers_done, aborts);
+        Self::done_handler(global_done_sender, browsers_done, aborts);
         global_done
     }
 
-    async fn spawn_browser(&self) -> (CmdSender, Arc<Notify>, AbortHandle) {
+    async fn spawn_browser(&self) -> (CmdSender, oneshot::Receiver<()>, AbortHandle) {
         let ((cmd_sender, response_receiver), done) =
             CopartBrowser::run(None, None, self.cancellation_token.clone())
                 .await
@@ -71,7 +70,7 @@ impl CopartBrowserPool {
         (cmd_sender, done, abort)
     }
 
-    async fn spawn_proxied_browser(&self) -> (CmdSender, Arc<Notify>, AbortHandle) {
+    async fn spawn_proxied_browser(&self) -> (CmdSender, oneshot::Receiver<()>, AbortHandle) {
         let ((cmd_sender, response_receiver), done) = CopartBrowser::run(
             Some(self.host.clone()),
             Some(self.port),
@@ -88,7 +87,11 @@ impl CopartBrowserPool {
     async fn spawn_browsers(
         &self,
         num_workers: usize,
-    ) -> (VecDeque<CmdSender>, Vec<Arc<Notify>>, Vec<AbortHandle>) {
+    ) -> (
+        VecDeque<CmdSender>,
+        Vec<oneshot::Receiver<()>>,
+        Vec<AbortHandle>,
+    ) {
         futures::stream::iter(0..num_workers)
             .map(async |_| self.spawn_proxied_browser().await)
             .buffer_unordered(num_workers)
@@ -145,7 +148,9 @@ impl CopartBrowserPool {
                             }
                         }
                     }
-                    CopartCmd::LotSearch { .. } | CopartCmd::LotImages(_) => {
+                    CopartCmd::LotSearch { .. }
+                    | CopartCmd::LotImages(_)
+                    | CopartCmd::ForceLotImages(_) => {
                         if let Err(e) = handle_cmd(cmd, &mut local_cmd_senders).await {
                             error!("failed to handle global cmd receive: {}", e);
                         }
@@ -158,16 +163,15 @@ impl CopartBrowserPool {
     }
 
     fn done_handler(
-        global_done: Arc<Notify>,
-        browsers_done: Vec<Arc<Notify>>,
+        global_done: oneshot::Sender<()>,
+        browsers_done: Vec<oneshot::Receiver<()>>,
         aborts: Vec<AbortHandle>,
     ) {
         tokio::spawn({
             async move {
-                futures::future::join_all(browsers_done.iter().map(async |n| n.notified().await))
-                    .await;
+                futures::future::join_all(browsers_done).await;
                 aborts.iter().for_each(|handle| handle.abort());
-                global_done.notify_waiters();
+                let _ = global_done.send(());
                 info!("pool closed")
             }
         });
@@ -1,7 +1,7 @@
 /// Represents response for lot vehicles on the whole page
 pub mod lot_search {
     use crate::impl_display_and_debug;
-    use common::io::copart::{LotVehicle, LotVehicleVector};
+    use common::io::copart::{LotPrices, LotVehicle, LotVehicleVector};
     use serde::{Deserialize, Serialize};
     use std::collections::HashMap;
     use std::fmt::{Debug, Formatter};
@@ -242,6 +242,14 @@ pub mod lot_search {
                         fuel_type: l.ft,
                         drive_type: l.drv,
                         keys_status: l.hk,
+                        trim: l.mtrim,
+                        yard: Some(l.yn),
+                        prices: Some(LotPrices {
+                            current_bid: l.dynamic_lot_details.current_bid as f64,
+                            buy_today_bid: l.dynamic_lot_details.buy_today_bid,
+                            high_bid: l.hb,
+                            sale_status: l.dynamic_lot_details.sale_status,
+                        }),
                     })
                     .collect(),
             )
@@ -320,6 +328,9 @@ pub mod lot_images {
 }
 
 pub mod auction {
+    use common::io::copart::{AuctionResultResponse, LotNumber};
+    use common::io::error::GeneralError;
+
     pub mod plain {
         use base64::Engine;
         use serde::{Deserialize, Deserializer, Serialize};
@@ -521,4 +532,110 @@ pub mod auction {
             }
         }
     }
+
+    impl TryFrom<plain::SoldMessage> for AuctionResultResponse {
+        type Error = GeneralError;
+
+        fn try_from(value: plain::SoldMessage) -> Result<Self, Self::Error> {
+            auction_result(
+                &value.lot_no,
+                &value.bid,
+                value.buyer_ctr,
+                value.buyer_st,
+                &value.min_met,
+            )
+        }
+    }
+
+    impl TryFrom<solace::SoldMessage> for AuctionResultResponse {
+        type Error = GeneralError;
+
+        fn try_from(value: solace::SoldMessage) -> Result<Self, Self::Error> {
+            auction_result(
+                &value.lot_no,
+                &value.bid,
+                value.buyer_ctr,
+                value.buyer_st,
+                &value.min_met,
+            )
+        }
+    }
+
+    /// Sold messages carry no sale date, the persister dates the result by the lot's
+    /// listed one, which redeliveries share.
+    fn auction_result(
+        lot_no: &str,
+        bid: &str,
+        buyer_country: String,
+        buyer_state: String,
+        min_met: &str,
+    ) -> Result<AuctionResultResponse, GeneralError> {
+        Ok(AuctionResultResponse {
+            lot_number: lot_no.trim().parse::<LotNumber>()?,
+            final_bid: bid.trim().parse::<f64>()?,
+            buyer_country: non_empty(buyer_country),
+            buyer_state: non_empty(buyer_state),
+            min_met: matches!(min_met.trim(), "Y" | "y" | "true" | "1"),
+            sold_at: None,
+        })
+    }
+
+    fn non_empty(value: String) -> Option<String> {
+        let value = value.trim();
+        (!value.is_empty()).then(|| value.to_string())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use base64::Engine;
+
+        /// Body of a sold event as the auction sends it, base64 encoded in both frames.
+        const SOLD: &str = r#"{"@class":"com.copart.g2.auction.message.SoldMessage","EMPTY":false,"FORMATNAME":"SOLD","APRFLG":"N","BID":"4250","ATTRIBUTE":"","BUYERST":" TX","BUYERCTR":"USA","MINMET":"Y","LOTNO":"61234567","BUYERNO":"512345","TYPE":"SOLD"}"#;
+
+        fn encoded(body: &str) -> String {
+            base64::engine::general_purpose::STANDARD.encode(body)
+        }
+
+        fn expect_sold(result: AuctionResultResponse) {
+            assert_eq!(result.lot_number, 61234567);
+            assert_eq!(result.final_bid, 4250.0);
+            assert_eq!(result.buyer_country.as_deref(), Some("USA"));
+            assert_eq!(result.buyer_state.as_deref(), Some("TX"));
+            assert!(result.min_met);
+            assert!(result.sold_at.is_none());
+        }
+
+        #[test]
+        fn test_plain_sold_message() -> Result<(), Box<dyn std::error::Error>> {
+            let frame = format!(r#"[{{"d":["61234567",{{"Data":"{}"}}]}}]"#, encoded(SOLD));
+            let msg = serde_json::from_str::<plain::SoldMessage>(&frame)?;
+            expect_sold(msg.try_into()?);
+            Ok(())
+        }
+
+        #[test]
+        fn test_solace_sold_message() -> Result<(), Box<dyn std::error::Error>> {
+            let frame = format!(r#"{{"data":"{}"}}"#, encoded(SOLD));
+            let msg = serde_json::from_str::<solace::SoldMessage>(&frame)?;
+            expect_sold(msg.try_into()?);
+
+            // an unsold lot has no buyer
+            let unsold = SOLD
+                .replace(r#""BUYERST":" TX""#, r#""BUYERST":"""#)
+                .replace(r#""BUYERCTR":"USA""#, r#""BUYERCTR":"""#)
+                .replace(r#""MINMET":"Y""#, r#""MINMET":"N""#);
+            let frame = format!(r#"{{"data":"{}"}}"#, encoded(&unsold));
+            let result = AuctionResultResponse::try_from(serde_json::from_str::<
+                solace::SoldMessage,
+            >(&frame)?)?;
+            assert!(result.buyer_country.is_none() && result.buyer_state.is_none());
+            assert!(!result.min_met);
+
+            let frame = format!(r#"{{"data":"{}"}}"#, encoded(&SOLD.replace("4250", "n/a")));
+            let msg = serde_json::from_str::<solace::SoldMessage>(&frame)?;
+            assert!(AuctionResultResponse::try_from(msg).is_err());
+            Ok(())
+        }
+    }
 }
@@ -0,0 +1,41 @@
+//! Embeds the SQL of every migration next to diesel's embedded migrations, which keep
+//! theirs private, so migration previews print it without touching a database.
+
+use std::fmt::Write;
+use std::path::Path;
+
+fn main() {
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").expect("cargo sets the manifest dir");
+    let migrations = Path::new(&manifest_dir).join("src/persistence/migrations");
+    println!("cargo:rerun-if-changed={}", migrations.display());
+
+    let mut names = std::fs::read_dir(&migrations)
+        .expect("failed to read migrations dir")
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().join("up.sql").is_file())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    names.sort();
+
+    let mut generated =
+        String::from("pub(crate) const MIGRATION_SQL: &[(&str, &str, Option<&str>)] = &[\n");
+    for name in names {
+        let dir = migrations.join(&name);
+        let down = dir.join("down.sql");
+        let down = match down.is_file() {
+            true => format!("Some(include_str!({:?}))", down.display().to_string()),
+            false => "None".to_string(),
+        };
+        writeln!(
+            generated,
+            "    ({name:?}, include_str!({:?}), {down}),",
+            dir.join("up.sql").display().to_string()
+        )
+        .expect("writing to a string");
+    }
+    generated.push_str("];\n");
+
+    let out_dir = std::env::var("OUT_DIR").expect("cargo sets the out dir");
+    std::fs::write(Path::new(&out_dir).join("migration_sql.rs"), generated)
+        .expect("failed to write migration sql");
+}
@@ -43,4 +43,8 @@ async fn main() {
         .create_absent_topic("copart_cmd_login_refresh")
         .await
         .expect("failed to recreate `copart_cmd_login_refresh` topic");
+    admin
+        .create_absent_topic("copart_response_auction_result")
+        .await
+        .expect("failed to recreate `copart_response_auction_result` topic");
 }
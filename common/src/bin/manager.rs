@@ -1,7 +1,10 @@
 use clap::Parser;
+use std::io::Write;
 
 mod cli {
     use clap::{Parser, Subcommand};
+    use common::export::{DEFAULT_BATCH_SIZE, DEFAULT_LAG_SECS, DEFAULT_MAX_ROWS_PER_FILE};
+    use common::persistence::backfill::DEFAULT_BACKFILL_BATCH_SIZE;
 
     #[derive(Parser)]
     #[command(
@@ -27,6 +30,34 @@ mod cli {
             #[clap(subcommand)]
             cmd: MinioCommand,
         },
+        Storage {
+            #[clap(subcommand)]
+            cmd: StorageCommand,
+        },
+        Takedown {
+            #[clap(subcommand)]
+            cmd: TakedownCommand,
+        },
+        Stub {
+            #[clap(subcommand)]
+            cmd: StubCommand,
+        },
+        /// Exports lots with images and auction outcomes to parquet partitioned by sale date
+        Export {
+            /// Local directory or bucket prefix as `s3://bucket/prefix`
+            #[arg(long)]
+            output: String,
+            /// Ignore the stored watermark and export all rows
+            #[arg(long)]
+            full: bool,
+            #[arg(long, default_value_t = DEFAULT_BATCH_SIZE)]
+            batch_size: usize,
+            #[arg(long, default_value_t = DEFAULT_MAX_ROWS_PER_FILE)]
+            max_rows_per_file: usize,
+            /// Leave rows updated within this many seconds to the next export
+            #[arg(long, default_value_t = DEFAULT_LAG_SECS)]
+            lag_secs: i64,
+        },
     }
 
     #[derive(Subcommand)]
@@ -39,9 +70,110 @@ mod cli {
 
     #[derive(Subcommand)]
     pub(crate) enum PostgresCommand {
-        Migrate,
-        RevertAll,
-        Redo,
+        /// Lists applied and pending migrations with the time they were applied
+        Status,
+        /// Runs pending migrations, reverts newer ones when `--to` is older than the latest applied
+        Migrate {
+            /// Migration version to stop at, e.g. `20261018091512`
+            #[arg(long)]
+            to: Option<String>,
+            /// Print the SQL of every step without applying it
+            #[arg(long)]
+            dry_run: bool,
+            /// Skip the confirmation of reverting migrations
+            #[arg(long)]
+            yes: bool,
+        },
+        /// Reverts the latest applied migrations
+        Revert {
+            #[arg(long, default_value_t = 1)]
+            steps: usize,
+            #[arg(long)]
+            dry_run: bool,
+            #[arg(long)]
+            yes: bool,
+        },
+        RevertAll {
+            #[arg(long)]
+            dry_run: bool,
+            #[arg(long)]
+            yes: bool,
+        },
+        Redo {
+            #[arg(long)]
+            yes: bool,
+        },
+        /// Re-derives canonical reference ids and the vin decode of all lot vehicles, run
+        /// after changing aliases or upgrading to a release deriving new fields
+        #[command(alias = "recanonicalize")]
+        BackfillLotVehicles {
+            #[arg(long, default_value_t = DEFAULT_BACKFILL_BATCH_SIZE)]
+            batch_size: i64,
+        },
+        /// Schedules copart images synced before perceptual hashes were computed for a
+        /// resync, the persister's retry queue requests their lots
+        BackfillPerceptualHashes,
+    }
+
+    #[derive(Subcommand)]
+    pub(crate) enum TakedownCommand {
+        /// Deletes lot image rows and bucket objects of vins with an active takedown
+        Erase {
+            /// Only this takedown, otherwise every active one not erased yet
+            #[arg(long)]
+            id: Option<i32>,
+            /// Erase already erased takedowns again
+            #[arg(long)]
+            all: bool,
+            /// Writes the verification report as json
+            #[arg(long)]
+            report: Option<std::path::PathBuf>,
+            #[arg(long)]
+            yes: bool,
+        },
+    }
+
+    #[derive(Subcommand)]
+    pub(crate) enum StorageCommand {
+        /// Compares bucket objects with the lot image variants and reports missing and orphaned ones
+        Reconcile {
+            #[arg(long, default_value_t = common::persistence::reconcile::DEFAULT_RECONCILE_BATCH_SIZE)]
+            batch_size: i64,
+            /// Deletes objects no variant points at
+            #[arg(long)]
+            delete_orphans: bool,
+            /// Objects modified within this many seconds are not orphans yet
+            #[arg(long, default_value_t = common::persistence::reconcile::DEFAULT_ORPHAN_GRACE.as_secs())]
+            orphan_grace_secs: u64,
+            /// Sends a lot images command for every lot with a missing object or variant
+            #[arg(long)]
+            requeue: bool,
+            /// Writes the report as json
+            #[arg(long)]
+            report: Option<std::path::PathBuf>,
+            #[arg(long)]
+            yes: bool,
+        },
+        /// Sends a lot images command for copart lots, images with an unchanged source are skipped
+        Resync {
+            #[arg(required = true)]
+            lot_numbers: Vec<i32>,
+            /// Downloads and uploads every image again
+            #[arg(long)]
+            force: bool,
+        },
+    }
+
+    #[derive(Subcommand)]
+    pub(crate) enum StubCommand {
+        /// Publishes the lots and auction results of a fixture as stub provider responses
+        Publish {
+            /// Json file with `lots` and optional `auction_results`
+            #[arg(long)]
+            fixture: std::path::PathBuf,
+            #[arg(long, default_value_t = common::io::provider::stub::StubSource::DEFAULT_PAGE_SIZE)]
+            page_size: usize,
+        },
     }
 
     #[derive(Subcommand)]
@@ -50,6 +182,17 @@ mod cli {
         DeleteBucket,
         RecreateBucket,
         CreateAbsentBucket,
+        /// Moves lot images keyed by lot and sequence to their sha256 content keys
+        Rekey {
+            #[arg(long, default_value_t = common::persistence::rekey::DEFAULT_REKEY_BATCH_SIZE)]
+            batch_size: i64,
+            /// Reads and hashes the objects and reports the saving without writing anything
+            #[arg(long)]
+            dry_run: bool,
+            /// Writes the report as json
+            #[arg(long)]
+            report: Option<std::path::PathBuf>,
+        },
     }
 }
 
@@ -64,6 +207,10 @@ mod kafka {
         "copart_response_lot_images",
         "copart_cmd_auction",
         "copart_cmd_login_refresh",
+        "copart_response_auction_result",
+        "stub_cmd_lot_search",
+        "stub_response_lot_search",
+        "stub_response_auction_result",
     ];
 
     const TOPICS_WITH_OPTS: &[(&str, &[(&str, &str)])] = &[(
@@ -152,33 +299,89 @@ mod kafka {
 }
 
 mod postgres {
-    use common::persistence::PG_POOL;
+    use common::io::provider::Provider;
+    use common::persistence::backfill;
+    use common::persistence::migrate::{self, MigrationStatus, MigrationStep};
+    use common::persistence::related;
+    use common::persistence::{PG_MIGRATIONS, PG_POOL};
     use diesel_async::AsyncMigrationHarness;
-    use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+    use diesel_migrations::MigrationHarness;
+
+    async fn statuses() -> Vec<MigrationStatus> {
+        let mut conn = PG_POOL.get().await.expect("failed to get pg connection");
+        migrate::status(&mut conn)
+            .await
+            .expect("failed to read migration status")
+    }
 
-    pub const PG_MIGRATIONS: EmbeddedMigrations = embed_migrations!("src/persistence/migrations");
+    async fn execute(plan: Vec<MigrationStep>, dry_run: bool, yes: bool) {
+        if plan.is_empty() {
+            println!("Nothing to do");
+            return;
+        }
+
+        if dry_run {
+            let steps = migrate::preview(&plan).expect("failed to read migration sql");
+            for (step, name, sql) in steps {
+                println!("-- {step}: {name}");
+                println!("{}", sql.trim_end());
+            }
+            println!("Dry run, nothing was applied");
+            return;
+        }
+
+        println!("Plan:");
+        for step in &plan {
+            println!("  {step}");
+        }
+        if migrate::is_destructive(&plan) && !crate::confirm("Reverting drops data", yes) {
+            println!("Aborted");
+            return;
+        }
 
-    pub(crate) async fn migrate() {
-        println!("Running migrations");
         let conn = PG_POOL.get().await.expect("failed to get pg connection");
-        let mut harness = AsyncMigrationHarness::new(conn);
-        harness
-            .run_pending_migrations(PG_MIGRATIONS)
-            .expect("failed to run migrations");
-        println!("Database migrated")
+        migrate::apply(&mut AsyncMigrationHarness::new(conn), &plan)
+            .expect("failed to apply migrations");
+        println!("Applied `{}` steps", plan.len())
     }
 
-    pub(crate) async fn revert_all() {
-        println!("Reverting all migrations");
-        let conn = PG_POOL.get().await.expect("failed to get pg connection");
-        let mut harness = AsyncMigrationHarness::new(conn);
-        harness
-            .revert_all_migrations(PG_MIGRATIONS)
-            .expect("failed to revert migrations");
-        println!("Database reverted")
+    pub(crate) async fn status() {
+        let statuses = statuses().await;
+        for status in &statuses {
+            let applied_at = status
+                .applied_at
+                .map(|at| at.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_else(|| "pending".to_string());
+            println!("{:<20} {:<20} {}", status.version, applied_at, status.name);
+        }
+        let pending = statuses.iter().filter(|s| s.applied_at.is_none()).count();
+        println!(
+            "Applied: `{}`, pending: `{pending}`",
+            statuses.len() - pending
+        )
+    }
+
+    pub(crate) async fn migrate(to: Option<String>, dry_run: bool, yes: bool) {
+        let plan = migrate::plan_migrate_to(&statuses().await, to.as_deref())
+            .expect("failed to plan migrations");
+        execute(plan, dry_run, yes).await
     }
 
-    pub(crate) async fn redo() {
+    pub(crate) async fn revert(steps: usize, dry_run: bool, yes: bool) {
+        let plan = migrate::plan_revert(&statuses().await, steps);
+        execute(plan, dry_run, yes).await
+    }
+
+    pub(crate) async fn revert_all(dry_run: bool, yes: bool) {
+        let plan = migrate::plan_revert(&statuses().await, usize::MAX);
+        execute(plan, dry_run, yes).await
+    }
+
+    pub(crate) async fn redo(yes: bool) {
+        if !crate::confirm("Redo reverts all migrations and drops data", yes) {
+            println!("Aborted");
+            return;
+        }
         println!("Redoing all migrations");
         let conn = PG_POOL.get().await.expect("failed to get pg connection");
         let mut harness = AsyncMigrationHarness::new(conn);
@@ -190,95 +393,329 @@ mod postgres {
             .expect("failed to run migrations");
         println!("Database redone")
     }
+
+    pub(crate) async fn backfill_lot_vehicles(batch_size: i64) {
+        println!("Backfilling lot vehicles");
+        let mut conn = PG_POOL.get().await.expect("failed to get pg connection");
+        let report = backfill::backfill_lot_vehicles(&mut conn, batch_size)
+            .await
+            .expect("failed to backfill lot vehicles");
+        println!(
+            "Lot vehicles backfilled, checked: `{}`, updated: `{}`",
+            report.checked, report.updated
+        )
+    }
+
+    pub(crate) async fn backfill_perceptual_hashes() {
+        let mut conn = PG_POOL.get().await.expect("failed to get pg connection");
+        let scheduled = related::schedule_unhashed_images(&mut conn, Provider::Copart)
+            .await
+            .expect("failed to schedule unhashed images");
+        println!("Scheduled `{scheduled}` unhashed images for a resync")
+    }
 }
 
 mod minio {
-    use aws_sdk_s3::types::{BucketLocationConstraint, CreateBucketConfiguration};
-    use common::bucket::S3_CLIENT;
-
-    const BUCKET_NAME: &str = "cars-lot-images";
+    use common::bucket::OBJECT_STORE;
+    use common::persistence::rekey::{self, RekeyOptions};
+    use common::persistence::PG_POOL;
+    use std::path::PathBuf;
 
     pub(crate) async fn create_bucket() {
-        println!("Creating bucket");
-        S3_CLIENT
+        println!("Creating bucket `{}`", OBJECT_STORE.location());
+        OBJECT_STORE
             .create_bucket()
-            .bucket(BUCKET_NAME)
-            .create_bucket_configuration(
-                CreateBucketConfiguration::builder()
-                    .location_constraint(BucketLocationConstraint::EuCentral1)
-                    .build(),
-            )
-            .send()
             .await
             .expect("failed to create bucket");
         println!("Bucket created");
     }
 
     pub(crate) async fn delete_bucket() {
-        println!("Deleting bucket");
-        S3_CLIENT
+        println!("Deleting bucket `{}`", OBJECT_STORE.location());
+        OBJECT_STORE
             .delete_bucket()
-            .bucket(BUCKET_NAME)
-            .send()
             .await
             .expect("failed to delete bucket");
         println!("Bucket deleted");
     }
 
     pub(crate) async fn recreate_bucket() {
-        println!("Recreating bucket");
-        S3_CLIENT
+        println!("Recreating bucket `{}`", OBJECT_STORE.location());
+        OBJECT_STORE
             .delete_bucket()
-            .bucket(BUCKET_NAME)
-            .send()
             .await
             .expect("failed to delete bucket");
-        S3_CLIENT
+        OBJECT_STORE
             .create_bucket()
-            .bucket(BUCKET_NAME)
-            .create_bucket_configuration(
-                CreateBucketConfiguration::builder()
-                    .location_constraint(BucketLocationConstraint::EuCentral1)
-                    .build(),
-            )
-            .send()
             .await
             .expect("failed to create bucket");
         println!("Bucket recreated");
     }
 
     pub(crate) async fn create_absent_bucket() {
-        println!("Creating absent bucket");
-        let bucket_list_response = S3_CLIENT
-            .list_buckets()
-            .send()
+        println!("Creating absent bucket `{}`", OBJECT_STORE.location());
+        let exists = OBJECT_STORE
+            .bucket_exists()
             .await
-            .expect("failed to list buckets");
-
-        let bucket_names = bucket_list_response
-            .buckets()
-            .iter()
-            .map(|b| b.name.clone().unwrap_or("".to_string()))
-            .collect::<Vec<_>>();
-
-        if bucket_names.contains(&BUCKET_NAME.to_owned()) {
+            .expect("failed to check bucket");
+        if exists {
             println!("Bucket already exists");
             return;
         }
 
-        S3_CLIENT
+        OBJECT_STORE
             .create_bucket()
-            .bucket(BUCKET_NAME)
-            .create_bucket_configuration(
-                CreateBucketConfiguration::builder()
-                    .location_constraint(BucketLocationConstraint::EuCentral1)
-                    .build(),
-            )
-            .send()
             .await
             .expect("failed to create bucket");
         println!("Bucket created");
     }
+
+    pub(crate) async fn rekey(batch_size: i64, dry_run: bool, report: Option<PathBuf>) {
+        println!("Rekeying lot images");
+        let mut conn = PG_POOL.get().await.expect("failed to get pg connection");
+        let summary = rekey::rekey_lot_images(
+            &mut conn,
+            OBJECT_STORE.as_ref(),
+            RekeyOptions {
+                batch_size,
+                dry_run,
+            },
+        )
+        .await
+        .expect("failed to rekey lot images");
+        println!(
+            "Rekeyed `{}` variants, legacy objects: `{}`, uploaded: `{}`, deleted: `{}`, kept: `{}`, missing: `{}`",
+            summary.variants_rekeyed,
+            summary.legacy_objects,
+            summary.objects_uploaded,
+            summary.objects_deleted,
+            summary.objects_kept.len(),
+            summary.missing_objects.len()
+        );
+        println!(
+            "Storage before: `{}` bytes, after: `{}` bytes, saved: `{}` bytes{}",
+            summary.bytes_before,
+            summary.bytes_after,
+            summary.bytes_saved,
+            if dry_run { " (dry run)" } else { "" }
+        );
+
+        if let Some(path) = report {
+            let json = serde_json::to_string_pretty(&summary).expect("failed to serialize report");
+            std::fs::write(&path, json).expect("failed to write report");
+            println!("Report written to `{}`", path.display());
+        }
+    }
+}
+
+mod storage {
+    use common::bucket::OBJECT_STORE;
+    use common::config::CONFIG;
+    use common::io::copart::{Copart, CopartCmd, LotNumber};
+    use common::io::provider::{LotProvider, Provider};
+    use common::kafka::{KafkaSender, ToTopic};
+    use common::persistence::reconcile::{self, ReconcileOptions};
+    use common::persistence::PG_POOL;
+    use std::path::PathBuf;
+
+    pub(crate) async fn reconcile(
+        options: ReconcileOptions,
+        requeue: bool,
+        report: Option<PathBuf>,
+        yes: bool,
+    ) {
+        if options.delete_orphans
+            && !crate::confirm(
+                &format!("Deleting orphaned objects of `{}`", OBJECT_STORE.location()),
+                yes,
+            )
+        {
+            println!("Aborted");
+            return;
+        }
+
+        println!(
+            "Reconciling `{}` with the database",
+            OBJECT_STORE.location()
+        );
+        let mut conn = PG_POOL.get().await.expect("failed to get pg connection");
+        let mut summary = reconcile::reconcile(&mut conn, OBJECT_STORE.as_ref(), options)
+            .await
+            .expect("failed to reconcile storage");
+
+        if requeue {
+            let sender = KafkaSender::new(CONFIG.kafka.url.to_owned());
+            for lot in &summary.lots_to_resync {
+                let cmd = match lot.provider {
+                    Provider::Copart => Copart::lot_images_cmd(lot.lot_number),
+                    Provider::Stub => None,
+                };
+                let Some(cmd) = cmd else {
+                    println!(
+                        "Lot `{}` at `{}` has no lot images command",
+                        lot.lot_number, lot.provider
+                    );
+                    continue;
+                };
+                sender
+                    .send(&cmd, &cmd.to_topic())
+                    .await
+                    .expect("failed to send lot images command");
+                summary.lots_requeued += 1;
+            }
+        }
+
+        println!(
+            "Checked `{}` keys and `{}` objects, missing: `{}`, orphaned: `{}` (`{}` bytes), deleted: `{}`, recent: `{}`",
+            summary.keys_checked,
+            summary.objects_checked,
+            summary.missing_objects.len(),
+            summary.orphaned_objects.len(),
+            summary.orphaned_bytes,
+            summary.orphans_deleted,
+            summary.recent_unreferenced
+        );
+        println!(
+            "Images without variants: `{}`, lots to resync: `{}`, requeued: `{}`",
+            summary.images_without_variants.len(),
+            summary.lots_to_resync.len(),
+            summary.lots_requeued
+        );
+
+        if let Some(path) = report {
+            let json = serde_json::to_string_pretty(&summary).expect("failed to serialize report");
+            std::fs::write(&path, json).expect("failed to write report");
+            println!("Report written to `{}`", path.display());
+        }
+    }
+
+    pub(crate) async fn resync(lot_numbers: Vec<LotNumber>, force: bool) {
+        let sender = KafkaSender::new(CONFIG.kafka.url.to_owned());
+        for lot_number in &lot_numbers {
+            let cmd = match force {
+                true => CopartCmd::ForceLotImages(*lot_number),
+                false => CopartCmd::LotImages(*lot_number),
+            };
+            sender
+                .send(&cmd, &cmd.to_topic())
+                .await
+                .expect("failed to send lot images command");
+        }
+        println!(
+            "Requested images of `{}` lots, force: `{force}`",
+            lot_numbers.len()
+        );
+    }
+}
+
+mod takedown {
+    use common::persistence::erasure::{self, ErasureReport};
+    use common::persistence::models::takedown::VinTakedown;
+    use common::persistence::PG_POOL;
+    use std::path::PathBuf;
+
+    pub(crate) async fn erase(id: Option<i32>, all: bool, report: Option<PathBuf>, yes: bool) {
+        let mut conn = PG_POOL.get().await.expect("failed to get pg connection");
+        let takedowns = erasure::takedowns_to_erase(&mut conn, all || id.is_some())
+            .await
+            .expect("failed to load takedowns")
+            .into_iter()
+            .filter(|t| id.is_none_or(|id| t.id == id))
+            .collect::<Vec<VinTakedown>>();
+        if takedowns.is_empty() {
+            println!("No active takedowns to erase");
+            return;
+        }
+
+        println!("Takedowns to erase:");
+        for takedown in &takedowns {
+            println!("  `{}` vin `{}`", takedown.id, takedown.vin);
+        }
+        if !crate::confirm("Erasure permanently deletes images", yes) {
+            println!("Aborted");
+            return;
+        }
+
+        let mut reports: Vec<ErasureReport> = vec![];
+        for takedown in &takedowns {
+            let report = erasure::erase_takedown(&mut conn, takedown)
+                .await
+                .expect("failed to erase takedown");
+            println!(
+                "Takedown `{}`: lots `{}`, deleted rows: `{}`, deleted objects: `{}`, shared objects kept: `{}`, verified: `{}`",
+                report.takedown_id,
+                report
+                    .lots
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                report.lot_image_rows_deleted,
+                report.objects_deleted.len(),
+                report.objects_shared.len(),
+                report.verified
+            );
+            reports.push(report);
+        }
+
+        if let Some(path) = report {
+            let json = serde_json::to_string_pretty(&reports).expect("failed to serialize report");
+            std::fs::write(&path, json).expect("failed to write report");
+            println!("Report written to `{}`", path.display());
+        }
+        let unverified = reports.iter().filter(|r| !r.verified).count();
+        if unverified > 0 {
+            println!("`{unverified}` erasures are not verified, run them again");
+            std::process::exit(1);
+        }
+    }
+}
+
+mod stub {
+    use common::config::CONFIG;
+    use common::io::provider::stub::{StubCmd, StubSource};
+    use common::kafka::{KafkaSender, ToTopic};
+    use std::path::Path;
+
+    pub(crate) async fn publish(fixture: &Path, page_size: usize) {
+        let source = StubSource::from_file(fixture)
+            .expect("failed to read stub fixture")
+            .page_size(page_size);
+        let sender = KafkaSender::new(CONFIG.kafka.url.to_owned());
+
+        let responses = (0..source.pages())
+            .map(|page_number| source.respond(&StubCmd::LotSearch { page_number }))
+            .chain(source.auction_results());
+        let mut published = 0;
+        for response in responses {
+            sender
+                .send(&response, &response.to_topic())
+                .await
+                .expect("failed to publish stub response");
+            published += 1;
+        }
+        println!("Published `{published}` stub responses")
+    }
+}
+
+mod export {
+    use common::export::{ExportOptions, ExportTarget};
+    use common::persistence::PG_POOL;
+
+    pub(crate) async fn export(options: ExportOptions, output: &str) {
+        let target = ExportTarget::parse(output);
+        println!("Exporting to `{target:?}`");
+        let mut conn = PG_POOL.get().await.expect("failed to get pg connection");
+        let summary = common::export::export(&mut conn, &target, options)
+            .await
+            .expect("failed to export");
+        println!(
+            "Exported `{}` rows to `{}` files, watermark: `{:?}`",
+            summary.rows,
+            summary.files.len(),
+            summary.watermark
+        );
+    }
 }
 
 #[tokio::main]
@@ -288,6 +725,71 @@ async fn main() {
         cli::Command::Kafka { cmd } => dispatch_kafka(cmd).await,
         cli::Command::Postgres { cmd } => dispatch_postgres(cmd).await,
         cli::Command::Minio { cmd } => dispatch_minio(cmd).await,
+        cli::Command::Storage { cmd } => dispatch_storage(cmd).await,
+        cli::Command::Takedown {
+            cmd:
+                cli::TakedownCommand::Erase {
+                    id,
+                    all,
+                    report,
+                    yes,
+                },
+        } => takedown::erase(id, all, report, yes).await,
+        cli::Command::Stub {
+            cmd: cli::StubCommand::Publish { fixture, page_size },
+        } => stub::publish(&fixture, page_size).await,
+        cli::Command::Export {
+            output,
+            full,
+            batch_size,
+            max_rows_per_file,
+            lag_secs,
+        } => {
+            let options = common::export::ExportOptions {
+                full,
+                batch_size,
+                max_rows_per_file,
+                lag_secs,
+            };
+            export::export(options, &output).await
+        }
+    }
+}
+
+/// Asks to type `yes` unless `--yes` was passed.
+fn confirm(action: &str, yes: bool) -> bool {
+    if yes {
+        return true;
+    }
+    print!("{action}, type `yes` to continue: ");
+    std::io::stdout().flush().expect("failed to flush stdout");
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .expect("failed to read answer");
+    answer.trim() == "yes"
+}
+
+async fn dispatch_storage(cmd: cli::StorageCommand) {
+    match cmd {
+        cli::StorageCommand::Reconcile {
+            batch_size,
+            delete_orphans,
+            orphan_grace_secs,
+            requeue,
+            report,
+            yes,
+        } => {
+            let options = common::persistence::reconcile::ReconcileOptions {
+                batch_size,
+                delete_orphans,
+                orphan_grace: std::time::Duration::from_secs(orphan_grace_secs),
+            };
+            storage::reconcile(options, requeue, report, yes).await
+        }
+        cli::StorageCommand::Resync { lot_numbers, force } => {
+            storage::resync(lot_numbers, force).await
+        }
     }
 }
 
@@ -302,9 +804,25 @@ async fn dispatch_kafka(cmd: cli::KafkaCommand) {
 
 async fn dispatch_postgres(cmd: cli::PostgresCommand) {
     match cmd {
-        cli::PostgresCommand::Migrate => postgres::migrate().await,
-        cli::PostgresCommand::RevertAll => postgres::revert_all().await,
-        cli::PostgresCommand::Redo => postgres::redo().await,
+        cli::PostgresCommand::Status => postgres::status().await,
+        cli::PostgresCommand::Migrate { to, dry_run, yes } => {
+            postgres::migrate(to, dry_run, yes).await
+        }
+        cli::PostgresCommand::Revert {
+            steps,
+            dry_run,
+            yes,
+        } => postgres::revert(steps, dry_run, yes).await,
+        cli::PostgresCommand::RevertAll { dry_run, yes } => {
+            postgres::revert_all(dry_run, yes).await
+        }
+        cli::PostgresCommand::Redo { yes } => postgres::redo(yes).await,
+        cli::PostgresCommand::BackfillLotVehicles { batch_size } => {
+            postgres::backfill_lot_vehicles(batch_size).await
+        }
+        cli::PostgresCommand::BackfillPerceptualHashes => {
+            postgres::backfill_perceptual_hashes().await
+        }
     };
 }
 
@@ -314,5 +832,10 @@ async fn dispatch_minio(cmd: cli::MinioCommand) {
         cli::MinioCommand::DeleteBucket => minio::delete_bucket().await,
         cli::MinioCommand::RecreateBucket => minio::recreate_bucket().await,
         cli::MinioCommand::CreateAbsentBucket => minio::create_absent_bucket().await,
+        cli::MinioCommand::Rekey {
+            batch_size,
+            dry_run,
+            report,
+        } => minio::rekey(batch_size, dry_run, report).await,
     }
 }
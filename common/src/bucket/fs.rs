@@ -0,0 +1,306 @@
+use crate::bucket::store::{ObjectMeta, ObjectStore, StoreError};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Objects as files under a root directory, a key's `/` separated segments are directories.
+/// Content types are not kept and presigned urls are plain `file://` urls.
+pub struct FsStore {
+    root: PathBuf,
+    location: String,
+}
+
+impl FsStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let root = root.into();
+        Self {
+            location: root.display().to_string(),
+            root,
+        }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, StoreError> {
+        let relative = Path::new(key);
+        let valid = !key.is_empty()
+            && relative
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)));
+        if !valid {
+            return Err(StoreError::InvalidKey(key.to_string()));
+        }
+        Ok(self.root.join(relative))
+    }
+
+    fn key(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let segments = relative
+            .components()
+            .map(|c| c.as_os_str().to_str())
+            .collect::<Option<Vec<_>>>()?;
+        Some(segments.join("/"))
+    }
+
+    /// Sibling file an object is written to before it is renamed to its path, unique per
+    /// write so concurrent writes of a key never write into the same file
+    async fn partial_path(&self, key: &str) -> Result<(PathBuf, PathBuf), StoreError> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let partial = path.with_file_name(format!(
+            "{}.{}{PARTIAL_SUFFIX}",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            Uuid::new_v4().as_simple()
+        ));
+        Ok((partial, path))
+    }
+
+    /// Every file under the root, directories are walked depth first
+    async fn walk(&self) -> Result<Vec<ObjectMeta>, StoreError> {
+        let mut objects = vec![];
+        let mut dirs = vec![self.root.clone()];
+        while let Some(dir) = dirs.pop() {
+            let mut entries = match tokio::fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let metadata = entry.metadata().await?;
+                let path = entry.path();
+                if metadata.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+                match self.key(&path) {
+                    Some(key) if !is_partial(&key) => objects.push(ObjectMeta {
+                        key,
+                        size: metadata.len(),
+                        last_modified: metadata.modified().ok(),
+                    }),
+                    _ => {}
+                }
+            }
+        }
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+}
+
+const PARTIAL_SUFFIX: &str = ".partial";
+
+fn is_partial(key: &str) -> bool {
+    key.ends_with(PARTIAL_SUFFIX)
+}
+
+/// Renames the written partial file to the object's path, a partial file left by a failed
+/// write or rename is removed
+async fn finish(
+    partial: &Path,
+    path: &Path,
+    written: std::io::Result<()>,
+) -> Result<(), StoreError> {
+    let renamed = match written {
+        Ok(()) => tokio::fs::rename(partial, path).await,
+        Err(e) => Err(e),
+    };
+    if renamed.is_err() {
+        let _ = tokio::fs::remove_file(partial).await;
+    }
+    Ok(renamed?)
+}
+
+#[async_trait]
+impl ObjectStore for FsStore {
+    fn location(&self) -> &str {
+        &self.location
+    }
+
+    /// Written to a sibling file first, readers never see a partial object
+    async fn put(&self, key: &str, blob: Bytes, _content_type: &str) -> Result<(), StoreError> {
+        let (partial, path) = self.partial_path(key).await?;
+        let written = tokio::fs::write(&partial, &blob).await;
+        finish(&partial, &path, written).await
+    }
+
+    async fn put_file(
+        &self,
+        key: &str,
+        source: &Path,
+        _content_type: &str,
+    ) -> Result<(), StoreError> {
+        let (partial, path) = self.partial_path(key).await?;
+        let written = tokio::fs::copy(source, &partial).await.map(|_| ());
+        finish(&partial, &path, written).await
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Bytes>, StoreError> {
+        match tokio::fs::read(self.path(key)?).await {
+            Ok(blob) => Ok(Some(Bytes::from(blob))),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn head(&self, key: &str) -> Result<Option<ObjectMeta>, StoreError> {
+        match tokio::fs::metadata(self.path(key)?).await {
+            Ok(metadata) if metadata.is_file() => Ok(Some(ObjectMeta {
+                key: key.to_string(),
+                size: metadata.len(),
+                last_modified: metadata.modified().ok(),
+            })),
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn delete(&self, keys: &[String]) -> Result<(), StoreError> {
+        for key in keys {
+            match tokio::fs::remove_file(self.path(key)?).await {
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Walks the whole root before yielding, fine for the sizes it is meant for
+    fn list<'a>(&'a self, prefix: &'a str) -> BoxStream<'a, Result<ObjectMeta, StoreError>> {
+        futures::stream::once(self.walk())
+            .map(move |walked| {
+                let objects = match walked {
+                    Ok(objects) => objects
+                        .into_iter()
+                        .filter(|o| o.key.starts_with(prefix))
+                        .map(Ok)
+                        .collect(),
+                    Err(e) => vec![Err(e)],
+                };
+                futures::stream::iter(objects)
+            })
+            .flatten()
+            .boxed()
+    }
+
+    async fn presign(&self, key: &str, _expires_in: Duration) -> Result<String, StoreError> {
+        let path = std::path::absolute(self.path(key)?)?;
+        Ok(format!("file://{}", path.display()))
+    }
+
+    async fn bucket_exists(&self) -> Result<bool, StoreError> {
+        Ok(tokio::fs::try_exists(&self.root).await?)
+    }
+
+    async fn create_bucket(&self) -> Result<(), StoreError> {
+        Ok(tokio::fs::create_dir_all(&self.root).await?)
+    }
+
+    async fn delete_bucket(&self) -> Result<(), StoreError> {
+        Ok(tokio::fs::remove_dir(&self.root).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bucket::testing::temp_fs_store;
+    use futures::TryStreamExt;
+
+    #[tokio::test]
+    async fn test_put_get_head_delete() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, store) = temp_fs_store();
+        store.create_bucket().await?;
+
+        store
+            .put("sha256/abc", Bytes::from_static(b"blob"), "image/webp")
+            .await?;
+        assert_eq!(
+            store.get("sha256/abc").await?,
+            Some(Bytes::from_static(b"blob"))
+        );
+        let head = store.head("sha256/abc").await?.unwrap();
+        assert_eq!((head.key.as_str(), head.size), ("sha256/abc", 4));
+        assert!(head.last_modified.is_some());
+        assert_eq!(store.head("sha256/missing").await?, None);
+
+        store
+            .delete(&["sha256/abc".to_string(), "sha256/missing".to_string()])
+            .await?;
+        assert_eq!(store.get("sha256/abc").await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_put_file() -> Result<(), Box<dyn std::error::Error>> {
+        let (dir, store) = temp_fs_store();
+        let source = dir.path().join("source");
+        tokio::fs::write(&source, b"file blob").await?;
+
+        store.put_file("sha256/file", &source, "image/webp").await?;
+        assert_eq!(
+            store.get("sha256/file").await?,
+            Some(Bytes::from_static(b"file blob"))
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_puts_of_a_key() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, store) = temp_fs_store();
+        let blobs = (0..8u8)
+            .map(|i| Bytes::from(vec![i; 64 * 1024]))
+            .collect::<Vec<_>>();
+
+        futures::future::try_join_all(
+            blobs
+                .iter()
+                .map(|blob| store.put("sha256/same", blob.clone(), "image/webp")),
+        )
+        .await?;
+        // one of the writes in full, no partial file left behind
+        assert!(blobs.contains(&store.get("sha256/same").await?.unwrap()));
+        let path = store.path("sha256/same")?;
+        let mut names = std::fs::read_dir(path.parent().unwrap())?
+            .map(|e| e.map(|e| e.file_name()))
+            .collect::<Result<Vec<_>, _>>()?;
+        names.sort();
+        assert_eq!(names, vec!["same"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_by_prefix() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, store) = temp_fs_store();
+        for key in ["sha256/b", "sha256/a", "1_1_standard"] {
+            store
+                .put(key, Bytes::from_static(b"x"), "image/webp")
+                .await?;
+        }
+
+        let keys = store
+            .list("sha256/")
+            .map_ok(|o| o.key)
+            .try_collect::<Vec<_>>()
+            .await?;
+        assert_eq!(keys, vec!["sha256/a", "sha256/b"]);
+        assert_eq!(store.list("").try_collect::<Vec<_>>().await?.len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rejects_escaping_keys() {
+        let (_dir, store) = temp_fs_store();
+        for key in ["../outside", "/absolute", "", "a/../../b"] {
+            assert!(matches!(
+                store.get(key).await,
+                Err(StoreError::InvalidKey(_))
+            ));
+        }
+    }
+}
@@ -1,24 +1,65 @@
+pub mod fs;
 pub mod policies;
+pub mod s3;
+pub mod store;
+#[cfg(any(test, feature = "test-harness"))]
+pub mod testing;
 
-use crate::config::CONFIG;
+use crate::bucket::fs::FsStore;
+use crate::bucket::s3::S3Store;
+use crate::bucket::store::ObjectStore;
+use crate::config::{Storage, CONFIG};
 use aws_config::environment::EnvironmentVariableCredentialsProvider;
 use aws_config::{BehaviorVersion, Region};
 use aws_sdk_s3::config::SharedCredentialsProvider;
 use aws_sdk_s3::Client;
-use std::sync::LazyLock;
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, LazyLock};
+
+/// Prefix of content addressed objects, older objects are keyed `{lot}_{seq}_{kind}`
+pub const CONTENT_KEY_PREFIX: &str = "sha256/";
+
+/// Hex encoded sha256 of the blob.
+pub fn sha256_hex(blob: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(blob))
+}
+
+/// Bucket key of a blob with the given hex sha256, identical blobs share one object.
+pub fn content_key(sha256: &str) -> String {
+    format!("{CONTENT_KEY_PREFIX}{sha256}")
+}
 
 pub fn init_s3() -> Client {
     let region = Region::new(CONFIG.s3.region.to_owned());
     let env_provider = EnvironmentVariableCredentialsProvider::new();
     let creds_provider = SharedCredentialsProvider::new(env_provider);
 
-    let config = aws_config::SdkConfig::builder()
+    let mut config = aws_config::SdkConfig::builder()
         .region(region)
         .credentials_provider(creds_provider)
-        .behavior_version(BehaviorVersion::latest())
+        .behavior_version(BehaviorVersion::latest());
+    if let Some(endpoint_url) = &CONFIG.s3.endpoint_url {
+        config = config.endpoint_url(endpoint_url);
+    }
+    let s3_config = aws_sdk_s3::config::Builder::from(&config.build())
+        .force_path_style(CONFIG.s3.force_path_style)
         .build();
 
-    Client::new(&config)
+    Client::from_conf(s3_config)
+}
+
+pub fn init_object_store() -> Arc<dyn ObjectStore> {
+    match &CONFIG.storage {
+        Storage::S3 => Arc::new(S3Store::new(
+            S3_CLIENT.clone(),
+            &CONFIG.s3.bucket,
+            &CONFIG.s3.region,
+        )),
+        Storage::Fs { root } => Arc::new(FsStore::new(root)),
+    }
 }
 
 pub static S3_CLIENT: LazyLock<Client> = LazyLock::new(|| init_s3());
+
+/// Store of the lot images, the configured `storage` backend
+pub static OBJECT_STORE: LazyLock<Arc<dyn ObjectStore>> = LazyLock::new(|| init_object_store());
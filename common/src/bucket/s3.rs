@@ -0,0 +1,328 @@
+use crate::bucket::store::{ObjectMeta, ObjectStore, StoreError};
+use async_trait::async_trait;
+use aws_sdk_s3::presigning::PresigningConfig;
+use aws_sdk_s3::primitives::{ByteStream, Length};
+use aws_sdk_s3::types::{
+    BucketLocationConstraint, CompletedMultipartUpload, CompletedPart, CreateBucketConfiguration,
+    Delete, ObjectIdentifier,
+};
+use aws_sdk_s3::Client;
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use tracing::warn;
+
+/// Keys per delete request, the s3 maximum
+const DELETE_BATCH_SIZE: usize = 1000;
+
+/// Files up to this size are put in a single request, larger ones in parts
+const MULTIPART_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// Part size of multipart uploads, s3 requires at least 5 MiB except for the last part
+const MULTIPART_PART_SIZE: u64 = 8 * 1024 * 1024;
+
+pub struct S3Store {
+    client: Client,
+    bucket: String,
+    region: String,
+}
+
+impl S3Store {
+    pub fn new(client: Client, bucket: impl Into<String>, region: impl Into<String>) -> Self {
+        Self {
+            client,
+            bucket: bucket.into(),
+            region: region.into(),
+        }
+    }
+
+    /// Parts are read from the file one at a time while they are sent
+    async fn put_multipart(
+        &self,
+        key: &str,
+        path: &Path,
+        size: u64,
+        content_type: &str,
+    ) -> Result<(), StoreError> {
+        let upload = self
+            .client
+            .create_multipart_upload()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type(content_type)
+            .send()
+            .await
+            .map_err(s3_error)?;
+        let upload_id = upload
+            .upload_id()
+            .ok_or_else(|| StoreError::S3(format!("no upload id for `{key}`")))?;
+
+        match self.upload_parts(key, path, size, upload_id).await {
+            Ok(parts) => self
+                .client
+                .complete_multipart_upload()
+                .bucket(&self.bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .multipart_upload(
+                    CompletedMultipartUpload::builder()
+                        .set_parts(Some(parts))
+                        .build(),
+                )
+                .send()
+                .await
+                .map(|_| ())
+                .map_err(s3_error),
+            Err(e) => {
+                // uploaded parts are billed until the upload is aborted
+                if let Err(abort) = self
+                    .client
+                    .abort_multipart_upload()
+                    .bucket(&self.bucket)
+                    .key(key)
+                    .upload_id(upload_id)
+                    .send()
+                    .await
+                {
+                    warn!("failed to abort multipart upload of `{key}`: `{abort}`");
+                }
+                Err(e)
+            }
+        }
+    }
+
+    async fn upload_parts(
+        &self,
+        key: &str,
+        path: &Path,
+        size: u64,
+        upload_id: &str,
+    ) -> Result<Vec<CompletedPart>, StoreError> {
+        let mut parts = vec![];
+        let mut offset = 0;
+        while offset < size {
+            let length = MULTIPART_PART_SIZE.min(size - offset);
+            let part_number = parts.len() as i32 + 1;
+            let body = ByteStream::read_from()
+                .path(path)
+                .offset(offset)
+                .length(Length::Exact(length))
+                .build()
+                .await
+                .map_err(s3_error)?;
+            let part = self
+                .client
+                .upload_part()
+                .bucket(&self.bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .body(body)
+                .send()
+                .await
+                .map_err(s3_error)?;
+            parts.push(
+                CompletedPart::builder()
+                    .set_e_tag(part.e_tag().map(str::to_string))
+                    .part_number(part_number)
+                    .build(),
+            );
+            offset += length;
+        }
+        Ok(parts)
+    }
+}
+
+fn s3_error(e: impl std::fmt::Display) -> StoreError {
+    StoreError::S3(e.to_string())
+}
+
+#[async_trait]
+impl ObjectStore for S3Store {
+    fn location(&self) -> &str {
+        &self.bucket
+    }
+
+    async fn put(&self, key: &str, blob: Bytes, content_type: &str) -> Result<(), StoreError> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type(content_type)
+            .body(ByteStream::from(blob))
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(s3_error)
+    }
+
+    async fn put_file(&self, key: &str, path: &Path, content_type: &str) -> Result<(), StoreError> {
+        let size = tokio::fs::metadata(path).await?.len();
+        if size > MULTIPART_THRESHOLD {
+            return self.put_multipart(key, path, size, content_type).await;
+        }
+        let body = ByteStream::from_path(path).await.map_err(s3_error)?;
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .content_type(content_type)
+            .body(body)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(s3_error)
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Bytes>, StoreError> {
+        let object = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await;
+        let object = match object {
+            Ok(object) => object,
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => {
+                return Ok(None);
+            }
+            Err(e) => return Err(s3_error(e)),
+        };
+        let blob = object.body.collect().await.map_err(s3_error)?.into_bytes();
+        Ok(Some(blob))
+    }
+
+    async fn head(&self, key: &str) -> Result<Option<ObjectMeta>, StoreError> {
+        match self
+            .client
+            .head_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(output) => Ok(Some(ObjectMeta {
+                key: key.to_string(),
+                size: output.content_length().unwrap_or_default().max(0) as u64,
+                last_modified: output
+                    .last_modified()
+                    .and_then(|t| SystemTime::try_from(*t).ok()),
+            })),
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => Ok(None),
+            Err(e) => Err(s3_error(e)),
+        }
+    }
+
+    async fn delete(&self, keys: &[String]) -> Result<(), StoreError> {
+        for batch in keys.chunks(DELETE_BATCH_SIZE) {
+            let objects = batch
+                .iter()
+                .map(|key| ObjectIdentifier::builder().key(key).build())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(s3_error)?;
+            let delete = Delete::builder()
+                .set_objects(Some(objects))
+                .quiet(true)
+                .build()
+                .map_err(s3_error)?;
+            let output = self
+                .client
+                .delete_objects()
+                .bucket(&self.bucket)
+                .delete(delete)
+                .send()
+                .await
+                .map_err(s3_error)?;
+            // callers verify the deletion, failed keys are only logged here
+            for error in output.errors() {
+                warn!(
+                    "failed to delete object `{:?}`: `{:?}`",
+                    error.key(),
+                    error.message()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn list<'a>(&'a self, prefix: &'a str) -> BoxStream<'a, Result<ObjectMeta, StoreError>> {
+        let pages = self
+            .client
+            .list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(prefix)
+            .into_paginator()
+            .send();
+        futures::stream::unfold(pages, |mut pages| async move {
+            let page = pages.next().await?;
+            let objects = match page {
+                Ok(page) => page
+                    .contents()
+                    .iter()
+                    .filter_map(|o| {
+                        o.key().map(|key| {
+                            Ok(ObjectMeta {
+                                key: key.to_string(),
+                                size: o.size().unwrap_or_default().max(0) as u64,
+                                last_modified: o
+                                    .last_modified()
+                                    .and_then(|t| SystemTime::try_from(*t).ok()),
+                            })
+                        })
+                    })
+                    .collect::<Vec<_>>(),
+                Err(e) => vec![Err(s3_error(e))],
+            };
+            Some((futures::stream::iter(objects), pages))
+        })
+        .flatten()
+        .boxed()
+    }
+
+    async fn presign(&self, key: &str, expires_in: Duration) -> Result<String, StoreError> {
+        let presigning = PresigningConfig::expires_in(expires_in).map_err(s3_error)?;
+        let request = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .presigned(presigning)
+            .await
+            .map_err(s3_error)?;
+        Ok(request.uri().to_string())
+    }
+
+    async fn bucket_exists(&self) -> Result<bool, StoreError> {
+        match self.client.head_bucket().bucket(&self.bucket).send().await {
+            Ok(_) => Ok(true),
+            Err(e) if e.as_service_error().is_some_and(|e| e.is_not_found()) => Ok(false),
+            Err(e) => Err(s3_error(e)),
+        }
+    }
+
+    async fn create_bucket(&self) -> Result<(), StoreError> {
+        let mut request = self.client.create_bucket().bucket(&self.bucket);
+        // us-east-1 is the default location and rejects an explicit constraint
+        if self.region != "us-east-1" {
+            request = request.create_bucket_configuration(
+                CreateBucketConfiguration::builder()
+                    .location_constraint(BucketLocationConstraint::from(self.region.as_str()))
+                    .build(),
+            );
+        }
+        request.send().await.map(|_| ()).map_err(s3_error)
+    }
+
+    async fn delete_bucket(&self) -> Result<(), StoreError> {
+        self.client
+            .delete_bucket()
+            .bucket(&self.bucket)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(s3_error)
+    }
+}
@@ -0,0 +1,59 @@
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum StoreError {
+    #[error("s3 error: `{0}`")]
+    S3(String),
+    #[error("io error: `{0}`")]
+    Io(#[from] std::io::Error),
+    #[error("invalid object key: `{0}`")]
+    InvalidKey(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectMeta {
+    pub key: String,
+    /// Bytes
+    pub size: u64,
+    /// `None` when the store does not tell
+    pub last_modified: Option<SystemTime>,
+}
+
+/// Objects of the lot images bucket, keys are `/` separated paths.
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    /// Bucket name or root directory, for messages
+    fn location(&self) -> &str;
+
+    async fn put(&self, key: &str, blob: Bytes, content_type: &str) -> Result<(), StoreError>;
+
+    /// Streams the file's content into the object, it is never read into memory at once
+    async fn put_file(&self, key: &str, path: &Path, content_type: &str) -> Result<(), StoreError>;
+
+    /// `None` when the object does not exist
+    async fn get(&self, key: &str) -> Result<Option<Bytes>, StoreError>;
+
+    /// `None` when the object does not exist
+    async fn head(&self, key: &str) -> Result<Option<ObjectMeta>, StoreError>;
+
+    /// Deleting a missing object is not an error
+    async fn delete(&self, keys: &[String]) -> Result<(), StoreError>;
+
+    /// Objects whose key starts with `prefix`, pages are fetched as the stream is polled
+    fn list<'a>(&'a self, prefix: &'a str) -> BoxStream<'a, Result<ObjectMeta, StoreError>>;
+
+    /// Url the object can be fetched from without credentials until it expires
+    async fn presign(&self, key: &str, expires_in: Duration) -> Result<String, StoreError>;
+
+    async fn bucket_exists(&self) -> Result<bool, StoreError>;
+
+    async fn create_bucket(&self) -> Result<(), StoreError>;
+
+    /// Fails when the bucket is not empty
+    async fn delete_bucket(&self) -> Result<(), StoreError>;
+}
@@ -0,0 +1,29 @@
+//! Object store test helpers, shared by the tests of every crate through the
+//! `test-harness` feature.
+
+use crate::bucket::fs::FsStore;
+use crate::bucket::s3::S3Store;
+use aws_sdk_s3::config::{BehaviorVersion, Credentials, Region};
+use aws_sdk_s3::Client;
+use tempfile::TempDir;
+
+/// A [`FsStore`] under a fresh temporary directory, removed when the returned dir is
+/// dropped. The store's root is a subdirectory, files next to it are not objects.
+pub fn temp_fs_store() -> (TempDir, FsStore) {
+    let dir = TempDir::new().expect("failed to create temp dir");
+    let store = FsStore::new(dir.path().join("bucket"));
+    (dir, store)
+}
+
+/// A [`S3Store`] of a client with static credentials, it presigns urls without a server
+/// to talk to.
+pub fn presigning_s3_store() -> S3Store {
+    let config = aws_sdk_s3::Config::builder()
+        .behavior_version(BehaviorVersion::latest())
+        .region(Region::new("us-east-1"))
+        .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+        .endpoint_url("http://localhost:9000")
+        .force_path_style(true)
+        .build();
+    S3Store::new(Client::from_conf(config), "lot-images", "us-east-1")
+}
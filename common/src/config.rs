@@ -22,6 +22,17 @@ pub static CONFIG: LazyLock<Config> = LazyLock::new(|| {
     unmarshalled.copart.password = copart_password;
     unmarshalled.data_bright.user = data_bright_user;
     unmarshalled.data_bright.password = data_bright_password;
+    // without operators every management request is rejected
+    unmarshalled.management.operators = std::env::var("MANAGEMENT_TOKENS")
+        .map(|tokens| Management::parse_operators(&tokens))
+        .unwrap_or_default();
+
+    // a filesystem store presigns `file://` urls of the api host, clients cannot fetch them
+    if let (Storage::Fs { .. }, ImageUrls::Presigned { .. }) =
+        (&unmarshalled.storage, &unmarshalled.images.urls)
+    {
+        panic!("the `presigned` image url strategy needs the `s3` storage backend");
+    }
 
     unmarshalled
 });
@@ -32,10 +43,18 @@ pub struct Config {
     pub copart: Copart,
     pub proxy: Proxy,
     pub s3: S3,
+    #[serde(default)]
+    pub storage: Storage,
     pub postgres: Postgres,
     pub kafka: Kafka,
     pub loki: Loki,
     pub data_bright: DataBright,
+    #[serde(default)]
+    pub images: Images,
+    #[serde(default)]
+    pub management: Management,
+    #[serde(default)]
+    pub providers: Providers,
 }
 
 // Default impl for serde to skip copart field
@@ -54,6 +73,32 @@ pub struct Proxy {
 #[derive(Deserialize)]
 pub struct S3 {
     pub region: String,
+    /// Overrides the aws endpoint, e.g. `http://localhost:9000` for minio
+    #[serde(default)]
+    pub endpoint_url: Option<String>,
+    /// Addresses buckets as `{endpoint}/{bucket}` instead of `{bucket}.{endpoint}`, minio needs it
+    #[serde(default)]
+    pub force_path_style: bool,
+    /// Bucket of the synced lot images
+    #[serde(default = "S3::default_bucket")]
+    pub bucket: String,
+}
+
+impl S3 {
+    fn default_bucket() -> String {
+        "cars-lot-images".to_string()
+    }
+}
+
+/// Where lot image objects are stored
+#[derive(Deserialize, Default)]
+#[serde(tag = "backend", rename_all = "snake_case")]
+pub enum Storage {
+    /// The `s3` section's bucket
+    #[default]
+    S3,
+    /// A local directory, one file per object, for development and tests
+    Fs { root: std::path::PathBuf },
 }
 
 #[derive(Deserialize)]
@@ -63,6 +108,9 @@ pub struct Postgres {
     pub user: String,
     pub password: String,
     pub db_name: String,
+    /// Run pending migrations when a service starts, replicas serialize on an advisory lock
+    #[serde(default)]
+    pub migrate_on_startup: bool,
 }
 
 #[derive(Deserialize)]
@@ -85,3 +133,116 @@ pub struct DataBright {
     pub password: String,
     pub allow_domains: Vec<String>,
 }
+
+#[derive(Deserialize)]
+pub struct Images {
+    /// Widths in pixels of the variants `imgsync` generates, narrower sources are not upscaled
+    #[serde(default = "Images::default_variant_widths")]
+    pub variant_widths: Vec<u32>,
+    /// Formats every variant width is encoded in, `avif` (lossy) or `webp` (lossless, larger
+    /// than camera sources, for clients without avif support)
+    #[serde(default = "Images::default_variant_formats")]
+    pub variant_formats: Vec<String>,
+    /// How the api turns bucket keys into urls clients fetch images from
+    #[serde(default)]
+    pub urls: ImageUrls,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum ImageUrls {
+    /// Signed GET urls to the bucket, expiring after `expiry_secs`. Needs the `s3` storage
+    /// backend.
+    Presigned {
+        #[serde(default = "ImageUrls::default_expiry_secs")]
+        expiry_secs: u64,
+    },
+    /// `{base_url}/{bucket key}`, for a public bucket or a cdn in front of it
+    Public { base_url: String },
+}
+
+impl ImageUrls {
+    fn default_expiry_secs() -> u64 {
+        3600
+    }
+}
+
+impl Default for ImageUrls {
+    fn default() -> Self {
+        Self::Presigned {
+            expiry_secs: Self::default_expiry_secs(),
+        }
+    }
+}
+
+/// Providers ingested besides copart
+#[derive(Deserialize, Default)]
+pub struct Providers {
+    /// Consume the topics of the fixture backed stub provider, for development
+    #[serde(default)]
+    pub stub: bool,
+}
+
+/// Internal api of operators, e.g. takedown management
+#[derive(Deserialize)]
+pub struct Management {
+    #[serde(default = "Management::default_bind")]
+    pub bind: String,
+    /// From `MANAGEMENT_TOKENS`, `actor:token` pairs separated by commas
+    #[serde(skip)]
+    pub operators: Vec<ManagementOperator>,
+}
+
+/// An operator authenticating with a bearer token, named in audit trails by `actor`.
+#[derive(Clone)]
+pub struct ManagementOperator {
+    pub actor: String,
+    pub token: String,
+}
+
+impl Management {
+    fn default_bind() -> String {
+        "127.0.0.1:8082".to_string()
+    }
+
+    fn parse_operators(tokens: &str) -> Vec<ManagementOperator> {
+        tokens
+            .split(',')
+            .filter_map(|pair| pair.trim().split_once(':'))
+            .filter(|(actor, token)| !actor.is_empty() && !token.is_empty())
+            .map(|(actor, token)| ManagementOperator {
+                actor: actor.to_string(),
+                token: token.to_string(),
+            })
+            .collect()
+    }
+}
+
+impl Default for Management {
+    fn default() -> Self {
+        Self {
+            bind: Self::default_bind(),
+            operators: vec![],
+        }
+    }
+}
+
+impl Images {
+    fn default_variant_widths() -> Vec<u32> {
+        vec![320, 800, 1600]
+    }
+
+    fn default_variant_formats() -> Vec<String> {
+        vec!["avif".to_string()]
+    }
+}
+
+impl Default for Images {
+    fn default() -> Self {
+        Self {
+            variant_widths: Self::default_variant_widths(),
+            variant_formats: Self::default_variant_formats(),
+            urls: ImageUrls::default(),
+        }
+    }
+}
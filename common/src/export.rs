@@ -0,0 +1,801 @@
+use crate::bucket::S3_CLIENT;
+use arrow_array::{
+    ArrayRef, BooleanArray, Float64Array, Int32Array, RecordBatch, StringArray,
+    TimestampMicrosecondArray,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef, TimeUnit};
+use aws_sdk_s3::primitives::ByteStream;
+use chrono::NaiveDateTime;
+use diesel::sql_types::{Bool, Float8, Int4, Nullable, Timestamp, Varchar};
+use diesel::QueryableByName;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+use thiserror::Error;
+use tracing::info;
+
+pub const DEFAULT_BATCH_SIZE: usize = 10_000;
+pub const DEFAULT_MAX_ROWS_PER_FILE: usize = 1_000_000;
+/// Longer than any write transaction is expected to stay open
+pub const DEFAULT_LAG_SECS: i64 = 10 * 60;
+const ROW_GROUP_SIZE: usize = 100_000;
+const WATERMARK_KEY: &str = "_watermark";
+const WATERMARK_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.6f";
+const UNKNOWN_SALE_DATE: &str = "unknown";
+
+/// One row per lot image variant, lots without images have empty image columns.
+/// Auction columns hold the latest sale outcome of the lot.
+///
+/// Rows are ordered by sale date so every partition is written in one go, the
+/// cursor keeps at most one batch in memory. Only rows updated up to `{horizon}` are
+/// exported, see [`ExportOptions::lag_secs`].
+const EXPORT_SQL: &str = "\
+SELECT lv.source, lv.lot_number, lv.make, lv.model, lv.year, lv.vehicle_type, lv.vin, lv.trim,
+       lv.estimated_retail_value, lv.estimated_repair_cost, lv.odometer, lv.currency,
+       lv.sale_date, lv.main_damage, lv.other_damage, lv.color, lv.fuel_type, lv.drive_type,
+       lv.country, lv.state, lv.yard,
+       li.id AS image_id, li.sequence_number AS image_sequence_number, li.image_type,
+       v.name AS variant_name, v.format AS variant_format, v.width AS variant_width,
+       v.height AS variant_height, v.bucket_key AS variant_bucket_key,
+       li.removed_at AS image_removed_at,
+       ar.final_bid, ar.buyer_country, ar.buyer_state, ar.min_met, ar.sold_at,
+       GREATEST(lv.updated_at, li.updated_at, v.created_at, ar.updated_at) AS updated_at
+FROM lot_vehicle lv
+LEFT JOIN lot_image li ON li.source = lv.source AND li.lot_vehicle_number = lv.lot_number
+LEFT JOIN lot_image_variant v ON v.lot_image_id = li.id
+LEFT JOIN LATERAL (
+    SELECT * FROM auction_result r
+    WHERE r.source = lv.source AND r.lot_vehicle_number = lv.lot_number
+    ORDER BY r.sold_at DESC
+    LIMIT 1
+) ar ON TRUE
+WHERE GREATEST(lv.updated_at, li.updated_at, v.created_at, ar.updated_at) > {watermark}
+  AND GREATEST(lv.updated_at, li.updated_at, v.created_at, ar.updated_at) <= {horizon}
+ORDER BY lv.sale_date::DATE NULLS LAST, lv.source, lv.lot_number, li.id, v.id";
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("diesel error: `{0}`")]
+    Diesel(#[from] diesel::result::Error),
+    #[error("arrow error: `{0}`")]
+    Arrow(#[from] ArrowError),
+    #[error("parquet error: `{0}`")]
+    Parquet(#[from] ParquetError),
+    #[error("io error: `{0}`")]
+    Io(#[from] std::io::Error),
+    #[error("s3 error: `{0}`")]
+    S3(String),
+    #[error("invalid watermark `{0}`")]
+    InvalidWatermark(String),
+}
+
+#[derive(QueryableByName)]
+struct Horizon {
+    #[diesel(sql_type = Timestamp)]
+    horizon: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, PartialEq, QueryableByName)]
+pub struct ExportRow {
+    #[diesel(sql_type = Varchar)]
+    pub source: String,
+    #[diesel(sql_type = Int4)]
+    pub lot_number: i32,
+    #[diesel(sql_type = Varchar)]
+    pub make: String,
+    #[diesel(sql_type = Varchar)]
+    pub model: String,
+    #[diesel(sql_type = Int4)]
+    pub year: i32,
+    #[diesel(sql_type = Varchar)]
+    pub vehicle_type: String,
+    #[diesel(sql_type = Nullable<Varchar>)]
+    pub vin: Option<String>,
+    #[diesel(sql_type = Nullable<Varchar>)]
+    pub trim: Option<String>,
+    #[diesel(sql_type = Float8)]
+    pub estimated_retail_value: f64,
+    #[diesel(sql_type = Float8)]
+    pub estimated_repair_cost: f64,
+    #[diesel(sql_type = Float8)]
+    pub odometer: f64,
+    #[diesel(sql_type = Varchar)]
+    pub currency: String,
+    #[diesel(sql_type = Nullable<Timestamp>)]
+    pub sale_date: Option<NaiveDateTime>,
+    #[diesel(sql_type = Varchar)]
+    pub main_damage: String,
+    #[diesel(sql_type = Nullable<Varchar>)]
+    pub other_damage: Option<String>,
+    #[diesel(sql_type = Varchar)]
+    pub color: String,
+    #[diesel(sql_type = Nullable<Varchar>)]
+    pub fuel_type: Option<String>,
+    #[diesel(sql_type = Nullable<Varchar>)]
+    pub drive_type: Option<String>,
+    #[diesel(sql_type = Varchar)]
+    pub country: String,
+    #[diesel(sql_type = Varchar)]
+    pub state: String,
+    #[diesel(sql_type = Nullable<Varchar>)]
+    pub yard: Option<String>,
+    #[diesel(sql_type = Nullable<Int4>)]
+    pub image_id: Option<i32>,
+    #[diesel(sql_type = Nullable<Int4>)]
+    pub image_sequence_number: Option<i32>,
+    #[diesel(sql_type = Nullable<Varchar>)]
+    pub image_type: Option<String>,
+    #[diesel(sql_type = Nullable<Varchar>)]
+    pub variant_name: Option<String>,
+    #[diesel(sql_type = Nullable<Varchar>)]
+    pub variant_format: Option<String>,
+    #[diesel(sql_type = Nullable<Int4>)]
+    pub variant_width: Option<i32>,
+    #[diesel(sql_type = Nullable<Int4>)]
+    pub variant_height: Option<i32>,
+    #[diesel(sql_type = Nullable<Varchar>)]
+    pub variant_bucket_key: Option<String>,
+    #[diesel(sql_type = Nullable<Timestamp>)]
+    pub image_removed_at: Option<NaiveDateTime>,
+    #[diesel(sql_type = Nullable<Float8>)]
+    pub final_bid: Option<f64>,
+    #[diesel(sql_type = Nullable<Varchar>)]
+    pub buyer_country: Option<String>,
+    #[diesel(sql_type = Nullable<Varchar>)]
+    pub buyer_state: Option<String>,
+    #[diesel(sql_type = Nullable<Bool>)]
+    pub min_met: Option<bool>,
+    #[diesel(sql_type = Nullable<Timestamp>)]
+    pub sold_at: Option<NaiveDateTime>,
+    #[diesel(sql_type = Timestamp)]
+    pub updated_at: NaiveDateTime,
+}
+
+impl ExportRow {
+    /// Hive style partition directory, e.g. `sale_date=2025-10-13`.
+    fn partition(&self) -> String {
+        match self.sale_date {
+            Some(sale_date) => format!("sale_date={}", sale_date.date()),
+            None => format!("sale_date={UNKNOWN_SALE_DATE}"),
+        }
+    }
+}
+
+static SCHEMA: LazyLock<SchemaRef> = LazyLock::new(|| {
+    let timestamp = || DataType::Timestamp(TimeUnit::Microsecond, None);
+    Arc::new(Schema::new(vec![
+        Field::new("source", DataType::Utf8, false),
+        Field::new("lot_number", DataType::Int32, false),
+        Field::new("make", DataType::Utf8, false),
+        Field::new("model", DataType::Utf8, false),
+        Field::new("year", DataType::Int32, false),
+        Field::new("vehicle_type", DataType::Utf8, false),
+        Field::new("vin", DataType::Utf8, true),
+        Field::new("trim", DataType::Utf8, true),
+        Field::new("estimated_retail_value", DataType::Float64, false),
+        Field::new("estimated_repair_cost", DataType::Float64, false),
+        Field::new("odometer", DataType::Float64, false),
+        Field::new("currency", DataType::Utf8, false),
+        Field::new("sale_date", timestamp(), true),
+        Field::new("main_damage", DataType::Utf8, false),
+        Field::new("other_damage", DataType::Utf8, true),
+        Field::new("color", DataType::Utf8, false),
+        Field::new("fuel_type", DataType::Utf8, true),
+        Field::new("drive_type", DataType::Utf8, true),
+        Field::new("country", DataType::Utf8, false),
+        Field::new("state", DataType::Utf8, false),
+        Field::new("yard", DataType::Utf8, true),
+        Field::new("image_id", DataType::Int32, true),
+        Field::new("image_sequence_number", DataType::Int32, true),
+        Field::new("image_type", DataType::Utf8, true),
+        Field::new("variant_name", DataType::Utf8, true),
+        Field::new("variant_format", DataType::Utf8, true),
+        Field::new("variant_width", DataType::Int32, true),
+        Field::new("variant_height", DataType::Int32, true),
+        Field::new("variant_bucket_key", DataType::Utf8, true),
+        Field::new("image_removed_at", timestamp(), true),
+        Field::new("final_bid", DataType::Float64, true),
+        Field::new("buyer_country", DataType::Utf8, true),
+        Field::new("buyer_state", DataType::Utf8, true),
+        Field::new("min_met", DataType::Boolean, true),
+        Field::new("sold_at", timestamp(), true),
+        Field::new("updated_at", timestamp(), false),
+    ]))
+});
+
+pub fn schema() -> SchemaRef {
+    SCHEMA.clone()
+}
+
+pub fn to_record_batch(rows: &[ExportRow]) -> Result<RecordBatch, ArrowError> {
+    fn strings<'a>(values: impl Iterator<Item = Option<&'a str>>) -> ArrayRef {
+        Arc::new(values.collect::<StringArray>())
+    }
+    fn micros(values: impl Iterator<Item = Option<NaiveDateTime>>) -> ArrayRef {
+        Arc::new(
+            values
+                .map(|v| v.map(|v| v.and_utc().timestamp_micros()))
+                .collect::<TimestampMicrosecondArray>(),
+        )
+    }
+    fn ints(values: impl Iterator<Item = Option<i32>>) -> ArrayRef {
+        Arc::new(values.collect::<Int32Array>())
+    }
+    fn floats(values: impl Iterator<Item = Option<f64>>) -> ArrayRef {
+        Arc::new(values.collect::<Float64Array>())
+    }
+
+    let r = rows;
+    RecordBatch::try_new(
+        schema(),
+        vec![
+            strings(r.iter().map(|r| Some(r.source.as_str()))),
+            ints(r.iter().map(|r| Some(r.lot_number))),
+            strings(r.iter().map(|r| Some(r.make.as_str()))),
+            strings(r.iter().map(|r| Some(r.model.as_str()))),
+            ints(r.iter().map(|r| Some(r.year))),
+            strings(r.iter().map(|r| Some(r.vehicle_type.as_str()))),
+            strings(r.iter().map(|r| r.vin.as_deref())),
+            strings(r.iter().map(|r| r.trim.as_deref())),
+            floats(r.iter().map(|r| Some(r.estimated_retail_value))),
+            floats(r.iter().map(|r| Some(r.estimated_repair_cost))),
+            floats(r.iter().map(|r| Some(r.odometer))),
+            strings(r.iter().map(|r| Some(r.currency.as_str()))),
+            micros(r.iter().map(|r| r.sale_date)),
+            strings(r.iter().map(|r| Some(r.main_damage.as_str()))),
+            strings(r.iter().map(|r| r.other_damage.as_deref())),
+            strings(r.iter().map(|r| Some(r.color.as_str()))),
+            strings(r.iter().map(|r| r.fuel_type.as_deref())),
+            strings(r.iter().map(|r| r.drive_type.as_deref())),
+            strings(r.iter().map(|r| Some(r.country.as_str()))),
+            strings(r.iter().map(|r| Some(r.state.as_str()))),
+            strings(r.iter().map(|r| r.yard.as_deref())),
+            ints(r.iter().map(|r| r.image_id)),
+            ints(r.iter().map(|r| r.image_sequence_number)),
+            strings(r.iter().map(|r| r.image_type.as_deref())),
+            strings(r.iter().map(|r| r.variant_name.as_deref())),
+            strings(r.iter().map(|r| r.variant_format.as_deref())),
+            ints(r.iter().map(|r| r.variant_width)),
+            ints(r.iter().map(|r| r.variant_height)),
+            strings(r.iter().map(|r| r.variant_bucket_key.as_deref())),
+            micros(r.iter().map(|r| r.image_removed_at)),
+            floats(r.iter().map(|r| r.final_bid)),
+            strings(r.iter().map(|r| r.buyer_country.as_deref())),
+            strings(r.iter().map(|r| r.buyer_state.as_deref())),
+            Arc::new(r.iter().map(|r| r.min_met).collect::<BooleanArray>()),
+            micros(r.iter().map(|r| r.sold_at)),
+            micros(r.iter().map(|r| Some(r.updated_at))),
+        ],
+    )
+}
+
+/// Where parquet files and the watermark go, a local directory or a bucket prefix.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExportTarget {
+    Local(PathBuf),
+    Bucket { bucket: String, prefix: String },
+}
+
+impl ExportTarget {
+    /// `s3://bucket/prefix` selects a bucket prefix, anything else is a local directory.
+    pub fn parse(output: &str) -> Self {
+        match output.strip_prefix("s3://") {
+            Some(rest) => {
+                let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+                Self::Bucket {
+                    bucket: bucket.to_string(),
+                    prefix: prefix.trim_matches('/').to_string(),
+                }
+            }
+            None => Self::Local(PathBuf::from(output)),
+        }
+    }
+
+    fn key(prefix: &str, relative: &str) -> String {
+        if prefix.is_empty() {
+            relative.to_string()
+        } else {
+            format!("{prefix}/{relative}")
+        }
+    }
+
+    /// Horizon of the latest export, `None` before the first export.
+    pub async fn read_watermark(&self) -> Result<Option<NaiveDateTime>, ExportError> {
+        let raw = match self {
+            Self::Local(dir) => match std::fs::read_to_string(dir.join(WATERMARK_KEY)) {
+                Ok(raw) => raw,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                Err(e) => return Err(e.into()),
+            },
+            Self::Bucket { bucket, prefix } => {
+                let object = S3_CLIENT
+                    .get_object()
+                    .bucket(bucket)
+                    .key(Self::key(prefix, WATERMARK_KEY))
+                    .send()
+                    .await;
+                let object = match object {
+                    Ok(object) => object,
+                    Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => {
+                        return Ok(None);
+                    }
+                    Err(e) => return Err(ExportError::S3(e.to_string())),
+                };
+                let bytes = object
+                    .body
+                    .collect()
+                    .await
+                    .map_err(|e| ExportError::S3(e.to_string()))?
+                    .into_bytes();
+                String::from_utf8_lossy(&bytes).into_owned()
+            }
+        };
+
+        NaiveDateTime::parse_from_str(raw.trim(), WATERMARK_FORMAT)
+            .map(Some)
+            .map_err(|_| ExportError::InvalidWatermark(raw))
+    }
+
+    pub async fn write_watermark(&self, watermark: NaiveDateTime) -> Result<(), ExportError> {
+        let raw = watermark.format(WATERMARK_FORMAT).to_string();
+        match self {
+            Self::Local(dir) => {
+                std::fs::create_dir_all(dir)?;
+                std::fs::write(dir.join(WATERMARK_KEY), raw)?;
+            }
+            Self::Bucket { bucket, prefix } => {
+                S3_CLIENT
+                    .put_object()
+                    .bucket(bucket)
+                    .key(Self::key(prefix, WATERMARK_KEY))
+                    .body(ByteStream::from(raw.into_bytes()))
+                    .send()
+                    .await
+                    .map_err(|e| ExportError::S3(e.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Directory finished files are written to, bucket exports stage them locally.
+    fn staging_dir(&self) -> PathBuf {
+        match self {
+            Self::Local(dir) => dir.clone(),
+            Self::Bucket { .. } => std::env::temp_dir().join("cars-export"),
+        }
+    }
+
+    /// Moves a finished file to its destination, uploads and removes it for bucket exports.
+    async fn publish(&self, path: &Path, relative: &str) -> Result<(), ExportError> {
+        let Self::Bucket { bucket, prefix } = self else {
+            return Ok(());
+        };
+
+        let body = ByteStream::from_path(path)
+            .await
+            .map_err(|e| ExportError::S3(e.to_string()))?;
+        S3_CLIENT
+            .put_object()
+            .bucket(bucket)
+            .key(Self::key(prefix, relative))
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| ExportError::S3(e.to_string()))?;
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
+
+pub struct ExportOptions {
+    /// Ignores the stored watermark and exports every row
+    pub full: bool,
+    /// Rows fetched from the cursor and written at once
+    pub batch_size: usize,
+    pub max_rows_per_file: usize,
+    /// Rows updated within this many seconds before the export are left to the next one.
+    /// `updated_at` is set when a transaction starts and the row shows once it commits,
+    /// a transaction open longer than this could commit rows behind the watermark.
+    pub lag_secs: i64,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            full: false,
+            batch_size: DEFAULT_BATCH_SIZE,
+            max_rows_per_file: DEFAULT_MAX_ROWS_PER_FILE,
+            lag_secs: DEFAULT_LAG_SECS,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ExportSummary {
+    pub rows: usize,
+    pub files: Vec<String>,
+    /// New watermark, every row updated up to it was exported
+    pub watermark: NaiveDateTime,
+}
+
+/// Writes parquet files below `<partition>/part-<run>-<n>.parquet`, one open file at a time.
+struct PartitionedWriter<'a> {
+    target: &'a ExportTarget,
+    run_id: String,
+    max_rows_per_file: usize,
+    current: Option<OpenFile>,
+    files: Vec<String>,
+}
+
+struct OpenFile {
+    partition: String,
+    relative: String,
+    path: PathBuf,
+    writer: ArrowWriter<File>,
+    rows: usize,
+}
+
+impl<'a> PartitionedWriter<'a> {
+    fn new(target: &'a ExportTarget, run_id: String, max_rows_per_file: usize) -> Self {
+        Self {
+            target,
+            run_id,
+            max_rows_per_file,
+            current: None,
+            files: vec![],
+        }
+    }
+
+    async fn write(&mut self, rows: &[ExportRow]) -> Result<(), ExportError> {
+        let mut rest = rows;
+        while let Some(first) = rest.first() {
+            let partition = first.partition();
+            let same_partition = rest
+                .iter()
+                .position(|r| r.partition() != partition)
+                .unwrap_or(rest.len());
+
+            let max_rows = self.max_rows_per_file;
+            let file = self.file_for(&partition).await?;
+            let chunk_len = same_partition.min(max_rows - file.rows);
+            let (chunk, tail) = rest.split_at(chunk_len);
+            file.writer.write(&to_record_batch(chunk)?)?;
+            file.rows += chunk.len();
+            rest = tail;
+        }
+        Ok(())
+    }
+
+    /// Open file of the partition with room left, earlier files are finished.
+    async fn file_for(&mut self, partition: &str) -> Result<&mut OpenFile, ExportError> {
+        let reusable = self
+            .current
+            .as_ref()
+            .is_some_and(|f| f.partition == partition && f.rows < self.max_rows_per_file);
+        if !reusable {
+            self.finish_current().await?;
+
+            let relative = format!(
+                "{partition}/part-{}-{:05}.parquet",
+                self.run_id,
+                self.files.len()
+            );
+            let path = self.target.staging_dir().join(&relative);
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            let props = WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .set_max_row_group_size(ROW_GROUP_SIZE)
+                .build();
+            let writer = ArrowWriter::try_new(File::create(&path)?, schema(), Some(props))?;
+            self.current = Some(OpenFile {
+                partition: partition.to_string(),
+                relative,
+                path,
+                writer,
+                rows: 0,
+            });
+        }
+        Ok(self.current.as_mut().expect("file opened above"))
+    }
+
+    async fn finish_current(&mut self) -> Result<(), ExportError> {
+        let Some(file) = self.current.take() else {
+            return Ok(());
+        };
+        file.writer.close()?;
+        self.target.publish(&file.path, &file.relative).await?;
+        info!("exported `{}` rows to `{}`", file.rows, file.relative);
+        self.files.push(file.relative);
+        Ok(())
+    }
+
+    async fn finish(mut self) -> Result<Vec<String>, ExportError> {
+        self.finish_current().await?;
+        Ok(self.files)
+    }
+}
+
+/// Streams lots, images and auction outcomes changed since the stored watermark
+/// to parquet files partitioned by sale date and advances the watermark to the horizon
+/// of the export, `lag_secs` before it started.
+///
+/// Rows are read through a cursor of a read only repeatable read transaction,
+/// so memory stays bounded by `batch_size` plus one parquet row group.
+pub async fn export(
+    conn: &mut AsyncPgConnection,
+    target: &ExportTarget,
+    options: ExportOptions,
+) -> Result<ExportSummary, ExportError> {
+    let watermark = match options.full {
+        true => None,
+        false => target.read_watermark().await?,
+    };
+    info!("exporting rows updated after `{watermark:?}`");
+
+    let run_id = chrono::Utc::now().format("%Y%m%dT%H%M%S").to_string();
+    let mut writer = PartitionedWriter::new(target, run_id, options.max_rows_per_file.max(1));
+    let batch_size = options.batch_size.max(1);
+    // timestamps are formatted by us, literals keep the cursor declaration parameterless
+    let literal = |t: NaiveDateTime| format!("'{}'::TIMESTAMP", t.format(WATERMARK_FORMAT));
+    let watermark_literal = match watermark {
+        Some(w) => literal(w),
+        None => "'-infinity'::TIMESTAMP".to_string(),
+    };
+    let lag_secs = options.lag_secs.max(0);
+
+    let (rows, new_watermark) = conn
+        .build_transaction()
+        .read_only()
+        .repeatable_read()
+        .run::<_, ExportError, _>(|conn| {
+            let writer = &mut writer;
+            async move {
+                let horizon = diesel::sql_query(
+                    "SELECT LOCALTIMESTAMP - make_interval(secs => $1) AS horizon",
+                )
+                .bind::<Float8, _>(lag_secs as f64)
+                .get_result::<Horizon>(conn)
+                .await?
+                .horizon;
+                // a larger lag than the previous export's does not move the watermark back
+                let horizon = watermark.map_or(horizon, |w| w.max(horizon));
+                diesel::sql_query(format!(
+                    "DECLARE lot_export NO SCROLL CURSOR FOR {}",
+                    EXPORT_SQL
+                        .replace("{watermark}", &watermark_literal)
+                        .replace("{horizon}", &literal(horizon))
+                ))
+                .execute(conn)
+                .await?;
+
+                let mut rows = 0;
+                loop {
+                    let batch = diesel::sql_query(format!("FETCH {batch_size} FROM lot_export"))
+                        .load::<ExportRow>(conn)
+                        .await?;
+                    if batch.is_empty() {
+                        break;
+                    }
+                    rows += batch.len();
+                    writer.write(&batch).await?;
+                }
+                Ok((rows, horizon))
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    let files = writer.finish().await?;
+    // advanced only after every file is published, a failed export is repeated as a whole
+    target.write_watermark(new_watermark).await?;
+
+    Ok(ExportSummary {
+        rows,
+        files,
+        watermark: new_watermark,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::testing::{fixtures, TestDb};
+    use arrow_array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    fn timestamp(day: u32) -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2025, 10, day)
+            .and_then(|d| d.and_hms_micro_opt(15, 30, 0, 123456))
+            .unwrap()
+    }
+
+    fn row(lot_number: i32, sale_day: Option<u32>, image_id: Option<i32>) -> ExportRow {
+        ExportRow {
+            source: "copart".to_string(),
+            lot_number,
+            make: "TOYOTA".to_string(),
+            model: "CAMRY".to_string(),
+            year: 2018,
+            vehicle_type: "V".to_string(),
+            vin: Some("4T1B11HK5JU000001".to_string()),
+            trim: None,
+            estimated_retail_value: 10000.0,
+            estimated_repair_cost: 2000.0,
+            odometer: 50000.0,
+            currency: "USD".to_string(),
+            sale_date: sale_day.map(timestamp),
+            main_damage: "FRONT END".to_string(),
+            other_damage: None,
+            color: "SILVER".to_string(),
+            fuel_type: Some("GAS".to_string()),
+            drive_type: None,
+            country: "USA".to_string(),
+            state: "TX".to_string(),
+            yard: Some("TX - DALLAS".to_string()),
+            image_id,
+            image_sequence_number: image_id,
+            image_type: image_id.map(|_| "IMAGE".to_string()),
+            variant_name: image_id.map(|_| "w800".to_string()),
+            variant_format: image_id.map(|_| "webp".to_string()),
+            variant_width: image_id.map(|_| 800),
+            variant_height: image_id.map(|_| 600),
+            variant_bucket_key: image_id.map(|id| format!("{lot_number}/{id}.webp")),
+            image_removed_at: None,
+            final_bid: Some(4200.5),
+            buyer_country: Some("USA".to_string()),
+            buyer_state: None,
+            min_met: Some(false),
+            sold_at: Some(timestamp(14)),
+            updated_at: timestamp(12),
+        }
+    }
+
+    fn from_record_batch(batch: &RecordBatch) -> Vec<(i32, Option<i64>, Option<i32>, f64)> {
+        let column = |name: &str| batch.column_by_name(name).unwrap().clone();
+        let lot_numbers = column("lot_number");
+        let lot_numbers = lot_numbers.as_any().downcast_ref::<Int32Array>().unwrap();
+        let sale_dates = column("sale_date");
+        let sale_dates = sale_dates
+            .as_any()
+            .downcast_ref::<TimestampMicrosecondArray>()
+            .unwrap();
+        let image_ids = column("image_id");
+        let image_ids = image_ids.as_any().downcast_ref::<Int32Array>().unwrap();
+        let final_bids = column("final_bid");
+        let final_bids = final_bids.as_any().downcast_ref::<Float64Array>().unwrap();
+
+        (0..batch.num_rows())
+            .map(|i| {
+                (
+                    lot_numbers.value(i),
+                    (!sale_dates.is_null(i)).then(|| sale_dates.value(i)),
+                    (!image_ids.is_null(i)).then(|| image_ids.value(i)),
+                    final_bids.value(i),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_schema_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let rows = vec![row(1, Some(13), Some(1)), row(2, None, None)];
+        let batch = to_record_batch(&rows)?;
+
+        let mut buffer = vec![];
+        let mut writer = ArrowWriter::try_new(&mut buffer, schema(), None)?;
+        writer.write(&batch)?;
+        writer.close()?;
+
+        let reader =
+            ParquetRecordBatchReaderBuilder::try_new(bytes::Bytes::from(buffer))?.build()?;
+        let read = reader.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(read.len(), 1);
+        assert_eq!(read[0].schema(), schema());
+        assert_eq!(read[0], batch);
+        assert_eq!(
+            from_record_batch(&read[0]),
+            vec![
+                (
+                    1,
+                    Some(timestamp(13).and_utc().timestamp_micros()),
+                    Some(1),
+                    4200.5
+                ),
+                (2, None, None, 4200.5),
+            ]
+        );
+        assert!(read[0].column_by_name("trim").unwrap().is_null(0));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_partitioned_writer_rolls_files() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join(format!("export-test-{}", std::process::id()));
+        let target = ExportTarget::Local(dir.clone());
+        let mut writer = PartitionedWriter::new(&target, "run".to_string(), 2);
+
+        writer
+            .write(&[
+                row(1, Some(13), Some(1)),
+                row(1, Some(13), Some(2)),
+                row(2, Some(13), Some(3)),
+            ])
+            .await?;
+        writer.write(&[row(3, None, None)]).await?;
+        let files = writer.finish().await?;
+
+        assert_eq!(
+            files,
+            vec![
+                "sale_date=2025-10-13/part-run-00000.parquet",
+                "sale_date=2025-10-13/part-run-00001.parquet",
+                "sale_date=unknown/part-run-00002.parquet",
+            ]
+        );
+        let rows = ParquetRecordBatchReaderBuilder::try_new(File::open(dir.join(&files[0]))?)?
+            .build()?
+            .map(|b| b.map(|b| b.num_rows()))
+            .sum::<Result<usize, _>>()?;
+        assert_eq!(rows, 2);
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_export_stops_at_horizon() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        db.seed(vec![fixtures::lot_vehicle(1)]).await?;
+        let dir = std::env::temp_dir().join(format!("export-horizon-{}", std::process::id()));
+        let target = ExportTarget::Local(dir.clone());
+        let mut conn = db.pool.get().await?;
+        let options = |lag_secs| ExportOptions {
+            lag_secs,
+            ..Default::default()
+        };
+
+        // updated within the lag, a transaction started as long ago might still commit
+        let summary = export(&mut conn, &target, options(3600)).await?;
+        assert_eq!(summary.rows, 0);
+        assert_eq!(target.read_watermark().await?, Some(summary.watermark));
+
+        let summary = export(&mut conn, &target, options(0)).await?;
+        assert_eq!(summary.rows, 1);
+        assert_eq!(summary.files.len(), 1);
+        let summary = export(&mut conn, &target, options(0)).await?;
+        assert_eq!(summary.rows, 0);
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(
+            ExportTarget::parse("s3://analytics/cars/lots/"),
+            ExportTarget::Bucket {
+                bucket: "analytics".to_string(),
+                prefix: "cars/lots".to_string()
+            }
+        );
+        assert_eq!(
+            ExportTarget::parse("s3://analytics"),
+            ExportTarget::Bucket {
+                bucket: "analytics".to_string(),
+                prefix: "".to_string()
+            }
+        );
+        assert_eq!(
+            ExportTarget::parse("./export"),
+            ExportTarget::Local(PathBuf::from("./export"))
+        );
+    }
+}
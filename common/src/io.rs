@@ -30,6 +30,8 @@ pub mod error {
         InvalidUrl(String),
         #[error("could not parse to int: {0}")]
         ParseInt(String),
+        #[error("could not parse to float: {0}")]
+        ParseFloat(String),
         #[error("browser worker pool is empty")]
         BrowserPoolEmpty,
         #[error("postgres pool error: `{0}`")]
@@ -40,6 +42,30 @@ pub mod error {
         Smf(String),
         #[error("s3 error: `{0}`")]
         S3(String),
+        #[error("vin takedown cannot move from `{from}` to `{to}`")]
+        TakedownTransition { from: String, to: String },
+        #[error("io error: `{0}`")]
+        Io(String),
+        #[error("image error: `{0}`")]
+        Image(String),
+        #[error("unknown lot source: `{0}`")]
+        UnknownSource(String),
+    }
+
+    impl From<std::io::Error> for GeneralError {
+        fn from(value: std::io::Error) -> Self {
+            Self::Io(value.to_string())
+        }
+    }
+
+    #[cfg(feature = "bucket")]
+    impl From<crate::bucket::store::StoreError> for GeneralError {
+        fn from(value: crate::bucket::store::StoreError) -> Self {
+            match value {
+                crate::bucket::store::StoreError::Io(e) => Self::Io(e.to_string()),
+                e => Self::S3(e.to_string()),
+            }
+        }
     }
 
     impl From<std::num::ParseIntError> for GeneralError {
@@ -48,6 +74,12 @@ pub mod error {
         }
     }
 
+    impl From<std::num::ParseFloatError> for GeneralError {
+        fn from(value: std::num::ParseFloatError) -> Self {
+            Self::ParseFloat(value.to_string())
+        }
+    }
+
     impl From<url::ParseError> for GeneralError {
         fn from(value: url::ParseError) -> Self {
             Self::InvalidUrl(value.to_string())
@@ -97,67 +129,111 @@ pub mod error {
     }
 }
 
-pub mod copart {
-    use crate::count_some_none;
+/// Provider neutral lot data, every auction house maps its raw responses onto these types.
+pub mod provider {
     use crate::io::error::GeneralError;
     use crate::kafka::ToTopic;
+    use serde::de::DeserializeOwned;
     use serde::{Deserialize, Serialize};
-    use std::fmt::{Debug, Formatter};
+    use std::fmt::{Debug, Display, Formatter};
 
     pub type LotNumber = i32;
     pub type PageNumber = usize;
-    pub type AuctionId = String;
-    pub type Base64Blob = String;
-    pub type DateTimeRfc3339 = String;
-    pub type LotYear = usize;
 
-    #[derive(Debug, Serialize, Deserialize)]
-    pub enum CopartCmd {
-        /// Sent by `sched` periodically, received by `browser` to fetch raw data from the provider
-        LotSearch {
-            page_number: PageNumber,
-            date_start: DateTimeRfc3339,
-            date_end: DateTimeRfc3339,
-            year_start: LotYear,
-            year_end: LotYear,
-        },
-        /// Sent by `persister` after lot search response has been received, received by `browser`
-        /// to fetch image urls from the provider
-        LotImages(LotNumber),
-        Auction(AuctionId),
-        LoginRefresh,
+    /// Auction house a lot comes from, stored as the `source` of every lot row.
+    #[derive(
+        Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+    )]
+    #[serde(rename_all = "lowercase")]
+    pub enum Provider {
+        #[default]
+        Copart,
+        /// Fixture backed provider, see [`stub`].
+        Stub,
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
-    pub enum CopartResponse {
-        /// Sent by `browser` after lot search cmd has been received, it includes raw data
-        /// from the provider of lot vehicles for a specified page number, received by `persister`
-        LotSearch(Result<LotSearchResponse, GeneralError>),
-        /// Sent by `browser` after lot images cmd has been received, it includes raw data
-        /// from the provider of single lot vehicle for specified lot number, received by `imgsync`
-        LotImages(Result<LotImagesResponse, GeneralError>),
-        SyncedImages(Result<SyncedImagesResponse, GeneralError>),
-    }
+    impl Provider {
+        pub const ALL: [Provider; 2] = [Provider::Copart, Provider::Stub];
 
-    impl ToTopic for CopartCmd {
-        fn to_topic(&self) -> String {
+        pub fn as_str(&self) -> &'static str {
             match self {
-                Self::LotSearch { .. } => "copart_cmd_lot_search".to_string(),
-                Self::LotImages(..) => "copart_cmd_lot_images".to_string(),
-                Self::Auction(_) => "copart_cmd_auction".to_string(),
-                Self::LoginRefresh => "copart_cmd_login_refresh".to_string(),
+                Self::Copart => "copart",
+                Self::Stub => "stub",
             }
         }
+
+        pub fn parse(source: &str) -> Option<Self> {
+            Self::ALL.into_iter().find(|p| p.as_str() == source)
+        }
+
+        /// Kafka topic of the provider, e.g. `copart_response_lot_search`.
+        pub fn topic(&self, name: &str) -> String {
+            format!("{}_{name}", self.as_str())
+        }
     }
 
-    impl ToTopic for CopartResponse {
-        fn to_topic(&self) -> String {
-            match self {
-                Self::LotSearch { .. } => "copart_response_lot_search".to_string(),
-                Self::LotImages { .. } => "copart_response_lot_images".to_string(),
-                Self::SyncedImages { .. } => "copart_response_synced_images".to_string(),
+    impl Display for Provider {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            f.write_str(self.as_str())
+        }
+    }
+
+    /// Lot numbers are only unique within a provider.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+    pub struct LotId {
+        pub provider: Provider,
+        pub lot_number: LotNumber,
+    }
+
+    impl LotId {
+        pub fn new(provider: Provider, lot_number: LotNumber) -> Self {
+            Self {
+                provider,
+                lot_number,
             }
         }
+
+        pub fn copart(lot_number: LotNumber) -> Self {
+            Self::new(Provider::Copart, lot_number)
+        }
+    }
+
+    impl Display for LotId {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(f, "{}:{}", self.provider, self.lot_number)
+        }
+    }
+
+    /// Responses `persister` stores, whichever provider produced them.
+    #[derive(Debug, Serialize, Deserialize)]
+    pub enum IngestResponse {
+        LotSearch(Result<LotSearchResponse, GeneralError>),
+        SyncedImages(Result<SyncedImagesResponse, GeneralError>),
+        AuctionResult(Result<AuctionResultResponse, GeneralError>),
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct IngestMsg {
+        pub provider: Provider,
+        pub response: IngestResponse,
+    }
+
+    /// An auction house plugged into the pipeline. Each provider keeps its own commands,
+    /// responses and topics, and hands the lot data over in the neutral types.
+    pub trait LotProvider {
+        const PROVIDER: Provider;
+        type Cmd: Serialize + ToTopic + Send + Sync;
+        type Response: DeserializeOwned + Send;
+
+        /// Topics carrying the responses `persister` ingests.
+        fn ingest_topics() -> Vec<String>;
+
+        /// `None` when the response is not meant for `persister`.
+        fn ingest(response: Self::Response) -> Option<IngestResponse>;
+
+        /// Command requesting images of a newly seen lot, `None` when the provider has no
+        /// image sync.
+        fn lot_images_cmd(lot_number: LotNumber) -> Option<Self::Cmd>;
     }
 
     #[derive(Debug, Serialize, Deserialize)]
@@ -167,21 +243,34 @@ pub mod copart {
     }
 
     #[derive(Debug, Serialize, Deserialize)]
-    pub struct LotImagesResponse {
+    pub struct SyncedImagesResponse {
         pub lot_number: LotNumber,
-        pub response: LotImagesVector,
+        pub response: SyncedImagesVector,
+        /// Images whose stored variants were kept because their source did not change
+        #[serde(default)]
+        pub skipped: usize,
+        /// Images whose source was downloaded
+        #[serde(default)]
+        pub fetched: usize,
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
-    pub struct SyncedImagesResponse {
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct AuctionResultResponse {
         pub lot_number: LotNumber,
-        pub response: SyncedImagesVector,
+        pub final_bid: f64,
+        pub buyer_country: Option<String>,
+        pub buyer_state: Option<String>,
+        pub min_met: bool,
+        /// Sale date sent with the result, copart's sold messages carry none and the lot's
+        /// listed sale date is stored instead
+        #[serde(default)]
+        pub sold_at: Option<chrono::NaiveDateTime>,
     }
 
-    #[derive(Serialize, Deserialize)]
+    #[derive(Clone, Serialize, Deserialize)]
     pub struct LotVehicleVector(pub Vec<LotVehicle>);
 
-    #[derive(Debug, Serialize, Deserialize)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct LotVehicle {
         pub lot_number: i32,
         pub make: String,
@@ -206,39 +295,147 @@ pub mod copart {
         pub fuel_type: Option<String>,
         pub drive_type: Option<String>,
         pub keys_status: Option<String>,
+        // missing in messages produced before trim and yard were collected
+        #[serde(default)]
+        pub trim: Option<String>,
+        #[serde(default)]
+        pub yard: Option<String>,
+        /// Bids at the time of the search, missing in messages produced before prices were collected
+        #[serde(default)]
+        pub prices: Option<LotPrices>,
     }
 
-    #[derive(Serialize, Deserialize)]
-    pub struct LotImagesVector(pub Vec<LotImages>);
-
-    #[derive(Debug, Serialize, Deserialize)]
-    pub struct LotImages {
-        pub thumbnail_url: Option<String>,
-        pub full_url: Option<String>,
-        pub high_res_url: Option<String>,
-        pub sequence_number: i32,
-        pub image_type: String,
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct LotPrices {
+        pub current_bid: f64,
+        pub buy_today_bid: f64,
+        pub high_bid: f64,
+        pub sale_status: String,
     }
 
     #[derive(Debug, Serialize, Deserialize)]
     pub struct SyncedImagesVector(pub Vec<SyncedImages>);
 
+    /// A lot image synced from its highest available resolution.
     #[derive(Debug, Serialize, Deserialize)]
     pub struct SyncedImages {
-        pub standard_bucket_key: Option<String>,
-        pub standard_mime_type: Option<String>,
-        pub standard_source_url: Option<String>,
+        pub source_url: Option<String>,
+        pub sequence_number: i32,
+        pub image_type: String,
+        /// Empty when the source could not be downloaded or decoded
+        #[serde(default)]
+        pub variants: Vec<SyncedImageVariant>,
+        /// 64 bit difference hash of the source, near-identical photos differ in few bits
+        #[serde(default)]
+        pub perceptual_hash: Option<i64>,
+        /// `ETag` of the source, `None` like empty variants or when it was not sent
+        #[serde(default)]
+        pub source_etag: Option<String>,
+        /// Bytes of the source, `None` like empty variants
+        #[serde(default)]
+        pub source_size: Option<i64>,
+        /// Missing in messages produced before statuses were reported
+        #[serde(default)]
+        pub status: ImageSyncStatus,
+        /// Stage a `Failed` image failed at
+        #[serde(default)]
+        pub error: Option<ImageSyncError>,
+    }
 
-        pub thumbnail_bucket_key: Option<String>,
-        pub thumbnail_mime_type: Option<String>,
-        pub thumbnail_source_url: Option<String>,
+    /// Outcome of syncing a lot image.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ImageSyncStatus {
+        /// Variants were generated and uploaded
+        #[default]
+        Synced,
+        /// The source did not change since the stored variants were generated
+        Unchanged,
+        /// The provider lists no url for the image, nothing is expected of it
+        NoSource,
+        /// Variants are missing after the retries, see [`ImageSyncError`]
+        Failed,
+    }
 
-        pub high_res_bucket_key: Option<String>,
-        pub high_res_mime_type: Option<String>,
-        pub high_res_source_url: Option<String>,
+    impl ImageSyncStatus {
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                Self::Synced => "synced",
+                Self::Unchanged => "unchanged",
+                Self::NoSource => "no_source",
+                Self::Failed => "failed",
+            }
+        }
 
-        pub sequence_number: i32,
-        pub image_type: String,
+        pub fn parse(status: &str) -> Option<Self> {
+            match status {
+                "synced" => Some(Self::Synced),
+                "unchanged" => Some(Self::Unchanged),
+                "no_source" => Some(Self::NoSource),
+                "failed" => Some(Self::Failed),
+                _ => None,
+            }
+        }
+    }
+
+    /// Stage a lot image failed at.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ImageSyncError {
+        /// The source could not be fetched, e.g. a timeout or a server error
+        Download,
+        /// The provider refused the source or served an unusable body, e.g. a `403`, an
+        /// empty body or one exceeding the size limit
+        Rejected,
+        /// The source is not an image or exceeds the decode limits
+        Decode,
+        /// A variant could not be put into the bucket
+        Upload,
+    }
+
+    impl ImageSyncError {
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                Self::Download => "download",
+                Self::Rejected => "rejected",
+                Self::Decode => "decode",
+                Self::Upload => "upload",
+            }
+        }
+
+        pub fn parse(error: &str) -> Option<Self> {
+            match error {
+                "download" => Some(Self::Download),
+                "rejected" => Some(Self::Rejected),
+                "decode" => Some(Self::Decode),
+                "upload" => Some(Self::Upload),
+                _ => None,
+            }
+        }
+
+        /// Whether a retry may succeed, the source of a rejected or undecodable image is
+        /// served again as it is and the image stays failed right away.
+        pub fn is_transient(&self) -> bool {
+            match self {
+                Self::Download | Self::Upload => true,
+                Self::Rejected | Self::Decode => false,
+            }
+        }
+    }
+
+    /// A resized and re-encoded copy of a lot image stored in the bucket.
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct SyncedImageVariant {
+        /// Width it was resized to, e.g. `w800`
+        pub name: String,
+        pub format: String,
+        pub mime_type: String,
+        pub width: i32,
+        pub height: i32,
+        pub size: i64,
+        /// Content key derived from `sha256`
+        pub bucket_key: String,
+        pub sha256: String,
     }
 
     impl Debug for LotVehicleVector {
@@ -247,6 +444,320 @@ pub mod copart {
         }
     }
 
+    /// Second provider proving the abstraction, it serves lots from a local json fixture
+    /// instead of an auction house.
+    pub mod stub {
+        use super::{
+            AuctionResultResponse, IngestResponse, LotNumber, LotProvider, LotSearchResponse,
+            LotVehicle, LotVehicleVector, PageNumber, Provider,
+        };
+        use crate::io::error::GeneralError;
+        use crate::kafka::ToTopic;
+        use serde::{Deserialize, Serialize};
+        use std::path::Path;
+
+        pub struct Stub;
+
+        #[derive(Debug, Serialize, Deserialize)]
+        pub enum StubCmd {
+            LotSearch { page_number: PageNumber },
+        }
+
+        #[derive(Debug, Serialize, Deserialize)]
+        pub enum StubResponse {
+            LotSearch(Result<LotSearchResponse, GeneralError>),
+            AuctionResult(Result<AuctionResultResponse, GeneralError>),
+        }
+
+        impl ToTopic for StubCmd {
+            fn to_topic(&self) -> String {
+                match self {
+                    Self::LotSearch { .. } => Stub::PROVIDER.topic("cmd_lot_search"),
+                }
+            }
+        }
+
+        impl ToTopic for StubResponse {
+            fn to_topic(&self) -> String {
+                match self {
+                    Self::LotSearch(_) => Stub::PROVIDER.topic("response_lot_search"),
+                    Self::AuctionResult(_) => Stub::PROVIDER.topic("response_auction_result"),
+                }
+            }
+        }
+
+        impl LotProvider for Stub {
+            const PROVIDER: Provider = Provider::Stub;
+            type Cmd = StubCmd;
+            type Response = StubResponse;
+
+            fn ingest_topics() -> Vec<String> {
+                ["response_lot_search", "response_auction_result"]
+                    .map(|name| Self::PROVIDER.topic(name))
+                    .to_vec()
+            }
+
+            fn ingest(response: Self::Response) -> Option<IngestResponse> {
+                Some(match response {
+                    StubResponse::LotSearch(resp) => IngestResponse::LotSearch(resp),
+                    StubResponse::AuctionResult(resp) => IngestResponse::AuctionResult(resp),
+                })
+            }
+
+            fn lot_images_cmd(_: LotNumber) -> Option<Self::Cmd> {
+                None
+            }
+        }
+
+        #[derive(Debug, Default, Serialize, Deserialize)]
+        pub struct StubFixture {
+            pub lots: Vec<LotVehicle>,
+            #[serde(default)]
+            pub auction_results: Vec<AuctionResultResponse>,
+        }
+
+        /// Answers stub commands from a fixture, paging lots the way a lot search does.
+        pub struct StubSource {
+            fixture: StubFixture,
+            page_size: usize,
+        }
+
+        impl StubSource {
+            pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+            pub fn new(fixture: StubFixture) -> Self {
+                Self {
+                    fixture,
+                    page_size: Self::DEFAULT_PAGE_SIZE,
+                }
+            }
+
+            pub fn from_json(json: &str) -> Result<Self, GeneralError> {
+                Ok(Self::new(serde_json::from_str(json)?))
+            }
+
+            pub fn from_file(path: impl AsRef<Path>) -> Result<Self, GeneralError> {
+                Self::from_json(&std::fs::read_to_string(path)?)
+            }
+
+            pub fn page_size(mut self, page_size: usize) -> Self {
+                self.page_size = page_size.max(1);
+                self
+            }
+
+            pub fn pages(&self) -> usize {
+                self.fixture.lots.len().div_ceil(self.page_size)
+            }
+
+            pub fn respond(&self, cmd: &StubCmd) -> StubResponse {
+                match cmd {
+                    StubCmd::LotSearch { page_number } => {
+                        let lots = self
+                            .fixture
+                            .lots
+                            .chunks(self.page_size)
+                            .nth(*page_number)
+                            .map(<[LotVehicle]>::to_vec)
+                            .unwrap_or_default();
+                        StubResponse::LotSearch(Ok(LotSearchResponse {
+                            page_number: *page_number,
+                            response: LotVehicleVector(lots),
+                        }))
+                    }
+                }
+            }
+
+            pub fn auction_results(&self) -> impl Iterator<Item = StubResponse> + '_ {
+                self.fixture
+                    .auction_results
+                    .iter()
+                    .map(|r| StubResponse::AuctionResult(Ok(r.clone())))
+            }
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use super::*;
+
+            const FIXTURE: &str = r#"{
+                "lots": [
+                    {"lot_number": 1, "make": "FORD", "model": "FOCUS", "year": 2015,
+                     "vehicle_type": "V", "vin": null, "estimated_retail_value": 5000.0,
+                     "estimated_repair_cost": 900.0, "odometer": 120000.0,
+                     "odometer_status": null, "engine_name": null, "engine_cylinders": null,
+                     "currency": "EUR", "sale_date": null, "main_damage": "REAR",
+                     "other_damage": null, "country": "DEU", "state": "BY",
+                     "transmission": null, "color": "BLUE", "fuel_type": null,
+                     "drive_type": null, "keys_status": null},
+                    {"lot_number": 2, "make": "FORD", "model": "FIESTA", "year": 2016,
+                     "vehicle_type": "V", "vin": null, "estimated_retail_value": 4000.0,
+                     "estimated_repair_cost": 700.0, "odometer": 90000.0,
+                     "odometer_status": null, "engine_name": null, "engine_cylinders": null,
+                     "currency": "EUR", "sale_date": null, "main_damage": "SIDE",
+                     "other_damage": null, "country": "DEU", "state": "BY",
+                     "transmission": null, "color": "RED", "fuel_type": null,
+                     "drive_type": null, "keys_status": null}
+                ]
+            }"#;
+
+            fn page(source: &StubSource, page_number: PageNumber) -> Vec<LotNumber> {
+                match source.respond(&StubCmd::LotSearch { page_number }) {
+                    StubResponse::LotSearch(Ok(lsr)) => {
+                        lsr.response.0.iter().map(|l| l.lot_number).collect()
+                    }
+                    other => panic!("unexpected stub response: `{other:?}`"),
+                }
+            }
+
+            #[test]
+            fn test_lot_search_pages_fixture() {
+                let source = StubSource::from_json(FIXTURE).unwrap().page_size(1);
+                assert_eq!(source.pages(), 2);
+                assert_eq!(page(&source, 0), vec![1]);
+                assert_eq!(page(&source, 1), vec![2]);
+                assert!(page(&source, 2).is_empty());
+            }
+
+            #[test]
+            fn test_stub_topics_are_provider_scoped() {
+                assert_eq!(
+                    Stub::ingest_topics(),
+                    vec!["stub_response_lot_search", "stub_response_auction_result"]
+                );
+                assert!(Stub::lot_images_cmd(1).is_none());
+            }
+        }
+    }
+}
+
+pub mod copart {
+    use crate::count_some_none;
+    use crate::io::error::GeneralError;
+    pub use crate::io::provider::{
+        AuctionResultResponse, ImageSyncError, ImageSyncStatus, LotNumber, LotPrices,
+        LotSearchResponse, LotVehicle, LotVehicleVector, PageNumber, SyncedImageVariant,
+        SyncedImages, SyncedImagesResponse, SyncedImagesVector,
+    };
+    use crate::io::provider::{IngestResponse, LotProvider, Provider};
+    use crate::kafka::ToTopic;
+    use serde::{Deserialize, Serialize};
+    use std::fmt::{Debug, Formatter};
+
+    pub type AuctionId = String;
+    pub type Base64Blob = String;
+    pub type DateTimeRfc3339 = String;
+    pub type LotYear = usize;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub enum CopartCmd {
+        /// Sent by `sched` periodically, received by `browser` to fetch raw data from the provider
+        LotSearch {
+            page_number: PageNumber,
+            date_start: DateTimeRfc3339,
+            date_end: DateTimeRfc3339,
+            year_start: LotYear,
+            year_end: LotYear,
+        },
+        /// Sent by `persister` after lot search response has been received, received by `browser`
+        /// to fetch image urls from the provider
+        LotImages(LotNumber),
+        /// Sent by `manager` for a manual resync, like `LotImages` but `imgsync` downloads
+        /// every image again instead of skipping unchanged ones
+        ForceLotImages(LotNumber),
+        Auction(AuctionId),
+        LoginRefresh,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub enum CopartResponse {
+        /// Sent by `browser` after lot search cmd has been received, it includes raw data
+        /// from the provider of lot vehicles for a specified page number, received by `persister`
+        LotSearch(Result<LotSearchResponse, GeneralError>),
+        /// Sent by `browser` after lot images cmd has been received, it includes raw data
+        /// from the provider of single lot vehicle for specified lot number, received by `imgsync`
+        LotImages(Result<LotImagesResponse, GeneralError>),
+        SyncedImages(Result<SyncedImagesResponse, GeneralError>),
+        /// Sent by `browser` when a lot is sold on the live auction websocket, received by `persister`
+        AuctionResult(Result<AuctionResultResponse, GeneralError>),
+    }
+
+    impl ToTopic for CopartCmd {
+        fn to_topic(&self) -> String {
+            let name = match self {
+                Self::LotSearch { .. } => "cmd_lot_search",
+                Self::LotImages(..) | Self::ForceLotImages(..) => "cmd_lot_images",
+                Self::Auction(_) => "cmd_auction",
+                Self::LoginRefresh => "cmd_login_refresh",
+            };
+            Copart::PROVIDER.topic(name)
+        }
+    }
+
+    pub struct Copart;
+
+    impl LotProvider for Copart {
+        const PROVIDER: Provider = Provider::Copart;
+        type Cmd = CopartCmd;
+        type Response = CopartResponse;
+
+        fn ingest_topics() -> Vec<String> {
+            [
+                "response_lot_search",
+                "response_synced_images",
+                "response_auction_result",
+            ]
+            .map(|name| Self::PROVIDER.topic(name))
+            .to_vec()
+        }
+
+        fn ingest(response: Self::Response) -> Option<IngestResponse> {
+            match response {
+                CopartResponse::LotSearch(resp) => Some(IngestResponse::LotSearch(resp)),
+                CopartResponse::SyncedImages(resp) => Some(IngestResponse::SyncedImages(resp)),
+                CopartResponse::AuctionResult(resp) => Some(IngestResponse::AuctionResult(resp)),
+                // consumed by `imgsync`
+                CopartResponse::LotImages(_) => None,
+            }
+        }
+
+        fn lot_images_cmd(lot_number: LotNumber) -> Option<Self::Cmd> {
+            Some(CopartCmd::LotImages(lot_number))
+        }
+    }
+
+    impl ToTopic for CopartResponse {
+        fn to_topic(&self) -> String {
+            let name = match self {
+                Self::LotSearch { .. } => "response_lot_search",
+                Self::LotImages { .. } => "response_lot_images",
+                Self::SyncedImages { .. } => "response_synced_images",
+                Self::AuctionResult { .. } => "response_auction_result",
+            };
+            Copart::PROVIDER.topic(name)
+        }
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct LotImagesResponse {
+        pub lot_number: LotNumber,
+        pub response: LotImagesVector,
+        /// Requested by `ForceLotImages`, unchanged images are synced again
+        #[serde(default)]
+        pub force: bool,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub struct LotImagesVector(pub Vec<LotImages>);
+
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct LotImages {
+        pub thumbnail_url: Option<String>,
+        pub full_url: Option<String>,
+        pub high_res_url: Option<String>,
+        pub sequence_number: i32,
+        pub image_type: String,
+    }
+
     impl Debug for LotImagesVector {
         fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
             let (some_thumbnail, none_thumbnail) =
@@ -260,4 +771,35 @@ pub mod copart {
             )
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_copart_topics_are_provider_scoped() {
+            let error = || GeneralError::ChannelSend;
+            let ingested = [
+                CopartResponse::LotSearch(Err(error())),
+                CopartResponse::SyncedImages(Err(error())),
+                CopartResponse::AuctionResult(Err(error())),
+            ];
+            assert_eq!(
+                ingested.iter().map(ToTopic::to_topic).collect::<Vec<_>>(),
+                Copart::ingest_topics()
+            );
+            assert_eq!(
+                CopartResponse::LotImages(Err(error())).to_topic(),
+                "copart_response_lot_images"
+            );
+            assert_eq!(
+                CopartCmd::ForceLotImages(1).to_topic(),
+                CopartCmd::LotImages(1).to_topic()
+            );
+            assert_eq!(
+                CopartCmd::LoginRefresh.to_topic(),
+                "copart_cmd_login_refresh"
+            );
+        }
+    }
 }
@@ -11,10 +11,9 @@ use rdkafka::{ClientConfig, Message};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use std::collections::HashMap;
-use std::sync::Arc;
 use std::time::Duration;
 use thiserror::Error;
-use tokio::sync::Notify;
+use tokio::sync::oneshot;
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info};
 use uuid::Uuid;
@@ -157,21 +156,22 @@ impl KafkaReceiver {
         }
     }
 
-    pub fn run_on<H>(self, receive_handle: H, cancellation_token: CancellationToken) -> Arc<Notify>
+    pub fn run_on<H>(
+        self,
+        receive_handle: H,
+        cancellation_token: CancellationToken,
+    ) -> oneshot::Receiver<()>
     where
         H: ReceiveHandle + Send + Sync + 'static,
     {
         let join_handle = tokio::spawn(self.run_on_blocking(receive_handle));
 
-        let done = Arc::new(Notify::new());
-        tokio::spawn({
-            let done = Arc::clone(&done);
-            async move {
-                cancellation_token.cancelled().await;
-                join_handle.abort_handle().abort();
-                info!("kafka cmd receiver closed");
-                done.notify_waiters();
-            }
+        let (done_sender, done) = oneshot::channel();
+        tokio::spawn(async move {
+            cancellation_token.cancelled().await;
+            join_handle.abort_handle().abort();
+            info!("kafka cmd receiver closed");
+            let _ = done_sender.send(());
         });
 
         done
@@ -243,21 +243,22 @@ impl KafkaSender {
         }
     }
 
-    pub fn run_on<H>(self, send_handle: H, cancellation_token: CancellationToken) -> Arc<Notify>
+    pub fn run_on<H>(
+        self,
+        send_handle: H,
+        cancellation_token: CancellationToken,
+    ) -> oneshot::Receiver<()>
     where
         H: SendHandle + Send + 'static,
     {
         let join_handle = tokio::spawn(self.run_on_blocking(send_handle));
 
-        let done = Arc::new(Notify::new());
-        tokio::spawn({
-            let done = Arc::clone(&done);
-            async move {
-                cancellation_token.cancelled().await;
-                join_handle.abort_handle().abort();
-                info!("kafka sender closed");
-                done.notify_waiters();
-            }
+        let (done_sender, done) = oneshot::channel();
+        tokio::spawn(async move {
+            cancellation_token.cancelled().await;
+            join_handle.abort_handle().abort();
+            info!("kafka sender closed");
+            let _ = done_sender.send(());
         });
 
         done
@@ -2,16 +2,21 @@
 pub mod bucket;
 #[cfg(feature = "config")]
 pub mod config;
+#[cfg(feature = "export")]
+pub mod export;
 #[cfg(feature = "io")]
 pub mod io;
 #[cfg(feature = "kafka")]
 pub mod kafka;
 #[cfg(feature = "logging")]
 pub mod logging;
-#[cfg(feature = "memprof")]
-pub mod memprof;
 #[cfg(feature = "persistence")]
 pub mod persistence;
+pub mod prof;
+#[cfg(feature = "service")]
+pub mod service;
+#[cfg(feature = "vin")]
+pub mod vin;
 
 pub async fn retry<F, O, E>(timeout: std::time::Duration, tries: usize, func: F) -> Result<O, E>
 where
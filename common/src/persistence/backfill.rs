@@ -0,0 +1,136 @@
+//! Re-derives the fields computed at ingestion for every stored lot vehicle: canonical
+//! reference ids and the vin decode. Lots stored before a field was derived, or before
+//! an alias changed, get it here instead of waiting to be listed again.
+
+use crate::io::error::GeneralError;
+use crate::persistence::canonical::{AliasCache, CanonicalIds, Canonicalizer};
+use crate::persistence::models::copart::{LotVehicle, VinFields};
+use crate::persistence::schema::lot_vehicle;
+use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, SelectableHelper};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use serde::Serialize;
+use tracing::{debug, instrument};
+
+pub const DEFAULT_BACKFILL_BATCH_SIZE: i64 = 1000;
+
+#[derive(Debug, Default, Serialize)]
+pub struct BackfillReport {
+    pub checked: usize,
+    /// Lots with at least one re-derived field which differed
+    pub updated: usize,
+}
+
+/// Pages through all lot vehicles in primary key order, a batch per transaction, and
+/// updates those whose derived fields differ. Listing fields and their history are left
+/// untouched. The alias table is loaded again only when it changed during the run.
+#[instrument(skip(conn))]
+pub async fn backfill_lot_vehicles(
+    conn: &mut AsyncPgConnection,
+    batch_size: i64,
+) -> Result<BackfillReport, GeneralError> {
+    let mut report = BackfillReport::default();
+    let aliases = AliasCache::default();
+    // an empty source sorts before all others
+    let mut last = (String::new(), i32::MIN);
+    loop {
+        let (batch_len, batch_updated, batch_last) = conn
+            .transaction::<_, GeneralError, _>(|conn| {
+                backfill_batch(conn, &aliases, last.clone(), batch_size).scope_boxed()
+            })
+            .await?;
+        report.checked += batch_len;
+        report.updated += batch_updated;
+        match batch_last {
+            Some(batch_last) => last = batch_last,
+            None => break,
+        }
+    }
+    Ok(report)
+}
+
+async fn backfill_batch(
+    conn: &mut AsyncPgConnection,
+    aliases: &AliasCache,
+    (last_source, last_lot_number): (String, i32),
+    batch_size: i64,
+) -> Result<(usize, usize, Option<(String, i32)>), GeneralError> {
+    let mut canonicalizer = Canonicalizer::load(conn, aliases).await?;
+    let batch = lot_vehicle::table
+        .filter(
+            lot_vehicle::source.gt(&last_source).or(lot_vehicle::source
+                .eq(&last_source)
+                .and(lot_vehicle::lot_number.gt(last_lot_number))),
+        )
+        .order((lot_vehicle::source.asc(), lot_vehicle::lot_number.asc()))
+        .limit(batch_size)
+        .select(LotVehicle::as_select())
+        .for_update()
+        .load(conn)
+        .await?;
+
+    let mut updated = 0;
+    for lv in &batch {
+        let ids = canonicalizer.canonical_ids(conn, lv.into()).await?;
+        let vin_fields = VinFields::decode(lv.vin.as_deref());
+        if ids == CanonicalIds::from(lv) && vin_fields == VinFields::from(lv) {
+            continue;
+        }
+        diesel::update(lot_vehicle::table.find((&lv.source, lv.lot_number)))
+            .set((
+                lot_vehicle::make_id.eq(ids.make_id),
+                lot_vehicle::model_id.eq(ids.model_id),
+                lot_vehicle::main_damage_id.eq(ids.main_damage_id),
+                lot_vehicle::color_id.eq(ids.color_id),
+                lot_vehicle::fuel_type_id.eq(ids.fuel_type_id),
+                lot_vehicle::drive_type_id.eq(ids.drive_type_id),
+                &vin_fields,
+            ))
+            .execute(conn)
+            .await?;
+        updated += 1;
+    }
+    debug!("updated `{updated}` of `{}` lot vehicles", batch.len());
+
+    Ok((
+        batch.len(),
+        updated,
+        batch.last().map(|lv| (lv.source.clone(), lv.lot_number)),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::provider::LotId;
+    use crate::persistence::repo::LotVehicleRepo;
+    use crate::persistence::testing::{fixtures, TestDb};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_backfill_lot_vehicles() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        let repo = db.repo();
+        // stored before canonical ids and the vin decode were derived
+        db.seed((1..=5).map(fixtures::lot_vehicle).collect())
+            .await?;
+        let mut conn = db.pool.get().await?;
+
+        let report = backfill_lot_vehicles(&mut conn, 2).await?;
+        assert_eq!((report.checked, report.updated), (5, 5));
+        for ln in 1..=5 {
+            let lv = repo
+                .lot_vehicle_by_id(LotId::copart(ln))
+                .await?
+                .unwrap()
+                .lot_vehicle;
+            assert!(lv.make_id.is_some() && lv.model_id.is_some());
+            assert_eq!(lv.vin_valid, Some(true));
+            assert_eq!(lv.vin_manufacturer.as_deref(), Some("Toyota"));
+        }
+
+        // a second run finds nothing to do
+        let report = backfill_lot_vehicles(&mut conn, 2).await?;
+        assert_eq!((report.checked, report.updated), (5, 0));
+        Ok(())
+    }
+}
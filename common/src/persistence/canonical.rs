@@ -0,0 +1,403 @@
+use crate::persistence::models::copart::{
+    LotVehicle, NewLotVehicle, NewReferenceAlias, NewReferenceValue,
+};
+use crate::persistence::schema::{reference_alias, reference_value};
+use diesel::dsl::sql;
+use diesel::sql_types::BigInt;
+use diesel::{ExpressionMethods, PgExpressionMethods, QueryDsl, QueryResult};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+/// Kinds of provider strings which are mapped onto canonical reference values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReferenceKind {
+    Make,
+    Model,
+    Damage,
+    Color,
+    Fuel,
+    Drive,
+}
+
+impl ReferenceKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Make => "make",
+            Self::Model => "model",
+            Self::Damage => "damage",
+            Self::Color => "color",
+            Self::Fuel => "fuel",
+            Self::Drive => "drive",
+        }
+    }
+
+    fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "make" => Some(Self::Make),
+            "model" => Some(Self::Model),
+            "damage" => Some(Self::Damage),
+            "color" => Some(Self::Color),
+            "fuel" => Some(Self::Fuel),
+            "drive" => Some(Self::Drive),
+            _ => None,
+        }
+    }
+}
+
+/// Raw provider strings of a lot vehicle which are canonicalized.
+pub struct RawReferences<'a> {
+    pub make: &'a str,
+    pub model: &'a str,
+    pub main_damage: &'a str,
+    pub color: &'a str,
+    pub fuel_type: Option<&'a str>,
+    pub drive_type: Option<&'a str>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CanonicalIds {
+    pub make_id: Option<i32>,
+    pub model_id: Option<i32>,
+    pub main_damage_id: Option<i32>,
+    pub color_id: Option<i32>,
+    pub fuel_type_id: Option<i32>,
+    pub drive_type_id: Option<i32>,
+}
+
+/// Alias lookup key, models are scoped by the canonical id of their make
+type AliasKey = (ReferenceKind, Option<i32>, String);
+
+/// The alias table as of a `reference_alias_version`.
+struct Aliases {
+    version: i64,
+    ids: HashMap<AliasKey, i32>,
+}
+
+/// Alias table shared by the transactions of a repo or a backfill. It is reloaded only
+/// once an alias was inserted, remapped or deleted since it was loaded.
+#[derive(Clone, Default)]
+pub struct AliasCache(Arc<Mutex<Option<Arc<Aliases>>>>);
+
+impl AliasCache {
+    async fn aliases(&self, conn: &mut AsyncPgConnection) -> QueryResult<Arc<Aliases>> {
+        // read before the table, a change committed in between is picked up next time
+        let version = diesel::select(sql::<BigInt>(
+            "(SELECT CASE WHEN is_called THEN last_value ELSE 0 END FROM reference_alias_version)",
+        ))
+        .get_result::<i64>(conn)
+        .await?;
+        let cached = self.0.lock().expect("alias cache lock poisoned").clone();
+        if let Some(aliases) = cached.filter(|a| a.version == version) {
+            return Ok(aliases);
+        }
+
+        let ids = reference_alias::table
+            .select((
+                reference_alias::kind,
+                reference_alias::make_id,
+                reference_alias::alias,
+                reference_alias::reference_value_id,
+            ))
+            .load::<(String, Option<i32>, String, i32)>(conn)
+            .await?
+            .into_iter()
+            .filter_map(|(kind, make_id, alias, id)| {
+                ReferenceKind::parse(&kind).map(|kind| ((kind, make_id, alias), id))
+            })
+            .collect();
+        let aliases = Arc::new(Aliases { version, ids });
+        debug!(
+            "loaded `{}` reference aliases of version `{version}`",
+            aliases.ids.len()
+        );
+        *self.0.lock().expect("alias cache lock poisoned") = Some(aliases.clone());
+        Ok(aliases)
+    }
+}
+
+/// Resolves raw provider strings to canonical reference ids through `reference_alias`.
+///
+/// Aliases are matched after [`normalize`], model aliases within the make of the lot.
+/// A spelling without an alias becomes its own canonical value, mapping it onto another
+/// value later is a matter of updating its alias and re-deriving with
+/// `manager postgres backfill-lot-vehicles`.
+pub struct Canonicalizer {
+    aliases: Arc<Aliases>,
+    /// Aliases created by the current transaction, shared once it committed and the
+    /// cache was reloaded
+    created: HashMap<AliasKey, i32>,
+}
+
+impl Canonicalizer {
+    pub async fn load(conn: &mut AsyncPgConnection, cache: &AliasCache) -> QueryResult<Self> {
+        Ok(Self {
+            aliases: cache.aliases(conn).await?,
+            created: HashMap::new(),
+        })
+    }
+
+    pub async fn canonical_ids(
+        &mut self,
+        conn: &mut AsyncPgConnection,
+        raw: RawReferences<'_>,
+    ) -> QueryResult<CanonicalIds> {
+        let make_id = self
+            .resolve(conn, ReferenceKind::Make, None, Some(raw.make))
+            .await?;
+        Ok(CanonicalIds {
+            make_id,
+            model_id: self
+                .resolve(conn, ReferenceKind::Model, make_id, Some(raw.model))
+                .await?,
+            main_damage_id: self
+                .resolve(conn, ReferenceKind::Damage, None, Some(raw.main_damage))
+                .await?,
+            color_id: self
+                .resolve(conn, ReferenceKind::Color, None, Some(raw.color))
+                .await?,
+            fuel_type_id: self
+                .resolve(conn, ReferenceKind::Fuel, None, raw.fuel_type)
+                .await?,
+            drive_type_id: self
+                .resolve(conn, ReferenceKind::Drive, None, raw.drive_type)
+                .await?,
+        })
+    }
+
+    /// Assigns canonical ids to a lot vehicle, raw values stay untouched.
+    pub async fn canonicalize(
+        &mut self,
+        conn: &mut AsyncPgConnection,
+        lot_vehicle: &mut NewLotVehicle,
+    ) -> QueryResult<()> {
+        let ids = self
+            .canonical_ids(conn, RawReferences::from(&*lot_vehicle))
+            .await?;
+        lot_vehicle.make_id = ids.make_id;
+        lot_vehicle.model_id = ids.model_id;
+        lot_vehicle.main_damage_id = ids.main_damage_id;
+        lot_vehicle.color_id = ids.color_id;
+        lot_vehicle.fuel_type_id = ids.fuel_type_id;
+        lot_vehicle.drive_type_id = ids.drive_type_id;
+        Ok(())
+    }
+
+    async fn resolve(
+        &mut self,
+        conn: &mut AsyncPgConnection,
+        kind: ReferenceKind,
+        make_id: Option<i32>,
+        raw: Option<&str>,
+    ) -> QueryResult<Option<i32>> {
+        let Some(alias) = raw.map(normalize).filter(|a| !a.is_empty()) else {
+            return Ok(None);
+        };
+        let key = (kind, make_id, alias);
+        if let Some(id) = self.aliases.ids.get(&key).or(self.created.get(&key)) {
+            return Ok(Some(*id));
+        }
+        let alias = &key.2;
+
+        diesel::insert_into(reference_value::table)
+            .values(NewReferenceValue {
+                kind: kind.as_str(),
+                name: alias,
+                make_id,
+            })
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .await?;
+        let value_id = reference_value::table
+            .filter(reference_value::kind.eq(kind.as_str()))
+            .filter(reference_value::make_id.is_not_distinct_from(make_id))
+            .filter(reference_value::name.eq(alias))
+            .select(reference_value::id)
+            .first::<i32>(conn)
+            .await?;
+
+        diesel::insert_into(reference_alias::table)
+            .values(NewReferenceAlias {
+                kind: kind.as_str(),
+                alias,
+                reference_value_id: value_id,
+                make_id,
+            })
+            .on_conflict_do_nothing()
+            .execute(conn)
+            .await?;
+        // a concurrent writer may have mapped the alias first
+        let id = reference_alias::table
+            .filter(reference_alias::kind.eq(kind.as_str()))
+            .filter(reference_alias::make_id.is_not_distinct_from(make_id))
+            .filter(reference_alias::alias.eq(alias))
+            .select(reference_alias::reference_value_id)
+            .first::<i32>(conn)
+            .await?;
+
+        self.created.insert(key, id);
+        Ok(Some(id))
+    }
+}
+
+impl<'a> From<&'a NewLotVehicle> for RawReferences<'a> {
+    fn from(value: &'a NewLotVehicle) -> Self {
+        Self {
+            make: &value.make,
+            model: &value.model,
+            main_damage: &value.main_damage,
+            color: &value.color,
+            fuel_type: value.fuel_type.as_deref(),
+            drive_type: value.drive_type.as_deref(),
+        }
+    }
+}
+
+impl<'a> From<&'a LotVehicle> for RawReferences<'a> {
+    fn from(value: &'a LotVehicle) -> Self {
+        Self {
+            make: &value.make,
+            model: &value.model,
+            main_damage: &value.main_damage,
+            color: &value.color,
+            fuel_type: value.fuel_type.as_deref(),
+            drive_type: value.drive_type.as_deref(),
+        }
+    }
+}
+
+impl From<&LotVehicle> for CanonicalIds {
+    fn from(value: &LotVehicle) -> Self {
+        Self {
+            make_id: value.make_id,
+            model_id: value.model_id,
+            main_damage_id: value.main_damage_id,
+            color_id: value.color_id,
+            fuel_type_id: value.fuel_type_id,
+            drive_type_id: value.drive_type_id,
+        }
+    }
+}
+
+impl From<&NewLotVehicle> for CanonicalIds {
+    fn from(value: &NewLotVehicle) -> Self {
+        Self {
+            make_id: value.make_id,
+            model_id: value.model_id,
+            main_damage_id: value.main_damage_id,
+            color_id: value.color_id,
+            fuel_type_id: value.fuel_type_id,
+            drive_type_id: value.drive_type_id,
+        }
+    }
+}
+
+/// Alias lookup key: uppercase alphanumeric words separated by a single space,
+/// e.g. `" Mercedes-benz "` becomes `"MERCEDES BENZ"`.
+pub fn normalize(raw: &str) -> String {
+    raw.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_uppercase())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::testing::TestDb;
+
+    fn raw<'a>(make: &'a str, model: &'a str) -> RawReferences<'a> {
+        RawReferences {
+            make,
+            model,
+            main_damage: "FRONT END",
+            color: "GRAY",
+            fuel_type: None,
+            drive_type: None,
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(" Mercedes-benz "), "MERCEDES BENZ");
+        assert_eq!(normalize("FRONT  END"), "FRONT END");
+        assert_eq!(normalize("gray"), "GRAY");
+        assert_eq!(normalize(" - "), "");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_model_aliases_are_scoped_by_make() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        let mut conn = db.pool.get().await?;
+        let mut canonicalizer = Canonicalizer::load(&mut conn, &AliasCache::default()).await?;
+
+        let bmw = canonicalizer
+            .canonical_ids(&mut conn, raw("BMW", "Z4"))
+            .await?;
+        let alpina = canonicalizer
+            .canonical_ids(&mut conn, raw("ALPINA", "Z4"))
+            .await?;
+        assert_ne!(bmw.make_id, alpina.make_id);
+        assert_ne!(bmw.model_id, alpina.model_id, "same spelling, another make");
+        // unscoped kinds are shared across makes
+        assert_eq!(bmw.color_id, alpina.color_id);
+
+        // a make alias leads to the models of the make it maps onto
+        diesel::insert_into(reference_alias::table)
+            .values(NewReferenceAlias {
+                kind: ReferenceKind::Make.as_str(),
+                alias: "BAYERISCHE MOTOREN WERKE",
+                reference_value_id: bmw.make_id.unwrap(),
+                make_id: None,
+            })
+            .execute(&mut conn)
+            .await?;
+        let mut canonicalizer = Canonicalizer::load(&mut conn, &AliasCache::default()).await?;
+        let spelled_out = canonicalizer
+            .canonical_ids(&mut conn, raw("Bayerische Motoren Werke", "z4"))
+            .await?;
+        assert_eq!(spelled_out, bmw);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_alias_cache_is_reloaded_after_a_change() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let db = TestDb::start().await?;
+        let mut conn = db.pool.get().await?;
+        let cache = AliasCache::default();
+
+        let mut canonicalizer = Canonicalizer::load(&mut conn, &cache).await?;
+        let camry = canonicalizer
+            .canonical_ids(&mut conn, raw("TOYOTA", "CAMRY"))
+            .await?;
+        let corolla = canonicalizer
+            .canonical_ids(&mut conn, raw("TOYOTA", "COROLLA"))
+            .await?;
+        let loaded = Canonicalizer::load(&mut conn, &cache).await?.aliases;
+        assert!(
+            !Arc::ptr_eq(&canonicalizer.aliases, &loaded),
+            "aliases were created"
+        );
+        assert_eq!(loaded.ids.len(), 4 + 1);
+        let unchanged = Canonicalizer::load(&mut conn, &cache).await?.aliases;
+        assert!(Arc::ptr_eq(&loaded, &unchanged));
+
+        // remapped by an operator
+        diesel::update(reference_alias::table)
+            .filter(reference_alias::alias.eq("COROLLA"))
+            .set(reference_alias::reference_value_id.eq(camry.model_id.unwrap()))
+            .execute(&mut conn)
+            .await?;
+        let mut canonicalizer = Canonicalizer::load(&mut conn, &cache).await?;
+        assert!(!Arc::ptr_eq(&loaded, &canonicalizer.aliases));
+        let remapped = canonicalizer
+            .canonical_ids(&mut conn, raw("TOYOTA", "COROLLA"))
+            .await?;
+        assert_ne!(remapped.model_id, corolla.model_id);
+        assert_eq!(remapped, camry);
+        Ok(())
+    }
+}
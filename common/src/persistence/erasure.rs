@@ -0,0 +1,353 @@
+//! Hard erasure of the images of vins with an active takedown: `lot_image` rows and
+//! every bucket object of their lots are deleted and checked to be gone afterwards.
+//! Content addressed objects still referenced by images of other lots are kept.
+//!
+//! A sync in flight may have found an object in the bucket and skipped its upload just
+//! before the erasure deleted it. Erased content keys are recorded in `erased_object`, and
+//! storing a sync pointing at one marks the image failed, so it is uploaded again on the
+//! retry. Objects of blocked lots uploaded after an erasure are queued in
+//! `takedown_pending_object` for the next one.
+
+use crate::bucket::{CONTENT_KEY_PREFIX, OBJECT_STORE};
+use crate::io::error::GeneralError;
+use crate::io::provider::{LotId, Provider};
+use crate::persistence::models::takedown::{NewVinTakedownAudit, TakedownState, VinTakedown};
+use crate::persistence::schema::{
+    erased_object, lot_image, lot_image_variant, lot_vehicle, takedown_pending_object,
+    vin_takedown, vin_takedown_audit,
+};
+use diesel::dsl::now;
+use diesel::pg::Pg;
+use diesel::sql_types::BigInt;
+use diesel::{ExpressionMethods, NullableExpressionMethods, QueryDsl, SelectableHelper};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use futures::TryStreamExt;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use tracing::{debug, info, instrument, warn};
+
+const ERASURE_ACTOR: &str = "erasure";
+
+/// Held exclusively while an erasure or a rekey checks which objects are still
+/// referenced and deletes the others, and shared by syncs storing variants.
+pub(crate) const ERASURE_LOCK_KEY: i64 = 0x6572_6173_7572_65;
+
+#[derive(Debug, Serialize)]
+pub struct ErasureReport {
+    pub takedown_id: i32,
+    pub vin: String,
+    pub lots: Vec<LotId>,
+    pub lot_image_rows_deleted: usize,
+    pub objects_deleted: Vec<String>,
+    /// Identical photos of other lots, left in the bucket
+    pub objects_shared: Vec<String>,
+    /// Rows still present after the erasure, zero once verified
+    pub remaining_lot_image_rows: i64,
+    /// Objects still present after the erasure, empty once verified
+    pub remaining_objects: Vec<String>,
+    pub verified: bool,
+}
+
+/// Active takedowns whose images were not erased yet, or all active ones with `include_erased`.
+pub async fn takedowns_to_erase(
+    conn: &mut AsyncPgConnection,
+    include_erased: bool,
+) -> Result<Vec<VinTakedown>, GeneralError> {
+    let mut query = vin_takedown::table
+        .filter(vin_takedown::state.eq(TakedownState::Active.as_str()))
+        .order(vin_takedown::id.asc())
+        .select(VinTakedown::as_select())
+        .into_boxed();
+    if !include_erased {
+        query = query.filter(vin_takedown::erased_at.is_null());
+    }
+    Ok(query.load(conn).await?)
+}
+
+/// Deletes images of every lot of the takedown's vin from the bucket and the database,
+/// then checks nothing is left. A verified erasure is recorded on the takedown and in
+/// its audit trail, an unverified one can be run again.
+#[instrument(skip_all, fields(takedown_id = takedown.id))]
+pub async fn erase_takedown(
+    conn: &mut AsyncPgConnection,
+    takedown: &VinTakedown,
+) -> Result<ErasureReport, GeneralError> {
+    let lots = takedown_lots(conn, &takedown.vin).await?;
+
+    // removed images included, their objects are never deleted by the sync
+    let mut keys = BTreeSet::new();
+    for lot in &lots {
+        keys.extend(stored_keys(conn, lot).await?);
+        if let Some(prefix) = lot_prefix(lot) {
+            keys.extend(list_keys(&prefix).await?);
+        }
+    }
+    // uploaded by syncs which arrived after an earlier erasure
+    keys.extend(
+        takedown_pending_object::table
+            .filter(takedown_pending_object::vin_takedown_id.eq(takedown.id))
+            .select(takedown_pending_object::bucket_key)
+            .load::<String>(conn)
+            .await?,
+    );
+
+    let takedown_id = takedown.id;
+    let (shared, keys, lot_image_rows_deleted) = conn
+        .transaction::<_, GeneralError, _>(|conn| {
+            delete_unshared(conn, takedown_id, &lots, keys).scope_boxed()
+        })
+        .await?;
+    debug!("deleted `{lot_image_rows_deleted}` lot image rows");
+
+    let mut remaining_lot_image_rows = 0;
+    for lot in &lots {
+        remaining_lot_image_rows += lot_images(lot).count().get_result::<i64>(conn).await?;
+    }
+
+    let mut remaining_objects = BTreeSet::new();
+    for key in &keys {
+        if OBJECT_STORE.head(key).await?.is_some() {
+            remaining_objects.insert(key.clone());
+        }
+    }
+    for lot in &lots {
+        if let Some(prefix) = lot_prefix(lot) {
+            remaining_objects.extend(list_keys(&prefix).await?);
+        }
+    }
+    let verified = remaining_lot_image_rows == 0 && remaining_objects.is_empty();
+
+    let report = ErasureReport {
+        takedown_id: takedown.id,
+        vin: takedown.vin.clone(),
+        lots,
+        lot_image_rows_deleted,
+        objects_deleted: keys,
+        objects_shared: shared.into_iter().collect(),
+        remaining_lot_image_rows,
+        remaining_objects: remaining_objects.into_iter().collect(),
+        verified,
+    };
+    if !verified {
+        warn!("erasure not verified: `{report:?}`");
+        return Ok(report);
+    }
+
+    // objects queued meanwhile leave the takedown due for another erasure
+    diesel::update(
+        vin_takedown::table
+            .find(takedown.id)
+            .filter(diesel::dsl::not(diesel::dsl::exists(
+                takedown_pending_object::table
+                    .filter(takedown_pending_object::vin_takedown_id.eq(takedown.id)),
+            ))),
+    )
+    .set(vin_takedown::erased_at.eq(now.nullable()))
+    .execute(conn)
+    .await?;
+    let note = format!(
+        "erased `{}` lot image rows and `{}` objects of lots [{}]",
+        report.lot_image_rows_deleted,
+        report.objects_deleted.len(),
+        report
+            .lots
+            .iter()
+            .map(LotId::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    diesel::insert_into(vin_takedown_audit::table)
+        .values(NewVinTakedownAudit {
+            vin_takedown_id: takedown.id,
+            from_state: Some(&takedown.state),
+            to_state: &takedown.state,
+            actor: ERASURE_ACTOR,
+            note: Some(&note),
+        })
+        .execute(conn)
+        .await?;
+    info!("{note}");
+    Ok(report)
+}
+
+/// Lots listed with the vin however the provider spelled it, takedown vins are normalized.
+async fn takedown_lots(
+    conn: &mut AsyncPgConnection,
+    vin: &str,
+) -> Result<Vec<LotId>, GeneralError> {
+    lot_vehicle::table
+        .filter(lot_vehicle::vin_normalized.eq(vin))
+        .order((lot_vehicle::source.asc(), lot_vehicle::lot_number.asc()))
+        .select((lot_vehicle::source, lot_vehicle::lot_number))
+        .load::<(String, i32)>(conn)
+        .await?
+        .into_iter()
+        .map(|(source, ln)| {
+            Provider::parse(&source)
+                .map(|p| LotId::new(p, ln))
+                .ok_or(GeneralError::UnknownSource(source))
+        })
+        .collect()
+}
+
+/// Deletes the objects among `keys` no other lot points at and the image rows of `lots`,
+/// returns the shared keys, the deleted ones and the number of deleted rows.
+async fn delete_unshared(
+    conn: &mut AsyncPgConnection,
+    takedown_id: i32,
+    lots: &[LotId],
+    keys: BTreeSet<String>,
+) -> Result<(BTreeSet<String>, Vec<String>, usize), GeneralError> {
+    // no sync stores a variant of an unshared object between the check and the deletion,
+    // see `upsert_lot_images`
+    diesel::sql_query("SELECT pg_advisory_xact_lock($1)")
+        .bind::<BigInt, _>(ERASURE_LOCK_KEY)
+        .execute(conn)
+        .await?;
+    let shared = shared_keys(conn, lots, &keys).await?;
+    // objects queued since they were listed wait for the next erasure
+    let listed = keys.iter().cloned().collect::<Vec<_>>();
+    let keys = keys.difference(&shared).cloned().collect::<Vec<_>>();
+    let erased = keys
+        .iter()
+        .filter(|k| k.starts_with(CONTENT_KEY_PREFIX))
+        .map(|k| erased_object::bucket_key.eq(k))
+        .collect::<Vec<_>>();
+    if !erased.is_empty() {
+        diesel::insert_into(erased_object::table)
+            .values(erased)
+            .on_conflict(erased_object::bucket_key)
+            .do_update()
+            .set(erased_object::erased_at.eq(now))
+            .execute(conn)
+            .await?;
+    }
+    OBJECT_STORE.delete(&keys).await?;
+
+    let mut lot_image_rows_deleted = 0;
+    for lot in lots {
+        lot_image_rows_deleted += diesel::delete(
+            lot_image::table
+                .filter(lot_image::source.eq(lot.provider.as_str()))
+                .filter(lot_image::lot_vehicle_number.eq(lot.lot_number)),
+        )
+        .execute(conn)
+        .await?;
+    }
+    diesel::delete(
+        takedown_pending_object::table
+            .filter(takedown_pending_object::vin_takedown_id.eq(takedown_id))
+            .filter(takedown_pending_object::bucket_key.eq_any(&listed)),
+    )
+    .execute(conn)
+    .await?;
+    Ok((shared, keys, lot_image_rows_deleted))
+}
+
+/// Objects synced by `imgsync` before content addressing are keyed
+/// `{lot number}_{sequence number}_{kind}`, only copart images were synced so the prefix
+/// of other providers' lots belongs to copart lots.
+fn lot_prefix(lot: &LotId) -> Option<String> {
+    (lot.provider == Provider::Copart).then(|| format!("{}_", lot.lot_number))
+}
+
+fn lot_images(lot: &LotId) -> lot_image::BoxedQuery<'static, Pg> {
+    lot_image::table
+        .filter(lot_image::source.eq(lot.provider.as_str()))
+        .filter(lot_image::lot_vehicle_number.eq(lot.lot_number))
+        .into_boxed()
+}
+
+async fn stored_keys(
+    conn: &mut AsyncPgConnection,
+    lot: &LotId,
+) -> Result<BTreeSet<String>, GeneralError> {
+    Ok(lot_image_variant::table
+        .inner_join(lot_image::table)
+        .filter(lot_image::source.eq(lot.provider.as_str()))
+        .filter(lot_image::lot_vehicle_number.eq(lot.lot_number))
+        .select(lot_image_variant::bucket_key)
+        .load::<String>(conn)
+        .await?
+        .into_iter()
+        .collect())
+}
+
+/// Content keys among `keys` which images of lots other than `lots` point at.
+async fn shared_keys(
+    conn: &mut AsyncPgConnection,
+    lots: &[LotId],
+    keys: &BTreeSet<String>,
+) -> Result<BTreeSet<String>, GeneralError> {
+    let content_keys = keys
+        .iter()
+        .filter(|k| k.starts_with(CONTENT_KEY_PREFIX))
+        .cloned()
+        .collect::<Vec<_>>();
+    if content_keys.is_empty() {
+        return Ok(BTreeSet::new());
+    }
+
+    let referencing = lot_image_variant::table
+        .inner_join(lot_image::table)
+        .filter(lot_image_variant::bucket_key.eq_any(&content_keys))
+        .select((
+            lot_image::source,
+            lot_image::lot_vehicle_number,
+            lot_image_variant::bucket_key,
+        ))
+        .load::<(String, i32, String)>(conn)
+        .await?;
+    Ok(referencing
+        .into_iter()
+        .filter(|(source, ln, _)| {
+            !lots
+                .iter()
+                .any(|l| l.provider.as_str() == source && l.lot_number == *ln)
+        })
+        .map(|(_, _, key)| key)
+        .collect())
+}
+
+async fn list_keys(prefix: &str) -> Result<Vec<String>, GeneralError> {
+    Ok(OBJECT_STORE
+        .list(prefix)
+        .map_ok(|o| o.key)
+        .try_collect()
+        .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::models::copart::NewLotVehicle;
+    use crate::persistence::testing::{fixtures, TestDb};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_takedown_lots_match_raw_vins() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        let vin = fixtures::lot_vehicle(1).vin.unwrap();
+        // relistings with the vin as the provider spelled it
+        db.seed(vec![
+            fixtures::lot_vehicle(1),
+            fixtures::lot_vehicle(2),
+            NewLotVehicle {
+                vin: Some(vin.to_lowercase()),
+                ..fixtures::lot_vehicle(3)
+            },
+            NewLotVehicle {
+                vin: Some(format!(" {}-{} ", &vin[..3], &vin[3..])),
+                ..fixtures::lot_vehicle(4)
+            },
+        ])
+        .await?;
+        let mut conn = db.pool.get().await?;
+
+        assert_eq!(
+            takedown_lots(&mut conn, &vin).await?,
+            vec![LotId::copart(1), LotId::copart(3), LotId::copart(4)]
+        );
+        Ok(())
+    }
+}
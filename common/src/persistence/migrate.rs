@@ -0,0 +1,366 @@
+use crate::persistence::{PgPool, PG_MIGRATIONS};
+use chrono::NaiveDateTime;
+use diesel::migration::{Migration, MigrationSource};
+use diesel::pg::Pg;
+use diesel::sql_types::{BigInt, Bool, Text, Timestamp};
+use diesel::QueryableByName;
+use diesel_async::pooled_connection::deadpool::PoolError;
+use diesel_async::{AsyncMigrationHarness, AsyncPgConnection, RunQueryDsl};
+use diesel_migrations::MigrationHarness;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use thiserror::Error;
+use tracing::info;
+
+/// Advisory lock key shared by every service migrating at startup
+const MIGRATION_LOCK_KEY: i64 = 0x6361_7273_6d69_6772;
+const MIGRATIONS_TABLE: &str = "__diesel_schema_migrations";
+
+// `MIGRATION_SQL`: (migration name, up.sql, down.sql) of every migration, see build.rs
+include!(concat!(env!("OUT_DIR"), "/migration_sql.rs"));
+
+#[derive(Debug, Error)]
+pub enum MigrateError {
+    #[error("diesel error: `{0}`")]
+    Diesel(#[from] diesel::result::Error),
+    #[error("postgres connection error: `{0}`")]
+    Connection(#[from] diesel::ConnectionError),
+    #[error("postgres pool error: `{0}`")]
+    PgPool(#[from] PoolError),
+    #[error("migration failed: `{0}`")]
+    Migration(String),
+    #[error("unknown migration version `{0}`")]
+    UnknownVersion(String),
+}
+
+impl From<Box<dyn std::error::Error + Send + Sync>> for MigrateError {
+    fn from(value: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        Self::Migration(value.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationStatus {
+    /// Directory timestamp without separators, e.g. `20261018091512`
+    pub version: String,
+    pub name: String,
+    pub applied_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MigrationStep {
+    Run(String),
+    Revert(String),
+}
+
+impl MigrationStep {
+    pub fn version(&self) -> &str {
+        match self {
+            Self::Run(version) | Self::Revert(version) => version,
+        }
+    }
+}
+
+impl Display for MigrationStep {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Run(version) => write!(f, "run `{version}`"),
+            Self::Revert(version) => write!(f, "revert `{version}`"),
+        }
+    }
+}
+
+#[derive(QueryableByName)]
+struct AppliedMigration {
+    #[diesel(sql_type = Text)]
+    version: String,
+    #[diesel(sql_type = Timestamp)]
+    run_on: NaiveDateTime,
+}
+
+#[derive(QueryableByName)]
+struct Exists {
+    #[diesel(sql_type = Bool)]
+    exists: bool,
+}
+
+fn migrations() -> Result<HashMap<String, Box<dyn Migration<Pg>>>, MigrateError> {
+    Ok(MigrationSource::<Pg>::migrations(&PG_MIGRATIONS)?
+        .into_iter()
+        .map(|m| (m.name().version().to_string(), m))
+        .collect())
+}
+
+/// Every embedded migration in version order with the time it was applied.
+pub async fn status(conn: &mut AsyncPgConnection) -> Result<Vec<MigrationStatus>, MigrateError> {
+    let table_exists = diesel::sql_query("SELECT to_regclass($1) IS NOT NULL AS exists")
+        .bind::<Text, _>(MIGRATIONS_TABLE)
+        .get_result::<Exists>(conn)
+        .await?
+        .exists;
+    let applied = match table_exists {
+        true => diesel::sql_query(format!("SELECT version, run_on FROM {MIGRATIONS_TABLE}"))
+            .load::<AppliedMigration>(conn)
+            .await?
+            .into_iter()
+            .map(|m| (m.version, m.run_on))
+            .collect(),
+        false => HashMap::new(),
+    };
+
+    let mut statuses = MigrationSource::<Pg>::migrations(&PG_MIGRATIONS)?
+        .into_iter()
+        .map(|m| {
+            let version = m.name().version().to_string();
+            MigrationStatus {
+                applied_at: applied.get(&version).copied(),
+                name: m.name().to_string(),
+                version,
+            }
+        })
+        .collect::<Vec<_>>();
+    statuses.sort_by(|a, b| a.version.cmp(&b.version));
+    Ok(statuses)
+}
+
+/// Steps bringing the database to `target`, every pending migration without one.
+/// Migrations newer than the target are reverted, newest first.
+pub fn plan_migrate_to(
+    statuses: &[MigrationStatus],
+    target: Option<&str>,
+) -> Result<Vec<MigrationStep>, MigrateError> {
+    if let Some(target) = target {
+        if !statuses.iter().any(|s| s.version == target) {
+            return Err(MigrateError::UnknownVersion(target.to_string()));
+        }
+    }
+    let wanted = |s: &MigrationStatus| target.is_none_or(|t| s.version.as_str() <= t);
+
+    let reverts = statuses
+        .iter()
+        .rev()
+        .filter(|s| s.applied_at.is_some() && !wanted(s))
+        .map(|s| MigrationStep::Revert(s.version.clone()));
+    let runs = statuses
+        .iter()
+        .filter(|s| s.applied_at.is_none() && wanted(s))
+        .map(|s| MigrationStep::Run(s.version.clone()));
+    Ok(reverts.chain(runs).collect())
+}
+
+/// Reverts the last `steps` applied migrations, newest first.
+pub fn plan_revert(statuses: &[MigrationStatus], steps: usize) -> Vec<MigrationStep> {
+    statuses
+        .iter()
+        .rev()
+        .filter(|s| s.applied_at.is_some())
+        .take(steps)
+        .map(|s| MigrationStep::Revert(s.version.clone()))
+        .collect()
+}
+
+pub fn is_destructive(plan: &[MigrationStep]) -> bool {
+    plan.iter().any(|s| matches!(s, MigrationStep::Revert(_)))
+}
+
+/// Applies the plan, each migration runs in its own transaction.
+pub fn apply(
+    harness: &mut impl MigrationHarness<Pg>,
+    plan: &[MigrationStep],
+) -> Result<(), MigrateError> {
+    let migrations = migrations()?;
+    for step in plan {
+        let migration = migrations
+            .get(step.version())
+            .ok_or_else(|| MigrateError::UnknownVersion(step.version().to_string()))?;
+        match step {
+            MigrationStep::Run(_) => harness.run_migration(migration.as_ref())?,
+            MigrationStep::Revert(_) => harness.revert_migration(migration.as_ref())?,
+        };
+        info!("migration step `{step}` done");
+    }
+    Ok(())
+}
+
+/// The SQL every step of the plan executes, `up.sql` of runs and `down.sql` of reverts.
+/// Read from the embedded migration files, nothing is sent to the database.
+pub fn preview(
+    plan: &[MigrationStep],
+) -> Result<Vec<(MigrationStep, String, &'static str)>, MigrateError> {
+    let migrations = migrations()?;
+    plan.iter()
+        .map(|step| {
+            let name = migrations
+                .get(step.version())
+                .map(|m| m.name().to_string())
+                .ok_or_else(|| MigrateError::UnknownVersion(step.version().to_string()))?;
+            let (_, up, down) = MIGRATION_SQL
+                .iter()
+                .find(|(embedded, _, _)| *embedded == name)
+                .ok_or_else(|| MigrateError::UnknownVersion(step.version().to_string()))?;
+            let sql = match step {
+                MigrationStep::Run(_) => up,
+                MigrationStep::Revert(_) => down
+                    .as_ref()
+                    .ok_or_else(|| MigrateError::Migration(format!("`{name}` has no down.sql")))?,
+            };
+            Ok((step.clone(), name, *sql))
+        })
+        .collect()
+}
+
+/// Runs pending migrations while holding a Postgres advisory lock, replicas
+/// starting at the same time wait for the first one and find nothing pending.
+pub async fn run_pending_migrations(pool: &PgPool) -> Result<Vec<String>, MigrateError> {
+    // session level lock, held by a connection of its own while the harness owns another
+    let mut lock_conn = pool.get().await?;
+    diesel::sql_query("SELECT pg_advisory_lock($1)")
+        .bind::<BigInt, _>(MIGRATION_LOCK_KEY)
+        .execute(&mut lock_conn)
+        .await?;
+
+    let migrated = async {
+        let mut harness = AsyncMigrationHarness::new(pool.get().await?);
+        let applied = harness
+            .run_pending_migrations(PG_MIGRATIONS)?
+            .into_iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>();
+        Ok::<_, MigrateError>(applied)
+    }
+    .await;
+
+    diesel::sql_query("SELECT pg_advisory_unlock($1)")
+        .bind::<BigInt, _>(MIGRATION_LOCK_KEY)
+        .execute(&mut lock_conn)
+        .await?;
+
+    let migrated = migrated?;
+    info!("applied `{}` pending migrations", migrated.len());
+    Ok(migrated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::testing::TestDb;
+
+    fn statuses(applied: usize, total: usize) -> Vec<MigrationStatus> {
+        (0..total)
+            .map(|i| MigrationStatus {
+                version: format!("2026101800000{i}"),
+                name: format!("2026-10-18-00000{i}_migration"),
+                applied_at: (i < applied).then(chrono::NaiveDateTime::default),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_migrate_to() -> Result<(), MigrateError> {
+        let statuses = statuses(2, 4);
+        assert_eq!(
+            plan_migrate_to(&statuses, None)?,
+            vec![
+                MigrationStep::Run("20261018000002".to_string()),
+                MigrationStep::Run("20261018000003".to_string()),
+            ]
+        );
+        assert_eq!(
+            plan_migrate_to(&statuses, Some("20261018000002"))?,
+            vec![MigrationStep::Run("20261018000002".to_string())]
+        );
+        assert_eq!(
+            plan_migrate_to(&statuses, Some("20261018000000"))?,
+            vec![MigrationStep::Revert("20261018000001".to_string())]
+        );
+        assert!(plan_migrate_to(&statuses, Some("20261018000001"))?.is_empty());
+        assert!(matches!(
+            plan_migrate_to(&statuses, Some("1")),
+            Err(MigrateError::UnknownVersion(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_plan_revert() {
+        let statuses = statuses(3, 4);
+        assert_eq!(
+            plan_revert(&statuses, 2),
+            vec![
+                MigrationStep::Revert("20261018000002".to_string()),
+                MigrationStep::Revert("20261018000001".to_string()),
+            ]
+        );
+        assert_eq!(plan_revert(&statuses, 10).len(), 3);
+        assert!(is_destructive(&plan_revert(&statuses, 1)));
+        assert!(!is_destructive(&[MigrationStep::Run("1".to_string())]));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrent_startup_migrations() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start_empty().await?;
+
+        let (first, second) = tokio::join!(
+            run_pending_migrations(&db.pool),
+            run_pending_migrations(&db.pool)
+        );
+        let total = MigrationSource::<Pg>::migrations(&PG_MIGRATIONS)?.len();
+        let mut applied = [first?.len(), second?.len()];
+        applied.sort();
+        assert_eq!(applied, [0, total]);
+
+        let mut conn = db.pool.get().await?;
+        let statuses = status(&mut conn).await?;
+        assert_eq!(statuses.len(), total);
+        assert!(statuses.iter().all(|s| s.applied_at.is_some()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_preview() -> Result<(), MigrateError> {
+        let statuses = statuses(0, 0);
+        assert!(preview(&plan_revert(&statuses, 1))?.is_empty());
+
+        let first = MigrationSource::<Pg>::migrations(&PG_MIGRATIONS)?
+            .iter()
+            .map(|m| m.name().version().to_string())
+            .min()
+            .unwrap();
+        let steps = preview(&[
+            MigrationStep::Run(first.clone()),
+            MigrationStep::Revert(first.clone()),
+        ])?;
+        assert_eq!(steps[0].1, "00000000000000_diesel_initial_setup");
+        assert!(steps[0]
+            .2
+            .contains("CREATE OR REPLACE FUNCTION diesel_manage_updated_at"));
+        assert!(steps[1].2.contains("DROP FUNCTION"));
+        assert!(matches!(
+            preview(&[MigrationStep::Run("1".to_string())]),
+            Err(MigrateError::UnknownVersion(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_revert_and_migrate() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        let mut conn = db.pool.get().await?;
+        let before = status(&mut conn).await?;
+
+        let plan = plan_revert(&before, 1);
+
+        apply(&mut AsyncMigrationHarness::new(db.pool.get().await?), &plan)?;
+        let after = status(&mut conn).await?;
+        assert_eq!(after.iter().filter(|s| s.applied_at.is_none()).count(), 1);
+        assert!(after.last().is_some_and(|s| s.applied_at.is_none()));
+
+        let plan = plan_migrate_to(&after, None)?;
+        apply(&mut AsyncMigrationHarness::new(db.pool.get().await?), &plan)?;
+        assert!(status(&mut conn)
+            .await?
+            .iter()
+            .all(|s| s.applied_at.is_some()));
+        Ok(())
+    }
+}
@@ -0,0 +1 @@
+DROP TABLE lot_vehicle_history;
@@ -0,0 +1,11 @@
+CREATE TABLE lot_vehicle_history
+(
+    id                 SERIAL PRIMARY KEY,
+    lot_vehicle_number INTEGER   NOT NULL REFERENCES lot_vehicle (lot_number),
+    field              VARCHAR   NOT NULL,
+    old_value          VARCHAR,
+    new_value          VARCHAR,
+    changed_at         TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX lot_vehicle_history_lot_vehicle_number ON lot_vehicle_history (lot_vehicle_number, changed_at);
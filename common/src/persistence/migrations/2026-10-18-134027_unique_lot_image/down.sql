@@ -0,0 +1,3 @@
+-- deduplicated rows are not restored
+ALTER TABLE lot_image DROP COLUMN removed_at;
+ALTER TABLE lot_image DROP CONSTRAINT unique_lot_image;
@@ -0,0 +1,13 @@
+-- keep the most recently synced row of every duplicated image
+DELETE
+FROM lot_image a
+    USING lot_image b
+WHERE a.lot_vehicle_number = b.lot_vehicle_number
+  AND a.sequence_number = b.sequence_number
+  AND a.image_type = b.image_type
+  AND a.id < b.id;
+
+ALTER TABLE lot_image
+    ADD CONSTRAINT unique_lot_image UNIQUE (lot_vehicle_number, sequence_number, image_type);
+
+ALTER TABLE lot_image ADD COLUMN removed_at TIMESTAMP;
@@ -0,0 +1,17 @@
+CREATE TABLE auction_result
+(
+    id                 SERIAL PRIMARY KEY,
+    lot_vehicle_number INTEGER          NOT NULL REFERENCES lot_vehicle (lot_number),
+    final_bid          DOUBLE PRECISION NOT NULL,
+    buyer_country      VARCHAR,
+    buyer_state        VARCHAR,
+    min_met            BOOLEAN          NOT NULL,
+    sold_at            TIMESTAMP        NOT NULL,
+    created_at         TIMESTAMP        NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at         TIMESTAMP        NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- a lot can be auctioned again when the minimum was not met, redelivered events share the timestamp
+CREATE UNIQUE INDEX unique_auction_result ON auction_result (lot_vehicle_number, sold_at);
+
+SELECT diesel_manage_updated_at('auction_result');
@@ -0,0 +1,12 @@
+ALTER TABLE lot_vehicle
+    DROP COLUMN make_id,
+    DROP COLUMN model_id,
+    DROP COLUMN main_damage_id,
+    DROP COLUMN color_id,
+    DROP COLUMN fuel_type_id,
+    DROP COLUMN drive_type_id;
+
+DROP TABLE reference_alias;
+DROP FUNCTION reference_alias_bump_version();
+DROP SEQUENCE reference_alias_version;
+DROP TABLE reference_value;
@@ -0,0 +1,60 @@
+CREATE TABLE reference_value
+(
+    id         SERIAL PRIMARY KEY,
+    kind       VARCHAR   NOT NULL,
+    name       VARCHAR   NOT NULL,
+    -- models belong to the make they were listed with, other kinds have no make
+    make_id    INTEGER REFERENCES reference_value (id),
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX unique_reference_value ON reference_value (kind, make_id, name) NULLS NOT DISTINCT;
+
+SELECT diesel_manage_updated_at('reference_value');
+
+CREATE TABLE reference_alias
+(
+    id                 SERIAL PRIMARY KEY,
+    kind               VARCHAR   NOT NULL,
+    alias              VARCHAR   NOT NULL,
+    reference_value_id INTEGER   NOT NULL REFERENCES reference_value (id),
+    -- the same model alias may mean different models of different makes
+    make_id            INTEGER REFERENCES reference_value (id),
+    created_at         TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at         TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX unique_reference_alias ON reference_alias (kind, make_id, alias) NULLS NOT DISTINCT;
+
+SELECT diesel_manage_updated_at('reference_alias');
+
+-- bumped by every committed change of an alias, cached alias tables are reloaded when it
+-- moved. A sequence takes no row lock, concurrent writers of new aliases do not wait on it.
+CREATE SEQUENCE reference_alias_version;
+
+CREATE FUNCTION reference_alias_bump_version() RETURNS trigger AS
+$$
+BEGIN
+    PERFORM nextval('reference_alias_version');
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- deferred to the commit, so a version is not taken by a transaction which rolls back
+CREATE CONSTRAINT TRIGGER reference_alias_bump_version
+    AFTER INSERT OR UPDATE OR DELETE
+    ON reference_alias
+    DEFERRABLE INITIALLY DEFERRED
+    FOR EACH ROW
+EXECUTE FUNCTION reference_alias_bump_version();
+
+ALTER TABLE lot_vehicle
+    ADD COLUMN make_id        INTEGER REFERENCES reference_value (id),
+    ADD COLUMN model_id       INTEGER REFERENCES reference_value (id),
+    ADD COLUMN main_damage_id INTEGER REFERENCES reference_value (id),
+    ADD COLUMN color_id       INTEGER REFERENCES reference_value (id),
+    ADD COLUMN fuel_type_id   INTEGER REFERENCES reference_value (id),
+    ADD COLUMN drive_type_id  INTEGER REFERENCES reference_value (id);
+
+CREATE INDEX lot_vehicle_make_id_model_id ON lot_vehicle (make_id, model_id);
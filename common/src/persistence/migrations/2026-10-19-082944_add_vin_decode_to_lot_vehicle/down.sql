@@ -0,0 +1,7 @@
+ALTER TABLE lot_vehicle
+    DROP COLUMN vin_valid,
+    DROP COLUMN vin_check_digit_valid,
+    DROP COLUMN vin_manufacturer,
+    DROP COLUMN vin_country,
+    DROP COLUMN vin_model_year,
+    DROP COLUMN vin_plant_code;
@@ -0,0 +1,7 @@
+ALTER TABLE lot_vehicle
+    ADD COLUMN vin_valid             BOOLEAN,
+    ADD COLUMN vin_check_digit_valid BOOLEAN,
+    ADD COLUMN vin_manufacturer      VARCHAR,
+    ADD COLUMN vin_country           VARCHAR,
+    ADD COLUMN vin_model_year        INTEGER,
+    ADD COLUMN vin_plant_code        VARCHAR;
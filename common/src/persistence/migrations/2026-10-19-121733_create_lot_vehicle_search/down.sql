@@ -0,0 +1,9 @@
+DROP INDEX lot_vehicle_vin_trgm;
+DROP INDEX lot_vehicle_search_text_trgm;
+DROP INDEX lot_vehicle_search_document;
+
+ALTER TABLE lot_vehicle
+    DROP COLUMN search_document,
+    DROP COLUMN search_text,
+    DROP COLUMN trim,
+    DROP COLUMN yard;
@@ -0,0 +1,26 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+ALTER TABLE lot_vehicle
+    ADD COLUMN trim  VARCHAR,
+    ADD COLUMN yard  VARCHAR;
+
+-- plain text of the searchable attributes, used for fuzzy matching and highlighting
+ALTER TABLE lot_vehicle
+    ADD COLUMN search_text TEXT GENERATED ALWAYS AS (
+        make || ' ' || model || ' ' || coalesce(trim, '') || ' ' ||
+        main_damage || ' ' || color || ' ' || coalesce(yard, '')
+        ) STORED;
+
+ALTER TABLE lot_vehicle
+    ADD COLUMN search_document TSVECTOR GENERATED ALWAYS AS (
+        setweight(to_tsvector('simple', make), 'A') ||
+        setweight(to_tsvector('simple', model), 'A') ||
+        setweight(to_tsvector('simple', coalesce(trim, '')), 'B') ||
+        setweight(to_tsvector('simple', main_damage), 'C') ||
+        setweight(to_tsvector('simple', color), 'C') ||
+        setweight(to_tsvector('simple', coalesce(yard, '')), 'D')
+        ) STORED;
+
+CREATE INDEX lot_vehicle_search_document ON lot_vehicle USING GIN (search_document);
+CREATE INDEX lot_vehicle_search_text_trgm ON lot_vehicle USING GIN (search_text gin_trgm_ops);
+CREATE INDEX lot_vehicle_vin_trgm ON lot_vehicle USING GIN (vin gin_trgm_ops);
@@ -0,0 +1,2 @@
+DROP TABLE vin_takedown_audit;
+DROP TABLE vin_takedown;
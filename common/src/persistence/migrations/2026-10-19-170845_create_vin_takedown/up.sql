@@ -0,0 +1,35 @@
+CREATE TABLE vin_takedown
+(
+    id            SERIAL PRIMARY KEY,
+    vin           VARCHAR   NOT NULL,
+    state         VARCHAR   NOT NULL DEFAULT 'requested'
+        CHECK (state IN ('requested', 'verified', 'active', 'revoked')),
+    contact_email VARCHAR   NOT NULL,
+    contact_name  VARCHAR,
+    reason        VARCHAR   NOT NULL,
+    requested_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    verified_at   TIMESTAMP,
+    activated_at  TIMESTAMP,
+    revoked_at    TIMESTAMP,
+    created_at    TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at    TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- a vin has at most one open takedown, revoked ones are kept for the audit trail
+CREATE UNIQUE INDEX unique_open_vin_takedown ON vin_takedown (vin) WHERE state <> 'revoked';
+CREATE INDEX vin_takedown_state_idx ON vin_takedown (state);
+
+SELECT diesel_manage_updated_at('vin_takedown');
+
+CREATE TABLE vin_takedown_audit
+(
+    id              SERIAL PRIMARY KEY,
+    vin_takedown_id INTEGER   NOT NULL REFERENCES vin_takedown (id),
+    from_state      VARCHAR,
+    to_state        VARCHAR   NOT NULL,
+    actor           VARCHAR   NOT NULL,
+    note            VARCHAR,
+    changed_at      TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX vin_takedown_audit_takedown_idx ON vin_takedown_audit (vin_takedown_id);
@@ -0,0 +1 @@
+ALTER TABLE vin_takedown DROP COLUMN erased_at;
@@ -0,0 +1,2 @@
+-- set once the images of the vin's lots were erased from the database and the bucket
+ALTER TABLE vin_takedown ADD COLUMN erased_at TIMESTAMP;
@@ -0,0 +1,36 @@
+-- lots of other providers cannot be keyed by the bare lot number and are deleted
+DELETE FROM lot_image WHERE source <> 'copart';
+DELETE FROM lot_vehicle_history WHERE source <> 'copart';
+DELETE FROM auction_result WHERE source <> 'copart';
+DELETE FROM lot_vehicle WHERE source <> 'copart';
+
+ALTER TABLE lot_image DROP CONSTRAINT lot_image_source_lot_vehicle_number_fkey;
+ALTER TABLE lot_vehicle_history DROP CONSTRAINT lot_vehicle_history_source_lot_vehicle_number_fkey;
+ALTER TABLE auction_result DROP CONSTRAINT auction_result_source_lot_vehicle_number_fkey;
+
+DROP INDEX lot_vehicle_history_lot_vehicle_number;
+CREATE INDEX lot_vehicle_history_lot_vehicle_number ON lot_vehicle_history (lot_vehicle_number, changed_at);
+
+DROP INDEX unique_auction_result;
+CREATE UNIQUE INDEX unique_auction_result ON auction_result (lot_vehicle_number, sold_at);
+
+ALTER TABLE lot_image DROP CONSTRAINT unique_lot_image;
+ALTER TABLE lot_image
+    ADD CONSTRAINT unique_lot_image UNIQUE (lot_vehicle_number, sequence_number, image_type);
+
+DROP INDEX lot_vehicle_vin;
+ALTER TABLE lot_vehicle DROP CONSTRAINT lot_vehicle_pkey;
+CREATE UNIQUE INDEX unique_lot_number ON lot_vehicle (lot_number);
+ALTER TABLE lot_vehicle ADD PRIMARY KEY (lot_number);
+
+ALTER TABLE lot_image DROP COLUMN source;
+ALTER TABLE lot_vehicle_history DROP COLUMN source;
+ALTER TABLE auction_result DROP COLUMN source;
+ALTER TABLE lot_vehicle DROP COLUMN source;
+
+ALTER TABLE lot_image
+    ADD FOREIGN KEY (lot_vehicle_number) REFERENCES lot_vehicle (lot_number);
+ALTER TABLE lot_vehicle_history
+    ADD FOREIGN KEY (lot_vehicle_number) REFERENCES lot_vehicle (lot_number);
+ALTER TABLE auction_result
+    ADD FOREIGN KEY (lot_vehicle_number) REFERENCES lot_vehicle (lot_number);
@@ -0,0 +1,37 @@
+-- lot numbers are only unique within an auction house, every lot row is scoped by its source
+ALTER TABLE lot_image DROP CONSTRAINT lot_image_lot_vehicle_number_fkey;
+ALTER TABLE lot_vehicle_history DROP CONSTRAINT lot_vehicle_history_lot_vehicle_number_fkey;
+ALTER TABLE auction_result DROP CONSTRAINT auction_result_lot_vehicle_number_fkey;
+
+-- existing rows all come from copart, new rows must name their source
+ALTER TABLE lot_vehicle ADD COLUMN source VARCHAR NOT NULL DEFAULT 'copart';
+ALTER TABLE lot_vehicle ALTER COLUMN source DROP DEFAULT;
+ALTER TABLE lot_image ADD COLUMN source VARCHAR NOT NULL DEFAULT 'copart';
+ALTER TABLE lot_image ALTER COLUMN source DROP DEFAULT;
+ALTER TABLE lot_vehicle_history ADD COLUMN source VARCHAR NOT NULL DEFAULT 'copart';
+ALTER TABLE lot_vehicle_history ALTER COLUMN source DROP DEFAULT;
+ALTER TABLE auction_result ADD COLUMN source VARCHAR NOT NULL DEFAULT 'copart';
+ALTER TABLE auction_result ALTER COLUMN source DROP DEFAULT;
+
+ALTER TABLE lot_vehicle DROP CONSTRAINT lot_vehicle_pkey;
+DROP INDEX unique_lot_number;
+ALTER TABLE lot_vehicle ADD PRIMARY KEY (source, lot_number);
+CREATE INDEX lot_vehicle_vin ON lot_vehicle (vin);
+
+ALTER TABLE lot_image
+    ADD FOREIGN KEY (source, lot_vehicle_number) REFERENCES lot_vehicle (source, lot_number);
+ALTER TABLE lot_vehicle_history
+    ADD FOREIGN KEY (source, lot_vehicle_number) REFERENCES lot_vehicle (source, lot_number);
+ALTER TABLE auction_result
+    ADD FOREIGN KEY (source, lot_vehicle_number) REFERENCES lot_vehicle (source, lot_number);
+
+ALTER TABLE lot_image DROP CONSTRAINT unique_lot_image;
+ALTER TABLE lot_image
+    ADD CONSTRAINT unique_lot_image UNIQUE (source, lot_vehicle_number, sequence_number, image_type);
+
+DROP INDEX unique_auction_result;
+CREATE UNIQUE INDEX unique_auction_result ON auction_result (source, lot_vehicle_number, sold_at);
+
+DROP INDEX lot_vehicle_history_lot_vehicle_number;
+CREATE INDEX lot_vehicle_history_lot_vehicle_number
+    ON lot_vehicle_history (source, lot_vehicle_number, changed_at);
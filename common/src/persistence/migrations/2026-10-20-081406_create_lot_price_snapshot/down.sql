@@ -0,0 +1 @@
+DROP TABLE lot_price_snapshot;
@@ -0,0 +1,16 @@
+CREATE TABLE lot_price_snapshot
+(
+    id                 SERIAL PRIMARY KEY,
+    source             VARCHAR          NOT NULL,
+    lot_vehicle_number INTEGER          NOT NULL,
+    current_bid        DOUBLE PRECISION NOT NULL,
+    buy_today_bid      DOUBLE PRECISION NOT NULL,
+    high_bid           DOUBLE PRECISION NOT NULL,
+    sale_status        VARCHAR          NOT NULL,
+    seen_at            TIMESTAMP        NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (source, lot_vehicle_number) REFERENCES lot_vehicle (source, lot_number)
+);
+
+-- a snapshot is only taken when the prices changed since the latest one of the lot
+CREATE INDEX lot_price_snapshot_lot_vehicle_number
+    ON lot_price_snapshot (source, lot_vehicle_number, seen_at);
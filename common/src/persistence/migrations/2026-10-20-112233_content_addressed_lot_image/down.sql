@@ -0,0 +1,41 @@
+-- only renditions named after the former kinds fit back, generated variants are dropped
+ALTER TABLE lot_image
+    ADD COLUMN standard_bucket_key  VARCHAR,
+    ADD COLUMN standard_mime_type   VARCHAR,
+    ADD COLUMN standard_source_url  VARCHAR,
+    ADD COLUMN thumbnail_bucket_key VARCHAR,
+    ADD COLUMN thumbnail_mime_type  VARCHAR,
+    ADD COLUMN thumbnail_source_url VARCHAR,
+    ADD COLUMN high_res_bucket_key  VARCHAR,
+    ADD COLUMN high_res_mime_type   VARCHAR,
+    ADD COLUMN high_res_source_url  VARCHAR;
+
+UPDATE lot_image li
+SET standard_bucket_key = v.bucket_key,
+    standard_mime_type  = v.mime_type,
+    standard_source_url = li.source_url
+FROM lot_image_variant v
+WHERE v.lot_image_id = li.id
+  AND v.name = 'standard';
+
+UPDATE lot_image li
+SET thumbnail_bucket_key = v.bucket_key,
+    thumbnail_mime_type  = v.mime_type,
+    thumbnail_source_url = li.source_url
+FROM lot_image_variant v
+WHERE v.lot_image_id = li.id
+  AND v.name = 'thumbnail';
+
+UPDATE lot_image li
+SET high_res_bucket_key = v.bucket_key,
+    high_res_mime_type  = v.mime_type,
+    high_res_source_url = li.source_url
+FROM lot_image_variant v
+WHERE v.lot_image_id = li.id
+  AND v.name = 'high_res';
+
+ALTER TABLE lot_image DROP COLUMN source_url;
+
+DROP TABLE lot_image_variant;
+DROP FUNCTION lot_image_variant_count_blob_refs();
+DROP TABLE image_blob;
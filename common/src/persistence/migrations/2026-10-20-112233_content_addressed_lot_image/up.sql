@@ -0,0 +1,88 @@
+-- every synced image is stored as a list of variants instead of the fixed
+-- standard/thumbnail/high_res renditions, each variant points at an object keyed by
+-- the sha256 of its content, identical photos share one object
+CREATE TABLE lot_image_variant
+(
+    id           SERIAL PRIMARY KEY,
+    lot_image_id INTEGER   NOT NULL REFERENCES lot_image (id) ON DELETE CASCADE,
+    -- configured variant, e.g. `w800`, renditions synced before are named after their kind
+    name         VARCHAR   NOT NULL,
+    format       VARCHAR   NOT NULL,
+    mime_type    VARCHAR   NOT NULL,
+    -- unknown for renditions synced before the variants were generated locally
+    width        INTEGER,
+    height       INTEGER,
+    -- unknown for renditions synced before content addressing until they are rekeyed
+    size         BIGINT,
+    bucket_key   VARCHAR   NOT NULL,
+    sha256       VARCHAR,
+    created_at   TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CONSTRAINT unique_lot_image_variant UNIQUE (lot_image_id, name, format)
+);
+
+CREATE INDEX lot_image_variant_sha256 ON lot_image_variant (sha256);
+CREATE INDEX lot_image_variant_bucket_key ON lot_image_variant (bucket_key);
+
+-- one row per stored object keyed by its content, `ref_count` is the number of
+-- variants pointing at it and is kept by the trigger below
+CREATE TABLE image_blob
+(
+    sha256     VARCHAR PRIMARY KEY,
+    size       BIGINT    NOT NULL,
+    ref_count  INTEGER   NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+SELECT diesel_manage_updated_at('image_blob');
+
+CREATE FUNCTION lot_image_variant_count_blob_refs() RETURNS trigger AS
+$$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') AND OLD.sha256 IS NOT NULL THEN
+        UPDATE image_blob SET ref_count = ref_count - 1 WHERE sha256 = OLD.sha256;
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') AND NEW.sha256 IS NOT NULL THEN
+        INSERT INTO image_blob (sha256, size, ref_count)
+        VALUES (NEW.sha256, coalesce(NEW.size, 0), 1)
+        ON CONFLICT (sha256) DO UPDATE SET ref_count = image_blob.ref_count + 1;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER lot_image_variant_count_blob_refs
+    AFTER INSERT OR DELETE OR UPDATE OF sha256
+    ON lot_image_variant
+    FOR EACH ROW
+EXECUTE FUNCTION lot_image_variant_count_blob_refs();
+
+ALTER TABLE lot_image ADD COLUMN source_url VARCHAR;
+UPDATE lot_image
+SET source_url = coalesce(high_res_source_url, standard_source_url, thumbnail_source_url);
+
+-- renditions keep their legacy `{lot}_{seq}_{kind}` keys until they are rekeyed, see
+-- `manager minio rekey`
+INSERT INTO lot_image_variant (lot_image_id, name, format, mime_type, bucket_key)
+SELECT id, kind, split_part(mime_type, '/', 2), mime_type, bucket_key
+FROM (SELECT id, 'standard' AS kind, coalesce(standard_mime_type, 'application/octet-stream') AS mime_type,
+             standard_bucket_key AS bucket_key
+      FROM lot_image
+      UNION ALL
+      SELECT id, 'thumbnail', coalesce(thumbnail_mime_type, 'application/octet-stream'), thumbnail_bucket_key
+      FROM lot_image
+      UNION ALL
+      SELECT id, 'high_res', coalesce(high_res_mime_type, 'application/octet-stream'), high_res_bucket_key
+      FROM lot_image) renditions
+WHERE bucket_key IS NOT NULL;
+
+ALTER TABLE lot_image
+    DROP COLUMN standard_bucket_key,
+    DROP COLUMN standard_mime_type,
+    DROP COLUMN standard_source_url,
+    DROP COLUMN thumbnail_bucket_key,
+    DROP COLUMN thumbnail_mime_type,
+    DROP COLUMN thumbnail_source_url,
+    DROP COLUMN high_res_bucket_key,
+    DROP COLUMN high_res_mime_type,
+    DROP COLUMN high_res_source_url;
@@ -0,0 +1 @@
+ALTER TABLE lot_image DROP COLUMN perceptual_hash;
@@ -0,0 +1,3 @@
+-- 64 bit difference hash of the synced source, compared by hamming distance
+-- (`bit_count(a # b)`) to find the same photo listed under other lots
+ALTER TABLE lot_image ADD COLUMN perceptual_hash BIGINT;
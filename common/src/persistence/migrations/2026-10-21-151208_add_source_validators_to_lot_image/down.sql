@@ -0,0 +1,3 @@
+ALTER TABLE lot_image
+    DROP COLUMN source_etag,
+    DROP COLUMN source_size;
@@ -0,0 +1,5 @@
+-- validators of the synced source, an image whose source still matches them is not
+-- downloaded again on redelivery or resync
+ALTER TABLE lot_image
+    ADD COLUMN source_etag VARCHAR,
+    ADD COLUMN source_size BIGINT;
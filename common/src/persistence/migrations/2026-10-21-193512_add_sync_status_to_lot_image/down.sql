@@ -0,0 +1,9 @@
+ALTER TABLE lot_vehicle DROP COLUMN images_complete_at;
+
+DROP INDEX lot_image_retry_at;
+
+ALTER TABLE lot_image
+    DROP COLUMN sync_status,
+    DROP COLUMN sync_error,
+    DROP COLUMN sync_attempts,
+    DROP COLUMN retry_at;
@@ -0,0 +1,43 @@
+-- outcome of the latest sync of an image: `synced`, `unchanged`, `no_source` or `failed`
+-- with the stage it failed at, failed images are retried with backoff until `retry_at`
+-- is cleared after the last attempt
+ALTER TABLE lot_image
+    ADD COLUMN sync_status   VARCHAR NOT NULL DEFAULT 'synced',
+    ADD COLUMN sync_error    VARCHAR,
+    ADD COLUMN sync_attempts INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN retry_at      TIMESTAMP;
+
+CREATE INDEX lot_image_retry_at ON lot_image (retry_at) WHERE retry_at IS NOT NULL;
+
+-- images synced before statuses were stored failed when they have no variants, their
+-- stage is unknown and their retries are spread over the next day rather than all due
+-- at once
+UPDATE lot_image i
+SET sync_status = CASE WHEN i.source_url IS NULL THEN 'no_source' ELSE 'failed' END,
+    retry_at    = CASE
+                      WHEN i.source_url IS NOT NULL
+                          THEN now() + random() * interval '1 day'
+                      END
+WHERE i.removed_at IS NULL
+  AND NOT EXISTS (SELECT 1 FROM lot_image_variant v WHERE v.lot_image_id = i.id);
+
+-- set once every image of the lot with a source synced, cleared when one fails
+ALTER TABLE lot_vehicle ADD COLUMN images_complete_at TIMESTAMP;
+
+-- marking the lots is not an update of their listing
+ALTER TABLE lot_vehicle DISABLE TRIGGER set_updated_at;
+UPDATE lot_vehicle v
+SET images_complete_at = now()
+WHERE EXISTS (SELECT 1
+              FROM lot_image i
+              WHERE i.source = v.source
+                AND i.lot_vehicle_number = v.lot_number
+                AND i.removed_at IS NULL
+                AND i.sync_status = 'synced')
+  AND NOT EXISTS (SELECT 1
+                  FROM lot_image i
+                  WHERE i.source = v.source
+                    AND i.lot_vehicle_number = v.lot_number
+                    AND i.removed_at IS NULL
+                    AND i.sync_status = 'failed');
+ALTER TABLE lot_vehicle ENABLE TRIGGER set_updated_at;
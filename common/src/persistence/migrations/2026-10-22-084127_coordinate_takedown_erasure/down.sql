@@ -0,0 +1,3 @@
+DROP TABLE takedown_pending_object;
+
+DROP TABLE erased_object;
@@ -0,0 +1,18 @@
+-- content addressed objects deleted by an erasure, a sync which found one of them in the
+-- bucket just before and skipped its upload is stored as failed and uploads it again on
+-- the retry, the row is dropped once such a sync arrives
+CREATE TABLE erased_object
+(
+    bucket_key VARCHAR PRIMARY KEY,
+    erased_at  TIMESTAMP NOT NULL DEFAULT now()
+);
+
+-- objects of lots of an active takedown uploaded by syncs in flight during its erasure,
+-- deleted by the next erasure of the takedown
+CREATE TABLE takedown_pending_object
+(
+    vin_takedown_id INTEGER   NOT NULL REFERENCES vin_takedown (id),
+    bucket_key      VARCHAR   NOT NULL,
+    queued_at       TIMESTAMP NOT NULL DEFAULT now(),
+    PRIMARY KEY (vin_takedown_id, bucket_key)
+);
@@ -0,0 +1,10 @@
+DROP INDEX lot_image_perceptual_hash_band_0;
+DROP INDEX lot_image_perceptual_hash_band_1;
+DROP INDEX lot_image_perceptual_hash_band_2;
+DROP INDEX lot_image_perceptual_hash_band_3;
+
+ALTER TABLE lot_image
+    DROP COLUMN perceptual_hash_band_0,
+    DROP COLUMN perceptual_hash_band_1,
+    DROP COLUMN perceptual_hash_band_2,
+    DROP COLUMN perceptual_hash_band_3;
@@ -0,0 +1,13 @@
+-- the 64 bit hash split into four 16 bit bands, two hashes within `d` bits of each other
+-- have a band within `d / 4` bits of the other's, so candidates of a related lots lookup
+-- are found by indexed band lookups rather than comparing every hash
+ALTER TABLE lot_image
+    ADD COLUMN perceptual_hash_band_0 INTEGER GENERATED ALWAYS AS ((perceptual_hash & 65535)::INTEGER) STORED,
+    ADD COLUMN perceptual_hash_band_1 INTEGER GENERATED ALWAYS AS (((perceptual_hash >> 16) & 65535)::INTEGER) STORED,
+    ADD COLUMN perceptual_hash_band_2 INTEGER GENERATED ALWAYS AS (((perceptual_hash >> 32) & 65535)::INTEGER) STORED,
+    ADD COLUMN perceptual_hash_band_3 INTEGER GENERATED ALWAYS AS (((perceptual_hash >> 48) & 65535)::INTEGER) STORED;
+
+CREATE INDEX lot_image_perceptual_hash_band_0 ON lot_image (perceptual_hash_band_0) WHERE removed_at IS NULL;
+CREATE INDEX lot_image_perceptual_hash_band_1 ON lot_image (perceptual_hash_band_1) WHERE removed_at IS NULL;
+CREATE INDEX lot_image_perceptual_hash_band_2 ON lot_image (perceptual_hash_band_2) WHERE removed_at IS NULL;
+CREATE INDEX lot_image_perceptual_hash_band_3 ON lot_image (perceptual_hash_band_3) WHERE removed_at IS NULL;
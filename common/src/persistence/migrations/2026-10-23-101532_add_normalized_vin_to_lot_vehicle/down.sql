@@ -0,0 +1,4 @@
+DROP INDEX lot_vehicle_vin_normalized;
+CREATE INDEX lot_vehicle_vin ON lot_vehicle (vin);
+
+ALTER TABLE lot_vehicle DROP COLUMN vin_normalized;
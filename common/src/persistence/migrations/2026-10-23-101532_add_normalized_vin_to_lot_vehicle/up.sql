@@ -0,0 +1,10 @@
+-- the raw vin is kept as received, takedowns and vin lookups compare this one. It is
+-- normalized the way `vin::normalize` normalizes user input, so rows stored before are
+-- covered without a backfill.
+ALTER TABLE lot_vehicle
+    ADD COLUMN vin_normalized VARCHAR GENERATED ALWAYS AS (
+        translate(upper(regexp_replace(vin, '[[:space:]-]', '', 'g')), 'IOQ', '100')
+        ) STORED;
+
+DROP INDEX lot_vehicle_vin;
+CREATE INDEX lot_vehicle_vin_normalized ON lot_vehicle (vin_normalized);
@@ -4,19 +4,43 @@ use diesel_async::pooled_connection::AsyncDieselConnectionManager;
 use diesel_async::AsyncPgConnection;
 use std::sync::LazyLock;
 
+pub mod backfill;
+pub mod canonical;
+pub mod erasure;
+#[cfg(any(test, feature = "migrations"))]
+pub mod migrate;
 pub mod models;
+pub mod reconcile;
+pub mod rekey;
+pub mod related;
+pub mod repo;
+pub mod retry;
 pub mod schema;
+pub mod search;
+#[cfg(any(test, feature = "test-harness"))]
+pub mod testing;
 
 pub type PgPool = Pool<AsyncPgConnection>;
 
+#[cfg(any(test, feature = "diesel_migrations"))]
+pub const PG_MIGRATIONS: diesel_migrations::EmbeddedMigrations =
+    diesel_migrations::embed_migrations!("src/persistence/migrations");
+
 pub fn init_pg_pool() -> PgPool {
-    let db_url = format!(
+    pg_pool(&pg_url())
+}
+
+pub fn pg_url() -> String {
+    format!(
         "postgres://{user}:{password}@{host}/{db_name}",
         user = CONFIG.postgres.user,
         password = CONFIG.postgres.password,
         host = CONFIG.postgres.host,
         db_name = CONFIG.postgres.db_name
-    );
+    )
+}
+
+pub fn pg_pool(db_url: &str) -> PgPool {
     let config = AsyncDieselConnectionManager::<AsyncPgConnection>::new(db_url);
     Pool::builder(config).build().expect("build pool")
 }
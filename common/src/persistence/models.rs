@@ -1,12 +1,18 @@
 pub mod copart {
-    use crate::io::copart::LotVehicleVector;
+    use crate::io::provider::{
+        AuctionResultResponse, LotVehicleVector, Provider, SyncedImageVariant,
+    };
+    use crate::persistence::canonical::CanonicalIds;
+    use crate::vin::{self, Vin};
     use diesel::prelude::*;
 
     #[derive(Queryable, Selectable, Identifiable)]
     #[diesel(table_name = crate::persistence::schema::lot_vehicle)]
     #[diesel(check_for_backend(diesel::pg::Pg))]
-    #[diesel(primary_key(lot_number))]
+    #[diesel(primary_key(source, lot_number))]
     pub struct LotVehicle {
+        /// One of [`Provider`], lot numbers are unique per source only
+        pub source: String,
         pub lot_number: i32,
         pub make: String,
         pub model: String,
@@ -32,11 +38,30 @@ pub mod copart {
         pub keys_status: Option<String>,
         pub created_at: chrono::NaiveDateTime,
         pub updated_at: chrono::NaiveDateTime,
+        pub make_id: Option<i32>,
+        pub model_id: Option<i32>,
+        pub main_damage_id: Option<i32>,
+        pub color_id: Option<i32>,
+        pub fuel_type_id: Option<i32>,
+        pub drive_type_id: Option<i32>,
+        pub vin_valid: Option<bool>,
+        pub vin_check_digit_valid: Option<bool>,
+        pub vin_manufacturer: Option<String>,
+        pub vin_country: Option<String>,
+        pub vin_model_year: Option<i32>,
+        pub vin_plant_code: Option<String>,
+        pub trim: Option<String>,
+        pub yard: Option<String>,
+        /// Set once every image of the lot with a source synced, cleared when one fails
+        pub images_complete_at: Option<chrono::NaiveDateTime>,
     }
 
-    #[derive(Insertable)]
+    #[derive(Insertable, AsChangeset)]
     #[diesel(table_name = crate::persistence::schema::lot_vehicle)]
+    #[diesel(primary_key(source, lot_number))]
+    #[diesel(treat_none_as_null = true)]
     pub struct NewLotVehicle {
+        pub source: String,
         pub lot_number: i32,
         pub make: String,
         pub model: String,
@@ -60,65 +85,263 @@ pub mod copart {
         pub fuel_type: Option<String>,
         pub drive_type: Option<String>,
         pub keys_status: Option<String>,
+        /// Canonical reference ids, assigned by [`crate::persistence::canonical::Canonicalizer`]
+        pub make_id: Option<i32>,
+        pub model_id: Option<i32>,
+        pub main_damage_id: Option<i32>,
+        pub color_id: Option<i32>,
+        pub fuel_type_id: Option<i32>,
+        pub drive_type_id: Option<i32>,
+        pub vin_valid: Option<bool>,
+        pub vin_check_digit_valid: Option<bool>,
+        pub vin_manufacturer: Option<String>,
+        pub vin_country: Option<String>,
+        pub vin_model_year: Option<i32>,
+        pub vin_plant_code: Option<String>,
+        pub trim: Option<String>,
+        pub yard: Option<String>,
     }
 
     pub struct NewLotVehicles(pub Vec<NewLotVehicle>);
 
-    impl From<LotVehicleVector> for NewLotVehicles {
-        fn from(value: LotVehicleVector) -> Self {
+    /// Compares given fields of a listing with the stored lot vehicle and
+    /// collects a history entry for every field which differs.
+    macro_rules! changed_fields {
+        ($new:expr, $current:expr, [$($field:ident),* $(,)?]) => {{
+            let mut changes = vec![];
+            $(
+                if $new.$field != $current.$field {
+                    changes.push(NewLotVehicleHistory {
+                        source: $current.source.clone(),
+                        lot_vehicle_number: $current.lot_number,
+                        field: stringify!($field),
+                        old_value: $current.$field.to_history_value(),
+                        new_value: $new.$field.to_history_value(),
+                    });
+                }
+            )*
+            changes
+        }};
+    }
+
+    impl NewLotVehicle {
+        /// Field level differences against the currently stored lot vehicle,
+        /// empty when the listing did not change.
+        pub fn changes(&self, current: &LotVehicle) -> Vec<NewLotVehicleHistory> {
+            changed_fields!(
+                self,
+                current,
+                [
+                    make,
+                    model,
+                    year,
+                    vehicle_type,
+                    vin,
+                    estimated_retail_value,
+                    estimated_repair_cost,
+                    odometer,
+                    odometer_status,
+                    engine_name,
+                    engine_cylinders,
+                    currency,
+                    sale_date,
+                    main_damage,
+                    other_damage,
+                    country,
+                    state,
+                    transmission,
+                    color,
+                    fuel_type,
+                    drive_type,
+                    keys_status,
+                    trim,
+                    yard,
+                ]
+            )
+        }
+
+        /// Whether the fields derived at ingestion, the canonical ids and the vin decode,
+        /// differ from the stored ones, e.g. for lots stored before a field was derived.
+        pub fn derived_changed(&self, current: &LotVehicle) -> bool {
+            CanonicalIds::from(self) != CanonicalIds::from(current)
+                || VinFields::from(self) != VinFields::from(current)
+        }
+    }
+
+    trait ToHistoryValue {
+        fn to_history_value(&self) -> Option<String>;
+    }
+
+    impl ToHistoryValue for String {
+        fn to_history_value(&self) -> Option<String> {
+            Some(self.clone())
+        }
+    }
+
+    impl ToHistoryValue for i32 {
+        fn to_history_value(&self) -> Option<String> {
+            Some(self.to_string())
+        }
+    }
+
+    impl ToHistoryValue for f64 {
+        fn to_history_value(&self) -> Option<String> {
+            Some(self.to_string())
+        }
+    }
+
+    impl ToHistoryValue for chrono::NaiveDateTime {
+        fn to_history_value(&self) -> Option<String> {
+            Some(self.format("%Y-%m-%dT%H:%M:%S").to_string())
+        }
+    }
+
+    impl<T: ToHistoryValue> ToHistoryValue for Option<T> {
+        fn to_history_value(&self) -> Option<String> {
+            self.as_ref().and_then(|v| v.to_history_value())
+        }
+    }
+
+    #[derive(Selectable, Queryable, Identifiable)]
+    #[diesel(table_name = crate::persistence::schema::lot_vehicle_history)]
+    #[diesel(check_for_backend(diesel::pg::Pg))]
+    pub struct LotVehicleHistory {
+        pub id: i32,
+        pub source: String,
+        pub lot_vehicle_number: i32,
+        pub field: String,
+        pub old_value: Option<String>,
+        pub new_value: Option<String>,
+        pub changed_at: chrono::NaiveDateTime,
+    }
+
+    #[derive(Insertable)]
+    #[diesel(table_name = crate::persistence::schema::lot_vehicle_history)]
+    pub struct NewLotVehicleHistory {
+        pub source: String,
+        pub lot_vehicle_number: i32,
+        pub field: &'static str,
+        pub old_value: Option<String>,
+        pub new_value: Option<String>,
+    }
+
+    impl NewLotVehicles {
+        pub fn new(provider: Provider, listings: LotVehicleVector) -> Self {
             Self(
-                value
+                listings
                     .0
                     .into_iter()
-                    .map(|v| NewLotVehicle {
-                        lot_number: v.lot_number,
-                        make: v.make,
-                        model: v.model,
-                        year: v.year,
-                        vehicle_type: v.vehicle_type,
-                        vin: v.vin,
-                        estimated_retail_value: v.estimated_retail_value,
-                        estimated_repair_cost: v.estimated_repair_cost,
-                        odometer: v.odometer,
-                        odometer_status: v.odometer_status,
-                        engine_name: v.engine_name,
-                        engine_cylinders: v.engine_cylinders,
-                        currency: v.currency,
-                        sale_date: v.sale_date,
-                        main_damage: v.main_damage,
-                        other_damage: v.other_damage,
-                        country: v.country,
-                        state: v.state,
-                        transmission: v.transmission,
-                        color: v.color,
-                        fuel_type: v.fuel_type,
-                        drive_type: v.drive_type,
-                        keys_status: v.keys_status,
+                    .map(|v| {
+                        let vin_fields = VinFields::decode(v.vin.as_deref());
+                        NewLotVehicle {
+                            source: provider.to_string(),
+                            lot_number: v.lot_number,
+                            make: v.make,
+                            model: v.model,
+                            year: v.year,
+                            vehicle_type: v.vehicle_type,
+                            vin: v.vin,
+                            estimated_retail_value: v.estimated_retail_value,
+                            estimated_repair_cost: v.estimated_repair_cost,
+                            odometer: v.odometer,
+                            odometer_status: v.odometer_status,
+                            engine_name: v.engine_name,
+                            engine_cylinders: v.engine_cylinders,
+                            currency: v.currency,
+                            sale_date: v.sale_date,
+                            main_damage: v.main_damage,
+                            other_damage: v.other_damage,
+                            country: v.country,
+                            state: v.state,
+                            transmission: v.transmission,
+                            color: v.color,
+                            fuel_type: v.fuel_type,
+                            drive_type: v.drive_type,
+                            keys_status: v.keys_status,
+                            make_id: None,
+                            model_id: None,
+                            main_damage_id: None,
+                            color_id: None,
+                            fuel_type_id: None,
+                            drive_type_id: None,
+                            vin_valid: vin_fields.vin_valid,
+                            vin_check_digit_valid: vin_fields.vin_check_digit_valid,
+                            vin_manufacturer: vin_fields.vin_manufacturer,
+                            vin_country: vin_fields.vin_country,
+                            vin_model_year: vin_fields.vin_model_year,
+                            vin_plant_code: vin_fields.vin_plant_code,
+                            trim: v.trim,
+                            yard: v.yard,
+                        }
                     })
                     .collect(),
             )
         }
     }
 
-    #[derive(Selectable, Queryable, Associations, Identifiable)]
+    /// Fields decoded from the vin of a lot vehicle.
+    #[derive(Debug, Default, PartialEq, AsChangeset)]
+    #[diesel(table_name = crate::persistence::schema::lot_vehicle)]
+    #[diesel(treat_none_as_null = true)]
+    pub struct VinFields {
+        pub vin_valid: Option<bool>,
+        pub vin_check_digit_valid: Option<bool>,
+        pub vin_manufacturer: Option<String>,
+        pub vin_country: Option<String>,
+        pub vin_model_year: Option<i32>,
+        pub vin_plant_code: Option<String>,
+    }
+
+    impl VinFields {
+        /// The raw vin is stored as received, the decode uses the normalized one as do
+        /// lookups and takedowns through the generated `vin_normalized` column.
+        pub fn decode(raw: Option<&str>) -> Self {
+            let vin_decode =
+                raw.map(|raw| Vin::parse(&vin::normalize(raw)).map(|vin| vin.decode()));
+            let decoded = vin_decode.as_ref().and_then(|d| d.as_ref().ok());
+            Self {
+                vin_valid: vin_decode.as_ref().map(|d| d.is_ok()),
+                vin_check_digit_valid: decoded.map(|d| d.check_digit_valid),
+                vin_manufacturer: decoded.and_then(|d| d.manufacturer).map(str::to_string),
+                vin_country: decoded.and_then(|d| d.country).map(str::to_string),
+                vin_model_year: decoded.and_then(|d| d.model_year),
+                vin_plant_code: decoded.map(|d| d.plant_code.to_string()),
+            }
+        }
+    }
+
+    impl From<&LotVehicle> for VinFields {
+        fn from(value: &LotVehicle) -> Self {
+            Self {
+                vin_valid: value.vin_valid,
+                vin_check_digit_valid: value.vin_check_digit_valid,
+                vin_manufacturer: value.vin_manufacturer.clone(),
+                vin_country: value.vin_country.clone(),
+                vin_model_year: value.vin_model_year,
+                vin_plant_code: value.vin_plant_code.clone(),
+            }
+        }
+    }
+
+    impl From<&NewLotVehicle> for VinFields {
+        fn from(value: &NewLotVehicle) -> Self {
+            Self {
+                vin_valid: value.vin_valid,
+                vin_check_digit_valid: value.vin_check_digit_valid,
+                vin_manufacturer: value.vin_manufacturer.clone(),
+                vin_country: value.vin_country.clone(),
+                vin_model_year: value.vin_model_year,
+                vin_plant_code: value.vin_plant_code.clone(),
+            }
+        }
+    }
+
+    #[derive(Selectable, Queryable, Identifiable)]
     #[diesel(table_name = crate::persistence::schema::lot_image)]
-    #[diesel(belongs_to(LotVehicle, foreign_key = lot_vehicle_number))]
     #[diesel(check_for_backend(diesel::pg::Pg))]
     pub struct LotImage {
         pub id: i32,
 
-        pub standard_bucket_key: Option<String>,
-        pub standard_mime_type: Option<String>,
-        pub standard_source_url: Option<String>,
-
-        pub thumbnail_bucket_key: Option<String>,
-        pub thumbnail_mime_type: Option<String>,
-        pub thumbnail_source_url: Option<String>,
-
-        pub high_res_bucket_key: Option<String>,
-        pub high_res_mime_type: Option<String>,
-        pub high_res_source_url: Option<String>,
-
         pub sequence_number: i32,
         pub image_type: String,
 
@@ -126,28 +349,328 @@ pub mod copart {
         pub updated_at: chrono::NaiveDateTime,
 
         pub lot_vehicle_number: i32,
+        /// Set once the image is no longer part of the lot's synced image set
+        pub removed_at: Option<chrono::NaiveDateTime>,
+        pub source: String,
+        /// Url of the highest resolution the variants were generated from
+        pub source_url: Option<String>,
+        /// Difference hash of the source, see [`crate::persistence::related`]
+        pub perceptual_hash: Option<i64>,
+        /// `ETag` the source was served with when the variants were generated
+        pub source_etag: Option<String>,
+        /// Bytes of the source when the variants were generated
+        pub source_size: Option<i64>,
+        /// One of [`crate::io::provider::ImageSyncStatus`] of the latest sync
+        pub sync_status: String,
+        /// One of [`crate::io::provider::ImageSyncError`] when the latest sync failed
+        pub sync_error: Option<String>,
+        /// Retries claimed since the last successful sync
+        pub sync_attempts: i32,
+        /// Next retry of a failed image, `None` once the attempts ran out
+        pub retry_at: Option<chrono::NaiveDateTime>,
     }
 
     #[derive(Insertable)]
     #[diesel(table_name = crate::persistence::schema::lot_image)]
     pub struct NewLotImage {
-        pub standard_bucket_key: Option<String>,
-        pub standard_mime_type: Option<String>,
-        pub standard_source_url: Option<String>,
-
-        pub thumbnail_bucket_key: Option<String>,
-        pub thumbnail_mime_type: Option<String>,
-        pub thumbnail_source_url: Option<String>,
-
-        pub high_res_bucket_key: Option<String>,
-        pub high_res_mime_type: Option<String>,
-        pub high_res_source_url: Option<String>,
-
         pub sequence_number: i32,
         pub image_type: String,
 
+        pub source: String,
         pub lot_vehicle_number: i32,
+        pub source_url: Option<String>,
+        /// Keeps the stored hash when `None`
+        pub perceptual_hash: Option<i64>,
+        /// Keep the stored validators when `None`
+        pub source_etag: Option<String>,
+        pub source_size: Option<i64>,
+        pub sync_status: String,
+        pub sync_error: Option<String>,
+
+        /// Replace the stored variants of the image on upsert, stored ones are kept when empty
+        #[diesel(skip_insertion)]
+        pub variants: Vec<NewLotImageVariant>,
+    }
+
+    /// A stored copy of a lot image. Renditions synced before the variants were
+    /// generated locally are named `standard`, `thumbnail` or `high_res` and have
+    /// no dimensions.
+    #[derive(Debug, Selectable, Queryable, Identifiable, Associations)]
+    #[diesel(table_name = crate::persistence::schema::lot_image_variant)]
+    #[diesel(belongs_to(LotImage))]
+    #[diesel(check_for_backend(diesel::pg::Pg))]
+    pub struct LotImageVariant {
+        pub id: i32,
+        pub lot_image_id: i32,
+        pub name: String,
+        pub format: String,
+        pub mime_type: String,
+        pub width: Option<i32>,
+        pub height: Option<i32>,
+        pub size: Option<i64>,
+        pub bucket_key: String,
+        pub sha256: Option<String>,
+        pub created_at: chrono::NaiveDateTime,
+    }
+
+    /// Inserted together with the id of its lot image.
+    #[derive(Clone, Insertable)]
+    #[diesel(table_name = crate::persistence::schema::lot_image_variant)]
+    pub struct NewLotImageVariant {
+        pub name: String,
+        pub format: String,
+        pub mime_type: String,
+        pub width: Option<i32>,
+        pub height: Option<i32>,
+        pub size: Option<i64>,
+        pub bucket_key: String,
+        pub sha256: Option<String>,
+    }
+
+    impl From<SyncedImageVariant> for NewLotImageVariant {
+        fn from(value: SyncedImageVariant) -> Self {
+            Self {
+                name: value.name,
+                format: value.format,
+                mime_type: value.mime_type,
+                width: Some(value.width),
+                height: Some(value.height),
+                size: Some(value.size),
+                bucket_key: value.bucket_key,
+                sha256: Some(value.sha256),
+            }
+        }
     }
 
     pub struct NewLotImages(pub Vec<NewLotImage>);
+
+    /// A content keyed object of the lot images bucket, `ref_count` is the number of
+    /// `lot_image` renditions pointing at it and is maintained by the database.
+    #[derive(Debug, Selectable, Queryable, Identifiable)]
+    #[diesel(table_name = crate::persistence::schema::image_blob)]
+    #[diesel(primary_key(sha256))]
+    #[diesel(check_for_backend(diesel::pg::Pg))]
+    pub struct ImageBlob {
+        pub sha256: String,
+        pub size: i64,
+        pub ref_count: i32,
+        pub created_at: chrono::NaiveDateTime,
+        pub updated_at: chrono::NaiveDateTime,
+    }
+
+    #[derive(Selectable, Queryable, Identifiable)]
+    #[diesel(table_name = crate::persistence::schema::auction_result)]
+    #[diesel(check_for_backend(diesel::pg::Pg))]
+    pub struct AuctionResult {
+        pub id: i32,
+        pub source: String,
+        pub lot_vehicle_number: i32,
+        pub final_bid: f64,
+        pub buyer_country: Option<String>,
+        pub buyer_state: Option<String>,
+        pub min_met: bool,
+        pub sold_at: chrono::NaiveDateTime,
+        pub created_at: chrono::NaiveDateTime,
+        pub updated_at: chrono::NaiveDateTime,
+    }
+
+    #[derive(Insertable)]
+    #[diesel(table_name = crate::persistence::schema::auction_result)]
+    pub struct NewAuctionResult {
+        pub source: String,
+        pub lot_vehicle_number: i32,
+        pub final_bid: f64,
+        pub buyer_country: Option<String>,
+        pub buyer_state: Option<String>,
+        pub min_met: bool,
+        /// Taken from the lot's listed sale date when the result came without one
+        pub sold_at: Option<chrono::NaiveDateTime>,
+    }
+
+    impl NewAuctionResult {
+        pub fn new(provider: Provider, value: AuctionResultResponse) -> Self {
+            Self {
+                source: provider.to_string(),
+                lot_vehicle_number: value.lot_number,
+                final_bid: value.final_bid,
+                buyer_country: value.buyer_country,
+                buyer_state: value.buyer_state,
+                min_met: value.min_met,
+                sold_at: value.sold_at,
+            }
+        }
+    }
+
+    #[derive(Selectable, Queryable, Identifiable)]
+    #[diesel(table_name = crate::persistence::schema::lot_price_snapshot)]
+    #[diesel(check_for_backend(diesel::pg::Pg))]
+    pub struct LotPriceSnapshot {
+        pub id: i32,
+        pub source: String,
+        pub lot_vehicle_number: i32,
+        pub current_bid: f64,
+        pub buy_today_bid: f64,
+        pub high_bid: f64,
+        pub sale_status: String,
+        pub seen_at: chrono::NaiveDateTime,
+    }
+
+    #[derive(Insertable)]
+    #[diesel(table_name = crate::persistence::schema::lot_price_snapshot)]
+    pub struct NewLotPriceSnapshot {
+        pub source: String,
+        pub lot_vehicle_number: i32,
+        pub current_bid: f64,
+        pub buy_today_bid: f64,
+        pub high_bid: f64,
+        pub sale_status: String,
+    }
+
+    impl NewLotPriceSnapshot {
+        /// Prices of every listing which carries them.
+        pub fn from_listings(provider: Provider, listings: &LotVehicleVector) -> Vec<Self> {
+            listings
+                .0
+                .iter()
+                .filter_map(|l| {
+                    l.prices.as_ref().map(|p| Self {
+                        source: provider.to_string(),
+                        lot_vehicle_number: l.lot_number,
+                        current_bid: p.current_bid,
+                        buy_today_bid: p.buy_today_bid,
+                        high_bid: p.high_bid,
+                        sale_status: p.sale_status.clone(),
+                    })
+                })
+                .collect()
+        }
+
+        pub fn same_prices(&self, latest: &LotPriceSnapshot) -> bool {
+            self.current_bid == latest.current_bid
+                && self.buy_today_bid == latest.buy_today_bid
+                && self.high_bid == latest.high_bid
+                && self.sale_status == latest.sale_status
+        }
+    }
+
+    #[derive(Insertable)]
+    #[diesel(table_name = crate::persistence::schema::reference_value)]
+    pub struct NewReferenceValue<'a> {
+        pub kind: &'a str,
+        pub name: &'a str,
+        pub make_id: Option<i32>,
+    }
+
+    #[derive(Insertable)]
+    #[diesel(table_name = crate::persistence::schema::reference_alias)]
+    pub struct NewReferenceAlias<'a> {
+        pub kind: &'a str,
+        pub alias: &'a str,
+        pub reference_value_id: i32,
+        pub make_id: Option<i32>,
+    }
+}
+
+pub mod takedown {
+    use diesel::prelude::*;
+
+    /// Lifecycle of a vin takedown, only an active one hides the vin.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum TakedownState {
+        Requested,
+        /// The requester proved they own the vehicle
+        Verified,
+        Active,
+        Revoked,
+    }
+
+    impl TakedownState {
+        pub fn as_str(&self) -> &'static str {
+            match self {
+                Self::Requested => "requested",
+                Self::Verified => "verified",
+                Self::Active => "active",
+                Self::Revoked => "revoked",
+            }
+        }
+
+        pub fn parse(state: &str) -> Option<Self> {
+            match state {
+                "requested" => Some(Self::Requested),
+                "verified" => Some(Self::Verified),
+                "active" => Some(Self::Active),
+                "revoked" => Some(Self::Revoked),
+                _ => None,
+            }
+        }
+
+        /// Takedowns move forward only, any open one can be revoked.
+        pub fn can_transition_to(&self, to: TakedownState) -> bool {
+            matches!(
+                (self, to),
+                (Self::Requested, Self::Verified)
+                    | (Self::Verified, Self::Active)
+                    | (
+                        Self::Requested | Self::Verified | Self::Active,
+                        Self::Revoked
+                    )
+            )
+        }
+    }
+
+    #[derive(Queryable, Selectable, Identifiable)]
+    #[diesel(table_name = crate::persistence::schema::vin_takedown)]
+    #[diesel(check_for_backend(diesel::pg::Pg))]
+    pub struct VinTakedown {
+        pub id: i32,
+        pub vin: String,
+        /// One of [`TakedownState`]
+        pub state: String,
+        pub contact_email: String,
+        pub contact_name: Option<String>,
+        pub reason: String,
+        pub requested_at: chrono::NaiveDateTime,
+        pub verified_at: Option<chrono::NaiveDateTime>,
+        pub activated_at: Option<chrono::NaiveDateTime>,
+        pub revoked_at: Option<chrono::NaiveDateTime>,
+        pub created_at: chrono::NaiveDateTime,
+        pub updated_at: chrono::NaiveDateTime,
+        /// Set once the images of the vin's lots were erased
+        pub erased_at: Option<chrono::NaiveDateTime>,
+    }
+
+    #[derive(Insertable)]
+    #[diesel(table_name = crate::persistence::schema::vin_takedown)]
+    pub struct NewVinTakedown {
+        /// Normalized vin
+        pub vin: String,
+        pub contact_email: String,
+        pub contact_name: Option<String>,
+        pub reason: String,
+    }
+
+    #[derive(Queryable, Selectable, Identifiable, Associations)]
+    #[diesel(table_name = crate::persistence::schema::vin_takedown_audit)]
+    #[diesel(belongs_to(VinTakedown, foreign_key = vin_takedown_id))]
+    #[diesel(check_for_backend(diesel::pg::Pg))]
+    pub struct VinTakedownAudit {
+        pub id: i32,
+        pub vin_takedown_id: i32,
+        /// Absent for the submission
+        pub from_state: Option<String>,
+        pub to_state: String,
+        pub actor: String,
+        pub note: Option<String>,
+        pub changed_at: chrono::NaiveDateTime,
+    }
+
+    #[derive(Insertable)]
+    #[diesel(table_name = crate::persistence::schema::vin_takedown_audit)]
+    pub struct NewVinTakedownAudit<'a> {
+        pub vin_takedown_id: i32,
+        pub from_state: Option<&'a str>,
+        pub to_state: &'a str,
+        pub actor: &'a str,
+        pub note: Option<&'a str>,
+    }
 }
@@ -0,0 +1,356 @@
+//! Checks the bucket against `lot_image_variant`: objects no variant points at are
+//! orphans, keys without an object are missing. Both sides are read in byte order of
+//! their keys and merged, so neither is held in memory.
+//!
+//! An object uploaded by `imgsync` before `persister` stored its variant looks orphaned,
+//! orphans younger than [`ReconcileOptions::orphan_grace`] are left alone.
+
+use crate::bucket::store::ObjectStore;
+use crate::io::error::GeneralError;
+use crate::io::provider::{LotId, Provider};
+use crate::persistence::schema::{lot_image, lot_image_variant};
+use diesel::dsl::{exists, not};
+use diesel::sql_types::{BigInt, Text};
+use diesel::{ExpressionMethods, QueryDsl, QueryableByName};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use futures::TryStreamExt;
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, VecDeque};
+use std::time::{Duration, SystemTime};
+use tracing::{info, instrument};
+
+pub const DEFAULT_RECONCILE_BATCH_SIZE: i64 = 1000;
+/// Far longer than a variant takes from its upload to its row
+pub const DEFAULT_ORPHAN_GRACE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Orphans deleted at once
+const DELETE_BATCH_SIZE: usize = 1000;
+
+/// The "C" collation orders like the bucket listing, bytewise.
+const KEYS_SQL: &str = r#"SELECT DISTINCT bucket_key COLLATE "C" AS bucket_key
+FROM lot_image_variant
+WHERE bucket_key COLLATE "C" > $1
+ORDER BY 1
+LIMIT $2"#;
+
+pub struct ReconcileOptions {
+    /// Variant keys loaded at once
+    pub batch_size: i64,
+    pub delete_orphans: bool,
+    /// Objects modified more recently may belong to a sync in flight, they are not
+    /// orphans yet
+    pub orphan_grace: Duration,
+}
+
+impl Default for ReconcileOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: DEFAULT_RECONCILE_BATCH_SIZE,
+            delete_orphans: false,
+            orphan_grace: DEFAULT_ORPHAN_GRACE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImageRef {
+    pub lot: LotId,
+    pub sequence_number: i32,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ReconcileReport {
+    /// Distinct variant keys
+    pub keys_checked: usize,
+    pub objects_checked: usize,
+    /// Variant keys without an object, removed images included
+    pub missing_objects: Vec<String>,
+    /// Objects no variant points at, older than the grace period
+    pub orphaned_objects: Vec<String>,
+    /// Objects no variant points at yet, modified within the grace period or at an
+    /// unknown time
+    pub recent_unreferenced: usize,
+    pub orphaned_bytes: u64,
+    pub orphans_deleted: usize,
+    /// Current images without any stored variant, e.g. after a failed upload
+    pub images_without_variants: Vec<ImageRef>,
+    /// Lots whose current images miss an object or a variant, their images need a sync
+    pub lots_to_resync: Vec<LotId>,
+    /// Lots a lot images command was sent for, filled by the caller requeueing them
+    pub lots_requeued: usize,
+}
+
+#[derive(QueryableByName)]
+struct KeyRow {
+    #[diesel(sql_type = Text)]
+    bucket_key: String,
+}
+
+/// Distinct variant keys in byte order, loaded a batch at a time.
+struct VariantKeys {
+    batch_size: i64,
+    buffer: VecDeque<String>,
+    last: String,
+    exhausted: bool,
+}
+
+impl VariantKeys {
+    fn new(batch_size: i64) -> Self {
+        Self {
+            batch_size,
+            buffer: VecDeque::new(),
+            last: String::new(),
+            exhausted: false,
+        }
+    }
+
+    async fn next(&mut self, conn: &mut AsyncPgConnection) -> Result<Option<String>, GeneralError> {
+        if self.buffer.is_empty() && !self.exhausted {
+            let batch = diesel::sql_query(KEYS_SQL)
+                .bind::<Text, _>(&self.last)
+                .bind::<BigInt, _>(self.batch_size)
+                .load::<KeyRow>(conn)
+                .await?;
+            self.exhausted = (batch.len() as i64) < self.batch_size;
+            if let Some(last) = batch.last() {
+                self.last = last.bucket_key.clone();
+            }
+            self.buffer.extend(batch.into_iter().map(|r| r.bucket_key));
+        }
+        Ok(self.buffer.pop_front())
+    }
+}
+
+#[instrument(skip_all, fields(location = store.location(), delete_orphans = options.delete_orphans))]
+pub async fn reconcile(
+    conn: &mut AsyncPgConnection,
+    store: &dyn ObjectStore,
+    options: ReconcileOptions,
+) -> Result<ReconcileReport, GeneralError> {
+    let mut report = ReconcileReport::default();
+    let mut pending_deletes = vec![];
+    let started = SystemTime::now();
+    // a modification time ahead of the clock counts as recent
+    let recent = |modified: Option<SystemTime>| {
+        modified.is_none_or(|m| {
+            !started
+                .duration_since(m)
+                .is_ok_and(|age| age >= options.orphan_grace)
+        })
+    };
+
+    let mut keys = VariantKeys::new(options.batch_size);
+    let mut objects = store.list("");
+    let mut key = keys.next(conn).await?;
+    let mut object = objects.try_next().await?;
+    loop {
+        let ordering = match (&key, &object) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(key), Some(object)) => key.as_bytes().cmp(object.key.as_bytes()),
+        };
+        match ordering {
+            Ordering::Less => {
+                report.keys_checked += 1;
+                report.missing_objects.extend(key.take());
+                key = keys.next(conn).await?;
+            }
+            Ordering::Greater => {
+                report.objects_checked += 1;
+                if let Some(orphan) = object.take() {
+                    if recent(orphan.last_modified) {
+                        report.recent_unreferenced += 1;
+                        object = objects.try_next().await?;
+                        continue;
+                    }
+                    report.orphaned_bytes += orphan.size;
+                    report.orphaned_objects.push(orphan.key.clone());
+                    if options.delete_orphans {
+                        pending_deletes.push(orphan.key);
+                    }
+                }
+                if pending_deletes.len() >= DELETE_BATCH_SIZE {
+                    store.delete(&pending_deletes).await?;
+                    report.orphans_deleted += pending_deletes.len();
+                    pending_deletes.clear();
+                }
+                object = objects.try_next().await?;
+            }
+            Ordering::Equal => {
+                report.keys_checked += 1;
+                report.objects_checked += 1;
+                key = keys.next(conn).await?;
+                object = objects.try_next().await?;
+            }
+        }
+    }
+    if !pending_deletes.is_empty() {
+        store.delete(&pending_deletes).await?;
+        report.orphans_deleted += pending_deletes.len();
+    }
+
+    report.images_without_variants = images_without_variants(conn).await?;
+    let mut lots = lots_missing_objects(conn, &report.missing_objects, options.batch_size).await?;
+    lots.extend(report.images_without_variants.iter().map(|i| i.lot));
+    report.lots_to_resync = lots.into_iter().collect();
+
+    info!(
+        "checked `{}` keys and `{}` objects, missing: `{}`, orphaned: `{}`, images without variants: `{}`",
+        report.keys_checked,
+        report.objects_checked,
+        report.missing_objects.len(),
+        report.orphaned_objects.len(),
+        report.images_without_variants.len()
+    );
+    Ok(report)
+}
+
+async fn images_without_variants(
+    conn: &mut AsyncPgConnection,
+) -> Result<Vec<ImageRef>, GeneralError> {
+    let images = lot_image::table
+        .filter(lot_image::removed_at.is_null())
+        .filter(not(exists(
+            lot_image_variant::table.filter(lot_image_variant::lot_image_id.eq(lot_image::id)),
+        )))
+        .order((
+            lot_image::source,
+            lot_image::lot_vehicle_number,
+            lot_image::sequence_number,
+        ))
+        .select((
+            lot_image::source,
+            lot_image::lot_vehicle_number,
+            lot_image::sequence_number,
+        ))
+        .load::<(String, i32, i32)>(conn)
+        .await?;
+    Ok(images
+        .into_iter()
+        .filter_map(|(source, ln, sequence_number)| {
+            Some(ImageRef {
+                lot: LotId::new(Provider::parse(&source)?, ln),
+                sequence_number,
+            })
+        })
+        .collect())
+}
+
+/// Lots with a current image pointing at one of the keys
+async fn lots_missing_objects(
+    conn: &mut AsyncPgConnection,
+    keys: &[String],
+    batch_size: i64,
+) -> Result<BTreeSet<LotId>, GeneralError> {
+    let mut lots = BTreeSet::new();
+    for batch in keys.chunks(batch_size.max(1) as usize) {
+        let referencing = lot_image_variant::table
+            .inner_join(lot_image::table)
+            .filter(lot_image_variant::bucket_key.eq_any(batch))
+            .filter(lot_image::removed_at.is_null())
+            .select((lot_image::source, lot_image::lot_vehicle_number))
+            .distinct()
+            .load::<(String, i32)>(conn)
+            .await?;
+        lots.extend(
+            referencing
+                .into_iter()
+                .filter_map(|(source, ln)| Some(LotId::new(Provider::parse(&source)?, ln))),
+        );
+    }
+    Ok(lots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bucket::content_key;
+    use crate::bucket::testing::temp_fs_store;
+    use crate::persistence::models::copart::NewLotImage;
+    use crate::persistence::testing::{fixtures, TestDb};
+    use bytes::Bytes;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_reconcile() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        db.seed(vec![fixtures::lot_vehicle(1), fixtures::lot_vehicle(2)])
+            .await?;
+        db.seed_images(vec![
+            fixtures::lot_image(1, 1),
+            fixtures::lot_image(2, 1),
+            NewLotImage {
+                variants: vec![],
+                ..fixtures::lot_image(2, 2)
+            },
+        ])
+        .await?;
+
+        let (_dir, store) = temp_fs_store();
+        for key in ["1/1/w320", "1/1/w800", "1/1/w1600", "2/1/w320", "2/1/w800"] {
+            store
+                .put(&content_key(key), Bytes::from_static(b"blob"), "image/webp")
+                .await?;
+        }
+        let orphan = content_key("orphan");
+        store
+            .put(&orphan, Bytes::from_static(b"orphan"), "image/webp")
+            .await?;
+
+        let mut conn = db.pool.get().await?;
+        let report = reconcile(
+            &mut conn,
+            &store,
+            ReconcileOptions {
+                orphan_grace: Duration::ZERO,
+                ..Default::default()
+            },
+        )
+        .await?;
+        assert_eq!(report.keys_checked, 6);
+        assert_eq!(report.objects_checked, 6);
+        assert_eq!(report.missing_objects, vec![content_key("2/1/w1600")]);
+        assert_eq!(report.orphaned_objects, vec![orphan.clone()]);
+        assert_eq!(report.orphaned_bytes, 6);
+        assert_eq!(report.orphans_deleted, 0);
+        assert_eq!(
+            report.images_without_variants,
+            vec![ImageRef {
+                lot: LotId::copart(2),
+                sequence_number: 2
+            }]
+        );
+        assert_eq!(report.lots_to_resync, vec![LotId::copart(2)]);
+        assert!(store.head(&orphan).await?.is_some());
+
+        // just uploaded, its variant may not be stored yet
+        let report = reconcile(
+            &mut conn,
+            &store,
+            ReconcileOptions {
+                delete_orphans: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+        assert!(report.orphaned_objects.is_empty());
+        assert_eq!(report.recent_unreferenced, 1);
+        assert!(store.head(&orphan).await?.is_some());
+
+        let report = reconcile(
+            &mut conn,
+            &store,
+            ReconcileOptions {
+                batch_size: 2,
+                delete_orphans: true,
+                orphan_grace: Duration::ZERO,
+            },
+        )
+        .await?;
+        assert_eq!(report.keys_checked, 6);
+        assert_eq!(report.orphans_deleted, 1);
+        assert!(store.head(&orphan).await?.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,314 @@
+//! Moves objects synced before content addressing, keyed `{lot}_{seq}_{kind}`, to their
+//! sha256 content keys. Identical photos collapse into a single object, the report
+//! tells how much storage that saved. Variants are updated one by one, so an interrupted
+//! run is resumed by running it again.
+//!
+//! Legacy objects are deleted once the variants are rekeyed, except those a variant
+//! stored during the run still points at. Those are rekeyed and deleted by the next run.
+
+use crate::bucket::store::ObjectStore;
+use crate::bucket::{content_key, sha256_hex, CONTENT_KEY_PREFIX};
+use crate::io::error::GeneralError;
+use crate::persistence::erasure::ERASURE_LOCK_KEY;
+use crate::persistence::models::copart::LotImageVariant;
+use crate::persistence::schema::lot_image_variant;
+use diesel::sql_types::BigInt;
+use diesel::{ExpressionMethods, QueryDsl, SelectableHelper, TextExpressionMethods};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use tracing::{debug, info, instrument, warn};
+
+pub const DEFAULT_REKEY_BATCH_SIZE: i64 = 500;
+
+pub struct RekeyOptions {
+    /// Variants loaded at once
+    pub batch_size: i64,
+    /// Reads and hashes the objects without writing anything
+    pub dry_run: bool,
+}
+
+impl Default for RekeyOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: DEFAULT_REKEY_BATCH_SIZE,
+            dry_run: false,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RekeyReport {
+    pub dry_run: bool,
+    /// Variants pointed at a content key
+    pub variants_rekeyed: usize,
+    /// Distinct legacy objects read
+    pub legacy_objects: usize,
+    /// Content objects written, the others were stored already
+    pub objects_uploaded: usize,
+    pub objects_deleted: usize,
+    /// Legacy keys a variant pointed at again by the end of the run, those are kept
+    pub objects_kept: Vec<String>,
+    /// Legacy keys of variants whose object is not in the bucket, those are left as they are
+    pub missing_objects: Vec<String>,
+    /// Size of the legacy objects
+    pub bytes_before: u64,
+    /// Size of the distinct content objects they collapsed into
+    pub bytes_after: u64,
+    pub bytes_saved: u64,
+}
+
+#[derive(Clone)]
+struct Rekeyed {
+    key: String,
+    sha256: String,
+    size: i64,
+}
+
+struct Rekeyer<'a> {
+    store: &'a dyn ObjectStore,
+    dry_run: bool,
+    /// Legacy keys already handled, `None` when the object was missing
+    legacy: HashMap<String, Option<Rekeyed>>,
+    /// Hashes known to be stored under their content key
+    stored: HashSet<String>,
+    report: RekeyReport,
+}
+
+impl Rekeyer<'_> {
+    async fn rekey(&mut self, key: &str, mime_type: &str) -> Result<Option<Rekeyed>, GeneralError> {
+        if let Some(rekeyed) = self.legacy.get(key) {
+            return Ok(rekeyed.clone());
+        }
+
+        let Some(blob) = self.store.get(key).await? else {
+            warn!("legacy object `{key}` is missing");
+            self.report.missing_objects.push(key.to_string());
+            self.legacy.insert(key.to_string(), None);
+            return Ok(None);
+        };
+
+        let sha256 = sha256_hex(&blob);
+        let rekeyed = Rekeyed {
+            key: content_key(&sha256),
+            size: blob.len() as i64,
+            sha256,
+        };
+        self.report.legacy_objects += 1;
+        self.report.bytes_before += blob.len() as u64;
+        if self.stored.insert(rekeyed.sha256.clone()) {
+            self.report.bytes_after += blob.len() as u64;
+            if self.store.head(&rekeyed.key).await?.is_none() {
+                if !self.dry_run {
+                    self.store.put(&rekeyed.key, blob, mime_type).await?;
+                }
+                self.report.objects_uploaded += 1;
+            }
+        }
+        debug!("`{key}` rekeyed to `{}`", rekeyed.key);
+        self.legacy.insert(key.to_string(), Some(rekeyed.clone()));
+        Ok(Some(rekeyed))
+    }
+}
+
+/// Rekeys every legacy variant, those of removed images included, and deletes the
+/// legacy objects once no variant points at them.
+#[instrument(skip_all, fields(dry_run = options.dry_run))]
+pub async fn rekey_lot_images(
+    conn: &mut AsyncPgConnection,
+    store: &dyn ObjectStore,
+    options: RekeyOptions,
+) -> Result<RekeyReport, GeneralError> {
+    let content_pattern = format!("{CONTENT_KEY_PREFIX}%");
+    let mut rekeyer = Rekeyer {
+        store,
+        dry_run: options.dry_run,
+        legacy: HashMap::new(),
+        stored: HashSet::new(),
+        report: RekeyReport {
+            dry_run: options.dry_run,
+            ..RekeyReport::default()
+        },
+    };
+
+    let mut last_id = 0;
+    loop {
+        let batch = lot_image_variant::table
+            .filter(lot_image_variant::id.gt(last_id))
+            .filter(lot_image_variant::bucket_key.not_like(content_pattern.as_str()))
+            .order(lot_image_variant::id.asc())
+            .limit(options.batch_size)
+            .select(LotImageVariant::as_select())
+            .load(conn)
+            .await?;
+        let Some(last) = batch.last() else { break };
+        last_id = last.id;
+
+        for variant in batch {
+            let Some(rekeyed) = rekeyer
+                .rekey(&variant.bucket_key, &variant.mime_type)
+                .await?
+            else {
+                continue;
+            };
+            rekeyer.report.variants_rekeyed += 1;
+            if !options.dry_run {
+                diesel::update(lot_image_variant::table.find(variant.id))
+                    .set((
+                        lot_image_variant::bucket_key.eq(rekeyed.key),
+                        lot_image_variant::sha256.eq(rekeyed.sha256),
+                        lot_image_variant::size.eq(rekeyed.size),
+                    ))
+                    .execute(conn)
+                    .await?;
+            }
+        }
+    }
+
+    let mut report = rekeyer.report;
+    let rekeyed_keys = rekeyer
+        .legacy
+        .into_iter()
+        .filter_map(|(key, rekeyed)| rekeyed.map(|_| key))
+        .collect::<Vec<_>>();
+    if !options.dry_run {
+        let (deleted, kept) = conn
+            .transaction::<_, GeneralError, _>(|conn| {
+                delete_unreferenced(conn, store, rekeyed_keys).scope_boxed()
+            })
+            .await?;
+        if !kept.is_empty() {
+            warn!(
+                "`{}` legacy objects are referenced again and kept",
+                kept.len()
+            );
+        }
+        report.objects_deleted = deleted;
+        report.objects_kept = kept;
+    }
+    report.bytes_saved = report.bytes_before.saturating_sub(report.bytes_after);
+    info!(
+        "rekeyed `{}` variants, `{}` legacy objects collapsed into `{}` new ones, saved `{}` bytes",
+        report.variants_rekeyed, report.legacy_objects, report.objects_uploaded, report.bytes_saved
+    );
+    Ok(report)
+}
+
+/// Deletes the legacy objects no variant points at, returns how many were deleted and
+/// the keys which were kept.
+async fn delete_unreferenced(
+    conn: &mut AsyncPgConnection,
+    store: &dyn ObjectStore,
+    keys: Vec<String>,
+) -> Result<(usize, Vec<String>), GeneralError> {
+    // syncs storing variants wait until the objects are gone, like for an erasure
+    diesel::sql_query("SELECT pg_advisory_xact_lock($1)")
+        .bind::<BigInt, _>(ERASURE_LOCK_KEY)
+        .execute(conn)
+        .await?;
+    let referenced = lot_image_variant::table
+        .filter(lot_image_variant::bucket_key.eq_any(&keys))
+        .select(lot_image_variant::bucket_key)
+        .distinct()
+        .load::<String>(conn)
+        .await?
+        .into_iter()
+        .collect::<HashSet<_>>();
+    let (kept, unreferenced): (Vec<_>, Vec<_>) =
+        keys.into_iter().partition(|k| referenced.contains(k));
+    store.delete(&unreferenced).await?;
+    Ok((unreferenced.len(), kept))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bucket::testing::temp_fs_store;
+    use crate::persistence::models::copart::{NewLotImage, NewLotImageVariant};
+    use crate::persistence::testing::{fixtures, TestDb};
+    use bytes::Bytes;
+
+    /// An image synced before content addressing, its rendition keyed by lot and sequence
+    fn legacy(ln: i32) -> NewLotImage {
+        NewLotImage {
+            variants: vec![NewLotImageVariant {
+                name: "standard".to_string(),
+                format: "jpeg".to_string(),
+                mime_type: "image/jpeg".to_string(),
+                width: None,
+                height: None,
+                size: None,
+                bucket_key: format!("{ln}_1_standard"),
+                sha256: None,
+            }],
+            ..fixtures::lot_image(ln, 1)
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_rekey_lot_images() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        db.seed(vec![fixtures::lot_vehicle(1), fixtures::lot_vehicle(2)])
+            .await?;
+        db.seed_images(vec![legacy(1), legacy(2)]).await?;
+        let (_dir, store) = temp_fs_store();
+        // the same photo synced for both lots
+        for key in ["1_1_standard", "2_1_standard"] {
+            store
+                .put(key, Bytes::from_static(b"photo"), "image/jpeg")
+                .await?;
+        }
+        let mut conn = db.pool.get().await?;
+
+        let report = rekey_lot_images(&mut conn, &store, RekeyOptions::default()).await?;
+        assert_eq!(
+            (
+                report.variants_rekeyed,
+                report.legacy_objects,
+                report.objects_uploaded,
+                report.objects_deleted,
+                report.bytes_saved
+            ),
+            (2, 2, 1, 2, 5)
+        );
+        assert!(report.objects_kept.is_empty());
+        let key = content_key(&sha256_hex(b"photo"));
+        assert_eq!(store.get(&key).await?, Some(Bytes::from_static(b"photo")));
+        assert_eq!(store.get("1_1_standard").await?, None);
+        assert_eq!(
+            lot_image_variant::table
+                .select(lot_image_variant::bucket_key)
+                .load::<String>(&mut conn)
+                .await?,
+            vec![key.clone(), key]
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_referenced_legacy_objects_are_kept() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        db.seed(vec![fixtures::lot_vehicle(3)]).await?;
+        // stored by a sync after its lot was rekeyed
+        db.seed_images(vec![legacy(3)]).await?;
+        let (_dir, store) = temp_fs_store();
+        for key in ["3_1_standard", "4_1_standard"] {
+            store
+                .put(key, Bytes::from_static(b"photo"), "image/jpeg")
+                .await?;
+        }
+        let mut conn = db.pool.get().await?;
+
+        let (deleted, kept) = delete_unreferenced(
+            &mut conn,
+            &store,
+            vec!["3_1_standard".to_string(), "4_1_standard".to_string()],
+        )
+        .await?;
+        assert_eq!((deleted, kept), (1, vec!["3_1_standard".to_string()]));
+        assert!(store.head("3_1_standard").await?.is_some());
+        assert_eq!(store.head("4_1_standard").await?, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,279 @@
+use crate::io::provider::Provider;
+use crate::persistence::schema::lot_image;
+use diesel::sql_types::{Array, BigInt, Integer, Text};
+use diesel::{
+    ExpressionMethods, NullableExpressionMethods, QueryDsl, QueryResult, QueryableByName,
+};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use std::collections::BTreeSet;
+
+/// Re-encoded and resized copies of a photo stay within a few bits of each other
+pub const DEFAULT_MAX_DISTANCE: i32 = 6;
+/// Beyond this unrelated photos of similar cars start to match
+pub const MAX_DISTANCE: i32 = 12;
+pub const DEFAULT_RELATED_LIMIT: i64 = 20;
+pub const MAX_RELATED_LIMIT: i64 = 100;
+
+/// Hashes are indexed in `perceptual_hash_band_0..=3`, the lowest band first
+const BANDS: usize = 4;
+const BAND_BITS: u32 = 16;
+const BAND_MASK: u64 = (1 << BAND_BITS) - 1;
+
+/// Lots with current images whose perceptual hash is within `$3` bits of one of the
+/// current images of lot `($1, $2)`, most shared images first.
+///
+/// Only images with a band in the candidates `$5..=$8` of its band are compared, see
+/// [`band_candidates`]. Lots of vins with an active takedown are never returned.
+const RELATED_SQL: &str = "\
+WITH lot_hashes AS (SELECT id, perceptual_hash
+                    FROM lot_image
+                    WHERE source = $1
+                      AND lot_vehicle_number = $2
+                      AND removed_at IS NULL
+                      AND perceptual_hash IS NOT NULL),
+     candidates AS (SELECT id, source, lot_vehicle_number, perceptual_hash
+                    FROM lot_image
+                    WHERE removed_at IS NULL
+                      AND perceptual_hash_band_0 = ANY ($5)
+                    UNION
+                    SELECT id, source, lot_vehicle_number, perceptual_hash
+                    FROM lot_image
+                    WHERE removed_at IS NULL
+                      AND perceptual_hash_band_1 = ANY ($6)
+                    UNION
+                    SELECT id, source, lot_vehicle_number, perceptual_hash
+                    FROM lot_image
+                    WHERE removed_at IS NULL
+                      AND perceptual_hash_band_2 = ANY ($7)
+                    UNION
+                    SELECT id, source, lot_vehicle_number, perceptual_hash
+                    FROM lot_image
+                    WHERE removed_at IS NULL
+                      AND perceptual_hash_band_3 = ANY ($8))
+SELECT i.source,
+       i.lot_vehicle_number AS lot_number,
+       count(DISTINCT h.id)::INTEGER AS shared_images,
+       min(bit_count((i.perceptual_hash # h.perceptual_hash)::BIT(64)))::INTEGER AS distance
+FROM candidates i
+         JOIN lot_hashes h ON bit_count((i.perceptual_hash # h.perceptual_hash)::BIT(64)) <= $3
+WHERE (i.source, i.lot_vehicle_number) <> ($1, $2)
+  AND NOT EXISTS (SELECT 1
+                  FROM lot_vehicle v
+                           JOIN vin_takedown t ON t.vin = v.vin_normalized AND t.state = 'active'
+                  WHERE v.source = i.source
+                    AND v.lot_number = i.lot_vehicle_number)
+GROUP BY i.source, i.lot_vehicle_number
+ORDER BY shared_images DESC, distance, lot_number DESC, i.source
+LIMIT $4";
+
+/// Current images of provider `$1` synced before their hash was computed. Scheduling
+/// them for a retry has the image retry queue request their lots again, imgsync does
+/// not skip an image without a hash as unchanged.
+const SCHEDULE_UNHASHED_SQL: &str = "\
+UPDATE lot_image
+SET retry_at = now()
+WHERE source = $1
+  AND removed_at IS NULL
+  AND perceptual_hash IS NULL
+  AND source_url IS NOT NULL
+  AND sync_status IN ('synced', 'unchanged')
+  AND retry_at IS NULL";
+
+#[derive(Debug, QueryableByName)]
+pub struct RelatedLot {
+    #[diesel(sql_type = Text)]
+    pub source: String,
+    #[diesel(sql_type = Integer)]
+    pub lot_number: i32,
+    /// Images of the queried lot with a near-identical photo in this lot
+    #[diesel(sql_type = Integer)]
+    pub shared_images: i32,
+    /// Smallest hamming distance between the photos of both lots
+    #[diesel(sql_type = Integer)]
+    pub distance: i32,
+}
+
+/// Other lots sharing near-identical photos with the lot, e.g. relistings of the same car
+/// under a new lot number or at another yard.
+pub async fn related_lots(
+    conn: &mut AsyncPgConnection,
+    source: &str,
+    lot_number: i32,
+    max_distance: i32,
+    limit: i64,
+) -> QueryResult<Vec<RelatedLot>> {
+    let hashes = lot_image::table
+        .filter(lot_image::source.eq(source))
+        .filter(lot_image::lot_vehicle_number.eq(lot_number))
+        .filter(lot_image::removed_at.is_null())
+        .filter(lot_image::perceptual_hash.is_not_null())
+        .select(lot_image::perceptual_hash.assume_not_null())
+        .load::<i64>(conn)
+        .await?;
+    if hashes.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let max_distance = max_distance.clamp(0, MAX_DISTANCE);
+    let [band_0, band_1, band_2, band_3] = band_candidates(&hashes, max_distance);
+    diesel::sql_query(RELATED_SQL)
+        .bind::<Text, _>(source)
+        .bind::<Integer, _>(lot_number)
+        .bind::<Integer, _>(max_distance)
+        .bind::<BigInt, _>(limit.clamp(1, MAX_RELATED_LIMIT))
+        .bind::<Array<Integer>, _>(band_0)
+        .bind::<Array<Integer>, _>(band_1)
+        .bind::<Array<Integer>, _>(band_2)
+        .bind::<Array<Integer>, _>(band_3)
+        .load(conn)
+        .await
+}
+
+/// Values of every band within `max_distance / BANDS` bits of that band of one of the
+/// hashes. A hash within `max_distance` bits of one of them differs in at most that
+/// many bits in one of its bands, so it has a band among the candidates.
+fn band_candidates(hashes: &[i64], max_distance: i32) -> [Vec<i32>; BANDS] {
+    let radius = max_distance.max(0) as u32 / BANDS as u32;
+    let flips = (0..=BAND_MASK)
+        .filter(|flip| flip.count_ones() <= radius)
+        .collect::<Vec<_>>();
+    std::array::from_fn(|band| {
+        hashes
+            .iter()
+            .map(|hash| (*hash as u64 >> (band as u32 * BAND_BITS)) & BAND_MASK)
+            .flat_map(|value| flips.iter().map(move |flip| (value ^ flip) as i32))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    })
+}
+
+/// Schedules a retry of the current images of the provider synced without a hash, so
+/// lots synced before hashes were computed are found by [`related_lots`]. Returns how
+/// many images were scheduled, the image retry queue requests their lots at its pace.
+pub async fn schedule_unhashed_images(
+    conn: &mut AsyncPgConnection,
+    provider: Provider,
+) -> QueryResult<usize> {
+    diesel::sql_query(SCHEDULE_UNHASHED_SQL)
+        .bind::<Text, _>(provider.as_str())
+        .execute(conn)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::models::copart::NewLotImage;
+    use crate::persistence::repo::LotImageRepo;
+    use crate::persistence::testing::{fixtures, TestDb};
+    use diesel::dsl::now;
+
+    fn hashed(ln: i32, sequence_number: i32, perceptual_hash: i64) -> NewLotImage {
+        NewLotImage {
+            perceptual_hash: Some(perceptual_hash),
+            ..fixtures::lot_image(ln, sequence_number)
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_related_lots() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        db.seed((1..=5).map(fixtures::lot_vehicle).collect())
+            .await?;
+        db.seed_images(vec![
+            hashed(1, 1, 0b1111_0000),
+            hashed(1, 2, 0x0F0F_0F0F_0F0F_0F0F),
+            // relisting: both photos again, one re-encoded with two flipped bits
+            hashed(2, 1, 0b1111_0000),
+            hashed(2, 2, 0x0F0F_0F0F_0F0F_0F0C),
+            // another yard: one photo, three bits off
+            hashed(3, 1, 0b1000_0000),
+            // unrelated car
+            hashed(4, 1, -1),
+            // no longer part of its lot
+            hashed(5, 1, 0b1111_0000),
+        ])
+        .await?;
+        let mut conn = db.pool.get().await?;
+        diesel::update(lot_image::table.filter(lot_image::lot_vehicle_number.eq(5)))
+            .set(lot_image::removed_at.eq(now.nullable()))
+            .execute(&mut conn)
+            .await?;
+
+        let related = related_lots(&mut conn, "copart", 1, DEFAULT_MAX_DISTANCE, 10).await?;
+        assert_eq!(
+            related
+                .iter()
+                .map(|r| (r.lot_number, r.shared_images, r.distance))
+                .collect::<Vec<_>>(),
+            vec![(2, 2, 0), (3, 1, 3)]
+        );
+
+        let related = related_lots(&mut conn, "copart", 1, 2, 10).await?;
+        assert_eq!(
+            related.iter().map(|r| r.lot_number).collect::<Vec<_>>(),
+            vec![2]
+        );
+        assert!(related_lots(&mut conn, "copart", 6, 6, 10)
+            .await?
+            .is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_band_candidates() {
+        let hash = 0x0123_4567_89AB_CDEFu64 as i64;
+        let [band_0, .., band_3] = band_candidates(&[hash], 3);
+        assert_eq!((band_0, band_3), (vec![0xCDEF], vec![0x0123]));
+
+        let bands = band_candidates(&[hash], MAX_DISTANCE);
+        // twelve flipped bits spread evenly over the bands still leave one band in reach
+        for flipped in [0x0007_0007_0007_0007u64, 0x000F_000F_000F_0000, 0x0FFF] {
+            let other = (hash as u64 ^ flipped) as i64;
+            assert!((0..BANDS).any(|band| {
+                let value = (other as u64 >> (band as u32 * BAND_BITS)) & BAND_MASK;
+                bands[band].contains(&(value as i32))
+            }));
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_schedule_unhashed_images() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        db.seed((1..=4).map(fixtures::lot_vehicle).collect())
+            .await?;
+        db.seed_images(vec![
+            // synced before hashes were computed
+            fixtures::lot_image(1, 1),
+            hashed(2, 1, 0b1111_0000),
+            // failed images are retried anyway
+            NewLotImage {
+                sync_status: "failed".to_string(),
+                sync_error: Some("download".to_string()),
+                ..fixtures::lot_image(3, 1)
+            },
+            fixtures::lot_image(4, 1),
+        ])
+        .await?;
+        let mut conn = db.pool.get().await?;
+        diesel::update(lot_image::table.filter(lot_image::lot_vehicle_number.eq(4)))
+            .set(lot_image::removed_at.eq(now.nullable()))
+            .execute(&mut conn)
+            .await?;
+
+        assert_eq!(
+            schedule_unhashed_images(&mut conn, Provider::Copart).await?,
+            1
+        );
+        assert_eq!(
+            schedule_unhashed_images(&mut conn, Provider::Copart).await?,
+            0
+        );
+        assert_eq!(
+            db.repo().claim_image_retries(Provider::Copart, 10).await?,
+            vec![crate::io::provider::LotId::copart(1)]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,1999 @@
+use crate::io::error::GeneralError;
+use crate::io::provider::{ImageSyncError, ImageSyncStatus, LotId, LotNumber, Provider};
+use crate::persistence::canonical::{AliasCache, Canonicalizer};
+use crate::persistence::erasure;
+use crate::persistence::models::copart::{
+    AuctionResult, ImageBlob, LotImage, LotImageVariant, LotPriceSnapshot, LotVehicle,
+    LotVehicleHistory, NewAuctionResult, NewLotImage, NewLotImages, NewLotPriceSnapshot,
+    NewLotVehicles,
+};
+use crate::persistence::models::takedown::{
+    NewVinTakedown, NewVinTakedownAudit, TakedownState, VinTakedown, VinTakedownAudit,
+};
+use crate::persistence::related::{self, RelatedLot};
+use crate::persistence::retry;
+use crate::persistence::schema::{
+    auction_result, erased_object, image_blob, lot_image, lot_image_variant, lot_price_snapshot,
+    lot_vehicle, lot_vehicle_history, takedown_pending_object, vin_takedown, vin_takedown_audit,
+};
+use crate::persistence::search::{self, SearchHit};
+use crate::persistence::PgPool;
+use crate::vin;
+use async_trait::async_trait;
+use diesel::dsl::now;
+use diesel::sql_types::{Array, BigInt, Integer, Nullable, Varchar};
+use diesel::upsert::excluded;
+use diesel::{
+    BelongingToDsl, ExpressionMethods, GroupedBy, NullableExpressionMethods, OptionalExtension,
+    QueryDsl, SelectableHelper,
+};
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use std::collections::{HashMap, HashSet};
+use tracing::{debug, instrument};
+
+/// A lot vehicle with its current images and latest sale outcome. Lots of a vin
+/// with an active takedown come with the vin redacted and without images.
+pub struct LotVehicleDetails {
+    pub lot_vehicle: LotVehicle,
+    pub lot_images: Vec<LotImageDetails>,
+    pub auction_result: Option<AuctionResult>,
+}
+
+/// A lot image with its stored variants, smallest first.
+pub struct LotImageDetails {
+    pub lot_image: LotImage,
+    pub variants: Vec<LotImageVariant>,
+}
+
+#[async_trait]
+pub trait LotVehicleRepo: Send + Sync {
+    /// Inserts unseen lot vehicles and updates re-seen ones, recording changed
+    /// fields in `lot_vehicle_history`. Returns ids of inserted lots only.
+    async fn upsert_lot_vehicles(
+        &self,
+        new_lot_vehicles: NewLotVehicles,
+    ) -> Result<Vec<LotId>, GeneralError>;
+
+    /// Stores a sale outcome, results of lots which were never searched are skipped. A
+    /// result without a sale date is dated by the lot's listed one.
+    async fn save_auction_result(
+        &self,
+        new_auction_result: NewAuctionResult,
+    ) -> Result<(), GeneralError>;
+
+    /// Lot vehicles without the hidden vins.
+    async fn lot_vehicles(&self, limit: i64) -> Result<Vec<LotVehicleDetails>, GeneralError>;
+
+    async fn lot_vehicle_by_id(&self, id: LotId)
+        -> Result<Option<LotVehicleDetails>, GeneralError>;
+
+    /// Latest updated lot of the vin, compared normalized on both sides, so lots stored
+    /// with the vin spelled differently are found. Hidden vins are not found.
+    async fn lot_vehicle_by_vin(
+        &self,
+        vin: &str,
+    ) -> Result<Option<LotVehicleDetails>, GeneralError>;
+
+    /// Every lot of the vin across providers ordered by sale date, unsold lots last.
+    /// Empty for hidden vins.
+    async fn lot_vehicles_by_vin(&self, vin: &str) -> Result<Vec<LotVehicleDetails>, GeneralError>;
+
+    /// Changed fields oldest first, `None` when the lot does not exist. Vin changes
+    /// of hidden vins are left out.
+    async fn lot_vehicle_history(
+        &self,
+        id: LotId,
+    ) -> Result<Option<Vec<LotVehicleHistory>>, GeneralError>;
+
+    /// Search hits with their lot vehicles, best match first. Hidden vins are left out.
+    async fn search_lot_vehicles(
+        &self,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<(SearchHit, LotVehicle)>, GeneralError>;
+
+    /// Lots sharing near-identical photos with the lot, most shared photos first.
+    /// `None` when the lot does not exist, empty when its vin is hidden. Lots of hidden
+    /// vins are left out.
+    async fn related_lot_vehicles(
+        &self,
+        id: LotId,
+        max_distance: i32,
+        limit: i64,
+    ) -> Result<Option<Vec<(RelatedLot, LotVehicle)>>, GeneralError>;
+}
+
+#[async_trait]
+pub trait LotImageRepo: Send + Sync {
+    /// Upserts the synced image set of a lot on (lot, sequence number, image type)
+    /// and marks stored images missing from the set as removed. Failed images are
+    /// scheduled for a retry and the lot is marked complete once none failed.
+    async fn upsert_lot_images(
+        &self,
+        id: LotId,
+        new_lot_images: NewLotImages,
+    ) -> Result<(), GeneralError>;
+
+    /// Lots of the provider with images due for a retry, at most `limit`. Every
+    /// claim counts as an attempt, a lot is not returned again until its synced images
+    /// arrive or the claim expires.
+    async fn claim_image_retries(
+        &self,
+        provider: Provider,
+        limit: i64,
+    ) -> Result<Vec<LotId>, GeneralError>;
+
+    /// Images which are not removed, in sequence order.
+    async fn lot_images(&self, id: LotId) -> Result<Vec<LotImageDetails>, GeneralError>;
+
+    /// Stored blobs of the given hashes with the number of renditions referencing them.
+    async fn image_blobs(&self, sha256s: Vec<String>) -> Result<Vec<ImageBlob>, GeneralError>;
+}
+
+#[async_trait]
+pub trait LotPriceRepo: Send + Sync {
+    /// Stores snapshots of lots whose prices changed since their latest snapshot, the
+    /// lots must exist. Returns the number of stored snapshots.
+    async fn save_price_snapshots(
+        &self,
+        snapshots: Vec<NewLotPriceSnapshot>,
+    ) -> Result<usize, GeneralError>;
+
+    /// Snapshots oldest first, `None` when the lot does not exist.
+    async fn price_snapshots(
+        &self,
+        id: LotId,
+    ) -> Result<Option<Vec<LotPriceSnapshot>>, GeneralError>;
+}
+
+#[async_trait]
+pub trait TakedownRepo: Send + Sync {
+    /// Opens a takedown in the requested state, an open takedown of the same vin
+    /// is returned instead of a new one.
+    async fn submit_takedown(
+        &self,
+        new_vin_takedown: NewVinTakedown,
+    ) -> Result<VinTakedown, GeneralError>;
+
+    /// Takedowns newest first, optionally in the given state.
+    async fn takedowns(
+        &self,
+        state: Option<TakedownState>,
+    ) -> Result<Vec<VinTakedown>, GeneralError>;
+
+    /// A takedown with its audit trail oldest first.
+    async fn takedown(
+        &self,
+        id: i32,
+    ) -> Result<Option<(VinTakedown, Vec<VinTakedownAudit>)>, GeneralError>;
+
+    /// The given normalized vins which have an active takedown, those are never ingested.
+    async fn blocked_vins(&self, vins: Vec<String>) -> Result<HashSet<String>, GeneralError>;
+
+    /// The active takedown of the lot's vin, `None` when the lot is not blocked.
+    async fn lot_takedown(&self, id: LotId) -> Result<Option<i32>, GeneralError>;
+
+    /// Queues objects uploaded for a lot of the takedown after its erasure started, the
+    /// takedown is due for erasure again until they are deleted.
+    async fn queue_erasure(
+        &self,
+        takedown_id: i32,
+        bucket_keys: Vec<String>,
+    ) -> Result<(), GeneralError>;
+
+    /// Moves a takedown to the next state and records who did it, `None` when it does not exist.
+    async fn transition_takedown(
+        &self,
+        id: i32,
+        to: TakedownState,
+        actor: &str,
+        note: Option<&str>,
+    ) -> Result<Option<VinTakedown>, GeneralError>;
+}
+
+/// Postgres implementation of the repositories over an explicit pool.
+#[derive(Clone)]
+pub struct PgRepo {
+    pool: PgPool,
+    aliases: AliasCache,
+}
+
+impl PgRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            aliases: AliasCache::default(),
+        }
+    }
+
+    async fn details(
+        conn: &mut AsyncPgConnection,
+        vehicle: Option<LotVehicle>,
+    ) -> Result<Option<LotVehicleDetails>, GeneralError> {
+        let Some(mut vehicle) = vehicle else {
+            return Ok(None);
+        };
+
+        let hidden = Self::vin_hidden(conn, vehicle.vin.as_deref()).await?;
+        let lot_images = match hidden {
+            true => vec![],
+            false => {
+                let images = lot_image::table
+                    .filter(lot_image::source.eq(&vehicle.source))
+                    .filter(lot_image::lot_vehicle_number.eq(vehicle.lot_number))
+                    .filter(lot_image::removed_at.is_null())
+                    .order(lot_image::sequence_number.asc())
+                    .select(LotImage::as_select())
+                    .load(conn)
+                    .await?;
+                Self::with_variants(conn, images).await?
+            }
+        };
+
+        let auction_result = auction_result::table
+            .filter(auction_result::source.eq(&vehicle.source))
+            .filter(auction_result::lot_vehicle_number.eq(vehicle.lot_number))
+            .order(auction_result::sold_at.desc())
+            .select(AuctionResult::as_select())
+            .first(conn)
+            .await
+            .optional()?;
+
+        if hidden {
+            redact_vin(&mut vehicle);
+        }
+        Ok(Some(LotVehicleDetails {
+            lot_vehicle: vehicle,
+            lot_images,
+            auction_result,
+        }))
+    }
+
+    async fn with_variants(
+        conn: &mut AsyncPgConnection,
+        images: Vec<LotImage>,
+    ) -> Result<Vec<LotImageDetails>, GeneralError> {
+        let variants = LotImageVariant::belonging_to(&images)
+            .order((lot_image_variant::width.asc(), lot_image_variant::id.asc()))
+            .select(LotImageVariant::as_select())
+            .load(conn)
+            .await?;
+        Ok(variants
+            .grouped_by(&images)
+            .into_iter()
+            .zip(images)
+            .map(|(variants, lot_image)| LotImageDetails {
+                lot_image,
+                variants,
+            })
+            .collect())
+    }
+
+    /// Whether the vin, raw or normalized, has an active takedown.
+    async fn vin_hidden(
+        conn: &mut AsyncPgConnection,
+        vin: Option<&str>,
+    ) -> Result<bool, GeneralError> {
+        let Some(vin) = vin else {
+            return Ok(false);
+        };
+        Ok(diesel::select(diesel::dsl::exists(
+            vin_takedown::table
+                .filter(vin_takedown::vin.eq(vin::normalize(vin)))
+                .filter(vin_takedown::state.eq(TakedownState::Active.as_str())),
+        ))
+        .get_result(conn)
+        .await?)
+    }
+}
+
+/// Lot rows of all tables are keyed by source and lot number.
+type LotKey = (String, LotNumber);
+
+/// Takes a transaction level lock per lot, keyed by its source and lot number, in the
+/// order given.
+const LOCK_LOTS_SQL: &str = "\
+SELECT pg_advisory_xact_lock(hashtext(l.source), l.lot_number)
+FROM (SELECT *
+      FROM unnest($1::varchar[], $2::int4[]) WITH ORDINALITY AS l(source, lot_number, n)
+      ORDER BY n) l";
+
+fn lot_key(vehicle: &LotVehicle) -> LotKey {
+    (vehicle.source.clone(), vehicle.lot_number)
+}
+
+fn redact_vin(vehicle: &mut LotVehicle) {
+    vehicle.vin = None;
+    vehicle.vin_valid = None;
+    vehicle.vin_check_digit_valid = None;
+    vehicle.vin_manufacturer = None;
+    vehicle.vin_country = None;
+    vehicle.vin_model_year = None;
+    vehicle.vin_plant_code = None;
+}
+
+#[async_trait]
+impl LotVehicleRepo for PgRepo {
+    #[instrument(skip_all)]
+    async fn upsert_lot_vehicles(
+        &self,
+        new_lot_vehicles: NewLotVehicles,
+    ) -> Result<Vec<LotId>, GeneralError> {
+        let mut conn = self.pool.get().await?;
+        let aliases = &self.aliases;
+        conn.transaction::<_, GeneralError, _>(|mut conn| {
+            async move {
+                debug!("lot vehicles to upsert `{}`", new_lot_vehicles.0.len());
+                // the same lot can be listed twice in one search, the latest listing wins
+                let mut new_lot_vehicles = new_lot_vehicles
+                    .0
+                    .into_iter()
+                    .map(|lv| ((lv.source.clone(), lv.lot_number), lv))
+                    .collect::<HashMap<LotKey, _>>();
+
+                let mut canonicalizer = Canonicalizer::load(&mut conn, aliases).await?;
+                for new_lv in new_lot_vehicles.values_mut() {
+                    canonicalizer.canonicalize(&mut conn, new_lv).await?;
+                }
+
+                let sources = new_lot_vehicles
+                    .keys()
+                    .map(|(source, _)| source.clone())
+                    .collect::<HashSet<_>>();
+                let mut current_lot_vehicles = lot_vehicle::table
+                    .filter(lot_vehicle::source.eq_any(sources))
+                    .filter(lot_vehicle::lot_number.eq_any(new_lot_vehicles.keys().map(|k| k.1)))
+                    .select(LotVehicle::as_select())
+                    .for_update()
+                    .load(&mut conn)
+                    .await?
+                    .into_iter()
+                    .map(|lv| (lot_key(&lv), lv))
+                    .collect::<HashMap<_, _>>();
+                debug!("repeating `{}` lot vehicles", current_lot_vehicles.len());
+
+                let mut unique_lot_vehicles = vec![];
+                let mut history = vec![];
+                let mut updated = 0;
+                for new_lv in new_lot_vehicles.into_values() {
+                    let key = (new_lv.source.clone(), new_lv.lot_number);
+                    let Some(current_lv) = current_lot_vehicles.remove(&key) else {
+                        unique_lot_vehicles.push(new_lv);
+                        continue;
+                    };
+
+                    // derived fields are rewritten without history, they follow the
+                    // listing and the aliases rather than the provider
+                    let changes = new_lv.changes(&current_lv);
+                    if changes.is_empty() && !new_lv.derived_changed(&current_lv) {
+                        continue;
+                    }
+                    diesel::update(lot_vehicle::table.find((&new_lv.source, new_lv.lot_number)))
+                        .set(&new_lv)
+                        .execute(&mut conn)
+                        .await?;
+                    history.extend(changes);
+                    updated += 1;
+                }
+
+                // concurrent search responses may insert the same lot, only rows inserted
+                // here are returned
+                let inserted = match unique_lot_vehicles.is_empty() {
+                    true => vec![],
+                    false => {
+                        diesel::insert_into(lot_vehicle::table)
+                            .values(&unique_lot_vehicles)
+                            .on_conflict_do_nothing()
+                            .returning((lot_vehicle::source, lot_vehicle::lot_number))
+                            .get_results::<(String, LotNumber)>(&mut conn)
+                            .await?
+                    }
+                };
+                debug!(
+                    "inserted `{}` of `{}` new lot vehicles",
+                    inserted.len(),
+                    unique_lot_vehicles.len()
+                );
+
+                let h = diesel::insert_into(lot_vehicle_history::table)
+                    .values(&history)
+                    .execute(&mut conn)
+                    .await?;
+                debug!("updated `{updated}` lot vehicles with `{h}` changed fields");
+
+                // a lot of an unknown source would never get its images requested
+                inserted
+                    .into_iter()
+                    .map(|(source, ln)| {
+                        Provider::parse(&source)
+                            .map(|p| LotId::new(p, ln))
+                            .ok_or(GeneralError::UnknownSource(source))
+                    })
+                    .collect()
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    #[instrument(skip_all, fields(source = new_auction_result.source, ln = new_auction_result.lot_vehicle_number))]
+    async fn save_auction_result(
+        &self,
+        mut new_auction_result: NewAuctionResult,
+    ) -> Result<(), GeneralError> {
+        let mut conn = self.pool.get().await?;
+        let Some(sale_date) = lot_vehicle::table
+            .find((
+                &new_auction_result.source,
+                new_auction_result.lot_vehicle_number,
+            ))
+            .select(lot_vehicle::sale_date)
+            .first::<Option<chrono::NaiveDateTime>>(&mut conn)
+            .await
+            .optional()?
+        else {
+            debug!("auction result of unknown lot vehicle skipped");
+            return Ok(());
+        };
+        // redeliveries of a result share the sale date, only a lot listed without one
+        // gets the time the result was stored
+        if new_auction_result.sold_at.is_none() {
+            if sale_date.is_none() {
+                debug!("auction result of a lot without a sale date");
+            }
+            new_auction_result.sold_at =
+                Some(sale_date.unwrap_or_else(|| chrono::Utc::now().naive_utc()));
+        }
+
+        let k = diesel::insert_into(auction_result::table)
+            .values(&new_auction_result)
+            .on_conflict_do_nothing() // Discard redelivered auction results
+            .execute(&mut conn)
+            .await?;
+        debug!("inserted `{k}` auction results");
+        Ok(())
+    }
+
+    async fn lot_vehicles(&self, limit: i64) -> Result<Vec<LotVehicleDetails>, GeneralError> {
+        let mut conn = self.pool.get().await?;
+        let all_vehicles = lot_vehicle::table
+            .filter(diesel::dsl::not(diesel::dsl::exists(
+                vin_takedown::table
+                    .filter(vin_takedown::vin.nullable().eq(lot_vehicle::vin_normalized))
+                    .filter(vin_takedown::state.eq(TakedownState::Active.as_str())),
+            )))
+            .select(LotVehicle::as_select())
+            .limit(limit)
+            .load(&mut conn)
+            .await?;
+
+        let lns = all_vehicles
+            .iter()
+            .map(|v| v.lot_number)
+            .collect::<Vec<_>>();
+        // other providers' lots of the same numbers are dropped by the grouping
+        let mut all_images = HashMap::<LotKey, Vec<LotImageDetails>>::new();
+        let images = lot_image::table
+            .filter(lot_image::lot_vehicle_number.eq_any(&lns))
+            .filter(lot_image::removed_at.is_null())
+            .order(lot_image::sequence_number.asc())
+            .select(LotImage::as_select())
+            .load(&mut conn)
+            .await?;
+        for image in Self::with_variants(&mut conn, images).await? {
+            all_images
+                .entry((
+                    image.lot_image.source.clone(),
+                    image.lot_image.lot_vehicle_number,
+                ))
+                .or_default()
+                .push(image);
+        }
+
+        // ordered latest first, so the first result of a lot is kept
+        let mut latest_results = HashMap::<LotKey, AuctionResult>::new();
+        for result in auction_result::table
+            .filter(auction_result::lot_vehicle_number.eq_any(&lns))
+            .order(auction_result::sold_at.desc())
+            .select(AuctionResult::as_select())
+            .load(&mut conn)
+            .await?
+        {
+            latest_results
+                .entry((result.source.clone(), result.lot_vehicle_number))
+                .or_insert(result);
+        }
+
+        Ok(all_vehicles
+            .into_iter()
+            .map(|lot_vehicle| {
+                let key = lot_key(&lot_vehicle);
+                LotVehicleDetails {
+                    lot_images: all_images.remove(&key).unwrap_or_default(),
+                    auction_result: latest_results.remove(&key),
+                    lot_vehicle,
+                }
+            })
+            .collect())
+    }
+
+    async fn lot_vehicle_by_id(
+        &self,
+        id: LotId,
+    ) -> Result<Option<LotVehicleDetails>, GeneralError> {
+        let mut conn = self.pool.get().await?;
+        let vehicle = lot_vehicle::table
+            .find((id.provider.as_str(), id.lot_number))
+            .select(LotVehicle::as_select())
+            .first(&mut conn)
+            .await
+            .optional()?;
+        Self::details(&mut conn, vehicle).await
+    }
+
+    async fn lot_vehicle_by_vin(
+        &self,
+        vin: &str,
+    ) -> Result<Option<LotVehicleDetails>, GeneralError> {
+        let mut conn = self.pool.get().await?;
+        if Self::vin_hidden(&mut conn, Some(vin)).await? {
+            return Ok(None);
+        }
+        let vehicle = lot_vehicle::table
+            .filter(lot_vehicle::vin_normalized.eq(vin::normalize(vin)))
+            .order(lot_vehicle::updated_at.desc())
+            .select(LotVehicle::as_select())
+            .first(&mut conn)
+            .await
+            .optional()?;
+        Self::details(&mut conn, vehicle).await
+    }
+
+    async fn lot_vehicles_by_vin(&self, vin: &str) -> Result<Vec<LotVehicleDetails>, GeneralError> {
+        let mut conn = self.pool.get().await?;
+        if Self::vin_hidden(&mut conn, Some(vin)).await? {
+            return Ok(vec![]);
+        }
+        let mut vehicles = lot_vehicle::table
+            .filter(lot_vehicle::vin_normalized.eq(vin::normalize(vin)))
+            .select(LotVehicle::as_select())
+            .load(&mut conn)
+            .await?;
+        // unsold lots last
+        vehicles.sort_by_key(|v| {
+            (
+                v.sale_date.is_none(),
+                v.sale_date,
+                v.source.clone(),
+                v.lot_number,
+            )
+        });
+
+        // a vin has a handful of lots at most
+        let mut all_details = Vec::with_capacity(vehicles.len());
+        for vehicle in vehicles {
+            all_details.extend(Self::details(&mut conn, Some(vehicle)).await?);
+        }
+        Ok(all_details)
+    }
+
+    async fn lot_vehicle_history(
+        &self,
+        id: LotId,
+    ) -> Result<Option<Vec<LotVehicleHistory>>, GeneralError> {
+        let mut conn = self.pool.get().await?;
+        let Some(vehicle) = lot_vehicle::table
+            .find((id.provider.as_str(), id.lot_number))
+            .select(LotVehicle::as_select())
+            .first(&mut conn)
+            .await
+            .optional()?
+        else {
+            return Ok(None);
+        };
+
+        let mut history = lot_vehicle_history::table
+            .filter(lot_vehicle_history::source.eq(&vehicle.source))
+            .filter(lot_vehicle_history::lot_vehicle_number.eq(vehicle.lot_number))
+            .order((
+                lot_vehicle_history::changed_at.asc(),
+                lot_vehicle_history::id.asc(),
+            ))
+            .select(LotVehicleHistory::as_select())
+            .load(&mut conn)
+            .await?;
+        if Self::vin_hidden(&mut conn, vehicle.vin.as_deref()).await? {
+            history.retain(|change| change.field != "vin");
+        }
+        Ok(Some(history))
+    }
+
+    async fn search_lot_vehicles(
+        &self,
+        query: &str,
+        limit: i64,
+    ) -> Result<Vec<(SearchHit, LotVehicle)>, GeneralError> {
+        let mut conn = self.pool.get().await?;
+        let hits = search::search_lot_vehicles(&mut conn, query, limit).await?;
+
+        let mut vehicles = lot_vehicle::table
+            .filter(lot_vehicle::lot_number.eq_any(hits.iter().map(|h| h.lot_number)))
+            .select(LotVehicle::as_select())
+            .load(&mut conn)
+            .await?
+            .into_iter()
+            .map(|v| (lot_key(&v), v))
+            .collect::<HashMap<_, _>>();
+
+        Ok(hits
+            .into_iter()
+            .filter_map(|hit| {
+                vehicles
+                    .remove(&(hit.source.clone(), hit.lot_number))
+                    .map(|v| (hit, v))
+            })
+            .collect())
+    }
+
+    async fn related_lot_vehicles(
+        &self,
+        id: LotId,
+        max_distance: i32,
+        limit: i64,
+    ) -> Result<Option<Vec<(RelatedLot, LotVehicle)>>, GeneralError> {
+        let mut conn = self.pool.get().await?;
+        let vehicle = lot_vehicle::table
+            .find((id.provider.as_str(), id.lot_number))
+            .select(LotVehicle::as_select())
+            .first(&mut conn)
+            .await
+            .optional()?;
+        let Some(vehicle) = vehicle else {
+            return Ok(None);
+        };
+        if Self::vin_hidden(&mut conn, vehicle.vin.as_deref()).await? {
+            return Ok(Some(vec![]));
+        }
+
+        let related = related::related_lots(
+            &mut conn,
+            id.provider.as_str(),
+            id.lot_number,
+            max_distance,
+            limit,
+        )
+        .await?;
+        let mut vehicles = lot_vehicle::table
+            .filter(lot_vehicle::lot_number.eq_any(related.iter().map(|r| r.lot_number)))
+            .select(LotVehicle::as_select())
+            .load(&mut conn)
+            .await?
+            .into_iter()
+            .map(|v| (lot_key(&v), v))
+            .collect::<HashMap<_, _>>();
+
+        Ok(Some(
+            related
+                .into_iter()
+                .filter_map(|r| {
+                    vehicles
+                        .remove(&(r.source.clone(), r.lot_number))
+                        .map(|v| (r, v))
+                })
+                .collect(),
+        ))
+    }
+}
+
+#[async_trait]
+impl LotImageRepo for PgRepo {
+    #[instrument(skip(self, new_lot_images))]
+    async fn upsert_lot_images(
+        &self,
+        lot_id: LotId,
+        new_lot_images: NewLotImages,
+    ) -> Result<(), GeneralError> {
+        use crate::persistence::schema::lot_image::dsl::*;
+
+        let mut conn = self.pool.get().await?;
+        conn.transaction::<_, GeneralError, _>(|mut conn| {
+            async move {
+                let mut new_lot_images = new_lot_images;
+                // an erasure may have deleted objects the sync found in the bucket and
+                // did not upload, those images are stored failed and uploaded on the retry
+                diesel::sql_query("SELECT pg_advisory_xact_lock_shared($1)")
+                    .bind::<BigInt, _>(erasure::ERASURE_LOCK_KEY)
+                    .execute(&mut conn)
+                    .await?;
+                let keys = new_lot_images
+                    .0
+                    .iter()
+                    .flat_map(|image| image.variants.iter().map(|v| v.bucket_key.clone()))
+                    .collect::<Vec<_>>();
+                let erased = diesel::delete(
+                    erased_object::table.filter(erased_object::bucket_key.eq_any(&keys)),
+                )
+                .returning(erased_object::bucket_key)
+                .get_results::<String>(&mut conn)
+                .await?
+                .into_iter()
+                .collect::<HashSet<_>>();
+                for image in new_lot_images.0.iter_mut().filter(|image| {
+                    image
+                        .variants
+                        .iter()
+                        .any(|v| erased.contains(&v.bucket_key))
+                }) {
+                    image.variants.clear();
+                    image.sync_status = ImageSyncStatus::Failed.as_str().to_string();
+                    image.sync_error = Some(ImageSyncError::Upload.as_str().to_string());
+                }
+                if !erased.is_empty() {
+                    debug!("`{}` objects of the sync were erased", erased.len());
+                }
+
+                let synced = diesel::insert_into(lot_image)
+                    .values(&new_lot_images.0)
+                    .on_conflict((source, lot_vehicle_number, sequence_number, image_type))
+                    .do_update()
+                    .set((
+                        // like the variants, a failed sync keeps the stored source, so the
+                        // kept variants stay next to the url they were generated from and a
+                        // changed url is fetched again on the retry
+                        source_url.eq(diesel::dsl::sql::<Nullable<Varchar>>(
+                            "CASE WHEN excluded.sync_status = 'failed' \
+                             THEN coalesce(lot_image.source_url, excluded.source_url) \
+                             ELSE excluded.source_url END",
+                        )),
+                        // the hash and validators are those of the stored variants, only
+                        // a sync generating new ones replaces them
+                        perceptual_hash.eq(diesel::dsl::sql::<Nullable<BigInt>>(
+                            "CASE WHEN excluded.sync_status = 'synced' \
+                             THEN excluded.perceptual_hash ELSE lot_image.perceptual_hash END",
+                        )),
+                        source_etag.eq(diesel::dsl::sql::<Nullable<Varchar>>(
+                            "CASE WHEN excluded.sync_status = 'synced' \
+                             THEN excluded.source_etag ELSE lot_image.source_etag END",
+                        )),
+                        source_size.eq(diesel::dsl::sql::<Nullable<BigInt>>(
+                            "CASE WHEN excluded.sync_status = 'synced' \
+                             THEN excluded.source_size ELSE lot_image.source_size END",
+                        )),
+                        sync_status.eq(excluded(sync_status)),
+                        sync_error.eq(excluded(sync_error)),
+                        removed_at.eq(None::<chrono::NaiveDateTime>),
+                    ))
+                    .returning((id, sequence_number, image_type))
+                    .get_results::<(i32, i32, String)>(&mut conn)
+                    .await?;
+                debug!("upserted `{}` lot images", synced.len());
+
+                let synced_ids = synced.iter().map(|(i, ..)| *i).collect::<Vec<_>>();
+                let ids_by_image = synced
+                    .into_iter()
+                    .map(|(i, seq, kind)| ((seq, kind), i))
+                    .collect::<HashMap<_, _>>();
+                let (failed, succeeded): (Vec<_>, Vec<_>) = new_lot_images
+                    .0
+                    .iter()
+                    .partition(|image| image.sync_status == ImageSyncStatus::Failed.as_str());
+                let image_ids = |images: Vec<&NewLotImage>| {
+                    images
+                        .into_iter()
+                        .map(|image| {
+                            ids_by_image[&(image.sequence_number, image.image_type.clone())]
+                        })
+                        .collect::<Vec<_>>()
+                };
+                // images failing with an unknown error are retried
+                let (terminal, failed): (Vec<_>, Vec<_>) = failed.into_iter().partition(|image| {
+                    image
+                        .sync_error
+                        .as_deref()
+                        .and_then(ImageSyncError::parse)
+                        .is_some_and(|e| !e.is_transient())
+                });
+                let (failed_ids, terminal_ids, succeeded_ids) =
+                    (image_ids(failed), image_ids(terminal), image_ids(succeeded));
+                retry::record_attempts(&mut conn, &failed_ids, &terminal_ids, &succeeded_ids)
+                    .await?;
+                debug!(
+                    "`{}` lot images failed to sync, `{}` for good",
+                    failed_ids.len() + terminal_ids.len(),
+                    terminal_ids.len()
+                );
+
+                let mut resynced_ids = vec![];
+                let mut new_variants = vec![];
+                for image in &new_lot_images.0 {
+                    if image.variants.is_empty() {
+                        continue;
+                    }
+                    let image_id = ids_by_image[&(image.sequence_number, image.image_type.clone())];
+                    resynced_ids.push(image_id);
+                    new_variants.extend(
+                        image
+                            .variants
+                            .iter()
+                            .map(|v| (lot_image_variant::lot_image_id.eq(image_id), v)),
+                    );
+                }
+                diesel::delete(
+                    lot_image_variant::table
+                        .filter(lot_image_variant::lot_image_id.eq_any(&resynced_ids)),
+                )
+                .execute(&mut conn)
+                .await?;
+                if !new_variants.is_empty() {
+                    let variants = diesel::insert_into(lot_image_variant::table)
+                        .values(new_variants)
+                        .execute(&mut conn)
+                        .await?;
+                    debug!(
+                        "stored `{variants}` variants of `{}` lot images",
+                        resynced_ids.len()
+                    );
+                }
+
+                let removed = diesel::update(
+                    lot_image
+                        .filter(source.eq(lot_id.provider.as_str()))
+                        .filter(lot_vehicle_number.eq(lot_id.lot_number))
+                        .filter(id.ne_all(&synced_ids))
+                        .filter(removed_at.is_null()),
+                )
+                .set(removed_at.eq(now.nullable()))
+                .execute(&mut conn)
+                .await?;
+                debug!("marked `{removed}` lot images as removed");
+
+                retry::update_images_complete(
+                    &mut conn,
+                    lot_id.provider.as_str(),
+                    lot_id.lot_number,
+                )
+                .await?;
+
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    async fn claim_image_retries(
+        &self,
+        provider: Provider,
+        limit: i64,
+    ) -> Result<Vec<LotId>, GeneralError> {
+        let mut conn = self.pool.get().await?;
+        Ok(retry::claim_due(&mut conn, provider.as_str(), limit)
+            .await?
+            .into_iter()
+            .map(|due| LotId::new(provider, due.lot_number))
+            .collect())
+    }
+
+    async fn lot_images(&self, id: LotId) -> Result<Vec<LotImageDetails>, GeneralError> {
+        let mut conn = self.pool.get().await?;
+        let images = lot_image::table
+            .filter(lot_image::source.eq(id.provider.as_str()))
+            .filter(lot_image::lot_vehicle_number.eq(id.lot_number))
+            .filter(lot_image::removed_at.is_null())
+            .order(lot_image::sequence_number.asc())
+            .select(LotImage::as_select())
+            .load(&mut conn)
+            .await?;
+        Self::with_variants(&mut conn, images).await
+    }
+
+    async fn image_blobs(&self, sha256s: Vec<String>) -> Result<Vec<ImageBlob>, GeneralError> {
+        let mut conn = self.pool.get().await?;
+        Ok(image_blob::table
+            .filter(image_blob::sha256.eq_any(sha256s))
+            .order(image_blob::sha256.asc())
+            .select(ImageBlob::as_select())
+            .load(&mut conn)
+            .await?)
+    }
+}
+
+#[async_trait]
+impl LotPriceRepo for PgRepo {
+    #[instrument(skip_all)]
+    async fn save_price_snapshots(
+        &self,
+        snapshots: Vec<NewLotPriceSnapshot>,
+    ) -> Result<usize, GeneralError> {
+        let mut conn = self.pool.get().await?;
+        conn.transaction::<_, GeneralError, _>(|mut conn| {
+            async move {
+                // the same lot can be listed twice in one search, the latest listing wins
+                let snapshots = snapshots
+                    .into_iter()
+                    .map(|s| ((s.source.clone(), s.lot_vehicle_number), s))
+                    .collect::<HashMap<LotKey, _>>();
+                let sources = snapshots
+                    .keys()
+                    .map(|(source, _)| source.clone())
+                    .collect::<HashSet<_>>();
+
+                // a concurrent batch listing the same lot waits for this one to commit, so
+                // it compares against the snapshot stored here. Locked in key order, so two
+                // batches never deadlock.
+                let mut lots = snapshots.keys().cloned().collect::<Vec<_>>();
+                lots.sort();
+                let (lot_sources, lot_numbers): (Vec<_>, Vec<_>) = lots.into_iter().unzip();
+                diesel::sql_query(LOCK_LOTS_SQL)
+                    .bind::<Array<Varchar>, _>(lot_sources)
+                    .bind::<Array<Integer>, _>(lot_numbers)
+                    .execute(&mut conn)
+                    .await?;
+
+                let latest = lot_price_snapshot::table
+                    .filter(lot_price_snapshot::source.eq_any(sources))
+                    .filter(
+                        lot_price_snapshot::lot_vehicle_number
+                            .eq_any(snapshots.keys().map(|k| k.1)),
+                    )
+                    .distinct_on((
+                        lot_price_snapshot::source,
+                        lot_price_snapshot::lot_vehicle_number,
+                    ))
+                    .order((
+                        lot_price_snapshot::source.asc(),
+                        lot_price_snapshot::lot_vehicle_number.asc(),
+                        lot_price_snapshot::seen_at.desc(),
+                        lot_price_snapshot::id.desc(),
+                    ))
+                    .select(LotPriceSnapshot::as_select())
+                    .load(&mut conn)
+                    .await?
+                    .into_iter()
+                    .map(|s| ((s.source.clone(), s.lot_vehicle_number), s))
+                    .collect::<HashMap<_, _>>();
+
+                let changed = snapshots
+                    .into_iter()
+                    .filter(|(key, s)| !latest.get(key).is_some_and(|l| s.same_prices(l)))
+                    .map(|(_, s)| s)
+                    .collect::<Vec<_>>();
+                let k = diesel::insert_into(lot_price_snapshot::table)
+                    .values(&changed)
+                    .execute(&mut conn)
+                    .await?;
+                debug!("inserted `{k}` changed lot price snapshots");
+                Ok(k)
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    async fn price_snapshots(
+        &self,
+        id: LotId,
+    ) -> Result<Option<Vec<LotPriceSnapshot>>, GeneralError> {
+        let mut conn = self.pool.get().await?;
+        let known_lot = diesel::select(diesel::dsl::exists(
+            lot_vehicle::table.find((id.provider.as_str(), id.lot_number)),
+        ))
+        .get_result::<bool>(&mut conn)
+        .await?;
+        if !known_lot {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            lot_price_snapshot::table
+                .filter(lot_price_snapshot::source.eq(id.provider.as_str()))
+                .filter(lot_price_snapshot::lot_vehicle_number.eq(id.lot_number))
+                .order((
+                    lot_price_snapshot::seen_at.asc(),
+                    lot_price_snapshot::id.asc(),
+                ))
+                .select(LotPriceSnapshot::as_select())
+                .load(&mut conn)
+                .await?,
+        ))
+    }
+}
+
+#[async_trait]
+impl TakedownRepo for PgRepo {
+    #[instrument(skip_all, fields(vin = new_vin_takedown.vin))]
+    async fn submit_takedown(
+        &self,
+        new_vin_takedown: NewVinTakedown,
+    ) -> Result<VinTakedown, GeneralError> {
+        let mut conn = self.pool.get().await?;
+        conn.transaction::<_, GeneralError, _>(|mut conn| {
+            async move {
+                let inserted = diesel::insert_into(vin_takedown::table)
+                    .values(&new_vin_takedown)
+                    .on_conflict_do_nothing() // An open takedown of the vin already exists
+                    .returning(VinTakedown::as_returning())
+                    .get_result(&mut conn)
+                    .await
+                    .optional()?;
+                let Some(takedown) = inserted else {
+                    debug!("vin takedown already open");
+                    return Ok(vin_takedown::table
+                        .filter(vin_takedown::vin.eq(&new_vin_takedown.vin))
+                        .filter(vin_takedown::state.ne(TakedownState::Revoked.as_str()))
+                        .select(VinTakedown::as_select())
+                        .first(&mut conn)
+                        .await?);
+                };
+
+                diesel::insert_into(vin_takedown_audit::table)
+                    .values(NewVinTakedownAudit {
+                        vin_takedown_id: takedown.id,
+                        from_state: None,
+                        to_state: &takedown.state,
+                        actor: &takedown.contact_email,
+                        note: None,
+                    })
+                    .execute(&mut conn)
+                    .await?;
+                debug!("vin takedown `{}` submitted", takedown.id);
+                Ok(takedown)
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    async fn takedowns(
+        &self,
+        state: Option<TakedownState>,
+    ) -> Result<Vec<VinTakedown>, GeneralError> {
+        let mut conn = self.pool.get().await?;
+        let mut query = vin_takedown::table
+            .order(vin_takedown::id.desc())
+            .select(VinTakedown::as_select())
+            .into_boxed();
+        if let Some(state) = state {
+            query = query.filter(vin_takedown::state.eq(state.as_str()));
+        }
+        Ok(query.load(&mut conn).await?)
+    }
+
+    async fn takedown(
+        &self,
+        id: i32,
+    ) -> Result<Option<(VinTakedown, Vec<VinTakedownAudit>)>, GeneralError> {
+        let mut conn = self.pool.get().await?;
+        let Some(takedown) = vin_takedown::table
+            .find(id)
+            .select(VinTakedown::as_select())
+            .first(&mut conn)
+            .await
+            .optional()?
+        else {
+            return Ok(None);
+        };
+
+        let audit = VinTakedownAudit::belonging_to(&takedown)
+            .order(vin_takedown_audit::id.asc())
+            .select(VinTakedownAudit::as_select())
+            .load(&mut conn)
+            .await?;
+        Ok(Some((takedown, audit)))
+    }
+
+    async fn blocked_vins(&self, vins: Vec<String>) -> Result<HashSet<String>, GeneralError> {
+        if vins.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let mut conn = self.pool.get().await?;
+        Ok(vin_takedown::table
+            .filter(vin_takedown::vin.eq_any(vins))
+            .filter(vin_takedown::state.eq(TakedownState::Active.as_str()))
+            .select(vin_takedown::vin)
+            .load::<String>(&mut conn)
+            .await?
+            .into_iter()
+            .collect())
+    }
+
+    async fn lot_takedown(&self, id: LotId) -> Result<Option<i32>, GeneralError> {
+        let mut conn = self.pool.get().await?;
+        let Some(raw) = lot_vehicle::table
+            .filter(lot_vehicle::source.eq(id.provider.as_str()))
+            .filter(lot_vehicle::lot_number.eq(id.lot_number))
+            .select(lot_vehicle::vin)
+            .first::<Option<String>>(&mut conn)
+            .await
+            .optional()?
+            .flatten()
+        else {
+            return Ok(None);
+        };
+        Ok(vin_takedown::table
+            .filter(vin_takedown::vin.eq(vin::normalize(&raw)))
+            .filter(vin_takedown::state.eq(TakedownState::Active.as_str()))
+            .select(vin_takedown::id)
+            .first(&mut conn)
+            .await
+            .optional()?)
+    }
+
+    #[instrument(skip(self, bucket_keys))]
+    async fn queue_erasure(
+        &self,
+        takedown_id: i32,
+        bucket_keys: Vec<String>,
+    ) -> Result<(), GeneralError> {
+        if bucket_keys.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.pool.get().await?;
+        conn.transaction::<_, GeneralError, _>(|mut conn| {
+            async move {
+                let queued = diesel::insert_into(takedown_pending_object::table)
+                    .values(
+                        bucket_keys
+                            .iter()
+                            .map(|key| {
+                                (
+                                    takedown_pending_object::vin_takedown_id.eq(takedown_id),
+                                    takedown_pending_object::bucket_key.eq(key),
+                                )
+                            })
+                            .collect::<Vec<_>>(),
+                    )
+                    .on_conflict_do_nothing()
+                    .execute(&mut conn)
+                    .await?;
+                diesel::update(vin_takedown::table.find(takedown_id))
+                    .set(vin_takedown::erased_at.eq(None::<chrono::NaiveDateTime>))
+                    .execute(&mut conn)
+                    .await?;
+                debug!("queued `{queued}` objects for erasure");
+                Ok(())
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+
+    #[instrument(skip(self, note))]
+    async fn transition_takedown(
+        &self,
+        id: i32,
+        to: TakedownState,
+        actor: &str,
+        note: Option<&str>,
+    ) -> Result<Option<VinTakedown>, GeneralError> {
+        let mut conn = self.pool.get().await?;
+        conn.transaction::<_, GeneralError, _>(|mut conn| {
+            async move {
+                let Some(current) = vin_takedown::table
+                    .find(id)
+                    .select(VinTakedown::as_select())
+                    .for_update()
+                    .first(&mut conn)
+                    .await
+                    .optional()?
+                else {
+                    return Ok(None);
+                };
+
+                let invalid = || GeneralError::TakedownTransition {
+                    from: current.state.clone(),
+                    to: to.as_str().to_string(),
+                };
+                let from = TakedownState::parse(&current.state).ok_or_else(invalid)?;
+                if !from.can_transition_to(to) {
+                    return Err(invalid());
+                }
+
+                let target = diesel::update(vin_takedown::table.find(id));
+                let state = vin_takedown::state.eq(to.as_str());
+                let updated = match to {
+                    TakedownState::Requested => return Err(invalid()),
+                    TakedownState::Verified => {
+                        target
+                            .set((state, vin_takedown::verified_at.eq(now.nullable())))
+                            .returning(VinTakedown::as_returning())
+                            .get_result(&mut conn)
+                            .await?
+                    }
+                    TakedownState::Active => {
+                        target
+                            .set((state, vin_takedown::activated_at.eq(now.nullable())))
+                            .returning(VinTakedown::as_returning())
+                            .get_result(&mut conn)
+                            .await?
+                    }
+                    TakedownState::Revoked => {
+                        target
+                            .set((state, vin_takedown::revoked_at.eq(now.nullable())))
+                            .returning(VinTakedown::as_returning())
+                            .get_result(&mut conn)
+                            .await?
+                    }
+                };
+
+                diesel::insert_into(vin_takedown_audit::table)
+                    .values(NewVinTakedownAudit {
+                        vin_takedown_id: id,
+                        from_state: Some(from.as_str()),
+                        to_state: to.as_str(),
+                        actor,
+                        note,
+                    })
+                    .execute(&mut conn)
+                    .await?;
+                debug!(
+                    "vin takedown moved from `{}` to `{}`",
+                    from.as_str(),
+                    to.as_str()
+                );
+                Ok(Some(updated))
+            }
+            .scope_boxed()
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::models::copart::{
+        NewAuctionResult, NewLotImage, NewLotPriceSnapshot, NewLotVehicle,
+    };
+    use crate::persistence::testing::{fixtures, TestDb};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_upsert_lot_vehicles_dedup() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        let repo = db.repo();
+
+        // a lot listed twice in one batch is inserted once, the latest listing wins
+        let inserted = repo
+            .upsert_lot_vehicles(NewLotVehicles(vec![
+                fixtures::lot_vehicle(1),
+                fixtures::lot_vehicle(2),
+                NewLotVehicle {
+                    odometer: 1.0,
+                    ..fixtures::lot_vehicle(2)
+                },
+            ]))
+            .await?;
+        let mut inserted_sorted = inserted.clone();
+        inserted_sorted.sort();
+        assert_eq!(inserted_sorted, vec![LotId::copart(1), LotId::copart(2)]);
+
+        // re-seen lots are not reported as new, unchanged ones leave no history
+        let inserted = repo
+            .upsert_lot_vehicles(NewLotVehicles(vec![
+                fixtures::lot_vehicle(1),
+                NewLotVehicle {
+                    odometer: 2.0,
+                    ..fixtures::lot_vehicle(2)
+                },
+                fixtures::lot_vehicle(3),
+            ]))
+            .await?;
+        assert_eq!(inserted, vec![LotId::copart(3)]);
+
+        assert_eq!(
+            repo.lot_vehicle_history(LotId::copart(1))
+                .await?
+                .map(|h| h.len()),
+            Some(0)
+        );
+        let history = repo.lot_vehicle_history(LotId::copart(2)).await?.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].field, "odometer");
+        assert_eq!(history[0].old_value.as_deref(), Some("1"));
+        assert_eq!(history[0].new_value.as_deref(), Some("2"));
+        assert!(repo.lot_vehicle_history(LotId::copart(4)).await?.is_none());
+
+        // canonical ids are assigned before insert
+        let lv = repo
+            .lot_vehicle_by_id(LotId::copart(3))
+            .await?
+            .unwrap()
+            .lot_vehicle;
+        assert!(lv.make_id.is_some());
+
+        // a lot stored before ids were derived gets them when listed again unchanged
+        db.seed(vec![fixtures::lot_vehicle(5)]).await?;
+        repo.upsert_lot_vehicles(NewLotVehicles(vec![fixtures::lot_vehicle(5)]))
+            .await?;
+        let lv = repo
+            .lot_vehicle_by_id(LotId::copart(5))
+            .await?
+            .unwrap()
+            .lot_vehicle;
+        assert!(lv.make_id.is_some());
+        assert_eq!(
+            repo.lot_vehicle_history(LotId::copart(5))
+                .await?
+                .map(|h| h.len()),
+            Some(0)
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_concurrently_listed_lot_is_inserted_once(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        let repo = db.repo();
+
+        // both may find the lot missing, the one blocked on the other's insert skips it
+        let listing = || NewLotVehicles(vec![fixtures::lot_vehicle(7)]);
+        let (first, second) = tokio::join!(
+            repo.upsert_lot_vehicles(listing()),
+            repo.upsert_lot_vehicles(listing())
+        );
+        assert_eq!([first?, second?].concat(), vec![LotId::copart(7)]);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_upsert_lot_images_marks_removed() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        let repo = db.repo();
+        db.seed(vec![fixtures::lot_vehicle(1)]).await?;
+
+        repo.upsert_lot_images(
+            LotId::copart(1),
+            NewLotImages(vec![
+                NewLotImage {
+                    source_etag: Some("\"v1\"".to_string()),
+                    source_size: Some(4096),
+                    ..fixtures::lot_image(1, 1)
+                },
+                fixtures::lot_image(1, 2),
+                fixtures::lot_image(1, 3),
+            ]),
+        )
+        .await?;
+        let first_ids = repo
+            .lot_images(LotId::copart(1))
+            .await?
+            .into_iter()
+            .map(|i| i.lot_image.id)
+            .collect::<Vec<_>>();
+        assert_eq!(first_ids.len(), 3);
+
+        // resync keeps the rows of re-seen images, replaces their variants and hides
+        // the disappeared one, an image which failed to sync keeps its variants
+        repo.upsert_lot_images(
+            LotId::copart(1),
+            NewLotImages(vec![
+                NewLotImage {
+                    variants: vec![fixtures::lot_image_variant("resynced", 640)],
+                    ..fixtures::lot_image(1, 2)
+                },
+                NewLotImage {
+                    source_url: Some("https://cs.copart.com/1/1/moved.jpg".to_string()),
+                    variants: vec![],
+                    sync_status: ImageSyncStatus::Failed.as_str().to_string(),
+                    sync_error: Some(ImageSyncError::Download.as_str().to_string()),
+                    ..fixtures::lot_image(1, 1)
+                },
+            ]),
+        )
+        .await?;
+        let images = repo.lot_images(LotId::copart(1)).await?;
+        assert_eq!(
+            images.iter().map(|i| i.lot_image.id).collect::<Vec<_>>(),
+            first_ids[..2]
+        );
+        assert_eq!(
+            images[0]
+                .variants
+                .iter()
+                .map(|v| v.width)
+                .collect::<Vec<_>>(),
+            vec![Some(320), Some(800), Some(1600)]
+        );
+        assert_eq!(images[1].variants.len(), 1);
+        assert_eq!(images[1].variants[0].sha256.as_deref(), Some("resynced"));
+        // as do its source and its validators, so the moved source is fetched on the retry
+        assert_eq!(
+            images[0].lot_image.source_url,
+            fixtures::lot_image(1, 1).source_url
+        );
+        assert_eq!(images[0].lot_image.source_etag.as_deref(), Some("\"v1\""));
+        assert_eq!(images[0].lot_image.source_size, Some(4096));
+
+        // a reappearing image is restored
+        repo.upsert_lot_images(
+            LotId::copart(1),
+            NewLotImages(vec![
+                fixtures::lot_image(1, 1),
+                fixtures::lot_image(1, 2),
+                fixtures::lot_image(1, 3),
+            ]),
+        )
+        .await?;
+        assert_eq!(
+            repo.lot_images(LotId::copart(1))
+                .await?
+                .into_iter()
+                .map(|i| i.lot_image.id)
+                .collect::<Vec<_>>(),
+            first_ids
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_failed_images_are_retried() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        let repo = db.repo();
+        db.seed(vec![fixtures::lot_vehicle(1)]).await?;
+        let failed = |sequence_number| NewLotImage {
+            variants: vec![],
+            sync_status: ImageSyncStatus::Failed.as_str().to_string(),
+            sync_error: Some("download".to_string()),
+            ..fixtures::lot_image(1, sequence_number)
+        };
+        let images_complete = async || -> Result<bool, GeneralError> {
+            Ok(repo
+                .lot_vehicle_by_id(LotId::copart(1))
+                .await?
+                .is_some_and(|d| d.lot_vehicle.images_complete_at.is_some()))
+        };
+
+        repo.upsert_lot_images(
+            LotId::copart(1),
+            NewLotImages(vec![
+                fixtures::lot_image(1, 1),
+                failed(2),
+                NewLotImage {
+                    source_url: None,
+                    variants: vec![],
+                    sync_status: ImageSyncStatus::NoSource.as_str().to_string(),
+                    ..fixtures::lot_image(1, 3)
+                },
+                NewLotImage {
+                    sync_error: Some(ImageSyncError::Rejected.as_str().to_string()),
+                    ..failed(4)
+                },
+            ]),
+        )
+        .await?;
+        let images = repo.lot_images(LotId::copart(1)).await?;
+        // a rejected source is served again as it is
+        assert_eq!(images[3].lot_image.sync_status, "failed");
+        assert!(images[3].lot_image.retry_at.is_none());
+        let image = &images[1].lot_image;
+        assert_eq!(image.sync_status, "failed");
+        assert_eq!(image.sync_error.as_deref(), Some("download"));
+        assert_eq!(image.sync_attempts, 0);
+        assert!(image.retry_at.is_some());
+        assert!(images[2].lot_image.retry_at.is_none());
+        assert!(!images_complete().await?);
+
+        // not due yet
+        let claim = async || repo.claim_image_retries(Provider::Copart, 10).await;
+        assert!(claim().await?.is_empty());
+        db.expire_retries(&[1]).await?;
+        assert!(repo
+            .claim_image_retries(Provider::Stub, 10)
+            .await?
+            .is_empty());
+        assert_eq!(claim().await?, vec![LotId::copart(1)]);
+        // in flight
+        assert!(claim().await?.is_empty());
+        let attempts = async || -> Result<(i32, bool), GeneralError> {
+            let image = &repo.lot_images(LotId::copart(1)).await?[1].lot_image;
+            Ok((image.sync_attempts, image.retry_at.is_some()))
+        };
+        assert_eq!(attempts().await?, (1, true));
+
+        // claims without synced images coming back count too, the last one clears the retry
+        for attempt in 2..=retry::MAX_SYNC_ATTEMPTS {
+            db.expire_retries(&[1]).await?;
+            assert_eq!(claim().await?, vec![LotId::copart(1)]);
+            assert_eq!(
+                attempts().await?,
+                (attempt, attempt < retry::MAX_SYNC_ATTEMPTS)
+            );
+        }
+        db.expire_retries(&[1]).await?;
+        assert!(claim().await?.is_empty());
+
+        // a failure of the last attempt is not retried
+        repo.upsert_lot_images(
+            LotId::copart(1),
+            NewLotImages(vec![fixtures::lot_image(1, 1), failed(2)]),
+        )
+        .await?;
+        assert_eq!(attempts().await?, (retry::MAX_SYNC_ATTEMPTS, false));
+
+        repo.upsert_lot_images(
+            LotId::copart(1),
+            NewLotImages(vec![fixtures::lot_image(1, 1), fixtures::lot_image(1, 2)]),
+        )
+        .await?;
+        let images = repo.lot_images(LotId::copart(1)).await?;
+        assert_eq!(images[1].lot_image.sync_status, "synced");
+        assert_eq!(images[1].lot_image.sync_attempts, 0);
+        assert!(images_complete().await?);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_image_blob_ref_count() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        let repo = db.repo();
+        db.seed(vec![fixtures::lot_vehicle(1), fixtures::lot_vehicle(2)])
+            .await?;
+        async fn ref_counts(repo: &PgRepo) -> Result<Vec<(String, i32)>, GeneralError> {
+            Ok(repo
+                .image_blobs(vec!["a".to_string(), "b".to_string()])
+                .await?
+                .into_iter()
+                .map(|b| (b.sha256, b.ref_count))
+                .collect())
+        }
+
+        // a stock photo shared by two lots is stored once and referenced twice
+        repo.upsert_lot_images(
+            LotId::copart(1),
+            NewLotImages(vec![fixtures::content_addressed_lot_image(1, 1, "a")]),
+        )
+        .await?;
+        repo.upsert_lot_images(
+            LotId::copart(2),
+            NewLotImages(vec![
+                fixtures::content_addressed_lot_image(2, 1, "a"),
+                fixtures::content_addressed_lot_image(2, 2, "b"),
+            ]),
+        )
+        .await?;
+        assert_eq!(
+            ref_counts(&repo).await?,
+            vec![("a".to_string(), 2), ("b".to_string(), 1)]
+        );
+
+        // a resynced variant moves its reference, a re-seen one keeps it
+        repo.upsert_lot_images(
+            LotId::copart(2),
+            NewLotImages(vec![
+                fixtures::content_addressed_lot_image(2, 1, "b"),
+                fixtures::content_addressed_lot_image(2, 2, "b"),
+            ]),
+        )
+        .await?;
+        assert_eq!(
+            ref_counts(&repo).await?,
+            vec![("a".to_string(), 1), ("b".to_string(), 2)]
+        );
+
+        // deleted images release the blobs of their variants
+        let mut conn = db.pool.get().await?;
+        diesel::delete(lot_image::table.filter(lot_image::lot_vehicle_number.eq(2)))
+            .execute(&mut conn)
+            .await?;
+        assert_eq!(
+            ref_counts(&repo).await?,
+            vec![("a".to_string(), 1), ("b".to_string(), 0)]
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_erased_objects_are_uploaded_again() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        let repo = db.repo();
+        db.seed(vec![fixtures::lot_vehicle(1), fixtures::lot_vehicle(2)])
+            .await?;
+        let mut conn = db.pool.get().await?;
+        let images = || {
+            NewLotImages(vec![
+                fixtures::content_addressed_lot_image(1, 1, "a"),
+                fixtures::content_addressed_lot_image(1, 2, "b"),
+            ])
+        };
+
+        // the sync found `a` in the bucket just before an erasure deleted it
+        diesel::insert_into(erased_object::table)
+            .values(erased_object::bucket_key.eq(crate::bucket::content_key("a")))
+            .execute(&mut conn)
+            .await?;
+        repo.upsert_lot_images(LotId::copart(1), images()).await?;
+        let stored = repo.lot_images(LotId::copart(1)).await?;
+        let erased = &stored[0];
+        assert_eq!(erased.lot_image.sync_status, "failed");
+        assert_eq!(erased.lot_image.sync_error.as_deref(), Some("upload"));
+        assert!(erased.variants.is_empty());
+        assert!(erased.lot_image.retry_at.is_some());
+        assert_eq!(stored[1].lot_image.sync_status, "synced");
+
+        // the retry uploaded it again
+        repo.upsert_lot_images(LotId::copart(1), images()).await?;
+        let stored = repo.lot_images(LotId::copart(1)).await?;
+        assert_eq!(stored[0].lot_image.sync_status, "synced");
+        assert_eq!(stored[0].variants.len(), 1);
+
+        // objects of a blocked lot queued after its erasure make it due again
+        let vin = fixtures::lot_vehicle(2).vin.unwrap();
+        let takedown = repo.submit_takedown(fixtures::vin_takedown(&vin)).await?;
+        assert_eq!(repo.lot_takedown(LotId::copart(2)).await?, None);
+        for state in [TakedownState::Verified, TakedownState::Active] {
+            repo.transition_takedown(takedown.id, state, "support", None)
+                .await?;
+        }
+        assert_eq!(
+            repo.lot_takedown(LotId::copart(2)).await?,
+            Some(takedown.id)
+        );
+        assert_eq!(repo.lot_takedown(LotId::copart(1)).await?, None);
+        diesel::update(vin_takedown::table.find(takedown.id))
+            .set(vin_takedown::erased_at.eq(now.nullable()))
+            .execute(&mut conn)
+            .await?;
+        repo.queue_erasure(takedown.id, vec![crate::bucket::content_key("c")])
+            .await?;
+        let (takedown, _) = repo.takedown(takedown.id).await?.unwrap();
+        assert!(takedown.erased_at.is_none());
+        assert_eq!(
+            takedown_pending_object::table
+                .filter(takedown_pending_object::vin_takedown_id.eq(takedown.id))
+                .select(takedown_pending_object::bucket_key)
+                .load::<String>(&mut conn)
+                .await?,
+            vec![crate::bucket::content_key("c")]
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_save_auction_result() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        let repo = db.repo();
+        db.seed(vec![fixtures::lot_vehicle(1)]).await?;
+
+        repo.save_auction_result(fixtures::auction_result(1))
+            .await?;
+        // redelivered and unknown lot results are discarded
+        repo.save_auction_result(fixtures::auction_result(1))
+            .await?;
+        repo.save_auction_result(fixtures::auction_result(2))
+            .await?;
+
+        let details = repo.lot_vehicle_by_id(LotId::copart(1)).await?.unwrap();
+        assert!(details.auction_result.is_some());
+        assert!(repo.lot_vehicle_by_id(LotId::copart(2)).await?.is_none());
+
+        // a result sent without a sale date gets the listed one, so redeliveries collapse
+        let sale_date =
+            chrono::NaiveDate::from_ymd_opt(2025, 11, 4).and_then(|d| d.and_hms_opt(17, 0, 0));
+        db.seed(vec![NewLotVehicle {
+            sale_date,
+            ..fixtures::lot_vehicle(3)
+        }])
+        .await?;
+        for _ in 0..2 {
+            repo.save_auction_result(NewAuctionResult {
+                sold_at: None,
+                ..fixtures::auction_result(3)
+            })
+            .await?;
+        }
+        let details = repo.lot_vehicle_by_id(LotId::copart(3)).await?.unwrap();
+        assert_eq!(details.auction_result.map(|r| r.sold_at), sale_date);
+
+        let mut conn = db.pool.get().await?;
+        let results = auction_result::table
+            .count()
+            .get_result::<i64>(&mut conn)
+            .await?;
+        assert_eq!(results, 2);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_price_snapshots_dedup() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        let repo = db.repo();
+        db.seed(vec![fixtures::lot_vehicle(1), fixtures::lot_vehicle(2)])
+            .await?;
+        let snapshot = |ln, current_bid| NewLotPriceSnapshot {
+            current_bid,
+            ..fixtures::price_snapshot(ln)
+        };
+
+        assert_eq!(
+            repo.save_price_snapshots(vec![snapshot(1, 100.0), snapshot(2, 0.0)])
+                .await?,
+            2
+        );
+        // unchanged prices are not snapshotted again
+        assert_eq!(
+            repo.save_price_snapshots(vec![snapshot(1, 100.0), snapshot(2, 0.0)])
+                .await?,
+            0
+        );
+        // within a batch the latest listing wins
+        assert_eq!(
+            repo.save_price_snapshots(vec![
+                snapshot(1, 150.0),
+                snapshot(1, 200.0),
+                snapshot(2, 0.0),
+            ])
+            .await?,
+            1
+        );
+        assert_eq!(
+            repo.save_price_snapshots(vec![NewLotPriceSnapshot {
+                sale_status: "SOLD".to_string(),
+                ..snapshot(1, 200.0)
+            }])
+            .await?,
+            1
+        );
+
+        let prices = repo.price_snapshots(LotId::copart(1)).await?.unwrap();
+        assert_eq!(
+            prices
+                .iter()
+                .map(|p| (p.current_bid, p.sale_status.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(100.0, "PURE SALE"), (200.0, "PURE SALE"), (200.0, "SOLD")]
+        );
+
+        // concurrent batches listing the same new prices store them once
+        let saved = futures::future::try_join_all(
+            (0..8).map(|_| repo.save_price_snapshots(vec![snapshot(2, 50.0), snapshot(1, 300.0)])),
+        )
+        .await?;
+        assert_eq!(saved.iter().sum::<usize>(), 2);
+        assert_eq!(
+            repo.price_snapshots(LotId::copart(2)).await?.unwrap().len(),
+            2
+        );
+        assert!(repo.price_snapshots(LotId::copart(3)).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_lot_vehicle_queries() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        let repo = db.repo();
+        db.seed(vec![fixtures::lot_vehicle(1), fixtures::lot_vehicle(2)])
+            .await?;
+        repo.upsert_lot_images(
+            LotId::copart(1),
+            NewLotImages(vec![fixtures::lot_image(1, 2), fixtures::lot_image(1, 1)]),
+        )
+        .await?;
+        repo.save_auction_result(fixtures::auction_result(2))
+            .await?;
+
+        let all = repo.lot_vehicles(500).await?;
+        assert_eq!(all.len(), 2);
+        let lot_1 = all.iter().find(|d| d.lot_vehicle.lot_number == 1).unwrap();
+        assert_eq!(
+            lot_1
+                .lot_images
+                .iter()
+                .map(|i| (i.lot_image.sequence_number, i.variants.len()))
+                .collect::<Vec<_>>(),
+            vec![(1, 3), (2, 3)]
+        );
+        assert!(lot_1.auction_result.is_none());
+        let lot_2 = all.iter().find(|d| d.lot_vehicle.lot_number == 2).unwrap();
+        assert!(lot_2.lot_images.is_empty());
+        assert!(lot_2.auction_result.is_some());
+        assert_eq!(repo.lot_vehicles(1).await?.len(), 1);
+
+        let vin = fixtures::lot_vehicle(2).vin.unwrap();
+        let by_vin = repo.lot_vehicle_by_vin(&vin).await?.unwrap();
+        assert_eq!(by_vin.lot_vehicle.lot_number, 2);
+        assert!(repo.lot_vehicle_by_vin("NOPE").await?.is_none());
+
+        let hits = repo.search_lot_vehicles("toyota", 20).await?;
+        assert_eq!(hits.len(), 2);
+        assert!(hits.iter().all(|(hit, lv)| hit.lot_number == lv.lot_number));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_related_lot_vehicles() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        let repo = db.repo();
+        db.seed((1..=3).map(fixtures::lot_vehicle).collect())
+            .await?;
+        db.seed_images(
+            (1..=3)
+                .map(|ln| NewLotImage {
+                    perceptual_hash: Some(0x00FF_00FF),
+                    ..fixtures::lot_image(ln, 1)
+                })
+                .collect(),
+        )
+        .await?;
+
+        let related = repo
+            .related_lot_vehicles(LotId::copart(1), 6, 20)
+            .await?
+            .unwrap();
+        assert_eq!(
+            related
+                .iter()
+                .map(|(r, lv)| (r.lot_number, lv.lot_number))
+                .collect::<Vec<_>>(),
+            vec![(3, 3), (2, 2)]
+        );
+        assert!(repo
+            .related_lot_vehicles(LotId::copart(4), 6, 20)
+            .await?
+            .is_none());
+
+        let vin = fixtures::lot_vehicle(2).vin.unwrap();
+        let takedown = repo.submit_takedown(fixtures::vin_takedown(&vin)).await?;
+        for state in [TakedownState::Verified, TakedownState::Active] {
+            repo.transition_takedown(takedown.id, state, "support", None)
+                .await?;
+        }
+        let related = repo
+            .related_lot_vehicles(LotId::copart(1), 6, 20)
+            .await?
+            .unwrap();
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].1.lot_number, 3);
+        assert!(repo
+            .related_lot_vehicles(LotId::copart(2), 6, 20)
+            .await?
+            .unwrap()
+            .is_empty());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_takedown_hides_vin() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        let repo = db.repo();
+        let vin = fixtures::lot_vehicle(1).vin.unwrap();
+        // a relisting of the same vehicle with the vin as the provider spelled it
+        let relisted = NewLotVehicle {
+            vin: Some(format!("{}-{}", vin[..8].to_lowercase(), &vin[8..])),
+            ..fixtures::lot_vehicle(3)
+        };
+        db.seed(vec![
+            fixtures::lot_vehicle(1),
+            fixtures::lot_vehicle(2),
+            relisted,
+        ])
+        .await?;
+        db.seed_images(vec![fixtures::lot_image(1, 1), fixtures::lot_image(3, 1)])
+            .await?;
+
+        let submitted = repo.submit_takedown(fixtures::vin_takedown(&vin)).await?;
+        assert_eq!(submitted.state, "requested");
+        // resubmitting returns the open takedown
+        let resubmitted = repo.submit_takedown(fixtures::vin_takedown(&vin)).await?;
+        assert_eq!(resubmitted.id, submitted.id);
+
+        // only an active takedown hides the vin
+        repo.transition_takedown(submitted.id, TakedownState::Verified, "support", None)
+            .await?;
+        assert!(repo.lot_vehicle_by_vin(&vin).await?.is_some());
+        assert!(matches!(
+            repo.transition_takedown(submitted.id, TakedownState::Requested, "support", None)
+                .await,
+            Err(GeneralError::TakedownTransition { .. })
+        ));
+        let active = repo
+            .transition_takedown(submitted.id, TakedownState::Active, "support", Some("ok"))
+            .await?
+            .unwrap();
+        assert!(active.activated_at.is_some());
+
+        assert!(repo.lot_vehicle_by_vin(&vin).await?.is_none());
+        let redacted = repo.lot_vehicle_by_id(LotId::copart(1)).await?.unwrap();
+        assert!(redacted.lot_vehicle.vin.is_none());
+        assert!(redacted.lot_images.is_empty());
+        let relisted = repo.lot_vehicle_by_id(LotId::copart(3)).await?.unwrap();
+        assert!(relisted.lot_vehicle.vin.is_none());
+        assert!(relisted.lot_images.is_empty());
+        let all = repo.lot_vehicles(500).await?;
+        assert_eq!(
+            all.iter()
+                .map(|d| d.lot_vehicle.lot_number)
+                .collect::<Vec<_>>(),
+            vec![2]
+        );
+        assert!(repo.search_lot_vehicles(&vin[11..], 20).await?.is_empty());
+
+        let (_, audit) = repo.takedown(submitted.id).await?.unwrap();
+        assert_eq!(
+            audit
+                .iter()
+                .map(|a| (a.from_state.as_deref(), a.to_state.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (None, "requested"),
+                (Some("requested"), "verified"),
+                (Some("verified"), "active"),
+            ]
+        );
+
+        // revoking shows the vin again and allows a new takedown
+        repo.transition_takedown(submitted.id, TakedownState::Revoked, "support", None)
+            .await?;
+        assert!(repo.lot_vehicle_by_vin(&vin).await?.is_some());
+        let new = repo.submit_takedown(fixtures::vin_takedown(&vin)).await?;
+        assert_ne!(new.id, submitted.id);
+        assert_eq!(repo.takedowns(Some(TakedownState::Revoked)).await?.len(), 1);
+        assert!(repo
+            .transition_takedown(0, TakedownState::Revoked, "support", None)
+            .await?
+            .is_none());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_lots_are_scoped_by_provider() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        let repo = db.repo();
+        let vin = fixtures::lot_vehicle(1).vin.unwrap();
+        let sold_at = |day| {
+            chrono::NaiveDate::from_ymd_opt(2025, 10, day).and_then(|d| d.and_hms_opt(12, 0, 0))
+        };
+
+        // the same lot number at another auction house is another lot
+        let inserted = repo
+            .upsert_lot_vehicles(NewLotVehicles(vec![
+                NewLotVehicle {
+                    sale_date: sold_at(20),
+                    ..fixtures::lot_vehicle(1)
+                },
+                NewLotVehicle {
+                    source: Provider::Stub.to_string(),
+                    sale_date: sold_at(10),
+                    odometer: 1.0,
+                    ..fixtures::lot_vehicle(1)
+                },
+            ]))
+            .await?;
+        assert_eq!(inserted.len(), 2);
+        let stub_lot = LotId::new(Provider::Stub, 1);
+        repo.upsert_lot_images(
+            stub_lot,
+            NewLotImages(vec![NewLotImage {
+                source: Provider::Stub.to_string(),
+                ..fixtures::lot_image(1, 1)
+            }]),
+        )
+        .await?;
+        repo.save_auction_result(NewAuctionResult {
+            source: Provider::Stub.to_string(),
+            ..fixtures::auction_result(1)
+        })
+        .await?;
+
+        let copart = repo.lot_vehicle_by_id(LotId::copart(1)).await?.unwrap();
+        assert!(copart.lot_images.is_empty());
+        assert!(copart.auction_result.is_none());
+        let stub = repo.lot_vehicle_by_id(stub_lot).await?.unwrap();
+        assert_eq!(stub.lot_vehicle.odometer, 1.0);
+        assert_eq!(stub.lot_images.len(), 1);
+        assert!(stub.auction_result.is_some());
+
+        // a vin's lots merged across providers, oldest sale first
+        let history = repo.lot_vehicles_by_vin(&vin).await?;
+        assert_eq!(
+            history
+                .iter()
+                .map(|d| d.lot_vehicle.source.as_str())
+                .collect::<Vec<_>>(),
+            vec!["stub", "copart"]
+        );
+        assert!(repo.lot_vehicles_by_vin("NOPE").await?.is_empty());
+        Ok(())
+    }
+}
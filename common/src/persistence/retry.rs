@@ -0,0 +1,157 @@
+use diesel::sql_types::{Array, BigInt, Integer, Text};
+use diesel::{QueryResult, QueryableByName};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+/// Delay of the first retry of a failed image, doubled on every further failure up to
+/// [`RETRY_MAX_SECS`]
+pub const RETRY_BASE_SECS: i64 = 5 * 60;
+pub const RETRY_MAX_SECS: i64 = 6 * 60 * 60;
+/// A failed image is requested again at most this many times, it stays failed after.
+/// Every claim counts, whether or not synced images come back for it.
+pub const MAX_SYNC_ATTEMPTS: i32 = 8;
+/// A claimed retry whose synced images never arrived is claimed again after this
+pub const RETRY_CLAIM_SECS: i64 = 60 * 60;
+pub const DEFAULT_RETRY_BATCH_SIZE: i64 = 100;
+
+/// Schedules the next attempt of the failed images `$1`, the attempts were counted when
+/// they were claimed.
+const FAILED_SQL: &str = "\
+UPDATE lot_image
+SET retry_at = CASE
+                   WHEN sync_attempts < $2
+                       THEN now() + make_interval(secs => least($3 * 2 ^ sync_attempts, $4))
+                   END
+WHERE id = ANY ($1)";
+
+/// Failed images `$1` whose failure a retry would not fix stay failed.
+const TERMINAL_SQL: &str = "\
+UPDATE lot_image
+SET retry_at = NULL
+WHERE id = ANY ($1)
+  AND retry_at IS NOT NULL";
+
+const SUCCEEDED_SQL: &str = "\
+UPDATE lot_image
+SET sync_attempts = 0,
+    retry_at      = NULL
+WHERE id = ANY ($1)
+  AND (sync_attempts <> 0 OR retry_at IS NOT NULL)";
+
+/// A lot is complete when it has a synced image and every image with a source synced.
+/// The first completion is kept while the lot stays complete.
+const IMAGES_COMPLETE_SQL: &str = "\
+UPDATE lot_vehicle v
+SET images_complete_at = CASE
+                             WHEN EXISTS (SELECT 1
+                                          FROM lot_image i
+                                          WHERE i.source = v.source
+                                            AND i.lot_vehicle_number = v.lot_number
+                                            AND i.removed_at IS NULL
+                                            AND i.sync_status IN ('synced', 'unchanged'))
+                                 AND NOT EXISTS (SELECT 1
+                                                 FROM lot_image i
+                                                 WHERE i.source = v.source
+                                                   AND i.lot_vehicle_number = v.lot_number
+                                                   AND i.removed_at IS NULL
+                                                   AND i.sync_status = 'failed')
+                                 THEN coalesce(v.images_complete_at, now())
+                             END
+WHERE v.source = $1
+  AND v.lot_number = $2";
+
+/// Lots of the provider `$3` with a failed image due for a retry. The claim counts as an
+/// attempt of their failed images and pushes their retries back by `$2` seconds, so a lot
+/// is not requested again while its retry is in flight. The last attempt clears them.
+/// Due images locked by a concurrent claim are skipped, so replicas never claim the same lot.
+const CLAIM_SQL: &str = "\
+WITH due AS (SELECT DISTINCT source, lot_vehicle_number
+             FROM (SELECT source, lot_vehicle_number
+                   FROM lot_image
+                   WHERE retry_at <= now()
+                     AND removed_at IS NULL
+                     AND source = $3
+                   ORDER BY source, lot_vehicle_number
+                   FOR UPDATE SKIP LOCKED) l
+             ORDER BY source, lot_vehicle_number
+             LIMIT $1)
+UPDATE lot_image i
+SET sync_attempts = i.sync_attempts + 1,
+    retry_at      = CASE
+                        WHEN i.sync_attempts + 1 < $4
+                            THEN now() + make_interval(secs => $2)
+                        END
+FROM due d
+WHERE i.source = d.source
+  AND i.lot_vehicle_number = d.lot_vehicle_number
+  AND i.retry_at IS NOT NULL
+RETURNING i.source, i.lot_vehicle_number AS lot_number";
+
+#[derive(Debug, QueryableByName)]
+pub struct DueLot {
+    #[diesel(sql_type = Text)]
+    pub source: String,
+    #[diesel(sql_type = Integer)]
+    pub lot_number: i32,
+}
+
+/// Schedules retries of the images that failed transiently, clears them of the ones that
+/// failed for good and of the synced ones.
+pub(crate) async fn record_attempts(
+    conn: &mut AsyncPgConnection,
+    failed: &[i32],
+    terminal: &[i32],
+    succeeded: &[i32],
+) -> QueryResult<()> {
+    if !failed.is_empty() {
+        diesel::sql_query(FAILED_SQL)
+            .bind::<Array<Integer>, _>(failed)
+            .bind::<Integer, _>(MAX_SYNC_ATTEMPTS)
+            .bind::<BigInt, _>(RETRY_BASE_SECS)
+            .bind::<BigInt, _>(RETRY_MAX_SECS)
+            .execute(conn)
+            .await?;
+    }
+    if !terminal.is_empty() {
+        diesel::sql_query(TERMINAL_SQL)
+            .bind::<Array<Integer>, _>(terminal)
+            .execute(conn)
+            .await?;
+    }
+    if !succeeded.is_empty() {
+        diesel::sql_query(SUCCEEDED_SQL)
+            .bind::<Array<Integer>, _>(succeeded)
+            .execute(conn)
+            .await?;
+    }
+    Ok(())
+}
+
+pub(crate) async fn update_images_complete(
+    conn: &mut AsyncPgConnection,
+    source: &str,
+    lot_number: i32,
+) -> QueryResult<usize> {
+    diesel::sql_query(IMAGES_COMPLETE_SQL)
+        .bind::<Text, _>(source)
+        .bind::<Integer, _>(lot_number)
+        .execute(conn)
+        .await
+}
+
+/// Claims at most `limit` lots of `source` with due retries, see [`CLAIM_SQL`].
+pub async fn claim_due(
+    conn: &mut AsyncPgConnection,
+    source: &str,
+    limit: i64,
+) -> QueryResult<Vec<DueLot>> {
+    let mut claimed = diesel::sql_query(CLAIM_SQL)
+        .bind::<BigInt, _>(limit.max(1))
+        .bind::<BigInt, _>(RETRY_CLAIM_SECS)
+        .bind::<Text, _>(source)
+        .bind::<Integer, _>(MAX_SYNC_ATTEMPTS)
+        .load::<DueLot>(conn)
+        .await?;
+    claimed.sort_by(|a, b| (&a.source, a.lot_number).cmp(&(&b.source, b.lot_number)));
+    claimed.dedup_by(|a, b| a.source == b.source && a.lot_number == b.lot_number);
+    Ok(claimed)
+}
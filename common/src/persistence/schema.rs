@@ -1,27 +1,89 @@
 // @generated automatically by Diesel CLI.
 
+pub mod sql_types {
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "tsvector", schema = "pg_catalog"))]
+    pub struct Tsvector;
+}
+
+diesel::table! {
+    auction_result (id) {
+        id -> Int4,
+        lot_vehicle_number -> Int4,
+        final_bid -> Float8,
+        buyer_country -> Nullable<Varchar>,
+        buyer_state -> Nullable<Varchar>,
+        min_met -> Bool,
+        sold_at -> Timestamp,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        source -> Varchar,
+    }
+}
+
+diesel::table! {
+    erased_object (bucket_key) {
+        bucket_key -> Varchar,
+        erased_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    image_blob (sha256) {
+        sha256 -> Varchar,
+        size -> Int8,
+        ref_count -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     lot_image (id) {
         id -> Int4,
-        standard_bucket_key -> Nullable<Varchar>,
-        standard_mime_type -> Nullable<Varchar>,
-        standard_source_url -> Nullable<Varchar>,
-        thumbnail_bucket_key -> Nullable<Varchar>,
-        thumbnail_mime_type -> Nullable<Varchar>,
-        thumbnail_source_url -> Nullable<Varchar>,
-        high_res_bucket_key -> Nullable<Varchar>,
-        high_res_mime_type -> Nullable<Varchar>,
-        high_res_source_url -> Nullable<Varchar>,
         sequence_number -> Int4,
         image_type -> Varchar,
         created_at -> Timestamp,
         updated_at -> Timestamp,
         lot_vehicle_number -> Int4,
+        removed_at -> Nullable<Timestamp>,
+        source -> Varchar,
+        source_url -> Nullable<Varchar>,
+        perceptual_hash -> Nullable<Int8>,
+        source_etag -> Nullable<Varchar>,
+        source_size -> Nullable<Int8>,
+        sync_status -> Varchar,
+        sync_error -> Nullable<Varchar>,
+        sync_attempts -> Int4,
+        retry_at -> Nullable<Timestamp>,
+        perceptual_hash_band_0 -> Nullable<Int4>,
+        perceptual_hash_band_1 -> Nullable<Int4>,
+        perceptual_hash_band_2 -> Nullable<Int4>,
+        perceptual_hash_band_3 -> Nullable<Int4>,
     }
 }
 
 diesel::table! {
-    lot_vehicle (lot_number) {
+    lot_image_variant (id) {
+        id -> Int4,
+        lot_image_id -> Int4,
+        name -> Varchar,
+        format -> Varchar,
+        mime_type -> Varchar,
+        width -> Nullable<Int4>,
+        height -> Nullable<Int4>,
+        size -> Nullable<Int8>,
+        bucket_key -> Varchar,
+        sha256 -> Nullable<Varchar>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Tsvector;
+
+    lot_vehicle (source, lot_number) {
         lot_number -> Int4,
         make -> Varchar,
         model -> Varchar,
@@ -47,12 +109,130 @@ diesel::table! {
         keys_status -> Nullable<Varchar>,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        make_id -> Nullable<Int4>,
+        model_id -> Nullable<Int4>,
+        main_damage_id -> Nullable<Int4>,
+        color_id -> Nullable<Int4>,
+        fuel_type_id -> Nullable<Int4>,
+        drive_type_id -> Nullable<Int4>,
+        vin_valid -> Nullable<Bool>,
+        vin_check_digit_valid -> Nullable<Bool>,
+        vin_manufacturer -> Nullable<Varchar>,
+        vin_country -> Nullable<Varchar>,
+        vin_model_year -> Nullable<Int4>,
+        vin_plant_code -> Nullable<Varchar>,
+        trim -> Nullable<Varchar>,
+        yard -> Nullable<Varchar>,
+        search_text -> Nullable<Text>,
+        search_document -> Nullable<Tsvector>,
+        source -> Varchar,
+        images_complete_at -> Nullable<Timestamp>,
+        vin_normalized -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    lot_price_snapshot (id) {
+        id -> Int4,
+        source -> Varchar,
+        lot_vehicle_number -> Int4,
+        current_bid -> Float8,
+        buy_today_bid -> Float8,
+        high_bid -> Float8,
+        sale_status -> Varchar,
+        seen_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    lot_vehicle_history (id) {
+        id -> Int4,
+        lot_vehicle_number -> Int4,
+        field -> Varchar,
+        old_value -> Nullable<Varchar>,
+        new_value -> Nullable<Varchar>,
+        changed_at -> Timestamp,
+        source -> Varchar,
+    }
+}
+
+diesel::table! {
+    reference_alias (id) {
+        id -> Int4,
+        kind -> Varchar,
+        alias -> Varchar,
+        reference_value_id -> Int4,
+        make_id -> Nullable<Int4>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    reference_value (id) {
+        id -> Int4,
+        kind -> Varchar,
+        name -> Varchar,
+        make_id -> Nullable<Int4>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    takedown_pending_object (vin_takedown_id, bucket_key) {
+        vin_takedown_id -> Int4,
+        bucket_key -> Varchar,
+        queued_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    vin_takedown (id) {
+        id -> Int4,
+        vin -> Varchar,
+        state -> Varchar,
+        contact_email -> Varchar,
+        contact_name -> Nullable<Varchar>,
+        reason -> Varchar,
+        requested_at -> Timestamp,
+        verified_at -> Nullable<Timestamp>,
+        activated_at -> Nullable<Timestamp>,
+        revoked_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+        erased_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    vin_takedown_audit (id) {
+        id -> Int4,
+        vin_takedown_id -> Int4,
+        from_state -> Nullable<Varchar>,
+        to_state -> Varchar,
+        actor -> Varchar,
+        note -> Nullable<Varchar>,
+        changed_at -> Timestamp,
     }
 }
 
-diesel::joinable!(lot_image -> lot_vehicle (lot_vehicle_number));
+diesel::joinable!(lot_image_variant -> lot_image (lot_image_id));
+diesel::joinable!(takedown_pending_object -> vin_takedown (vin_takedown_id));
+diesel::joinable!(vin_takedown_audit -> vin_takedown (vin_takedown_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    auction_result,
+    erased_object,
+    image_blob,
     lot_image,
+    lot_image_variant,
+    lot_price_snapshot,
     lot_vehicle,
+    lot_vehicle_history,
+    reference_alias,
+    reference_value,
+    takedown_pending_object,
+    vin_takedown,
+    vin_takedown_audit,
 );
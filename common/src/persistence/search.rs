@@ -0,0 +1,194 @@
+use diesel::sql_types::{BigInt, Float4, Integer, Nullable, Text};
+use diesel::{QueryResult, QueryableByName};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+pub const DEFAULT_SEARCH_LIMIT: i64 = 20;
+pub const MAX_SEARCH_LIMIT: i64 = 100;
+/// Shortest vin suffix which is matched, listings commonly show the last 6 characters
+pub const MIN_PARTIAL_VIN_LEN: usize = 6;
+
+const HIGHLIGHT_START: &str = "<mark>";
+const HIGHLIGHT_STOP: &str = "</mark>";
+
+/// Ranks lot vehicles by the generated `search_document` (weighted make, model,
+/// trim, damage, color and yard), falls back to trigram word similarity for
+/// misspelled queries and matches vin suffixes.
+///
+/// Full text matches outweigh fuzzy ones, a matching vin suffix outweighs both.
+/// Lots of vins with an active takedown are never returned.
+const SEARCH_SQL: &str = "\
+SELECT source,
+       lot_number,
+       (ts_rank(search_document, to_tsquery('simple', $1))
+           + word_similarity($2, search_text)
+           + CASE WHEN vin LIKE '%' || $3 THEN 1 ELSE 0 END)::REAL AS rank,
+       ts_headline('simple', search_text, to_tsquery('simple', $1), $4) AS highlight
+FROM lot_vehicle
+WHERE (search_document @@ to_tsquery('simple', $1)
+    OR $2 <% search_text
+    OR vin LIKE '%' || $3)
+  AND NOT EXISTS (SELECT 1 FROM vin_takedown t WHERE t.vin = lot_vehicle.vin_normalized AND t.state = 'active')
+ORDER BY rank DESC, lot_number DESC, source
+LIMIT $5";
+
+#[derive(Debug, QueryableByName)]
+pub struct SearchHit {
+    #[diesel(sql_type = Text)]
+    pub source: String,
+    #[diesel(sql_type = Integer)]
+    pub lot_number: i32,
+    #[diesel(sql_type = Float4)]
+    pub rank: f32,
+    /// Searchable attributes with matched words wrapped in `<mark>` tags
+    #[diesel(sql_type = Text)]
+    pub highlight: String,
+}
+
+/// Free text search over lot vehicles, best match first.
+///
+/// Every word of the query is matched as a prefix, so `toyo cam` finds a toyota camry.
+pub async fn search_lot_vehicles(
+    conn: &mut AsyncPgConnection,
+    query: &str,
+    limit: i64,
+) -> QueryResult<Vec<SearchHit>> {
+    let words = words(query);
+    if words.is_empty() {
+        return Ok(vec![]);
+    }
+    let ts_query = words
+        .iter()
+        .map(|w| format!("{w}:*"))
+        .collect::<Vec<_>>()
+        .join(" & ");
+
+    diesel::sql_query(SEARCH_SQL)
+        .bind::<Text, _>(ts_query)
+        .bind::<Text, _>(words.join(" "))
+        .bind::<Nullable<Text>, _>(partial_vin(&words))
+        .bind::<Text, _>(format!(
+            "StartSel={HIGHLIGHT_START}, StopSel={HIGHLIGHT_STOP}, HighlightAll=true"
+        ))
+        .bind::<BigInt, _>(limit.clamp(1, MAX_SEARCH_LIMIT))
+        .load(conn)
+        .await
+}
+
+/// Lowercase alphanumeric words, anything else would be tsquery syntax.
+fn words(query: &str) -> Vec<String> {
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+/// A single word query of vin length or shorter is also tried as a vin suffix.
+fn partial_vin(words: &[String]) -> Option<String> {
+    match words {
+        [word] if (MIN_PARTIAL_VIN_LEN..=17).contains(&word.len()) && word.is_ascii() => {
+            Some(word.to_uppercase())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persistence::models::copart::NewLotVehicle;
+    use crate::persistence::testing::{fixtures, TestDb};
+
+    fn lot(
+        lot_number: i32,
+        (make, model, trim): (&str, &str, &str),
+        damage: &str,
+        vin: &str,
+    ) -> NewLotVehicle {
+        NewLotVehicle {
+            make: make.to_string(),
+            model: model.to_string(),
+            trim: Some(trim.to_string()),
+            main_damage: damage.to_string(),
+            vin: Some(vin.to_string()),
+            ..fixtures::lot_vehicle(lot_number)
+        }
+    }
+
+    async fn fixtures_db() -> Result<TestDb, Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        db.seed(vec![
+            lot(
+                1,
+                ("TOYOTA", "CAMRY", "SE"),
+                "FRONT END",
+                "4T1B11HK5JU000001",
+            ),
+            lot(
+                2,
+                ("TOYOTA", "COROLLA", "LE"),
+                "REAR END",
+                "2T1BURHE0JC000002",
+            ),
+            lot(
+                3,
+                ("HONDA", "ACCORD", "CAMRY EDITION"),
+                "HAIL",
+                "1HGCV1F30JA000003",
+            ),
+            lot(4, ("FORD", "F150", "XLT"), "FRONT END", "1FTEW1EP0JF123456"),
+        ])
+        .await?;
+        Ok(db)
+    }
+
+    fn lot_numbers(hits: &[SearchHit]) -> Vec<i32> {
+        hits.iter().map(|h| h.lot_number).collect()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_search_ranking() -> Result<(), Box<dyn std::error::Error>> {
+        let db = fixtures_db().await?;
+        let mut conn = db.pool.get().await?;
+
+        // a model match outweighs the same word in the trim
+        let hits = search_lot_vehicles(&mut conn, "camry", DEFAULT_SEARCH_LIMIT).await?;
+        assert_eq!(lot_numbers(&hits), vec![1, 3]);
+        assert!(hits[0].rank > hits[1].rank);
+
+        let hits = search_lot_vehicles(&mut conn, "toyota camry", DEFAULT_SEARCH_LIMIT).await?;
+        assert_eq!(hits[0].lot_number, 1);
+        assert!(hits[0].highlight.contains("<mark>TOYOTA</mark>"));
+        assert!(hits[0].highlight.contains("<mark>CAMRY</mark>"));
+
+        let hits = search_lot_vehicles(&mut conn, "toyo", DEFAULT_SEARCH_LIMIT).await?;
+        assert_eq!(lot_numbers(&hits), vec![2, 1]);
+
+        let hits = search_lot_vehicles(&mut conn, "front end", 1).await?;
+        assert_eq!(hits.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_search_fuzzy_and_partial_vin() -> Result<(), Box<dyn std::error::Error>> {
+        let db = fixtures_db().await?;
+        let mut conn = db.pool.get().await?;
+
+        let hits = search_lot_vehicles(&mut conn, "corrola", DEFAULT_SEARCH_LIMIT).await?;
+        assert_eq!(hits.first().map(|h| h.lot_number), Some(2));
+
+        let hits = search_lot_vehicles(&mut conn, "123456", DEFAULT_SEARCH_LIMIT).await?;
+        assert_eq!(lot_numbers(&hits), vec![4]);
+
+        let hits = search_lot_vehicles(&mut conn, " -- ", DEFAULT_SEARCH_LIMIT).await?;
+        assert!(hits.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_partial_vin() {
+        assert_eq!(partial_vin(&words("u000001")), Some("U000001".to_string()));
+        assert_eq!(partial_vin(&words("12345")), None);
+        assert_eq!(partial_vin(&words("toyota camry")), None);
+    }
+}
@@ -0,0 +1,205 @@
+use axum::body::Body;
+use axum::extract::Query;
+use axum::http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use pprof::protos::Message;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::info;
+
+/// Profiling endpoints merged into the admin server of every service
+/// (see [`crate::service::Service::admin`]):
+///
+/// * `GET /prof/heap/flamegraph` - jemalloc heap flamegraph (svg)
+/// * `GET /prof/heap/pprof` - raw jemalloc heap dump in gzipped pprof format
+/// * `GET /prof/cpu/pprof?seconds=N` - sampled cpu profile in pprof protobuf format
+/// * `GET /prof/cpu/flamegraph?seconds=N` - sampled cpu profile as a flamegraph (svg)
+/// * `GET /prof/tasks` - tokio runtime task dump (plain text)
+pub struct Prof;
+
+const DEFAULT_CPU_PROFILE_SECONDS: u64 = 30;
+const MAX_CPU_PROFILE_SECONDS: u64 = 300;
+const DEFAULT_CPU_PROFILE_FREQUENCY: i32 = 99;
+#[cfg(tokio_taskdump)]
+const TASK_DUMP_TIMEOUT: Duration = Duration::from_secs(5);
+
+// pprof-rs supports a single active profiler per process
+static CPU_PROFILE_LOCK: Mutex<()> = Mutex::const_new(());
+
+#[derive(Deserialize)]
+pub struct CpuProfileParams {
+    seconds: Option<u64>,
+    frequency: Option<i32>,
+}
+
+impl CpuProfileParams {
+    fn duration(&self) -> Duration {
+        Duration::from_secs(
+            self.seconds
+                .unwrap_or(DEFAULT_CPU_PROFILE_SECONDS)
+                .clamp(1, MAX_CPU_PROFILE_SECONDS),
+        )
+    }
+
+    fn frequency(&self) -> i32 {
+        self.frequency
+            .unwrap_or(DEFAULT_CPU_PROFILE_FREQUENCY)
+            .clamp(1, 1000)
+    }
+}
+
+impl Prof {
+    pub fn router() -> axum::Router {
+        axum::Router::new()
+            .route(
+                "/prof/heap/flamegraph",
+                axum::routing::get(Self::handle_get_heap_flamegraph),
+            )
+            .route(
+                "/prof/heap/pprof",
+                axum::routing::get(Self::handle_get_heap_pprof),
+            )
+            .route(
+                "/prof/cpu/pprof",
+                axum::routing::get(Self::handle_get_cpu_pprof),
+            )
+            .route(
+                "/prof/cpu/flamegraph",
+                axum::routing::get(Self::handle_get_cpu_flamegraph),
+            )
+            .route("/prof/tasks", axum::routing::get(Self::handle_get_tasks))
+    }
+
+    pub async fn handle_get_heap_flamegraph() -> Result<impl IntoResponse, (StatusCode, String)> {
+        let mut prof_ctl = Self::prof_ctl()?.lock().await;
+        Self::require_profiling_activated(&prof_ctl)?;
+        let svg = prof_ctl.dump_flamegraph().map_err(internal)?;
+        Response::builder()
+            .header(CONTENT_TYPE, "image/svg+xml")
+            .body(Body::from(svg))
+            .map_err(internal)
+    }
+
+    /// Heap dump is gzipped by jemalloc_pprof and can be fed straight to
+    /// `go tool pprof`, including `-base` for diffing two dumps.
+    pub async fn handle_get_heap_pprof() -> Result<impl IntoResponse, (StatusCode, String)> {
+        let mut prof_ctl = Self::prof_ctl()?.lock().await;
+        Self::require_profiling_activated(&prof_ctl)?;
+        let pprof = prof_ctl.dump_pprof().map_err(internal)?;
+        Response::builder()
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .header(CONTENT_DISPOSITION, "attachment; filename=\"heap.pb.gz\"")
+            .body(Body::from(pprof))
+            .map_err(internal)
+    }
+
+    pub async fn handle_get_cpu_pprof(
+        Query(params): Query<CpuProfileParams>,
+    ) -> Result<impl IntoResponse, (StatusCode, String)> {
+        let report = Self::profile_cpu(&params).await?;
+        let profile = report.pprof().map_err(internal)?;
+        let mut body = Vec::new();
+        profile.encode(&mut body).map_err(internal)?;
+        Response::builder()
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .header(CONTENT_DISPOSITION, "attachment; filename=\"cpu.pb\"")
+            .body(Body::from(body))
+            .map_err(internal)
+    }
+
+    pub async fn handle_get_cpu_flamegraph(
+        Query(params): Query<CpuProfileParams>,
+    ) -> Result<impl IntoResponse, (StatusCode, String)> {
+        let report = Self::profile_cpu(&params).await?;
+        let mut svg = Vec::new();
+        report.flamegraph(&mut svg).map_err(internal)?;
+        Response::builder()
+            .header(CONTENT_TYPE, "image/svg+xml")
+            .body(Body::from(svg))
+            .map_err(internal)
+    }
+
+    #[cfg(tokio_taskdump)]
+    pub async fn handle_get_tasks() -> Result<impl IntoResponse, (StatusCode, String)> {
+        use std::fmt::Write;
+
+        let dump =
+            tokio::time::timeout(TASK_DUMP_TIMEOUT, tokio::runtime::Handle::current().dump())
+                .await
+                .map_err(|_| {
+                    (
+                        StatusCode::GATEWAY_TIMEOUT,
+                        "tokio task dump timed out".to_string(),
+                    )
+                })?;
+
+        let mut body = String::new();
+        for (i, task) in dump.tasks().iter().enumerate() {
+            writeln!(body, "task {i} [id: {}]:\n{}\n", task.id(), task.trace())
+                .map_err(internal)?;
+        }
+        Response::builder()
+            .header(CONTENT_TYPE, "text/plain; charset=utf-8")
+            .body(Body::from(body))
+            .map_err(internal)
+    }
+
+    #[cfg(not(tokio_taskdump))]
+    pub async fn handle_get_tasks() -> Result<impl IntoResponse, (StatusCode, String)> {
+        Err::<Response, _>((
+            StatusCode::NOT_IMPLEMENTED,
+            "tokio task dumps require `--cfg tokio_taskdump`".to_string(),
+        ))
+    }
+
+    async fn profile_cpu(params: &CpuProfileParams) -> Result<pprof::Report, (StatusCode, String)> {
+        let _guard = CPU_PROFILE_LOCK.try_lock().map_err(|_| {
+            (
+                StatusCode::CONFLICT,
+                "cpu profiling already in progress".to_string(),
+            )
+        })?;
+
+        let (duration, frequency) = (params.duration(), params.frequency());
+        info!("cpu profiling for `{duration:?}` at `{frequency}` Hz");
+        // the profiler guard is not meant to be held across await points,
+        // so sampling happens on a blocking thread
+        tokio::task::spawn_blocking(move || {
+            let guard = pprof::ProfilerGuardBuilder::default()
+                .frequency(frequency)
+                .blocklist(&["libc", "libgcc", "pthread", "vdso"])
+                .build()?;
+            std::thread::sleep(duration);
+            guard.report().build()
+        })
+        .await
+        .map_err(internal)?
+        .map_err(internal)
+    }
+
+    fn prof_ctl(
+    ) -> Result<&'static Arc<Mutex<jemalloc_pprof::JemallocProfCtl>>, (StatusCode, String)> {
+        jemalloc_pprof::PROF_CTL.as_ref().ok_or((
+            StatusCode::FORBIDDEN,
+            "jemalloc profiling control not available".to_string(),
+        ))
+    }
+
+    fn require_profiling_activated(
+        prof_ctl: &jemalloc_pprof::JemallocProfCtl,
+    ) -> Result<(), (StatusCode, String)> {
+        if prof_ctl.activated() {
+            Ok(())
+        } else {
+            Err((StatusCode::FORBIDDEN, "heap profiling not activated".into()))
+        }
+    }
+}
+
+fn internal(err: impl ToString) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+}
@@ -0,0 +1,29 @@
+//! Profiling of the services. The endpoints need the `prof` feature, the allocator is
+//! declared by every binary with [`jemalloc!`](crate::jemalloc).
+
+#[cfg(feature = "prof")]
+mod endpoints;
+
+#[cfg(feature = "prof")]
+pub use endpoints::{CpuProfileParams, Prof};
+
+/// Declares jemalloc as the global allocator of a binary, which must depend on
+/// `tikv-jemallocator`. With the binary's own `prof` feature jemalloc samples the heap
+/// for the `/prof/heap` endpoints.
+///
+/// ```ignore
+/// common::jemalloc!();
+/// ```
+#[macro_export]
+macro_rules! jemalloc {
+    () => {
+        #[cfg(not(target_env = "msvc"))]
+        #[global_allocator]
+        static GLOBAL: ::tikv_jemallocator::Jemalloc = ::tikv_jemallocator::Jemalloc;
+
+        #[allow(non_upper_case_globals)]
+        #[unsafe(export_name = "malloc_conf")]
+        #[cfg(feature = "prof")]
+        pub static malloc_conf: &[u8] = b"prof:true,prof_active:true,lg_prof_sample:19\0";
+    };
+}
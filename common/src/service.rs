@@ -0,0 +1,504 @@
+use crate::logging::setup_logging;
+use axum::routing::get;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+use tokio::net::ToSocketAddrs;
+use tokio::sync::oneshot;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Shutdown stages, components are stopped stage by stage in declaration order.
+/// Every component of a stage is cancelled at once and the next stage starts
+/// after all of them either finished or hit their timeout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    /// Sources of new work, e.g. kafka receivers, http listeners
+    Ingress,
+    /// Components working on already received messages, e.g. sinks, browser pools
+    Processing,
+    /// Sinks of processed work, e.g. kafka senders
+    Egress,
+    /// Admin server, stopped last so profiling stays available during shutdown
+    Admin,
+}
+
+/// Runtime shared by every binary: sets up logging, runs the admin server,
+/// waits for SIGINT/SIGTERM/SIGHUP and shuts registered components down
+/// in [`Stage`] order.
+///
+/// ```ignore
+/// let mut service = Service::new("persister").admin("0.0.0.0:6970");
+/// service.register("persister sink", Stage::Processing, |token| sink.run(token));
+/// service.run().await;
+/// ```
+pub struct Service {
+    name: &'static str,
+    admin_addr: Option<String>,
+    shutdown_timeout: Duration,
+    /// Parent of every component token, cancelled once the last stage stopped
+    token: CancellationToken,
+    components: Vec<Component>,
+}
+
+struct Component {
+    name: &'static str,
+    stage: Stage,
+    timeout: Duration,
+    token: CancellationToken,
+    /// Cancelled once the component reported it is done or dropped its sender, whether or
+    /// not it was stopped
+    exited: CancellationToken,
+}
+
+impl Service {
+    /// Must be called inside the tokio runtime, logging spawns its loki task.
+    pub fn new(name: &'static str) -> Self {
+        setup_logging(name);
+        info!("starting app");
+
+        Self {
+            name,
+            admin_addr: None,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            token: CancellationToken::new(),
+            components: vec![],
+        }
+    }
+
+    /// Serves `/health` and, with the `prof` feature, the profiling endpoints.
+    pub fn admin(mut self, addr: impl Into<String>) -> Self {
+        self.admin_addr = Some(addr.into());
+        self
+    }
+
+    /// Default time a single component is given to stop after its cancellation.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Token for components which need it before they can be started, pass it back with
+    /// [`Service::register_running`] to have it cancelled in the component's stage. A token
+    /// which is never passed back is cancelled after the last stage.
+    pub fn token(&self) -> CancellationToken {
+        self.token.child_token()
+    }
+
+    pub fn register<F>(&mut self, name: &'static str, stage: Stage, start: F)
+    where
+        F: FnOnce(CancellationToken) -> oneshot::Receiver<()>,
+    {
+        self.register_with_timeout(name, stage, self.shutdown_timeout, start)
+    }
+
+    pub fn register_with_timeout<F>(
+        &mut self,
+        name: &'static str,
+        stage: Stage,
+        timeout: Duration,
+        start: F,
+    ) where
+        F: FnOnce(CancellationToken) -> oneshot::Receiver<()>,
+    {
+        let token = self.token();
+        let done = start(token.clone());
+        self.push(name, stage, timeout, token, done);
+    }
+
+    /// Registers a component which was already started with a token from [`Service::token`].
+    pub fn register_running(
+        &mut self,
+        name: &'static str,
+        stage: Stage,
+        token: CancellationToken,
+        done: oneshot::Receiver<()>,
+    ) {
+        self.push(name, stage, self.shutdown_timeout, token, done);
+    }
+
+    fn push(
+        &mut self,
+        name: &'static str,
+        stage: Stage,
+        timeout: Duration,
+        token: CancellationToken,
+        done: oneshot::Receiver<()>,
+    ) {
+        info!("component `{name}` started in `{stage}` stage");
+        self.components
+            .push(Component::watch(name, stage, timeout, token, done));
+    }
+
+    /// Runs until a shutdown signal is received, then stops every component
+    /// and returns the shutdown report.
+    pub async fn run(mut self) -> ShutdownReport {
+        if let Some(addr) = self.admin_addr.take() {
+            let service_name = self.name;
+            self.register("admin server", Stage::Admin, |token| {
+                serve_admin(addr, service_name, token)
+            });
+        }
+
+        info!("app started");
+        let signal = shutdown_signal().await;
+        info!("exiting on `{signal}`");
+
+        // a second signal skips the graceful shutdown
+        tokio::spawn(async {
+            let signal = shutdown_signal().await;
+            warn!("received `{signal}` during shutdown, exiting immediately");
+            std::process::exit(130);
+        });
+
+        let report = self.shutdown().await;
+        report.log();
+        info!("exited");
+        report
+    }
+
+    async fn shutdown(mut self) -> ShutdownReport {
+        let started = Instant::now();
+        // stable sort keeps registration order within a stage
+        self.components.sort_by_key(|c| c.stage);
+
+        let mut entries = Vec::with_capacity(self.components.len());
+        let mut components = self.components.into_iter().peekable();
+        while let Some(stage) = components.peek().map(|c| c.stage) {
+            let mut stopping = JoinSet::new();
+            while let Some(component) = components.next_if(|c| c.stage == stage) {
+                stopping.spawn(component.stop());
+            }
+
+            let mut stage_entries = stopping.join_all().await;
+            stage_entries.sort_by_key(|e| e.name);
+            entries.extend(stage_entries);
+        }
+        self.token.cancel();
+
+        ShutdownReport {
+            entries,
+            elapsed: started.elapsed(),
+        }
+    }
+}
+
+impl Component {
+    /// Waits for the component to be done, so one which exits before the shutdown is told
+    /// apart from one which does not stop in time. The channel keeps an exit which happened
+    /// before the registration.
+    fn watch(
+        name: &'static str,
+        stage: Stage,
+        timeout: Duration,
+        token: CancellationToken,
+        done: oneshot::Receiver<()>,
+    ) -> Self {
+        let exited = CancellationToken::new();
+        tokio::spawn({
+            let (token, exited) = (token.clone(), exited.clone());
+            async move {
+                let _ = done.await;
+                if !token.is_cancelled() {
+                    warn!("component `{name}` exited before the shutdown");
+                }
+                exited.cancel();
+            }
+        });
+
+        Self {
+            name,
+            stage,
+            timeout,
+            token,
+            exited,
+        }
+    }
+
+    async fn stop(self) -> ReportEntry {
+        let outcome = match self.exited.is_cancelled() {
+            true => Outcome::ExitedEarly,
+            false => {
+                let started = Instant::now();
+                self.token.cancel();
+                match tokio::time::timeout(self.timeout, self.exited.cancelled()).await {
+                    Ok(()) => Outcome::Stopped(started.elapsed()),
+                    Err(_) => Outcome::TimedOut(self.timeout),
+                }
+            }
+        };
+
+        ReportEntry {
+            name: self.name,
+            stage: self.stage,
+            outcome,
+        }
+    }
+}
+
+pub struct ShutdownReport {
+    pub entries: Vec<ReportEntry>,
+    pub elapsed: Duration,
+}
+
+pub struct ReportEntry {
+    pub name: &'static str,
+    pub stage: Stage,
+    pub outcome: Outcome,
+}
+
+pub enum Outcome {
+    Stopped(Duration),
+    TimedOut(Duration),
+    /// Done before the shutdown cancelled it
+    ExitedEarly,
+}
+
+impl ShutdownReport {
+    pub fn is_clean(&self) -> bool {
+        self.entries
+            .iter()
+            .all(|e| matches!(e.outcome, Outcome::Stopped(_)))
+    }
+
+    fn log(&self) {
+        for entry in &self.entries {
+            match entry.outcome {
+                Outcome::Stopped(_) => info!("shutdown report: {entry}"),
+                Outcome::TimedOut(_) | Outcome::ExitedEarly => warn!("shutdown report: {entry}"),
+            }
+        }
+        info!(
+            "shutdown of `{}` components finished in `{:?}`, clean: `{}`",
+            self.entries.len(),
+            self.elapsed,
+            self.is_clean()
+        );
+    }
+}
+
+impl Display for Stage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ingress => write!(f, "ingress"),
+            Self::Processing => write!(f, "processing"),
+            Self::Egress => write!(f, "egress"),
+            Self::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl Display for ReportEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.outcome {
+            Outcome::Stopped(elapsed) => {
+                write!(
+                    f,
+                    "[{}] `{}` stopped in `{elapsed:?}`",
+                    self.stage, self.name
+                )
+            }
+            Outcome::TimedOut(timeout) => write!(
+                f,
+                "[{}] `{}` timed out after `{timeout:?}`",
+                self.stage, self.name
+            ),
+            Outcome::ExitedEarly => write!(
+                f,
+                "[{}] `{}` exited before the shutdown",
+                self.stage, self.name
+            ),
+        }
+    }
+}
+
+fn serve_admin(
+    addr: impl ToSocketAddrs + Send + 'static,
+    service_name: &'static str,
+    cancellation_token: CancellationToken,
+) -> oneshot::Receiver<()> {
+    let (done_sender, done) = oneshot::channel();
+    tokio::spawn(async move {
+        let app = axum::Router::new().route("/health", get(move || async move { service_name }));
+        #[cfg(feature = "prof")]
+        let app = app.merge(crate::prof::Prof::router());
+
+        let listener = match tokio::net::TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("failed to bind admin server: `{e}`");
+                cancellation_token.cancelled().await;
+                let _ = done_sender.send(());
+                return;
+            }
+        };
+
+        if let Err(e) = axum::serve(listener, app)
+            .with_graceful_shutdown(cancellation_token.cancelled_owned())
+            .await
+        {
+            error!("admin server failed: `{e}`");
+        }
+        let _ = done_sender.send(());
+    });
+
+    done
+}
+
+#[cfg(unix)]
+async fn shutdown_signal() -> &'static str {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut interrupt = signal(SignalKind::interrupt()).expect("failed to listen for SIGINT");
+    let mut terminate = signal(SignalKind::terminate()).expect("failed to listen for SIGTERM");
+    let mut hangup = signal(SignalKind::hangup()).expect("failed to listen for SIGHUP");
+
+    tokio::select! {
+        _ = interrupt.recv() => "SIGINT",
+        _ = terminate.recv() => "SIGTERM",
+        _ = hangup.recv() => "SIGHUP",
+    }
+}
+
+#[cfg(not(unix))]
+async fn shutdown_signal() -> &'static str {
+    tokio::signal::ctrl_c()
+        .await
+        .expect("failed to listen for ctrl c event");
+    "ctrl-c"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn component(
+        name: &'static str,
+        stage: Stage,
+        stop_delay: Duration,
+        order: Arc<Mutex<Vec<&'static str>>>,
+    ) -> Component {
+        let token = CancellationToken::new();
+        let (done_sender, done) = oneshot::channel();
+        tokio::spawn({
+            let token = token.clone();
+            async move {
+                token.cancelled().await;
+                tokio::time::sleep(stop_delay).await;
+                order.lock().unwrap().push(name);
+                let _ = done_sender.send(());
+            }
+        });
+
+        Component::watch(name, stage, Duration::from_millis(100), token, done)
+    }
+
+    fn service(components: Vec<Component>) -> Service {
+        Service {
+            name: "test",
+            admin_addr: None,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            token: CancellationToken::new(),
+            components,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shutdown_follows_stage_order() {
+        let order = Arc::new(Mutex::new(vec![]));
+        let service = service(vec![
+            component("sender", Stage::Egress, Duration::ZERO, order.clone()),
+            component(
+                "sink",
+                Stage::Processing,
+                Duration::from_millis(50),
+                order.clone(),
+            ),
+            component(
+                "receiver",
+                Stage::Ingress,
+                Duration::from_millis(50),
+                order.clone(),
+            ),
+        ]);
+
+        let report = service.shutdown().await;
+
+        assert_eq!(*order.lock().unwrap(), vec!["receiver", "sink", "sender"]);
+        assert!(report.is_clean());
+        assert_eq!(report.entries.len(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shutdown_reports_timed_out_component() {
+        let order = Arc::new(Mutex::new(vec![]));
+        let service = service(vec![
+            component(
+                "stuck",
+                Stage::Processing,
+                Duration::from_secs(60),
+                order.clone(),
+            ),
+            component("sender", Stage::Egress, Duration::ZERO, order.clone()),
+        ]);
+
+        let report = service.shutdown().await;
+
+        assert!(!report.is_clean());
+        assert!(matches!(report.entries[0].outcome, Outcome::TimedOut(_)));
+        assert!(matches!(report.entries[1].outcome, Outcome::Stopped(_)));
+        assert_eq!(*order.lock().unwrap(), vec!["sender"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_shutdown_reports_early_exit() {
+        let order = Arc::new(Mutex::new(vec![]));
+        // exits before it is registered
+        let (done_sender, done) = oneshot::channel();
+        done_sender.send(()).unwrap();
+        let crashed = Component::watch(
+            "crashed",
+            Stage::Processing,
+            Duration::from_millis(100),
+            CancellationToken::new(),
+            done,
+        );
+        let service = service(vec![
+            crashed,
+            component("sender", Stage::Egress, Duration::ZERO, order.clone()),
+        ]);
+        tokio::task::yield_now().await;
+
+        let report = service.shutdown().await;
+
+        assert!(!report.is_clean());
+        assert!(matches!(report.entries[0].outcome, Outcome::ExitedEarly));
+        assert!(matches!(report.entries[1].outcome, Outcome::Stopped(_)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_service_token_is_cancelled_by_shutdown() {
+        let mut service = service(vec![]);
+        let registered = service.token();
+        let (done_sender, done) = oneshot::channel();
+        tokio::spawn({
+            let token = registered.clone();
+            async move {
+                token.cancelled().await;
+                let _ = done_sender.send(());
+            }
+        });
+        service.register_running("component", Stage::Processing, registered.clone(), done);
+        let unregistered = service.token();
+        assert!(!unregistered.is_cancelled());
+
+        let report = service.shutdown().await;
+
+        assert!(report.is_clean());
+        assert!(registered.is_cancelled() && unregistered.is_cancelled());
+    }
+}
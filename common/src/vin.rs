@@ -0,0 +1,342 @@
+use std::fmt::{Display, Formatter};
+use thiserror::Error;
+
+pub const VIN_LENGTH: usize = 17;
+const CHECK_DIGIT_POSITION: usize = 8;
+const MODEL_YEAR_POSITION: usize = 9;
+const PLANT_CODE_POSITION: usize = 10;
+const WEIGHTS: [u32; VIN_LENGTH] = [8, 7, 6, 5, 4, 3, 2, 10, 0, 9, 8, 7, 6, 5, 4, 3, 2];
+
+/// Order of characters in ISO 3780 country ranges, letters I, O, Q are never used.
+const RANGE_ORDER: &str = "ABCDEFGHJKLMNPRSTUVWXYZ1234567890";
+
+/// ISO 3780 country ranges of the first two characters, inclusive.
+const COUNTRIES: &[(char, char, char, &str)] = &[
+    ('A', 'A', 'H', "South Africa"),
+    ('J', 'A', '0', "Japan"),
+    ('K', 'L', 'R', "South Korea"),
+    ('L', 'A', '0', "China"),
+    ('M', 'A', 'E', "India"),
+    ('M', 'F', 'K', "Indonesia"),
+    ('M', 'L', 'R', "Thailand"),
+    ('N', 'L', 'R', "Turkey"),
+    ('P', 'L', 'R', "Malaysia"),
+    ('S', 'A', 'M', "United Kingdom"),
+    ('S', 'N', 'T', "Germany"),
+    ('S', 'U', 'Z', "Poland"),
+    ('T', 'A', 'H', "Switzerland"),
+    ('T', 'J', 'P', "Czech Republic"),
+    ('T', 'R', 'V', "Hungary"),
+    ('T', 'W', '1', "Portugal"),
+    ('U', 'U', '7', "Romania"),
+    ('V', 'A', 'E', "Austria"),
+    ('V', 'F', 'R', "France"),
+    ('V', 'S', 'W', "Spain"),
+    ('W', 'A', '0', "Germany"),
+    ('X', 'L', 'R', "Netherlands"),
+    ('X', '3', '0', "Russia"),
+    ('Y', 'A', 'E', "Belgium"),
+    ('Y', 'F', 'K', "Finland"),
+    ('Y', 'S', 'W', "Sweden"),
+    ('Z', 'A', 'R', "Italy"),
+    ('1', 'A', '0', "United States"),
+    ('2', 'A', '0', "Canada"),
+    ('3', 'A', 'W', "Mexico"),
+    ('4', 'A', '0', "United States"),
+    ('5', 'A', '0', "United States"),
+    ('6', 'A', 'W', "Australia"),
+    ('7', 'A', 'E', "New Zealand"),
+    ('8', 'A', 'E', "Argentina"),
+    ('9', 'A', 'E', "Brazil"),
+    ('9', '3', '9', "Brazil"),
+];
+
+/// World manufacturer identifiers of the makes most often seen in listings.
+const MANUFACTURERS: &[(&str, &str)] = &[
+    ("19U", "Acura"),
+    ("1C3", "Chrysler"),
+    ("1C4", "Chrysler"),
+    ("1C6", "Ram"),
+    ("1D7", "Dodge"),
+    ("1FA", "Ford"),
+    ("1FM", "Ford"),
+    ("1FT", "Ford"),
+    ("1G1", "Chevrolet"),
+    ("1G6", "Cadillac"),
+    ("1GC", "Chevrolet"),
+    ("1GK", "GMC"),
+    ("1GN", "Chevrolet"),
+    ("1GT", "GMC"),
+    ("1HG", "Honda"),
+    ("1J4", "Jeep"),
+    ("1LN", "Lincoln"),
+    ("1N4", "Nissan"),
+    ("1N6", "Nissan"),
+    ("1VW", "Volkswagen"),
+    ("2C3", "Chrysler"),
+    ("2FM", "Ford"),
+    ("2G1", "Chevrolet"),
+    ("2HG", "Honda"),
+    ("2HK", "Honda"),
+    ("2T1", "Toyota"),
+    ("2T3", "Toyota"),
+    ("3FA", "Ford"),
+    ("3GN", "Chevrolet"),
+    ("3N1", "Nissan"),
+    ("3VW", "Volkswagen"),
+    ("4S3", "Subaru"),
+    ("4S4", "Subaru"),
+    ("4T1", "Toyota"),
+    ("4T3", "Toyota"),
+    ("5FN", "Honda"),
+    ("5J6", "Honda"),
+    ("5N1", "Nissan"),
+    ("5NP", "Hyundai"),
+    ("5TD", "Toyota"),
+    ("5UX", "BMW"),
+    ("5XY", "Kia"),
+    ("5YJ", "Tesla"),
+    ("7SA", "Tesla"),
+    ("JA3", "Mitsubishi"),
+    ("JF1", "Subaru"),
+    ("JF2", "Subaru"),
+    ("JHM", "Honda"),
+    ("JM1", "Mazda"),
+    ("JN1", "Nissan"),
+    ("JN8", "Nissan"),
+    ("JT2", "Toyota"),
+    ("JTD", "Toyota"),
+    ("JTE", "Toyota"),
+    ("JTH", "Lexus"),
+    ("KM8", "Hyundai"),
+    ("KMH", "Hyundai"),
+    ("KNA", "Kia"),
+    ("KND", "Kia"),
+    ("SAJ", "Jaguar"),
+    ("SAL", "Land Rover"),
+    ("VF1", "Renault"),
+    ("VF3", "Peugeot"),
+    ("WAU", "Audi"),
+    ("WBA", "BMW"),
+    ("WBS", "BMW M"),
+    ("WDB", "Mercedes-Benz"),
+    ("WDD", "Mercedes-Benz"),
+    ("W1K", "Mercedes-Benz"),
+    ("WP0", "Porsche"),
+    ("WP1", "Porsche"),
+    ("WVW", "Volkswagen"),
+    ("WVG", "Volkswagen"),
+    ("YV1", "Volvo"),
+    ("ZFF", "Ferrari"),
+];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum VinError {
+    #[error("vin must have {VIN_LENGTH} characters, got: `{0}`")]
+    InvalidLength(usize),
+    #[error("vin contains invalid character `{0}` at position `{1}`")]
+    InvalidCharacter(char, usize),
+    #[error("vin check digit mismatch, expected: `{expected}`, actual: `{actual}`")]
+    CheckDigitMismatch { expected: char, actual: char },
+}
+
+/// Vehicle identification number with a valid ISO 3779 format,
+/// the check digit is verified separately as not every region uses it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vin(String);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VinDecode {
+    pub wmi: String,
+    pub manufacturer: Option<&'static str>,
+    pub country: Option<&'static str>,
+    pub model_year: Option<i32>,
+    pub plant_code: char,
+    pub check_digit_valid: bool,
+}
+
+impl Vin {
+    /// Parses already normalized input, see [`normalize`].
+    pub fn parse(input: &str) -> Result<Self, VinError> {
+        let len = input.chars().count();
+        if len != VIN_LENGTH {
+            return Err(VinError::InvalidLength(len));
+        }
+        if let Some((pos, c)) = input
+            .chars()
+            .enumerate()
+            .find(|(_, c)| transliterate(*c).is_none())
+        {
+            return Err(VinError::InvalidCharacter(c, pos + 1));
+        }
+        Ok(Self(input.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn check_digit(&self) -> char {
+        self.char_at(CHECK_DIGIT_POSITION)
+    }
+
+    pub fn expected_check_digit(&self) -> char {
+        let sum = self
+            .0
+            .chars()
+            .zip(WEIGHTS)
+            .map(|(c, w)| transliterate(c).unwrap_or(0) * w)
+            .sum::<u32>();
+        match sum % 11 {
+            10 => 'X',
+            d => char::from_digit(d, 10).unwrap_or('0'),
+        }
+    }
+
+    pub fn validate_check_digit(&self) -> Result<(), VinError> {
+        let (expected, actual) = (self.expected_check_digit(), self.check_digit());
+        if expected == actual {
+            Ok(())
+        } else {
+            Err(VinError::CheckDigitMismatch { expected, actual })
+        }
+    }
+
+    pub fn decode(&self) -> VinDecode {
+        let wmi = &self.0[..3];
+        VinDecode {
+            wmi: wmi.to_string(),
+            manufacturer: MANUFACTURERS
+                .iter()
+                .find(|(code, _)| *code == wmi)
+                .map(|(_, name)| *name),
+            country: country(self.char_at(0), self.char_at(1)),
+            model_year: self.model_year(),
+            plant_code: self.char_at(PLANT_CODE_POSITION),
+            check_digit_valid: self.validate_check_digit().is_ok(),
+        }
+    }
+
+    /// Model year codes repeat every 30 years, north american VINs tell the cycles
+    /// apart by a letter (2010 onwards) or a digit (until 2009) at position 7.
+    fn model_year(&self) -> Option<i32> {
+        let code = self.char_at(MODEL_YEAR_POSITION);
+        let offset = match code {
+            'A'..='H' => code as i32 - 'A' as i32,
+            'J'..='N' => code as i32 - 'J' as i32 + 8,
+            'P' => 13,
+            'R'..='T' => code as i32 - 'R' as i32 + 14,
+            'V'..='Y' => code as i32 - 'V' as i32 + 17,
+            '1'..='9' => code as i32 - '1' as i32 + 21,
+            _ => return None,
+        };
+        let cycle_start = if self.char_at(6).is_ascii_digit() {
+            1980
+        } else {
+            2010
+        };
+        Some(cycle_start + offset)
+    }
+
+    fn char_at(&self, pos: usize) -> char {
+        self.0.as_bytes()[pos] as char
+    }
+}
+
+impl Display for Vin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Normalizes user input: drops whitespace and dashes, uppercases and replaces
+/// letters I, O, Q, which are never used in a VIN, with the digits they are confused with.
+pub fn normalize(input: &str) -> String {
+    input
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| match c.to_ascii_uppercase() {
+            'I' => '1',
+            'O' | 'Q' => '0',
+            c => c,
+        })
+        .collect()
+}
+
+fn transliterate(c: char) -> Option<u32> {
+    match c {
+        '0'..='9' => c.to_digit(10),
+        'A'..='H' => Some(c as u32 - 'A' as u32 + 1),
+        'J'..='N' => Some(c as u32 - 'J' as u32 + 1),
+        'P' => Some(7),
+        'R' => Some(9),
+        'S'..='Z' => Some(c as u32 - 'S' as u32 + 2),
+        _ => None,
+    }
+}
+
+fn country(first: char, second: char) -> Option<&'static str> {
+    let pos = RANGE_ORDER.find(second)?;
+    COUNTRIES
+        .iter()
+        .find(|(c, start, end, _)| {
+            *c == first
+                && RANGE_ORDER.find(*start).is_some_and(|s| s <= pos)
+                && RANGE_ORDER.find(*end).is_some_and(|e| pos <= e)
+        })
+        .map(|(_, _, _, name)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_digit() {
+        let vin = Vin::parse("1M8GDM9AXKP042788").unwrap();
+        assert_eq!(vin.expected_check_digit(), 'X');
+        assert!(vin.validate_check_digit().is_ok());
+
+        let vin = Vin::parse("1M8GDM9A1KP042788").unwrap();
+        assert_eq!(
+            vin.validate_check_digit(),
+            Err(VinError::CheckDigitMismatch {
+                expected: 'X',
+                actual: '1'
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_format() {
+        assert_eq!(Vin::parse("1HGCM8263"), Err(VinError::InvalidLength(9)));
+        assert_eq!(
+            Vin::parse("1HGCM82633A0O4352"),
+            Err(VinError::InvalidCharacter('O', 13))
+        );
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(" 1hgcm82633a0o4352 "), "1HGCM82633A004352");
+        assert_eq!(normalize("5YJ-3E1EA-IKF317000"), "5YJ3E1EA1KF317000");
+    }
+
+    #[test]
+    fn test_decode() {
+        let decode = Vin::parse("1HGCM82633A004352").unwrap().decode();
+        assert_eq!(decode.wmi, "1HG");
+        assert_eq!(decode.manufacturer, Some("Honda"));
+        assert_eq!(decode.country, Some("United States"));
+        assert_eq!(decode.model_year, Some(2003));
+        assert_eq!(decode.plant_code, 'A');
+        assert!(decode.check_digit_valid);
+
+        let decode = Vin::parse("5YJ3E1EA1KF317000").unwrap().decode();
+        assert_eq!(decode.manufacturer, Some("Tesla"));
+        assert_eq!(decode.model_year, Some(2019));
+
+        let decode = Vin::parse("WBA3A5C51CF256651").unwrap().decode();
+        assert_eq!(decode.country, Some("Germany"));
+    }
+}
@@ -98,14 +98,20 @@ export interface Vehicle {
     lotImages: [VehicleImage]
 }
 
+export interface VehicleImageVariant {
+    name: string
+    format: string
+    mimeType: string
+    width: number | null
+    height: number | null
+    size: number | null
+    url: string
+}
+
 export interface VehicleImage {
-    highResBucketKey: string | null
-    highResMimeType: string | null
     sequenceNumber: number
-    standardBucketKey: string | null
-    standardMimeType: string | null
-    thumbnailBucketKey: string | null
-    thumbnailMimeType: string | null
+    // smallest first
+    variants: VehicleImageVariant[]
 }
 
 async function fetchVehicleByVin(vin: string): Promise<Vehicle | null> {
@@ -7,15 +7,23 @@ import {Button} from "@/components/ui/button"
 import {ChevronLeft, ChevronRight, Maximize2} from "lucide-react"
 import {Dialog, DialogContent, DialogTitle} from "@/components/ui/dialog"
 import {VisuallyHidden} from "@radix-ui/react-visually-hidden"
-import {VehicleImage} from "@/app/vin/[vin]/page";
+import {VehicleImage, VehicleImageVariant} from "@/app/vin/[vin]/page";
 
 
 interface VehicleGalleryProps {
     images: VehicleImage[]
 }
 
-function fullImageUrl(key: string | null): string {
-    return key ? `https://cdn.carauctions24.eu/${key}` : "/placeholder.svg"
+function largest(image: VehicleImage): VehicleImageVariant | undefined {
+    return image.variants[image.variants.length - 1]
+}
+
+function smallest(image: VehicleImage): VehicleImageVariant | undefined {
+    return image.variants[0]
+}
+
+function imageUrl(variant: VehicleImageVariant | undefined): string {
+    return variant?.url ?? "/placeholder.svg"
 }
 
 export function VehicleGallery({images}: VehicleGalleryProps) {
@@ -44,9 +52,9 @@ export function VehicleGallery({images}: VehicleGalleryProps) {
             <Card className="relative group">
                 <div className="aspect-video relative bg-muted overflow-hidden rounded-lg">
                     {
-                        images[selectedIndex].highResMimeType?.startsWith("video") ? (
+                        largest(images[selectedIndex])?.mimeType.startsWith("video") ? (
                             <video
-                                src={fullImageUrl(images[selectedIndex].highResBucketKey)}
+                                src={imageUrl(largest(images[selectedIndex]))}
                                 autoPlay
                                 loop
                                 playsInline
@@ -54,7 +62,7 @@ export function VehicleGallery({images}: VehicleGalleryProps) {
                             />
                         ) : (
                             <Image
-                                src={fullImageUrl(images[selectedIndex].highResBucketKey)}
+                                src={imageUrl(largest(images[selectedIndex]))}
                                 alt={`Vehicle image ${selectedIndex + 1}`}
                                 fill
                                 className="object-cover"
@@ -117,7 +125,7 @@ export function VehicleGallery({images}: VehicleGalleryProps) {
                             }`}
                         >
                             <Image
-                                src={fullImageUrl(image.thumbnailBucketKey)}
+                                src={imageUrl(smallest(image))}
                                 alt={`Thumbnail ${index + 1}`}
                                 fill
                                 className="object-cover"
@@ -137,7 +145,7 @@ export function VehicleGallery({images}: VehicleGalleryProps) {
                     <div className="relative w-full h-full flex items-center justify-center">
                         <div className="relative w-full h-full">
                             <Image
-                                src={fullImageUrl(images[selectedIndex].highResBucketKey)}
+                                src={imageUrl(largest(images[selectedIndex]))}
                                 alt={`Vehicle image ${selectedIndex + 1}`}
                                 fill
                                 className="object-contain"
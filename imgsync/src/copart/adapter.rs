@@ -76,6 +76,7 @@ mod tests {
                 &MsgIn::LotImages(Ok(LotImagesResponse {
                     lot_number: 69,
                     response: LotImagesVector(vec![]),
+                    force: false,
                 })),
                 "copart_response_synced_images",
             )
@@ -102,6 +103,8 @@ mod tests {
             .send(MsgOut::SyncedImages(Ok(SyncedImagesResponse {
                 lot_number: 69,
                 response: SyncedImagesVector(vec![]),
+                skipped: 0,
+                fetched: 0,
             })))
             .await?;
 
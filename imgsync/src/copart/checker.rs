@@ -0,0 +1,238 @@
+use crate::copart::requester::{highest_resolution_url, CopartRequester};
+use async_trait::async_trait;
+use common::bucket::store::ObjectStore;
+use common::io::copart::{ImageSyncStatus, LotImages, LotImagesVector, LotNumber, SyncedImages};
+use common::io::provider::LotId;
+use common::persistence::repo::{LotImageDetails, LotImageRepo};
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{debug, error, instrument, warn};
+
+#[async_trait]
+pub trait CopartSyncCheckerExt {
+    /// Splits the images of a lot into unchanged ones, sent back without variants so the
+    /// stored ones are kept, and the ones to download.
+    async fn split_unchanged(
+        &self,
+        lot_number: LotNumber,
+        images: LotImagesVector,
+    ) -> (Vec<SyncedImages>, LotImagesVector);
+}
+
+/// Compares lot images with their stored rows and bucket objects. An image is unchanged
+/// when its source url is the stored one, every stored variant is in the bucket and the
+/// source still matches the stored `ETag` or size. Images synced before validators and
+/// hashes were stored are fetched once more to get them.
+pub struct CopartSyncChecker<R: LotImageRepo> {
+    repo: R,
+    requester: CopartRequester,
+    store: Arc<dyn ObjectStore>,
+}
+
+impl<R: LotImageRepo> CopartSyncChecker<R> {
+    pub fn new(repo: R, requester: CopartRequester, store: Arc<dyn ObjectStore>) -> Self {
+        Self {
+            repo,
+            requester,
+            store,
+        }
+    }
+
+    async fn unchanged(&self, img: &LotImages, stored: &LotImageDetails) -> bool {
+        let Some(url) = highest_resolution_url(img) else {
+            return false;
+        };
+        let image = &stored.lot_image;
+        if image.source_url.as_deref() != Some(url.as_str()) || stored.variants.is_empty() {
+            return false;
+        }
+
+        let objects = futures::future::join_all(
+            stored
+                .variants
+                .iter()
+                .map(|v| self.store.head(&v.bucket_key)),
+        )
+        .await;
+        if !objects.iter().all(|o| matches!(o, Ok(Some(_)))) {
+            debug!(url, "stored variant missing in bucket");
+            return false;
+        }
+
+        if image.perceptual_hash.is_none()
+            || (image.source_etag.is_none() && image.source_size.is_none())
+        {
+            debug!(url, "stored image has no validators or hash");
+            return false;
+        }
+        match self.requester.source_validators(&url).await {
+            Ok(current) => current.matches(image.source_etag.as_deref(), image.source_size),
+            Err(e) => {
+                warn!(download_error = ?e, url, "source validators request failed");
+                false
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<R: LotImageRepo> CopartSyncCheckerExt for CopartSyncChecker<R> {
+    #[instrument(skip(self, images))]
+    async fn split_unchanged(
+        &self,
+        lot_number: LotNumber,
+        images: LotImagesVector,
+    ) -> (Vec<SyncedImages>, LotImagesVector) {
+        let stored = match self.repo.lot_images(LotId::copart(lot_number)).await {
+            Ok(stored) => stored,
+            Err(e) => {
+                error!(repo_error = ?e, "loading stored lot images failed, syncing all");
+                return (vec![], images);
+            }
+        };
+        let stored = stored
+            .into_iter()
+            .map(|i| {
+                (
+                    (i.lot_image.sequence_number, i.lot_image.image_type.clone()),
+                    i,
+                )
+            })
+            .collect::<HashMap<_, _>>();
+
+        let checked = futures::stream::iter(images.0)
+            .map(async |img| {
+                let unchanged = match stored.get(&(img.sequence_number, img.image_type.clone())) {
+                    Some(stored) => self.unchanged(&img, stored).await,
+                    None => false,
+                };
+                (img, unchanged)
+            })
+            .buffer_unordered(4)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut skipped = vec![];
+        let mut pending = vec![];
+        for (img, unchanged) in checked {
+            match unchanged {
+                true => skipped.push(SyncedImages {
+                    source_url: highest_resolution_url(&img),
+                    sequence_number: img.sequence_number,
+                    image_type: img.image_type,
+                    variants: vec![],
+                    perceptual_hash: None,
+                    source_etag: None,
+                    source_size: None,
+                    status: ImageSyncStatus::Unchanged,
+                    error: None,
+                }),
+                false => pending.push(img),
+            }
+        }
+        (skipped, LotImagesVector(pending))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::bucket::content_key;
+    use common::bucket::testing::temp_fs_store;
+    use common::persistence::models::copart::NewLotImage;
+    use common::persistence::testing::{fixtures, TestDb};
+    use tokio_util::bytes::Bytes;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn lot_images(mock_srv: &MockServer, sequence_number: i32) -> LotImages {
+        LotImages {
+            thumbnail_url: None,
+            full_url: None,
+            high_res_url: Some(format!("{}/{sequence_number}.jpg", mock_srv.uri())),
+            sequence_number,
+            image_type: "IMAGE".to_string(),
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_split_unchanged() -> Result<(), Box<dyn std::error::Error>> {
+        let mock_srv = MockServer::start().await;
+        for (sequence_number, etag) in [(1, "\"v1\""), (2, "\"v2\""), (4, "\"v1\"")] {
+            Mock::given(method("HEAD"))
+                .and(path(format!("/{sequence_number}.jpg")))
+                .respond_with(ResponseTemplate::new(200).insert_header("ETag", etag))
+                .mount(&mock_srv)
+                .await;
+        }
+
+        let db = TestDb::start().await?;
+        db.seed(vec![fixtures::lot_vehicle(1)]).await?;
+        let stored = |sequence_number| NewLotImage {
+            source_url: Some(format!("{}/{sequence_number}.jpg", mock_srv.uri())),
+            source_etag: Some("\"v1\"".to_string()),
+            perceptual_hash: Some(sequence_number as i64),
+            ..fixtures::lot_image(1, sequence_number)
+        };
+        db.seed_images(vec![
+            // unchanged
+            stored(1),
+            // re-encoded by the provider
+            stored(2),
+            // synced before validators and hashes were stored
+            NewLotImage {
+                source_etag: None,
+                perceptual_hash: None,
+                ..stored(3)
+            },
+            // a variant is missing in the bucket
+            stored(4),
+        ])
+        .await?;
+
+        let (_dir, store) = temp_fs_store();
+        let store = Arc::new(store);
+        for sequence_number in 1..=4 {
+            for width in [320, 800, 1600] {
+                if (sequence_number, width) == (4, 1600) {
+                    continue;
+                }
+                store
+                    .put(
+                        &content_key(&format!("1/{sequence_number}/w{width}")),
+                        Bytes::from_static(b"blob"),
+                        "image/webp",
+                    )
+                    .await?;
+            }
+        }
+        let checker = CopartSyncChecker::new(db.repo(), CopartRequester::new(), store);
+
+        let (skipped, pending) = checker
+            .split_unchanged(
+                1,
+                LotImagesVector((1..=5).map(|seq| lot_images(&mock_srv, seq)).collect()),
+            )
+            .await;
+
+        let skipped_images = skipped;
+        let mut skipped = skipped_images
+            .iter()
+            .map(|s| s.sequence_number)
+            .collect::<Vec<_>>();
+        skipped.sort();
+        assert_eq!(skipped, vec![1]);
+        assert!(skipped_images
+            .iter()
+            .all(|s| s.status == ImageSyncStatus::Unchanged));
+        let mut pending = pending
+            .0
+            .iter()
+            .map(|p| p.sequence_number)
+            .collect::<Vec<_>>();
+        pending.sort();
+        assert_eq!(pending, vec![2, 3, 4, 5]);
+        Ok(())
+    }
+}
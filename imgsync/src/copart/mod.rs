@@ -1,4 +1,5 @@
 pub mod adapter;
+pub mod checker;
 pub mod requester;
 pub mod sink;
 pub mod uploader;
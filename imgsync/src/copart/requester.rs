@@ -1,26 +1,74 @@
+use crate::processor::ensure_complete;
+use crate::spool::SpooledBlob;
 use async_trait::async_trait;
-use common::io::copart::LotImagesVector;
+use common::io::copart::{ImageSyncError, LotImages, LotImagesVector};
 use common::{count_some_none, retry_async};
 use futures::StreamExt;
-use reqwest::IntoUrl;
+use image::{ImageFormat, ImageReader};
+use reqwest::header::{HeaderMap, CONTENT_LENGTH, ETAG};
+use reqwest::{IntoUrl, StatusCode};
 use std::fmt::{Debug, Formatter};
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
+use thiserror::Error;
 use tokio::sync::Semaphore;
-use tokio_util::bytes::Bytes;
-use tracing::{error, info, instrument};
+use tracing::{debug, error, info, instrument};
+
+/// Larger sources are rejected, copart's high resolution photos are a few MiB
+pub const MAX_SOURCE_BYTES: u64 = 32 * 1024 * 1024;
 
 pub struct LotImageBlobsVector(pub Vec<LotImageBlobs>);
 
+/// Only the highest resolution available is downloaded, smaller variants are generated from it.
 pub struct LotImageBlobs {
-    pub standard: Option<Bytes>,
-    pub high_res: Option<Bytes>,
-    pub thumbnail: Option<Bytes>,
-    pub standard_url: Option<String>,
-    pub high_res_url: Option<String>,
-    pub thumbnail_url: Option<String>,
+    pub blob: Option<SpooledBlob>,
+    /// The url the blob was downloaded from
+    pub url: Option<String>,
+    /// `ETag` the blob was served with
+    pub etag: Option<String>,
     pub sequence_number: i32,
     pub image_type: String,
+    /// Why a listed url has no blob, see [`DownloadError::sync_error`]
+    pub error: Option<ImageSyncError>,
+}
+
+/// A source spooled with the `ETag` it was served with, its format is detected from its
+/// magic bytes and its dimensions are decoded from its header.
+pub struct DownloadedSource {
+    pub blob: SpooledBlob,
+    pub etag: Option<String>,
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// What the provider tells about a source without sending it, compared with the
+/// validators stored when the variants were generated.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SourceValidators {
+    pub etag: Option<String>,
+    /// `Content-Length`
+    pub size: Option<i64>,
+}
+
+impl SourceValidators {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+        Self {
+            etag: header(ETAG).map(str::to_string),
+            size: header(CONTENT_LENGTH).and_then(|v| v.parse().ok()),
+        }
+    }
+
+    /// `ETag`s are compared when both are known, otherwise sizes. A source with neither
+    /// known counts as changed.
+    pub fn matches(&self, etag: Option<&str>, size: Option<i64>) -> bool {
+        match (self.etag.as_deref(), etag) {
+            (Some(current), Some(stored)) => current == stored,
+            _ => self.size.is_some() && self.size == size,
+        }
+    }
 }
 
 /// Do not wrap `CopartRequester` in a [`Rc`] or [`Arc`]
@@ -29,6 +77,47 @@ pub struct LotImageBlobs {
 pub struct CopartRequester {
     http: reqwest::Client,
     usage_permit: Arc<Semaphore>,
+    max_source_bytes: u64,
+}
+
+#[derive(Error, Debug)]
+pub enum DownloadError {
+    #[error("http error: `{0}`")]
+    Http(#[from] reqwest::Error),
+    #[error("spool error: `{0}`")]
+    Spool(#[from] std::io::Error),
+    #[error("unexpected status: `{0}`")]
+    Status(StatusCode),
+    #[error("empty body")]
+    Empty,
+    #[error("body exceeds `{0}` bytes")]
+    TooLarge(u64),
+    /// Magic bytes of no format `imgsync` decodes, e.g. an html error page
+    #[error("unsupported format: `{0:?}`")]
+    UnsupportedFormat(Option<ImageFormat>),
+    #[error("decode error: `{0}`")]
+    Decode(#[from] image::ImageError),
+}
+
+impl DownloadError {
+    /// Only failures of the transfer are retried, a rejected body is served again as it is.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Http(_) | Self::Spool(_) => true,
+            Self::Status(status) => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            Self::Empty | Self::TooLarge(_) | Self::UnsupportedFormat(_) | Self::Decode(_) => false,
+        }
+    }
+
+    pub fn sync_error(&self) -> ImageSyncError {
+        match self {
+            Self::UnsupportedFormat(_) | Self::Decode(_) => ImageSyncError::Decode,
+            _ if self.is_transient() => ImageSyncError::Download,
+            _ => ImageSyncError::Rejected,
+        }
+    }
 }
 
 #[async_trait]
@@ -41,11 +130,59 @@ impl CopartRequester {
         Self {
             http: reqwest::Client::new(),
             usage_permit: Arc::new(Semaphore::new(32)),
+            max_source_bytes: MAX_SOURCE_BYTES,
         }
     }
 
-    async fn download_content(&self, url: impl IntoUrl) -> Result<Bytes, reqwest::Error> {
-        Ok(self.http.get(url).send().await?.bytes().await?)
+    pub fn with_max_source_bytes(mut self, max_source_bytes: u64) -> Self {
+        self.max_source_bytes = max_source_bytes;
+        self
+    }
+
+    /// The body is spooled to a file as it arrives, a blob is never held in memory whole.
+    /// Error statuses, empty or oversized bodies and bodies which are not a decodable
+    /// image are rejected. Pixels are decoded once, by the processor, only the header and
+    /// the completeness of a jpeg are checked here.
+    pub async fn download_content(
+        &self,
+        url: impl IntoUrl,
+    ) -> Result<DownloadedSource, DownloadError> {
+        let response = self.http.get(url).send().await?;
+        if !response.status().is_success() {
+            return Err(DownloadError::Status(response.status()));
+        }
+        let max = self.max_source_bytes;
+        if response.content_length().is_some_and(|len| len > max) {
+            return Err(DownloadError::TooLarge(max));
+        }
+
+        let etag = SourceValidators::from_headers(response.headers()).etag;
+        // the length is not always announced, the body is counted as it arrives
+        let mut received = 0u64;
+        let blob = SpooledBlob::from_stream(response.bytes_stream().map(move |c| {
+            let c = c?;
+            received += c.len() as u64;
+            match received > max {
+                true => Err(DownloadError::TooLarge(max)),
+                false => Ok(c),
+            }
+        }))
+        .await?;
+        if blob.size() == 0 {
+            return Err(DownloadError::Empty);
+        }
+
+        let path = blob.path().to_path_buf();
+        let (format, width, height) = tokio::task::spawn_blocking(move || probe_image(&path))
+            .await
+            .map_err(std::io::Error::other)??;
+        Ok(DownloadedSource {
+            blob,
+            etag,
+            format,
+            width,
+            height,
+        })
     }
 
     async fn download_content_with_retry(
@@ -53,8 +190,24 @@ impl CopartRequester {
         url: impl IntoUrl + Clone,
         timeout: Duration,
         tries: usize,
-    ) -> Result<Bytes, reqwest::Error> {
-        retry_async(timeout, tries, || self.download_content(url.to_owned())).await
+    ) -> Result<DownloadedSource, DownloadError> {
+        // rejected bodies end the retries as an `Ok`
+        retry_async(timeout, tries, || async {
+            match self.download_content(url.to_owned()).await {
+                Err(e) if !e.is_transient() => Ok(Err(e)),
+                downloaded => downloaded.map(Ok),
+            }
+        })
+        .await?
+    }
+
+    /// Validators of the source from a `HEAD` request, its body is not sent.
+    pub async fn source_validators(
+        &self,
+        url: impl IntoUrl,
+    ) -> Result<SourceValidators, DownloadError> {
+        let response = self.http.head(url).send().await?.error_for_status()?;
+        Ok(SourceValidators::from_headers(response.headers()))
     }
 }
 
@@ -65,14 +218,7 @@ impl CopartRequesterExt for CopartRequester {
         let sample_cmds = cmds
             .0
             .iter()
-            .flat_map(|li| {
-                [
-                    li.full_url.to_owned(),
-                    li.thumbnail_url.to_owned(),
-                    li.full_url.to_owned(),
-                ]
-            })
-            .filter_map(|url| url)
+            .filter_map(highest_resolution_url)
             .take(3)
             .collect::<Vec<String>>();
         info!(sample_lot_images = ?sample_cmds, "sample lot images");
@@ -82,37 +228,40 @@ impl CopartRequesterExt for CopartRequester {
                 .download_content_with_retry(url, Duration::from_millis(300), 5)
                 .await
             {
-                Ok(b) => Some(b),
+                Ok(d) => {
+                    debug!(url, format = ?d.format, d.width, d.height, "downloaded image");
+                    Ok(Some(d))
+                }
                 Err(e) => {
-                    error!(download_error = ?e, "download image blobs failed");
-                    None
+                    error!(download_error = ?e, url, "download image blobs failed");
+                    Err(e.sync_error())
                 }
             },
-            None => None,
+            None => Ok(None),
         };
 
         let blobs = LotImageBlobsVector(
             futures::stream::iter(cmds.0)
                 .map(async |img| {
-                    // permits are open per 3 urls, 4 concurrent lot images and 64 semaphore limit
-                    // thus maximum socket usage is 3 * 4 * 64 = 768
+                    // permits are open per url, 4 concurrent lot images and 32 semaphore limit
+                    // thus maximum socket usage is 4 * 32 = 128
+                    let url = highest_resolution_url(&img);
                     let _permit = unsafe { self.usage_permit.acquire().await.unwrap_unchecked() };
-                    let (standard, thumbnail, high_res) = tokio::join!(
-                        option_download_content(&img.full_url),
-                        option_download_content(&img.thumbnail_url),
-                        option_download_content(&img.high_res_url)
-                    );
+                    let downloaded = option_download_content(&url).await;
                     drop(_permit);
 
+                    let (blob, etag, error) = match downloaded {
+                        Ok(Some(d)) => (Some(d.blob), d.etag, None),
+                        Ok(None) => (None, None, None),
+                        Err(e) => (None, None, Some(e)),
+                    };
                     LotImageBlobs {
-                        standard,
-                        thumbnail,
-                        high_res,
-                        standard_url: img.full_url,
-                        thumbnail_url: img.thumbnail_url,
-                        high_res_url: img.high_res_url,
+                        blob,
+                        url,
+                        etag,
                         sequence_number: img.sequence_number,
                         image_type: img.image_type,
+                        error,
                     }
                 })
                 .buffer_unordered(4)
@@ -125,23 +274,38 @@ impl CopartRequesterExt for CopartRequester {
     }
 }
 
+pub(crate) fn highest_resolution_url(img: &LotImages) -> Option<String> {
+    img.high_res_url
+        .as_ref()
+        .or(img.full_url.as_ref())
+        .or(img.thumbnail_url.as_ref())
+        .cloned()
+}
+
+/// Format from the magic bytes and dimensions from the header of a spooled source, the
+/// extension of its url is not trusted.
+fn probe_image(path: &Path) -> Result<(ImageFormat, u32, u32), DownloadError> {
+    let reader = ImageReader::open(path)?.with_guessed_format()?;
+    let format = match reader.format() {
+        Some(format @ (ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP)) => format,
+        other => return Err(DownloadError::UnsupportedFormat(other)),
+    };
+    ensure_complete(path, Some(format))?;
+    let (width, height) = reader.into_dimensions()?;
+    Ok((format, width, height))
+}
+
 impl Debug for LotImageBlobsVector {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        let (some_thumbnail, none_thumbnail) = count_some_none(&self.0, |i| i.thumbnail.as_deref());
-        let (some_high, none_high) = count_some_none(&self.0, |i| i.high_res.as_deref());
-        let (some_std, none_std) = count_some_none(&self.0, |i| i.standard.as_deref());
+        let (some, none) = count_some_none(&self.0, |i| i.blob.as_ref());
 
-        write!(
-            f,
-            "thumbnail {{some: {some_thumbnail}, none: {none_thumbnail}}}, high_res {{some: {some_high}, none: {none_high}}}, standard {{some: {some_std}, none: {none_std}}}",
-        )
+        write!(f, "blob {{some: {some}, none: {none}}}")
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use common::io::copart::LotImages;
     use tokio::time::{Duration, Instant};
     use uuid::Uuid;
     use wiremock::matchers::{method, path};
@@ -157,6 +321,27 @@ mod tests {
         mock_server
     }
 
+    /// A jpeg cut short a few bytes into the entropy coded data of its scan
+    fn truncated_jpeg() -> Vec<u8> {
+        let mut jpeg = std::io::Cursor::new(vec![]);
+        image::RgbImage::from_fn(64, 48, |x, y| image::Rgb([x as u8 * 4, y as u8 * 5, 128]))
+            .write_to(&mut jpeg, ImageFormat::Jpeg)
+            .unwrap();
+        let mut jpeg = jpeg.into_inner();
+        let sos = jpeg.windows(2).position(|w| w == [0xFF, 0xDA]).unwrap();
+        let length = u16::from_be_bytes([jpeg[sos + 2], jpeg[sos + 3]]) as usize;
+        jpeg.truncate(sos + 2 + length + 16);
+        jpeg
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut png = std::io::Cursor::new(vec![]);
+        image::RgbImage::new(width, height)
+            .write_to(&mut png, ImageFormat::Png)
+            .unwrap();
+        png.into_inner()
+    }
+
     fn random_mock_url(mock_srv: &MockServer) -> String {
         format!(
             "{}/img?_nocache={}",
@@ -197,4 +382,181 @@ mod tests {
             true
         );
     }
+
+    #[tokio::test]
+    async fn test_downloads_highest_resolution_only() {
+        let mock_srv = MockServer::start().await;
+        for (route, calls, width) in [("/high", 1, 3), ("/full", 0, 2), ("/thumb", 1, 1)] {
+            Mock::given(method("GET"))
+                .and(path(route))
+                .respond_with(ResponseTemplate::new(200).set_body_bytes(png(width, 1)))
+                .expect(calls)
+                .mount(&mock_srv)
+                .await;
+        }
+        let requester = CopartRequester::new();
+        let url = |route: &str| Some(format!("{}{route}", mock_srv.uri()));
+
+        let blobs = requester
+            .download_images(LotImagesVector(vec![
+                LotImages {
+                    thumbnail_url: url("/thumb"),
+                    full_url: url("/full"),
+                    high_res_url: url("/high"),
+                    sequence_number: 1,
+                    image_type: "jpg".to_string(),
+                },
+                LotImages {
+                    thumbnail_url: url("/thumb"),
+                    full_url: None,
+                    high_res_url: None,
+                    sequence_number: 2,
+                    image_type: "jpg".to_string(),
+                },
+            ]))
+            .await;
+
+        let mut blobs = blobs.0;
+        blobs.sort_by_key(|b| b.sequence_number);
+        assert_eq!(blobs[0].url, url("/high"));
+        assert_eq!(
+            blobs[0].blob.as_ref().unwrap().read().await.unwrap(),
+            png(3, 1)
+        );
+        assert_eq!(blobs[1].url, url("/thumb"));
+    }
+
+    #[tokio::test]
+    async fn test_source_validators() {
+        let mock_srv = MockServer::start().await;
+        Mock::given(method("HEAD"))
+            .and(path("/img"))
+            .respond_with(
+                // the body is not sent in reply to `HEAD`, only its length
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"v1\"")
+                    .set_body_bytes(vec![0u8; 4096]),
+            )
+            .mount(&mock_srv)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/img"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("ETag", "\"v1\"")
+                    .set_body_bytes(png(4, 2)),
+            )
+            .mount(&mock_srv)
+            .await;
+        let requester = CopartRequester::new();
+        let url = format!("{}/img", mock_srv.uri());
+
+        let validators = requester.source_validators(&url).await.unwrap();
+        assert_eq!(validators.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(validators.size, Some(4096));
+        assert!(validators.matches(Some("\"v1\""), None));
+        assert!(!validators.matches(Some("\"v2\""), Some(4096)));
+        // without a stored etag the sizes decide
+        assert!(validators.matches(None, Some(4096)));
+        assert!(!SourceValidators::default().matches(None, None));
+
+        let downloaded = requester.download_content(&url).await.unwrap();
+        assert_eq!(downloaded.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(downloaded.blob.read().await.unwrap(), png(4, 2));
+        assert_eq!(downloaded.format, ImageFormat::Png);
+        assert_eq!((downloaded.width, downloaded.height), (4, 2));
+    }
+
+    #[tokio::test]
+    async fn test_bad_payloads_are_rejected() {
+        let mock_srv = MockServer::start().await;
+        let html = b"<!DOCTYPE html><html><body>Access denied</body></html>".as_slice();
+        let mut truncated = png(4, 2);
+        truncated.truncate(12);
+        let mut oversized = png(4, 2);
+        oversized.resize(4096, 0);
+        for (route, template) in [
+            (
+                "/forbidden",
+                ResponseTemplate::new(403).set_body_bytes(html),
+            ),
+            ("/html", ResponseTemplate::new(200).set_body_bytes(html)),
+            ("/empty", ResponseTemplate::new(200)),
+            (
+                "/truncated",
+                ResponseTemplate::new(200).set_body_bytes(truncated),
+            ),
+            (
+                "/truncated_jpeg",
+                ResponseTemplate::new(200).set_body_bytes(truncated_jpeg()),
+            ),
+            (
+                "/oversized",
+                ResponseTemplate::new(200).set_body_bytes(oversized),
+            ),
+            ("/unavailable", ResponseTemplate::new(503)),
+        ] {
+            Mock::given(method("GET"))
+                .and(path(route))
+                .respond_with(template)
+                .mount(&mock_srv)
+                .await;
+        }
+        let requester = CopartRequester::new().with_max_source_bytes(1024);
+        let download = async |route: &str| {
+            requester
+                .download_content(format!("{}{route}", mock_srv.uri()))
+                .await
+                .err()
+                .unwrap()
+        };
+
+        let forbidden = download("/forbidden").await;
+        assert!(matches!(forbidden, DownloadError::Status(s) if s == StatusCode::FORBIDDEN));
+        assert!(!forbidden.is_transient());
+        assert_eq!(forbidden.sync_error(), ImageSyncError::Rejected);
+        let html = download("/html").await;
+        assert!(matches!(html, DownloadError::UnsupportedFormat(None)));
+        assert_eq!(html.sync_error(), ImageSyncError::Decode);
+        assert!(matches!(download("/empty").await, DownloadError::Empty));
+        assert!(matches!(
+            download("/truncated").await,
+            DownloadError::Decode(_)
+        ));
+        // the header is valid, the scan is not all there
+        let truncated_jpeg = download("/truncated_jpeg").await;
+        assert!(matches!(truncated_jpeg, DownloadError::Decode(_)));
+        assert_eq!(truncated_jpeg.sync_error(), ImageSyncError::Decode);
+        let oversized = download("/oversized").await;
+        assert!(matches!(oversized, DownloadError::TooLarge(1024)));
+        assert_eq!(oversized.sync_error(), ImageSyncError::Rejected);
+        let unavailable = download("/unavailable").await;
+        assert!(unavailable.is_transient());
+        assert_eq!(unavailable.sync_error(), ImageSyncError::Download);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_payload_is_not_retried() {
+        let mock_srv = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/html"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(b"<html></html>".as_slice()))
+            .expect(1)
+            .mount(&mock_srv)
+            .await;
+        let requester = CopartRequester::new();
+
+        let blobs = requester
+            .download_images(LotImagesVector(vec![LotImages {
+                thumbnail_url: None,
+                full_url: None,
+                high_res_url: Some(format!("{}/html", mock_srv.uri())),
+                sequence_number: 1,
+                image_type: "jpg".to_string(),
+            }]))
+            .await;
+
+        assert!(blobs.0[0].blob.is_none());
+        assert_eq!(blobs.0[0].error, Some(ImageSyncError::Decode));
+    }
 }
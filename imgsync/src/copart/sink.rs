@@ -1,12 +1,16 @@
-use crate::copart::requester::{CopartRequesterExt, LotImageBlobsVector};
+use crate::copart::checker::CopartSyncCheckerExt;
+use crate::copart::requester::CopartRequesterExt;
 use crate::copart::uploader::CopartUploaderExt;
-use common::io::copart::{CopartResponse, LotImagesResponse, LotNumber, SyncedImagesResponse};
+use crate::processor::ImageProcessor;
+use common::io::copart::{
+    CopartResponse, ImageSyncStatus, LotImagesResponse, SyncedImagesResponse,
+};
 use common::io::error::GeneralError;
 use std::sync::Arc;
 use tokio::sync::mpsc::{Receiver, Sender};
-use tokio::sync::{Notify, Semaphore};
+use tokio::sync::{oneshot, Semaphore};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, instrument, warn};
+use tracing::{debug, error, info, instrument, warn};
 
 pub type MsgIn = CopartResponse;
 pub type MsgOut = CopartResponse;
@@ -16,29 +20,30 @@ pub struct ExternalSignaling {
     pub response_receiver: Receiver<MsgOut>,
 }
 
-pub struct CopartImageSyncSink<R: CopartRequesterExt, U: CopartUploaderExt> {
+pub struct CopartImageSyncSink<C: CopartSyncCheckerExt, R: CopartRequesterExt, U: CopartUploaderExt>
+{
     cmd_receiver: Receiver<MsgIn>,
-    msg_handler: Arc<SingleMsgHandler<R, U>>,
+    msg_handler: Arc<SingleMsgHandler<C, R, U>>,
     usage_permits: Arc<Semaphore>,
 }
 
-struct SingleMsgHandler<R: CopartRequesterExt, U: CopartUploaderExt> {
+struct SingleMsgHandler<C: CopartSyncCheckerExt, R: CopartRequesterExt, U: CopartUploaderExt> {
+    checker: C,
     requester: R,
+    processor: ImageProcessor,
     uploader: U,
     response_sender: Sender<MsgOut>,
 }
 
-pub struct LotImageBlobsResponse {
-    pub lot_number: LotNumber,
-    pub response: LotImageBlobsVector,
-}
-
-impl<R: CopartRequesterExt, U: CopartUploaderExt> SingleMsgHandler<R, U> {
+impl<C: CopartSyncCheckerExt, R: CopartRequesterExt, U: CopartUploaderExt>
+    SingleMsgHandler<C, R, U>
+{
     async fn handle_message(&self, msg: MsgIn) {
         match msg {
             MsgIn::LotImages(resp) => self.handle_lot_images(resp).await,
             MsgIn::LotSearch(_) => warn!(""),
             MsgIn::SyncedImages(_) => warn!(""),
+            MsgIn::AuctionResult(_) => warn!(""),
         }
     }
 
@@ -46,16 +51,41 @@ impl<R: CopartRequesterExt, U: CopartUploaderExt> SingleMsgHandler<R, U> {
     async fn handle_lot_images(&self, incoming_msg: Result<LotImagesResponse, GeneralError>) {
         match incoming_msg {
             Ok(images) => {
-                let blobs = self.requester.download_images(images.response).await;
-                let blobs_response = LotImageBlobsResponse {
-                    lot_number: images.lot_number,
-                    response: blobs,
+                let (skipped, pending) = match images.force {
+                    true => (vec![], images.response),
+                    false => {
+                        self.checker
+                            .split_unchanged(images.lot_number, images.response)
+                            .await
+                    }
                 };
-
-                let synced = self.uploader.upload_images(blobs_response.into()).await;
+                let blobs = self.requester.download_images(pending).await;
+                let fetched = blobs.0.iter().filter(|b| b.blob.is_some()).count();
+                let processed = self.processor.process_images(blobs).await;
+                debug!(processed = ?processed, "processed blobs");
+
+                let mut synced = self.uploader.upload_images(processed.into()).await;
+                let skipped_count = skipped.len();
+                let failed = synced
+                    .0
+                    .iter()
+                    .filter(|s| s.status == ImageSyncStatus::Failed)
+                    .count();
+                // sent along so the persister does not mark them removed
+                synced.0.extend(skipped);
+                info!(
+                    lot_number = images.lot_number,
+                    skipped = skipped_count,
+                    fetched,
+                    failed,
+                    force = images.force,
+                    "synced lot images"
+                );
                 let synced_response = SyncedImagesResponse {
                     lot_number: images.lot_number,
                     response: synced,
+                    skipped: skipped_count,
+                    fetched,
                 };
 
                 let _ = self
@@ -68,12 +98,18 @@ impl<R: CopartRequesterExt, U: CopartUploaderExt> SingleMsgHandler<R, U> {
     }
 }
 
-impl<R, U> CopartImageSyncSink<R, U>
+impl<C, R, U> CopartImageSyncSink<C, R, U>
 where
+    C: CopartSyncCheckerExt + Send + Sync + 'static,
     R: CopartRequesterExt + Send + Sync + 'static,
     U: CopartUploaderExt + Send + Sync + 'static,
 {
-    pub fn new(requester: R, uploader: U) -> (Self, ExternalSignaling) {
+    pub fn new(
+        checker: C,
+        requester: R,
+        processor: ImageProcessor,
+        uploader: U,
+    ) -> (Self, ExternalSignaling) {
         let (cmd_sender, cmd_receiver) = tokio::sync::mpsc::channel(32);
         let (response_sender, response_receiver) = tokio::sync::mpsc::channel(32);
         let external_signaling = ExternalSignaling {
@@ -82,7 +118,9 @@ where
         };
         let msg_handler = Arc::new(SingleMsgHandler {
             response_sender,
+            checker,
             requester,
+            processor,
             uploader,
         });
         let sink = Self {
@@ -93,17 +131,14 @@ where
         (sink, external_signaling)
     }
 
-    pub fn run(self, cancellation_token: CancellationToken) -> Arc<Notify> {
+    pub fn run(self, cancellation_token: CancellationToken) -> oneshot::Receiver<()> {
         let join_handle = tokio::spawn(self.run_blocking());
 
-        let done = Arc::new(Notify::new());
-        tokio::spawn({
-            let done = done.clone();
-            async move {
-                cancellation_token.cancelled().await;
-                join_handle.abort();
-                done.notify_waiters();
-            }
+        let (done_sender, done) = oneshot::channel();
+        tokio::spawn(async move {
+            cancellation_token.cancelled().await;
+            join_handle.abort();
+            let _ = done_sender.send(());
         });
         done
     }
@@ -133,14 +168,28 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::copart::requester::LotImageBlobs;
+    use crate::copart::requester::{LotImageBlobs, LotImageBlobsVector};
     use crate::copart::sink::{CopartImageSyncSink, MsgIn};
     use crate::copart::uploader::NewLotImages;
+    use crate::processor::VariantFormat;
     use async_trait::async_trait;
-    use common::io::copart::{LotImagesVector, SyncedImagesVector};
+    use common::io::copart::{LotImagesVector, LotNumber, SyncedImages, SyncedImagesVector};
     use std::time::Duration;
     use tokio::time::Instant;
 
+    struct NopCopartSyncChecker;
+
+    #[async_trait]
+    impl CopartSyncCheckerExt for NopCopartSyncChecker {
+        async fn split_unchanged(
+            &self,
+            _lot_number: LotNumber,
+            images: LotImagesVector,
+        ) -> (Vec<SyncedImages>, LotImagesVector) {
+            (vec![], images)
+        }
+    }
+
     struct NopCopartRequester;
 
     #[async_trait]
@@ -148,14 +197,12 @@ mod tests {
         async fn download_images(&self, _cmds: LotImagesVector) -> LotImageBlobsVector {
             tokio::time::sleep(Duration::from_millis(20)).await;
             LotImageBlobsVector(vec![LotImageBlobs {
-                standard: None,
-                high_res: None,
-                thumbnail: None,
-                standard_url: None,
-                high_res_url: None,
-                thumbnail_url: None,
+                blob: None,
+                url: None,
+                etag: None,
                 sequence_number: 1,
                 image_type: "jpg".to_string(),
+                error: None,
             }])
         }
     }
@@ -164,14 +211,19 @@ mod tests {
 
     #[async_trait]
     impl CopartUploaderExt for NopCopartUploader {
-        async fn upload_images(&self, new_lot_images: NewLotImages) -> SyncedImagesVector {
-            todo!()
+        async fn upload_images(&self, _new_lot_images: NewLotImages) -> SyncedImagesVector {
+            SyncedImagesVector(vec![])
         }
     }
 
     #[tokio::test]
     async fn test_sink_concurrency() -> Result<(), Box<dyn std::error::Error>> {
-        let (sink, mut sig) = CopartImageSyncSink::new(NopCopartRequester, NopCopartUploader);
+        let (sink, mut sig) = CopartImageSyncSink::new(
+            NopCopartSyncChecker,
+            NopCopartRequester,
+            ImageProcessor::new(vec![320], vec![VariantFormat::Avif]),
+            NopCopartUploader,
+        );
         tokio::spawn(sink.run_blocking());
 
         for _ in 0..16 {
@@ -179,6 +231,7 @@ mod tests {
                 .send(MsgIn::LotImages(Ok(LotImagesResponse {
                     lot_number: 69,
                     response: LotImagesVector(vec![]),
+                    force: false,
                 })))
                 .await?;
         }
@@ -1,18 +1,20 @@
-use crate::copart::sink::LotImageBlobsResponse;
+use crate::processor::{EncodedVariant, ProcessedImages};
+use crate::spool::SpooledBlob;
 use async_trait::async_trait;
-use aws_sdk_s3::primitives::ByteStream;
-use common::bucket::S3_CLIENT;
-use common::io::copart::{LotNumber, SyncedImages, SyncedImagesVector};
-use common::io::error::GeneralError;
+use common::bucket::content_key;
+use common::bucket::store::ObjectStore;
+use common::io::copart::{
+    ImageSyncError, ImageSyncStatus, SyncedImageVariant, SyncedImages, SyncedImagesVector,
+};
 use common::retry_async;
 use futures::StreamExt;
-use mime_guess::MimeGuess;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::Semaphore;
-use tokio_util::bytes::Bytes;
+use tracing::debug;
 
 pub struct CopartUploader {
+    store: Arc<dyn ObjectStore>,
     usage_permit: Arc<Semaphore>,
 }
 
@@ -33,25 +35,60 @@ impl CopartUploaderExt for CopartUploader {
                         .await
                         .unwrap_unchecked()
                 };
-                let (result_standard, result_thumbnail, result_high_res) = tokio::join!(
-                    maybe_upload(img.standard.as_ref()),
-                    maybe_upload(img.thumbnail.as_ref()),
-                    maybe_upload(img.high_res.as_ref())
-                );
+                let uploaded =
+                    futures::future::join_all(img.variants.iter().map(|v| self.maybe_upload(v)))
+                        .await;
                 drop(_permit);
 
+                // an image with a variant failing to upload is sent without variants, the stored
+                // ones are kept with the validators of their source and it is synced again on
+                // its retry unless that source is unchanged
+                if uploaded.iter().any(Option::is_none) {
+                    return SyncedImages {
+                        source_url: img.source_url,
+                        sequence_number: img.sequence_number,
+                        image_type: img.image_type,
+                        variants: vec![],
+                        perceptual_hash: None,
+                        source_etag: None,
+                        source_size: None,
+                        status: ImageSyncStatus::Failed,
+                        error: Some(ImageSyncError::Upload),
+                    };
+                }
+                let status = match (&img.source_url, img.error) {
+                    (_, Some(_)) => ImageSyncStatus::Failed,
+                    (None, None) => ImageSyncStatus::NoSource,
+                    (Some(_), None) => ImageSyncStatus::Synced,
+                };
+                let variants = img
+                    .variants
+                    .into_iter()
+                    .zip(uploaded)
+                    .filter_map(|(info, uploaded)| {
+                        uploaded.map(|meta| SyncedImageVariant {
+                            name: info.name,
+                            format: info.format,
+                            mime_type: meta.mime_type,
+                            width: info.width,
+                            height: info.height,
+                            size: meta.size,
+                            bucket_key: meta.key,
+                            sha256: meta.sha256,
+                        })
+                    })
+                    .collect();
+
                 SyncedImages {
-                    standard_bucket_key: result_standard.as_ref().map(|m| m.key.to_owned()),
-                    standard_mime_type: result_standard.as_ref().map(|m| m.mime_type.to_owned()),
-                    standard_source_url: img.standard.map(|i| i.url),
-                    thumbnail_bucket_key: result_thumbnail.as_ref().map(|m| m.key.to_owned()),
-                    thumbnail_mime_type: result_thumbnail.as_ref().map(|m| m.mime_type.to_owned()),
-                    thumbnail_source_url: img.thumbnail.map(|i| i.url),
-                    high_res_bucket_key: result_high_res.as_ref().map(|m| m.key.to_owned()),
-                    high_res_mime_type: result_high_res.as_ref().map(|m| m.mime_type.to_owned()),
-                    high_res_source_url: img.high_res.map(|i| i.url),
+                    source_url: img.source_url,
                     sequence_number: img.sequence_number,
                     image_type: img.image_type,
+                    variants,
+                    perceptual_hash: img.perceptual_hash,
+                    source_etag: img.source_etag,
+                    source_size: img.source_size,
+                    status,
+                    error: img.error,
                 }
             })
             .buffer_unordered(16)
@@ -63,146 +100,227 @@ impl CopartUploaderExt for CopartUploader {
 }
 
 impl CopartUploader {
-    pub fn new() -> Self {
+    pub fn new(store: Arc<dyn ObjectStore>) -> Self {
         Self {
+            store,
             usage_permit: Arc::new(Semaphore::new(32)),
         }
     }
-}
 
-async fn maybe_upload(image_info: Option<&ImageInfo>) -> Option<PutObjectMeta> {
-    if let Some(image_info) = image_info {
-        put_object_with_retry(
-            &image_info.bucket_key,
-            &image_info.blob,
-            &image_info.mime_type,
-            Duration::from_millis(300),
-            5,
-        )
+    /// Objects are keyed by their content, so a blob already in the bucket is not uploaded again.
+    async fn maybe_upload(&self, image_info: &ImageInfo) -> Option<PutObjectMeta> {
+        let meta = PutObjectMeta {
+            key: image_info.bucket_key.to_owned(),
+            mime_type: image_info.mime_type.to_owned(),
+            sha256: image_info.sha256.to_owned(),
+            size: image_info.blob.size() as i64,
+        };
+        let stored = retry_async(Duration::from_millis(300), 5, || {
+            self.store.head(&image_info.bucket_key)
+        })
+        .await
+        .ok()?;
+        if stored.is_some() {
+            debug!(
+                key = image_info.bucket_key,
+                "object already stored, skipping upload"
+            );
+            return Some(meta);
+        }
+
+        // the spooled file is streamed again on every retry
+        retry_async(Duration::from_millis(300), 5, || {
+            self.store.put_file(
+                &image_info.bucket_key,
+                image_info.blob.path(),
+                &image_info.mime_type,
+            )
+        })
         .await
         .ok()
-    } else {
-        None
+        .map(|_| meta)
     }
 }
 
 struct PutObjectMeta {
     key: String,
     mime_type: String,
-}
-
-async fn put_object(
-    key: &String,
-    content: &Bytes,
-    mime_type: &String,
-) -> Result<PutObjectMeta, GeneralError> {
-    // tokio::util::bytes::Bytes is cheaply cloneable, so we can clone it on every retry
-    let stream = ByteStream::from(content.clone());
-    Ok(S3_CLIENT
-        .put_object()
-        .bucket("cars-lot-images")
-        .key(key)
-        .content_type(mime_type)
-        .body(stream)
-        .send()
-        .await
-        .map(|_| PutObjectMeta {
-            key: key.to_owned(),
-            mime_type: mime_type.to_owned(),
-        })
-        .map_err(|e| GeneralError::S3(e.to_string()))?)
-}
-
-async fn put_object_with_retry(
-    key: &String,
-    content: &Bytes,
-    mime_type: &String,
-    timeout: Duration,
-    tries: usize,
-) -> Result<PutObjectMeta, GeneralError> {
-    retry_async(timeout, tries, || put_object(key, content, mime_type)).await
+    sha256: String,
+    size: i64,
 }
 
 pub struct ImageInfo {
-    blob: Bytes,
-    url: String,
+    blob: SpooledBlob,
+    name: String,
+    format: String,
     mime_type: String,
+    width: i32,
+    height: i32,
+    sha256: String,
     bucket_key: String,
 }
 
 pub struct NewLotImage {
-    standard: Option<ImageInfo>,
-    thumbnail: Option<ImageInfo>,
-    high_res: Option<ImageInfo>,
+    variants: Vec<ImageInfo>,
+    source_url: Option<String>,
     sequence_number: i32,
     image_type: String,
+    perceptual_hash: Option<i64>,
+    source_etag: Option<String>,
+    source_size: Option<i64>,
+    error: Option<ImageSyncError>,
 }
 
 pub struct NewLotImages(pub Vec<NewLotImage>);
 
-impl ImageInfo {
-    fn new(blob: Bytes, url: String, bucket_key: String) -> Self {
-        let mime = MimeGuess::from_path(&url).first_or_octet_stream();
+impl From<EncodedVariant> for ImageInfo {
+    fn from(value: EncodedVariant) -> Self {
+        let sha256 = value.blob.sha256().to_string();
         Self {
-            blob,
-            url,
-            bucket_key,
-            mime_type: mime.to_string(),
+            bucket_key: content_key(&sha256),
+            sha256,
+            blob: value.blob,
+            name: value.name,
+            format: value.format.as_str().to_string(),
+            mime_type: value.format.mime_type().to_string(),
+            width: value.width as i32,
+            height: value.height as i32,
         }
     }
 }
 
-fn standard_key(lot_number: LotNumber, sequence_number: i32) -> String {
-    format!("{}_{}_standard", lot_number, sequence_number)
-}
-
-fn thumbnail_key(lot_number: LotNumber, sequence_number: i32) -> String {
-    format!("{}_{}_thumbnail", lot_number, sequence_number)
-}
-
-fn high_res_key(lot_number: LotNumber, sequence_number: i32) -> String {
-    format!("{}_{}_high-res", lot_number, sequence_number)
-}
-
-impl From<LotImageBlobsResponse> for NewLotImages {
-    fn from(value: LotImageBlobsResponse) -> Self {
+impl From<ProcessedImages> for NewLotImages {
+    fn from(value: ProcessedImages) -> Self {
         Self(
             value
-                .response
                 .0
                 .into_iter()
-                .map(|i| {
-                    let standard = i.standard.map(|std| {
-                        ImageInfo::new(
-                            std,
-                            unsafe { i.standard_url.unwrap_unchecked() },
-                            standard_key(value.lot_number, i.sequence_number),
-                        )
-                    });
-                    let thumbnail = i.thumbnail.map(|thumb| {
-                        ImageInfo::new(
-                            thumb,
-                            unsafe { i.thumbnail_url.unwrap_unchecked() },
-                            thumbnail_key(value.lot_number, i.sequence_number),
-                        )
-                    });
-                    let high_res = i.high_res.map(|high| {
-                        ImageInfo::new(
-                            high,
-                            unsafe { i.high_res_url.unwrap_unchecked() },
-                            high_res_key(value.lot_number, i.sequence_number),
-                        )
-                    });
-
-                    NewLotImage {
-                        standard,
-                        thumbnail,
-                        high_res,
-                        sequence_number: i.sequence_number,
-                        image_type: i.image_type,
-                    }
+                .map(|i| NewLotImage {
+                    variants: i.variants.into_iter().map(ImageInfo::from).collect(),
+                    source_url: i.source_url,
+                    sequence_number: i.sequence_number,
+                    image_type: i.image_type,
+                    perceptual_hash: i.perceptual_hash,
+                    source_etag: i.source_etag,
+                    source_size: i.source_size,
+                    error: i.error,
                 })
                 .collect(),
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::{ProcessedImage, VariantFormat};
+    use common::bucket::sha256_hex;
+    use common::bucket::testing::temp_fs_store;
+    use tokio_util::bytes::Bytes;
+
+    fn variant(name: &str, blob: &[u8]) -> EncodedVariant {
+        EncodedVariant {
+            name: name.to_string(),
+            format: VariantFormat::WebP,
+            width: 320,
+            height: 240,
+            blob: SpooledBlob::from_bytes(blob).unwrap(),
+        }
+    }
+
+    fn processed(sequence_number: i32, variants: Vec<EncodedVariant>) -> ProcessedImage {
+        ProcessedImage {
+            source_url: Some(format!("https://cs.copart.com/{sequence_number}.jpg")),
+            sequence_number,
+            image_type: "IMG".to_string(),
+            variants,
+            perceptual_hash: Some(sequence_number as i64),
+            source_etag: Some(format!("\"{sequence_number}\"")),
+            source_size: Some(1024),
+            error: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upload_to_content_keys() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, store) = temp_fs_store();
+        let store = Arc::new(store);
+        let uploader = CopartUploader::new(store.clone());
+
+        let synced = uploader
+            .upload_images(
+                ProcessedImages(vec![
+                    processed(
+                        1,
+                        vec![variant("w320", b"small"), variant("w800", b"large")],
+                    ),
+                    // identical photo of another sequence shares the object
+                    processed(2, vec![variant("w320", b"small")]),
+                    ProcessedImage {
+                        variants: vec![],
+                        perceptual_hash: None,
+                        error: Some(ImageSyncError::Decode),
+                        ..processed(3, vec![])
+                    },
+                    ProcessedImage {
+                        source_url: None,
+                        ..processed(4, vec![])
+                    },
+                ])
+                .into(),
+            )
+            .await;
+
+        let mut synced = synced.0;
+        synced.sort_by_key(|s| s.sequence_number);
+        let small_key = content_key(&sha256_hex(b"small"));
+        assert_eq!(
+            synced[0]
+                .variants
+                .iter()
+                .map(|v| (v.name.as_str(), v.bucket_key.as_str(), v.size))
+                .collect::<Vec<_>>(),
+            vec![
+                ("w320", small_key.as_str(), 5),
+                ("w800", content_key(&sha256_hex(b"large")).as_str(), 5)
+            ]
+        );
+        assert_eq!(synced[0].variants[0].mime_type, "image/webp");
+        assert_eq!(synced[0].perceptual_hash, Some(1));
+        assert_eq!(synced[0].source_etag.as_deref(), Some("\"1\""));
+        assert_eq!(synced[1].variants[0].bucket_key, small_key);
+        assert_eq!(synced[0].status, ImageSyncStatus::Synced);
+        assert!(synced[2].variants.is_empty());
+        assert_eq!(synced[2].status, ImageSyncStatus::Failed);
+        assert_eq!(synced[2].error, Some(ImageSyncError::Decode));
+        assert_eq!(synced[3].status, ImageSyncStatus::NoSource);
+
+        assert_eq!(
+            store.get(&small_key).await?,
+            Some(Bytes::from_static(b"small"))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stored_object_is_not_uploaded_again() -> Result<(), Box<dyn std::error::Error>> {
+        let (_dir, store) = temp_fs_store();
+        let store = Arc::new(store);
+        let key = content_key(&sha256_hex(b"blob"));
+        // a different body under the same key tells whether the upload was skipped
+        store
+            .put(&key, Bytes::from_static(b"stored"), "image/webp")
+            .await?;
+        let uploader = CopartUploader::new(store.clone());
+
+        let synced = uploader
+            .upload_images(
+                ProcessedImages(vec![processed(1, vec![variant("w320", b"blob")])]).into(),
+            )
+            .await;
+
+        assert_eq!(synced.0[0].variants[0].bucket_key, key);
+        assert_eq!(store.get(&key).await?, Some(Bytes::from_static(b"stored")));
+        Ok(())
+    }
+}
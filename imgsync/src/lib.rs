@@ -1 +1,3 @@
 pub mod copart;
+pub mod processor;
+pub mod spool;
@@ -1,80 +1,66 @@
+use common::bucket::OBJECT_STORE;
+use common::config::CONFIG;
 use common::kafka::{KafkaReceiver, KafkaSender};
-use common::logging::setup_logging;
+use common::persistence::init_pg_pool;
+use common::persistence::repo::PgRepo;
+use common::service::{Service, Stage};
 use imgsync::copart::adapter::{CopartSinkRxKafkaAdapter, CopartSinkTxKafkaAdapter};
+use imgsync::copart::checker::CopartSyncChecker;
 use imgsync::copart::requester::CopartRequester;
 use imgsync::copart::sink::CopartImageSyncSink;
-use tokio_util::sync::CancellationToken;
-use tracing::info;
-
-#[cfg(feature = "prof")]
-use common::memprof::MemProf;
-
-use common::config::CONFIG;
 use imgsync::copart::uploader::CopartUploader;
-#[cfg(not(target_env = "msvc"))]
-use tikv_jemallocator::Jemalloc;
-
-#[cfg(not(target_env = "msvc"))]
-#[global_allocator]
-static GLOBAL: Jemalloc = Jemalloc;
+use imgsync::processor::ImageProcessor;
+use imgsync::spool;
+use tracing::{info, warn};
 
-#[allow(non_upper_case_globals)]
-#[unsafe(export_name = "malloc_conf")]
-#[cfg(feature = "prof")]
-pub static malloc_conf: &[u8] = b"prof:true,prof_active:true,lg_prof_sample:19\0";
+common::jemalloc!();
 
 #[tokio::main]
 async fn main() {
-    setup_logging("imgsync");
-    let cancellation_token = CancellationToken::new();
-    info!("starting app");
-
-    let (copart_sink, copart_sig) =
-        CopartImageSyncSink::new(CopartRequester::new(), CopartUploader::new());
-    let copart_sink_done = copart_sink.run(cancellation_token.clone());
+    let mut service = Service::new("imgsync").admin("0.0.0.0:6969");
+    match spool::sweep() {
+        Ok(0) => {}
+        Ok(removed) => info!("removed `{removed}` spool files of a previous run"),
+        Err(e) => warn!(spool_error = ?e, "sweeping spool files failed"),
+    }
 
-    let rx_done = KafkaReceiver::new(
-        CONFIG.kafka.url.to_owned(),
-        "copart_response_lot_images_0",
-        &["copart_response_lot_images"],
-    )
-    .run_on(
-        CopartSinkTxKafkaAdapter {
-            cmd_sender: copart_sig.cmd_sender,
-        },
-        cancellation_token.clone(),
+    let requester = CopartRequester::new();
+    let (copart_sink, copart_sig) = CopartImageSyncSink::new(
+        CopartSyncChecker::new(
+            PgRepo::new(init_pg_pool()),
+            requester.clone(),
+            OBJECT_STORE.clone(),
+        ),
+        requester,
+        ImageProcessor::from_config(),
+        CopartUploader::new(OBJECT_STORE.clone()),
     );
+    service.register("copart image sync sink", Stage::Processing, |token| {
+        copart_sink.run(token)
+    });
 
-    let tx_done = KafkaSender::new(CONFIG.kafka.url.to_owned()).run_on(
-        CopartSinkRxKafkaAdapter {
-            response_receiver: copart_sig.response_receiver,
-        },
-        cancellation_token.clone(),
-    );
-
-    #[cfg(feature = "prof")]
-    let prof_done = MemProf::start("0.0.0.0:6969", cancellation_token.clone());
+    service.register("kafka receiver", Stage::Ingress, |token| {
+        KafkaReceiver::new(
+            CONFIG.kafka.url.to_owned(),
+            "copart_response_lot_images_0",
+            &["copart_response_lot_images"],
+        )
+        .run_on(
+            CopartSinkTxKafkaAdapter {
+                cmd_sender: copart_sig.cmd_sender,
+            },
+            token,
+        )
+    });
 
-    info!("app started");
-    tokio::signal::ctrl_c()
-        .await
-        .expect("failed to listen for ctrl c event");
-    info!("exiting");
-    cancellation_token.cancel();
+    service.register("kafka sender", Stage::Egress, |token| {
+        KafkaSender::new(CONFIG.kafka.url.to_owned()).run_on(
+            CopartSinkRxKafkaAdapter {
+                response_receiver: copart_sig.response_receiver,
+            },
+            token,
+        )
+    });
 
-    #[cfg(feature = "prof")]
-    tokio::join!(
-        tx_done.notified(),
-        rx_done.notified(),
-        copart_sink_done.notified(),
-        prof_done.notified(),
-    );
-
-    #[cfg(not(feature = "prof"))]
-    tokio::join!(
-        tx_done.notified(),
-        rx_done.notified(),
-        copart_sink_done.notified(),
-    );
-    info!("exited");
+    service.run().await;
 }
@@ -0,0 +1,535 @@
+use crate::copart::requester::LotImageBlobsVector;
+use crate::spool::{SpoolWriter, SpooledBlob};
+use common::config::CONFIG;
+use common::io::copart::ImageSyncError;
+use common::io::error::GeneralError;
+use futures::StreamExt;
+use image::codecs::avif::AvifEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::error::DecodingError;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageError, ImageFormat, ImageReader, ImageResult, Limits};
+use std::borrow::Cow;
+use std::fmt::{Debug, Formatter};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tracing::{error, instrument};
+
+/// Slowest speed still fast enough for the sync, 1 is slowest and 10 fastest
+const AVIF_SPEED: u8 = 8;
+const AVIF_QUALITY: u8 = 70;
+
+/// Decoded pixels of one source image, larger sources are rejected before their pixels
+/// are allocated.
+///
+/// Downloads and variants are spooled to files, decoding is what bounds the memory of a
+/// sync: a decode holds the source's pixels, at most their 8 bit copy when the source
+/// has deeper channels, and one resized variant with its encoder's buffers. With
+/// [`MAX_CONCURRENT_DECODES`] at once that is at most about 2 GiB.
+pub const MAX_DECODE_ALLOC: u64 = 256 * 1024 * 1024;
+/// Sources decoded at once by a processor and its clones, whatever the number of cores
+pub const MAX_CONCURRENT_DECODES: usize = 4;
+
+/// Decodes a spooled source within [`MAX_DECODE_ALLOC`].
+fn decode_source(source: &Path) -> ImageResult<DynamicImage> {
+    let mut limits = Limits::default();
+    limits.max_alloc = Some(MAX_DECODE_ALLOC);
+    let mut reader = ImageReader::open(source)?.with_guessed_format()?;
+    ensure_complete(source, reader.format())?;
+    reader.limits(limits);
+    reader.decode()
+}
+
+/// Rejects a jpeg without its end of image marker without decoding it. The jpeg decoder
+/// fills the missing part of a truncated jpeg with gray rather than failing, the other
+/// decoders fail on a truncated source.
+pub fn ensure_complete(source: &Path, format: Option<ImageFormat>) -> ImageResult<()> {
+    match format == Some(ImageFormat::Jpeg) && !jpeg_is_complete(source)? {
+        true => Err(ImageError::Decoding(DecodingError::new(
+            ImageFormat::Jpeg.into(),
+            "truncated before its end of image marker",
+        ))),
+        false => Ok(()),
+    }
+}
+
+/// Walks the segments of a jpeg and the entropy coded data of its scans, true when they
+/// end at an end of image marker. Read through a buffer, the file is not loaded whole.
+fn jpeg_is_complete(source: &Path) -> std::io::Result<bool> {
+    let mut reader = BufReader::new(File::open(source)?);
+    let mut byte = || -> std::io::Result<Option<u8>> {
+        let mut b = [0u8];
+        Ok(match reader.read(&mut b)? {
+            0 => None,
+            _ => Some(b[0]),
+        })
+    };
+    if (byte()?, byte()?) != (Some(0xFF), Some(0xD8)) {
+        return Ok(false);
+    }
+    let mut marker = None;
+    loop {
+        let code = match marker.take() {
+            Some(code) => code,
+            None => {
+                if byte()? != Some(0xFF) {
+                    return Ok(false);
+                }
+                let Some(mut code) = byte()? else {
+                    return Ok(false);
+                };
+                // fill bytes before a marker
+                while code == 0xFF {
+                    let Some(next) = byte()? else {
+                        return Ok(false);
+                    };
+                    code = next;
+                }
+                code
+            }
+        };
+        match code {
+            0xD9 => return Ok(true),
+            // markers without a segment
+            0x01 | 0xD0..=0xD7 => continue,
+            _ => {}
+        }
+        let (Some(high), Some(low)) = (byte()?, byte()?) else {
+            return Ok(false);
+        };
+        let length = u16::from_be_bytes([high, low]);
+        for _ in 2..length {
+            if byte()?.is_none() {
+                return Ok(false);
+            }
+        }
+        if code != 0xDA {
+            continue;
+        }
+        // entropy coded data runs up to a marker other than a stuffed zero or a restart
+        let mut previous = 0u8;
+        loop {
+            let Some(current) = byte()? else {
+                return Ok(false);
+            };
+            if previous == 0xFF && !matches!(current, 0x00 | 0xD0..=0xD7 | 0xFF) {
+                marker = Some(current);
+                break;
+            }
+            previous = current;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VariantFormat {
+    /// Lossless, the pure rust encoder has no lossy mode. Usually larger than a camera
+    /// jpeg, only for clients without avif support.
+    WebP,
+    /// Lossy at [`AVIF_QUALITY`], the default
+    Avif,
+}
+
+impl VariantFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format {
+            "webp" => Some(Self::WebP),
+            "avif" => Some(Self::Avif),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::WebP => "webp",
+            Self::Avif => "avif",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::WebP => "image/webp",
+            Self::Avif => "image/avif",
+        }
+    }
+}
+
+pub struct EncodedVariant {
+    /// `w{width}`, a configured width above the source's is named after the source width
+    pub name: String,
+    pub format: VariantFormat,
+    pub width: u32,
+    pub height: u32,
+    pub blob: SpooledBlob,
+}
+
+/// Variants and perceptual hash of a decoded source.
+pub struct ProcessedSource {
+    pub variants: Vec<EncodedVariant>,
+    /// See [`dhash`]
+    pub perceptual_hash: u64,
+}
+
+pub struct ProcessedImage {
+    pub source_url: Option<String>,
+    pub sequence_number: i32,
+    pub image_type: String,
+    /// Empty when the source was not downloaded or could not be processed
+    pub variants: Vec<EncodedVariant>,
+    /// Stored as a signed `BIGINT`, `None` like empty variants
+    pub perceptual_hash: Option<i64>,
+    /// Validators of the source the variants were generated from, `None` like empty variants
+    pub source_etag: Option<String>,
+    pub source_size: Option<i64>,
+    /// Stage the image failed at, `None` when processed or without a source
+    pub error: Option<ImageSyncError>,
+}
+
+pub struct ProcessedImages(pub Vec<ProcessedImage>);
+
+/// Generates the configured variants of downloaded lot images. Decoding, resizing and
+/// encoding are pure rust and run on the blocking pool, at most one image per core and
+/// no more than [`MAX_CONCURRENT_DECODES`].
+#[derive(Clone)]
+pub struct ImageProcessor {
+    widths: Arc<Vec<u32>>,
+    formats: Arc<Vec<VariantFormat>>,
+    usage_permit: Arc<Semaphore>,
+}
+
+impl ImageProcessor {
+    pub fn new(widths: Vec<u32>, formats: Vec<VariantFormat>) -> Self {
+        let mut widths = widths;
+        widths.sort_unstable();
+        widths.dedup();
+        let cores = std::thread::available_parallelism().map_or(4, |n| n.get());
+        Self {
+            widths: Arc::new(widths),
+            formats: Arc::new(formats),
+            usage_permit: Arc::new(Semaphore::new(cores.min(MAX_CONCURRENT_DECODES))),
+        }
+    }
+
+    pub fn from_config() -> Self {
+        let formats = CONFIG
+            .images
+            .variant_formats
+            .iter()
+            .map(|f| {
+                VariantFormat::parse(f).unwrap_or_else(|| panic!("unknown variant format `{f}`"))
+            })
+            .collect();
+        Self::new(CONFIG.images.variant_widths.clone(), formats)
+    }
+
+    #[instrument(skip_all)]
+    pub async fn process_images(&self, blobs: LotImageBlobsVector) -> ProcessedImages {
+        let processed = futures::stream::iter(blobs.0)
+            .map(async |img| {
+                let mut error = img.error;
+                let processed = match img.blob {
+                    Some(blob) => {
+                        let size = blob.size();
+                        match self.process_blocking(blob).await {
+                            Ok(processed) => Some((processed, size)),
+                            Err(e) => {
+                                error!(process_error = ?e, url = img.url, "processing image failed");
+                                error = Some(ImageSyncError::Decode);
+                                None
+                            }
+                        }
+                    }
+                    None => None,
+                };
+                let (variants, perceptual_hash, source_etag, source_size) = match processed {
+                    Some((p, size)) => (
+                        p.variants,
+                        Some(p.perceptual_hash as i64),
+                        img.etag,
+                        Some(size as i64),
+                    ),
+                    None => (vec![], None, None, None),
+                };
+                ProcessedImage {
+                    source_url: img.url,
+                    sequence_number: img.sequence_number,
+                    image_type: img.image_type,
+                    variants,
+                    perceptual_hash,
+                    source_etag,
+                    source_size,
+                    error,
+                }
+            })
+            .buffer_unordered(4)
+            .collect()
+            .await;
+        ProcessedImages(processed)
+    }
+
+    async fn process_blocking(&self, blob: SpooledBlob) -> Result<ProcessedSource, GeneralError> {
+        let _permit = unsafe {
+            self.usage_permit
+                .clone()
+                .acquire_owned()
+                .await
+                .unwrap_unchecked()
+        };
+        let processor = self.clone();
+        tokio::task::spawn_blocking(move || processor.process(blob.path()))
+            .await
+            .map_err(|e| GeneralError::Image(e.to_string()))?
+    }
+
+    /// One variant per configured width in every format. Widths above the source's
+    /// collapse into a single variant of the source width. The source is decoded from
+    /// its file and the variants are encoded into files.
+    pub fn process(&self, source: &Path) -> Result<ProcessedSource, GeneralError> {
+        let image = decode_source(source).map_err(|e| GeneralError::Image(e.to_string()))?;
+        // 8 bit sources are kept as decoded rather than copied
+        let image = match image.color().has_alpha() {
+            true => DynamicImage::ImageRgba8(image.into_rgba8()),
+            false => DynamicImage::ImageRgb8(image.into_rgb8()),
+        };
+
+        let mut variants = vec![];
+        let mut produced_widths = vec![];
+        for &target in self.widths.iter() {
+            let width = target.min(image.width());
+            if produced_widths.contains(&width) {
+                continue;
+            }
+            produced_widths.push(width);
+
+            let height =
+                ((image.height() as u64 * width as u64) / image.width() as u64).max(1) as u32;
+            let resized = match width == image.width() {
+                true => Cow::Borrowed(&image),
+                false => Cow::Owned(image.resize_exact(width, height, FilterType::Lanczos3)),
+            };
+            for &format in self.formats.iter() {
+                variants.push(encode(&resized, format)?);
+            }
+        }
+        Ok(ProcessedSource {
+            variants,
+            perceptual_hash: dhash(&image),
+        })
+    }
+}
+
+/// Difference hash: the image shrunk to 9x8 grayscale pixels, a bit per pixel set when
+/// it is brighter than its right neighbour. Re-encoded, resized or slightly cropped copies
+/// of a photo differ in a few bits, compare hashes by their hamming distance.
+pub fn dhash(image: &DynamicImage) -> u64 {
+    let small = image
+        .grayscale()
+        .resize_exact(9, 8, FilterType::Triangle)
+        .to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let brighter = small.get_pixel(x, y).0[0] > small.get_pixel(x + 1, y).0[0];
+            hash = (hash << 1) | brighter as u64;
+        }
+    }
+    hash
+}
+
+fn encode(image: &DynamicImage, format: VariantFormat) -> Result<EncodedVariant, GeneralError> {
+    let mut writer = SpoolWriter::new()?;
+    let written = match format {
+        VariantFormat::WebP => image.write_with_encoder(WebPEncoder::new_lossless(&mut writer)),
+        VariantFormat::Avif => image.write_with_encoder(AvifEncoder::new_with_speed_quality(
+            &mut writer,
+            AVIF_SPEED,
+            AVIF_QUALITY,
+        )),
+    };
+    written.map_err(|e| GeneralError::Image(e.to_string()))?;
+
+    Ok(EncodedVariant {
+        name: format!("w{}", image.width()),
+        format,
+        width: image.width(),
+        height: image.height(),
+        blob: writer.finish()?,
+    })
+}
+
+impl Debug for ProcessedImages {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let variants = self.0.iter().map(|i| i.variants.len()).sum::<usize>();
+        let failed = self.0.iter().filter(|i| i.variants.is_empty()).count();
+        write!(
+            f,
+            "images: {}, variants: {variants}, without variants: {failed}",
+            self.0.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+    use std::io::Cursor;
+
+    fn jpeg(width: u32, height: u32) -> SpooledBlob {
+        let image = RgbImage::from_fn(width, height, |x, y| {
+            image::Rgb([(x % 256) as u8, (y % 256) as u8, ((x + y) % 256) as u8])
+        });
+        let mut buffer = Cursor::new(vec![]);
+        DynamicImage::ImageRgb8(image)
+            .write_to(&mut buffer, ImageFormat::Jpeg)
+            .unwrap();
+        SpooledBlob::from_bytes(&buffer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn test_truncated_jpeg_is_not_decoded() -> Result<(), Box<dyn std::error::Error>> {
+        let source = jpeg(64, 48);
+        assert!(jpeg_is_complete(source.path())?);
+        assert_eq!(decode_source(source.path())?.width(), 64);
+
+        let mut bytes = std::fs::read(source.path())?;
+        bytes.truncate(bytes.len() - 32);
+        let truncated = SpooledBlob::from_bytes(&bytes)?;
+        assert!(!jpeg_is_complete(truncated.path())?);
+        assert!(matches!(
+            decode_source(truncated.path()),
+            Err(ImageError::Decoding(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_variants_keep_aspect_ratio() -> Result<(), Box<dyn std::error::Error>> {
+        let processor = ImageProcessor::new(vec![800, 320, 1600], vec![VariantFormat::WebP]);
+        let variants = processor.process(jpeg(1200, 900).path())?.variants;
+
+        // 1600 is wider than the source, so it is the source size and named after it
+        assert_eq!(
+            variants
+                .iter()
+                .map(|v| (v.name.as_str(), v.width, v.height))
+                .collect::<Vec<_>>(),
+            vec![("w320", 320, 240), ("w800", 800, 600), ("w1200", 1200, 900)]
+        );
+        for variant in &variants {
+            assert_eq!(variant.format, VariantFormat::WebP);
+            let decoded = image::load_from_memory_with_format(
+                &std::fs::read(variant.blob.path())?,
+                ImageFormat::WebP,
+            )?;
+            assert_eq!(
+                (decoded.width(), decoded.height()),
+                (variant.width, variant.height)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_small_source_is_not_upscaled() -> Result<(), Box<dyn std::error::Error>> {
+        let processor = ImageProcessor::new(vec![320, 800, 1600], vec![VariantFormat::Avif]);
+        let variants = processor.process(jpeg(200, 100).path())?.variants;
+
+        assert_eq!(variants.len(), 1);
+        assert_eq!((variants[0].width, variants[0].height), (200, 100));
+        Ok(())
+    }
+
+    #[test]
+    fn test_avif_variants() -> Result<(), Box<dyn std::error::Error>> {
+        let processor =
+            ImageProcessor::new(vec![64], vec![VariantFormat::Avif, VariantFormat::WebP]);
+        let variants = processor.process(jpeg(128, 64).path())?.variants;
+
+        assert_eq!(
+            variants.iter().map(|v| v.format).collect::<Vec<_>>(),
+            vec![VariantFormat::Avif, VariantFormat::WebP]
+        );
+        assert_eq!((variants[0].width, variants[0].height), (64, 32));
+        assert!(variants[0].blob.size() > 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_variants_are_smaller_than_source() -> Result<(), Box<dyn std::error::Error>> {
+        // noise like the sensor noise of a camera photo, which lossless encoders keep
+        let mut seed = 0x2545_f491_u32;
+        let image = RgbImage::from_fn(480, 360, |x, y| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            let noise = seed % 24;
+            image::Rgb([
+                ((x * 200 / 480) + noise) as u8,
+                ((y * 200 / 360) + noise) as u8,
+                (((x + y) * 100 / 840) + noise) as u8,
+            ])
+        });
+        let mut buffer = Cursor::new(vec![]);
+        DynamicImage::ImageRgb8(image).write_to(&mut buffer, ImageFormat::Jpeg)?;
+        let source = SpooledBlob::from_bytes(&buffer.into_inner())?;
+
+        let processor = ImageProcessor::new(vec![160, 320, 640], vec![VariantFormat::Avif]);
+        let variants = processor.process(source.path())?.variants;
+
+        assert_eq!(
+            variants.iter().map(|v| v.name.as_str()).collect::<Vec<_>>(),
+            vec!["w160", "w320", "w480"]
+        );
+        for variant in &variants {
+            assert!(
+                variant.blob.size() < source.size(),
+                "`{}` is `{}` bytes, the source `{}`",
+                variant.name,
+                variant.blob.size(),
+                source.size()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_dhash_matches_resized_copies() -> Result<(), Box<dyn std::error::Error>> {
+        // brighter towards the right with a dark band, so mirroring flips most bits
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(1200, 900, |x, y| {
+            let band = if (300..400).contains(&y) {
+                0
+            } else {
+                x * 200 / 1200
+            };
+            image::Rgb([band as u8; 3])
+        }));
+        let mut source = Cursor::new(vec![]);
+        image.write_to(&mut source, ImageFormat::Jpeg)?;
+        let source = SpooledBlob::from_bytes(&source.into_inner())?;
+
+        let processor = ImageProcessor::new(vec![320], vec![VariantFormat::WebP]);
+        let mut processed = processor.process(source.path())?;
+        let resized = processed.variants.remove(0);
+        let resized = image::load_from_memory(&std::fs::read(resized.blob.path())?)?;
+
+        assert!((processed.perceptual_hash ^ dhash(&image)).count_ones() <= 2);
+        assert!((processed.perceptual_hash ^ dhash(&resized)).count_ones() <= 2);
+        assert!((processed.perceptual_hash ^ dhash(&image.fliph())).count_ones() > 32);
+        Ok(())
+    }
+
+    #[test]
+    fn test_undecodable_source() -> Result<(), Box<dyn std::error::Error>> {
+        let processor = ImageProcessor::new(vec![320], vec![VariantFormat::WebP]);
+        let source = SpooledBlob::from_bytes(b"not an image")?;
+        assert!(matches!(
+            processor.process(source.path()),
+            Err(GeneralError::Image(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,203 @@
+use futures::{Stream, StreamExt};
+use sha2::{Digest, Sha256};
+use std::fmt::{Debug, Formatter};
+use std::io::{BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use tokio::io::AsyncWriteExt;
+use tokio_util::bytes::Bytes;
+use uuid::Uuid;
+
+/// Capacity of the buffers between a stream or an encoder and the spool file
+const SPOOL_BUFFER_SIZE: usize = 64 * 1024;
+
+static SPOOL_DIR: LazyLock<PathBuf> = LazyLock::new(|| std::env::temp_dir().join("imgsync"));
+
+fn spool_path() -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(SPOOL_DIR.as_path())?;
+    Ok(SPOOL_DIR.join(Uuid::new_v4().as_simple().to_string()))
+}
+
+/// Removes the spool files a previous run left behind when it was killed before dropping
+/// its blobs, returns how many. Run at startup before anything is spooled, the spool
+/// directory belongs to a single imgsync.
+pub fn sweep() -> std::io::Result<usize> {
+    sweep_dir(SPOOL_DIR.as_path())
+}
+
+fn sweep_dir(dir: &Path) -> std::io::Result<usize> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let mut removed = 0;
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            std::fs::remove_file(entry.path())?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// A blob kept in a temporary file instead of memory, hashed while it was written.
+/// The file is removed when the blob is dropped.
+pub struct SpooledBlob {
+    path: PathBuf,
+    size: u64,
+    sha256: String,
+}
+
+impl SpooledBlob {
+    /// Writes the chunks as they arrive, at most one chunk and the write buffer are in memory.
+    pub async fn from_stream<S, E>(stream: S) -> Result<Self, E>
+    where
+        S: Stream<Item = Result<Bytes, E>>,
+        E: From<std::io::Error>,
+    {
+        let path = spool_path()?;
+        let mut file = tokio::io::BufWriter::with_capacity(
+            SPOOL_BUFFER_SIZE,
+            tokio::fs::File::create(&path).await?,
+        );
+        // removes the file when the stream fails halfway
+        let mut blob = Self {
+            path,
+            size: 0,
+            sha256: String::new(),
+        };
+        let mut hasher = Sha256::new();
+        let mut stream = std::pin::pin!(stream);
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            hasher.update(&chunk);
+            file.write_all(&chunk).await?;
+            blob.size += chunk.len() as u64;
+        }
+        file.flush().await?;
+        blob.sha256 = format!("{:x}", hasher.finalize());
+        Ok(blob)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> std::io::Result<Self> {
+        let mut writer = SpoolWriter::new()?;
+        writer.write_all(bytes)?;
+        writer.finish()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Hex encoded sha256 of the content
+    pub fn sha256(&self) -> &str {
+        &self.sha256
+    }
+
+    pub async fn read(&self) -> std::io::Result<Vec<u8>> {
+        tokio::fs::read(&self.path).await
+    }
+}
+
+impl Drop for SpooledBlob {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl Debug for SpooledBlob {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({} bytes)", self.path.display(), self.size)
+    }
+}
+
+/// Blocking writer spooling encoder output, see [`SpooledBlob`].
+pub struct SpoolWriter {
+    blob: SpooledBlob,
+    file: BufWriter<std::fs::File>,
+    hasher: Sha256,
+}
+
+impl SpoolWriter {
+    pub fn new() -> std::io::Result<Self> {
+        let path = spool_path()?;
+        let file = BufWriter::with_capacity(SPOOL_BUFFER_SIZE, std::fs::File::create(&path)?);
+        Ok(Self {
+            blob: SpooledBlob {
+                path,
+                size: 0,
+                sha256: String::new(),
+            },
+            file,
+            hasher: Sha256::new(),
+        })
+    }
+
+    pub fn finish(mut self) -> std::io::Result<SpooledBlob> {
+        self.file.flush()?;
+        self.blob.sha256 = format!("{:x}", self.hasher.finalize());
+        Ok(self.blob)
+    }
+}
+
+impl Write for SpoolWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.file.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.blob.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::bucket::sha256_hex;
+
+    #[tokio::test]
+    async fn test_spool_stream() -> Result<(), Box<dyn std::error::Error>> {
+        let chunks = ["first ", "second ", "third"]
+            .map(|c| Ok::<_, std::io::Error>(Bytes::from_static(c.as_bytes())));
+
+        let blob = SpooledBlob::from_stream(futures::stream::iter(chunks)).await?;
+
+        assert_eq!(blob.read().await?, b"first second third");
+        assert_eq!(blob.size(), 18);
+        assert_eq!(blob.sha256(), sha256_hex(b"first second third"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_drop_removes_file() -> Result<(), Box<dyn std::error::Error>> {
+        let blob = SpooledBlob::from_bytes(b"blob")?;
+        let path = blob.path().to_path_buf();
+        assert!(path.exists());
+
+        drop(blob);
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sweep_dir() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempfile::TempDir::new()?;
+        assert_eq!(sweep_dir(&dir.path().join("missing"))?, 0);
+        for name in ["left", "behind"] {
+            std::fs::write(dir.path().join(name), b"blob")?;
+        }
+
+        assert_eq!(sweep_dir(dir.path())?, 2);
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,283 @@
+//! Peak memory of a lot image sync must not grow with the image size: the download is
+//! spooled to a file, decoded into variants and put into the store, for s3 in parts.
+//! Heap is counted by the global allocator of this test binary, its own process so other
+//! test binaries do not skew the peak, and the tests of this one run one at a time.
+//! Where the kernel can reset it, the peak resident set size is checked as well.
+//!
+//! The padded images decode to a single pixel, decoding is bounded by
+//! [`MAX_DECODE_ALLOC`] instead, which [`test_oversized_source_is_not_decoded`] checks.
+
+use aws_sdk_s3::config::{BehaviorVersion, Credentials, Region};
+use common::bucket::s3::S3Store;
+use common::bucket::store::ObjectStore;
+use common::bucket::testing::temp_fs_store;
+use imgsync::copart::requester::CopartRequester;
+use imgsync::processor::{ImageProcessor, VariantFormat, MAX_DECODE_ALLOC};
+use imgsync::spool::SpooledBlob;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use testcontainers_modules::minio::MinIO;
+use testcontainers_modules::testcontainers::runners::AsyncRunner;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+
+struct CountingAlloc;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(allocated, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const MIB: usize = 1024 * 1024;
+
+/// Well below the smallest image, a buffered blob would exceed it
+const CEILING: usize = 4 * MIB;
+/// Resident memory also counts thread stacks and allocator slack, still well below the
+/// smallest image
+const RSS_CEILING: usize = 16 * MIB;
+
+/// Peaks are process wide, a test measuring at the same time would skew them
+static MEASURING: Mutex<()> = Mutex::const_new(());
+
+/// Chunks written by the server come from this static, they are not on the heap
+static CHUNK: [u8; 64 * 1024] = [0xAB; 64 * 1024];
+
+/// A 1x1 png, decoders stop at its end and never read the padding after it
+static PNG: [u8; 69] = [
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x02, 0x00, 0x00, 0x00, 0x90, 0x77, 0x53,
+    0xde, 0x00, 0x00, 0x00, 0x0c, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x60, 0x60, 0x60, 0x00,
+    0x00, 0x00, 0x04, 0x00, 0x01, 0xf6, 0x17, 0x38, 0x55, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e,
+    0x44, 0xae, 0x42, 0x60, 0x82,
+];
+
+/// Answers `GET /{size}` with a png padded to `size` bytes, wiremock keeps whole bodies
+/// in memory.
+async fn image_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let mut head = [0u8; 1024];
+                let mut read = 0;
+                while !head[..read].windows(4).any(|w| w == b"\r\n\r\n") {
+                    read += socket.read(&mut head[read..]).await.unwrap();
+                }
+                let request = std::str::from_utf8(&head[..read]).unwrap();
+                let size = request
+                    .split_whitespace()
+                    .nth(1)
+                    .and_then(|path| path.trim_start_matches('/').parse::<usize>().ok())
+                    .unwrap();
+
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: {size}\r\nConnection: close\r\n\r\n"
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+                socket.write_all(&PNG).await.unwrap();
+                let mut remaining = size - PNG.len();
+                while remaining > 0 {
+                    let chunk = remaining.min(CHUNK.len());
+                    socket.write_all(&CHUNK[..chunk]).await.unwrap();
+                    remaining -= chunk;
+                }
+                socket.shutdown().await.unwrap();
+            });
+        }
+    });
+    format!("http://{addr}")
+}
+
+/// Resets the peak resident set size to the current one, returns the current one.
+/// `None` where the kernel does not support resetting it.
+fn reset_peak_rss() -> Option<usize> {
+    std::fs::write("/proc/self/clear_refs", "5").ok()?;
+    proc_status_bytes("VmRSS")
+}
+
+fn proc_status_bytes(field: &str) -> Option<usize> {
+    std::fs::read_to_string("/proc/self/status")
+        .ok()?
+        .lines()
+        .find_map(|line| {
+            let kib = line.strip_prefix(field)?.strip_prefix(':')?;
+            kib.trim().strip_suffix(" kB")?.parse::<usize>().ok()
+        })
+        .map(|kib| kib * 1024)
+}
+
+struct Peaks {
+    heap: usize,
+    rss: Option<usize>,
+}
+
+impl Peaks {
+    fn assert_below_ceiling(&self, what: &str) {
+        assert!(
+            self.heap < CEILING,
+            "{what} peaked at {} heap bytes",
+            self.heap
+        );
+        if let Some(rss) = self.rss {
+            assert!(rss < RSS_CEILING, "{what} peaked at {rss} resident bytes");
+        }
+    }
+}
+
+/// Peaks above the baseline while the image is synced: downloaded, processed and put
+/// into the store with its variants
+async fn sync_peaks(
+    requester: &CopartRequester,
+    processor: &ImageProcessor,
+    store: &dyn ObjectStore,
+    base_url: &str,
+    size: usize,
+) -> Peaks {
+    let rss_baseline = reset_peak_rss();
+    let baseline = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(baseline, Ordering::Relaxed);
+
+    let blob = requester
+        .download_content(format!("{base_url}/{size}"))
+        .await
+        .unwrap()
+        .blob;
+    let processed = processor.process(blob.path()).unwrap();
+    for variant in &processed.variants {
+        store
+            .put_file(
+                &format!("sha256/{}", variant.blob.sha256()),
+                variant.blob.path(),
+                variant.format.mime_type(),
+            )
+            .await
+            .unwrap();
+    }
+    // variants of the padded image are tiny, the source is what takes s3 parts
+    let key = format!("sha256/{}", blob.sha256());
+    store
+        .put_file(&key, blob.path(), "image/png")
+        .await
+        .unwrap();
+
+    let heap = PEAK.load(Ordering::Relaxed).saturating_sub(baseline);
+    let rss = rss_baseline
+        .zip(proc_status_bytes("VmHWM"))
+        .map(|(baseline, peak)| peak.saturating_sub(baseline));
+    assert_eq!(blob.size(), size as u64);
+    assert_eq!(processed.variants.len(), 1);
+    assert_eq!(
+        store.head(&key).await.unwrap().map(|m| m.size),
+        Some(size as u64)
+    );
+    Peaks { heap, rss }
+}
+
+async fn assert_sync_below_ceiling(store: &dyn ObjectStore) {
+    let base_url = image_server().await;
+    let requester = CopartRequester::new().with_max_source_bytes(256 * MIB as u64);
+    let processor = ImageProcessor::new(vec![320], vec![VariantFormat::WebP]);
+    // connection pool and lazily initialized statics are not part of the measurement
+    sync_peaks(&requester, &processor, store, &base_url, MIB).await;
+
+    sync_peaks(&requester, &processor, store, &base_url, 8 * MIB)
+        .await
+        .assert_below_ceiling("8 MiB image");
+    sync_peaks(&requester, &processor, store, &base_url, 128 * MIB)
+        .await
+        .assert_below_ceiling("128 MiB image");
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn test_fs_sync_peak_is_independent_of_image_size() {
+    let _measuring = MEASURING.lock().await;
+    let (_dir, store) = temp_fs_store();
+    assert_sync_below_ceiling(&store).await;
+}
+
+/// Images above the multipart threshold are put in parts read from the spooled file
+#[tokio::test(flavor = "current_thread")]
+async fn test_s3_sync_peak_is_independent_of_image_size() {
+    let _measuring = MEASURING.lock().await;
+    let container = MinIO::default().start().await.unwrap();
+    let endpoint = format!(
+        "http://{}:{}",
+        container.get_host().await.unwrap(),
+        container.get_host_port_ipv4(9000).await.unwrap()
+    );
+    let config = aws_sdk_s3::Config::builder()
+        .behavior_version(BehaviorVersion::latest())
+        .region(Region::new("us-east-1"))
+        .endpoint_url(endpoint)
+        .credentials_provider(Credentials::new(
+            "minioadmin",
+            "minioadmin",
+            None,
+            None,
+            "test",
+        ))
+        .force_path_style(true)
+        .build();
+    let store = S3Store::new(aws_sdk_s3::Client::from_conf(config), "images", "us-east-1");
+    store.create_bucket().await.unwrap();
+
+    assert_sync_below_ceiling(&store).await;
+}
+
+fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let crc = !kind.iter().chain(data).fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| match crc & 1 {
+            1 => (crc >> 1) ^ 0xEDB8_8320,
+            _ => crc >> 1,
+        })
+    });
+    let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+    chunk.extend_from_slice(kind);
+    chunk.extend_from_slice(data);
+    chunk.extend_from_slice(&crc.to_be_bytes());
+    chunk
+}
+
+/// A source whose pixels would take four times [`MAX_DECODE_ALLOC`] is rejected before
+/// they are allocated
+#[tokio::test(flavor = "current_thread")]
+async fn test_oversized_source_is_not_decoded() {
+    let _measuring = MEASURING.lock().await;
+    // rgba, four bytes a pixel
+    let side = (MAX_DECODE_ALLOC as f64).sqrt() as u32;
+    let mut ihdr = side.to_be_bytes().to_vec();
+    ihdr.extend_from_slice(&side.to_be_bytes());
+    ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+    let mut png = PNG[..8].to_vec();
+    png.extend(png_chunk(b"IHDR", &ihdr));
+    png.extend(png_chunk(b"IDAT", &[]));
+    png.extend(png_chunk(b"IEND", &[]));
+    let blob = SpooledBlob::from_bytes(&png).unwrap();
+    let processor = ImageProcessor::new(vec![320], vec![VariantFormat::WebP]);
+
+    let baseline = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(baseline, Ordering::Relaxed);
+    assert!(processor.process(blob.path()).is_err());
+    let peak = PEAK.load(Ordering::Relaxed).saturating_sub(baseline);
+    assert!(peak < CEILING, "oversized source peaked at {peak} bytes");
+}
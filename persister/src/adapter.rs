@@ -0,0 +1,79 @@
+use async_trait::async_trait;
+use common::io::provider::{IngestMsg, LotId, LotProvider};
+use common::kafka::{KafkaError, ReceiveHandle, SendHandle, SendMsg, ToTopic};
+use std::marker::PhantomData;
+use tokio::sync::mpsc::{Receiver, Sender};
+use tracing::{error, warn};
+
+/// Receives the responses of a provider and hands them to the sink as provider neutral messages.
+pub struct ProviderSinkTxKafkaAdapter<P: LotProvider> {
+    pub cmd_sender: Sender<IngestMsg>,
+    provider: PhantomData<fn() -> P>,
+}
+
+impl<P: LotProvider> ProviderSinkTxKafkaAdapter<P> {
+    pub fn new(cmd_sender: Sender<IngestMsg>) -> Self {
+        Self {
+            cmd_sender,
+            provider: PhantomData,
+        }
+    }
+}
+
+#[async_trait]
+impl<P: LotProvider> ReceiveHandle for ProviderSinkTxKafkaAdapter<P> {
+    type RxItem = P::Response;
+
+    async fn on_message(&self, maybe_msg: Result<Self::RxItem, KafkaError>) {
+        match maybe_msg.map(P::ingest) {
+            Ok(Some(response)) => self
+                .cmd_sender
+                .send(IngestMsg {
+                    provider: P::PROVIDER,
+                    response,
+                })
+                .await
+                .expect("tokio mpsc channel - cmd receiver is gone"),
+            Ok(None) => warn!(
+                "persister received `{}` response which is not meant for it",
+                P::PROVIDER
+            ),
+            Err(e) => error!("kafka receive failed: `{e}`"),
+        };
+    }
+}
+
+/// Requests images of newly inserted lots of the provider, lots of other providers are skipped.
+pub struct ProviderSinkRxKafkaAdapter<P: LotProvider> {
+    pub response_receiver: Receiver<LotId>,
+    provider: PhantomData<fn() -> P>,
+}
+
+impl<P: LotProvider> ProviderSinkRxKafkaAdapter<P> {
+    pub fn new(response_receiver: Receiver<LotId>) -> Self {
+        Self {
+            response_receiver,
+            provider: PhantomData,
+        }
+    }
+}
+
+#[async_trait]
+impl<P: LotProvider> SendHandle for ProviderSinkRxKafkaAdapter<P> {
+    type TxItem = P::Cmd;
+
+    async fn next(&mut self) -> Option<SendMsg<Self::TxItem>> {
+        while let Some(id) = self.response_receiver.recv().await {
+            if id.provider != P::PROVIDER {
+                continue;
+            }
+            if let Some(msg) = P::lot_images_cmd(id.lot_number) {
+                return Some(SendMsg {
+                    topic: msg.to_topic(),
+                    msg,
+                });
+            }
+        }
+        None
+    }
+}
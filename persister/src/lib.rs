@@ -1 +1,3 @@
-pub mod copart;
+pub mod adapter;
+pub mod retry;
+pub mod sink;
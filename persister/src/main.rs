@@ -1,77 +1,82 @@
-use common::kafka::{KafkaReceiver, KafkaSender};
-use common::logging::setup_logging;
-use persister::copart::adapter::{CopartSinkRxKafkaAdapter, CopartSinkTxKafkaAdapter};
-use persister::copart::sink::CopartPersisterSink;
-use persister::copart::CopartPersister;
-use tokio_util::sync::CancellationToken;
-use tracing::info;
-
-#[cfg(feature = "prof")]
-use common::memprof::MemProf;
-
 use common::config::CONFIG;
-#[cfg(not(target_env = "msvc"))]
-use tikv_jemallocator::Jemalloc;
-
-#[cfg(not(target_env = "msvc"))]
-#[global_allocator]
-static GLOBAL: Jemalloc = Jemalloc;
+use common::io::copart::Copart;
+use common::io::provider::stub::Stub;
+use common::io::provider::{IngestMsg, LotProvider};
+use common::kafka::{KafkaReceiver, KafkaSender};
+use common::persistence::init_pg_pool;
+use common::persistence::migrate::run_pending_migrations;
+use common::persistence::repo::PgRepo;
+use common::service::{Service, Stage};
+use persister::adapter::{ProviderSinkRxKafkaAdapter, ProviderSinkTxKafkaAdapter};
+use persister::retry::ImageRetryQueue;
+use persister::sink::PersisterSink;
+use tokio::sync::mpsc::Sender;
 
-#[allow(non_upper_case_globals)]
-#[unsafe(export_name = "malloc_conf")]
-#[cfg(feature = "prof")]
-pub static malloc_conf: &[u8] = b"prof:true,prof_active:true,lg_prof_sample:19\0";
+common::jemalloc!();
 
 #[tokio::main]
 async fn main() {
-    setup_logging("persister");
-    info!("starting app");
-    let cancellation_token = CancellationToken::new();
+    let mut service = Service::new("persister").admin("0.0.0.0:6970");
 
-    let (sink, sig) = CopartPersisterSink::new(CopartPersister);
-    let sink_done = sink.run(cancellation_token.clone());
+    let pool = init_pg_pool();
+    if CONFIG.postgres.migrate_on_startup {
+        run_pending_migrations(&pool)
+            .await
+            .expect("failed to run pending migrations");
+    }
+    let (sink, sig) = PersisterSink::new(PgRepo::new(pool.clone()));
+    service.register("persister sink", Stage::Processing, |token| sink.run(token));
+    let (retry_queue, retry_receiver) = ImageRetryQueue::<Copart, _>::new(PgRepo::new(pool));
+    service.register("image retry queue", Stage::Processing, |token| {
+        retry_queue.run(token)
+    });
 
-    let rx_done = KafkaReceiver::new(
-        CONFIG.kafka.url.to_owned(),
+    register_receiver::<Copart>(
+        &mut service,
+        "copart kafka receiver",
         "consumer_group",
-        &[
-            "copart_response_lot_search",
-            "copart_response_synced_images",
-        ],
-    )
-    .run_on(
-        CopartSinkTxKafkaAdapter {
-            cmd_sender: sig.cmd_sender,
-        },
-        cancellation_token.clone(),
+        sig.cmd_sender.clone(),
     );
-    let tx_done = KafkaSender::new(CONFIG.kafka.url.to_owned()).run_on(
-        CopartSinkRxKafkaAdapter {
-            response_receiver: sig.response_receiver,
-        },
-        cancellation_token.clone(),
-    );
-
-    #[cfg(feature = "prof")]
-    let prof_done = MemProf::start("0.0.0.0:6970", cancellation_token.clone());
+    if CONFIG.providers.stub {
+        register_receiver::<Stub>(
+            &mut service,
+            "stub kafka receiver",
+            "stub_consumer_group",
+            sig.cmd_sender,
+        );
+    }
 
-    info!("app started");
-    tokio::signal::ctrl_c()
-        .await
-        .expect("failed to listen for ctrl c event");
-    info!("exiting");
-    cancellation_token.cancel();
+    // only copart lots have their images synced
+    service.register("kafka sender", Stage::Egress, |token| {
+        KafkaSender::new(CONFIG.kafka.url.to_owned()).run_on(
+            ProviderSinkRxKafkaAdapter::<Copart>::new(sig.response_receiver),
+            token,
+        )
+    });
+    service.register("retry kafka sender", Stage::Egress, |token| {
+        KafkaSender::new(CONFIG.kafka.url.to_owned()).run_on(
+            ProviderSinkRxKafkaAdapter::<Copart>::new(retry_receiver),
+            token,
+        )
+    });
 
-    #[cfg(feature = "prof")]
-    tokio::join!(
-        tx_done.notified(),
-        rx_done.notified(),
-        sink_done.notified(),
-        prof_done.notified()
-    );
-
-    #[cfg(not(feature = "prof"))]
-    tokio::join!(tx_done.notified(), rx_done.notified(), sink_done.notified(),);
+    service.run().await;
+}
 
-    info!("exited");
+/// Every provider is consumed in its own group, as its responses are of its own type.
+fn register_receiver<P: LotProvider + 'static>(
+    service: &mut Service,
+    name: &'static str,
+    consumer_group: &'static str,
+    cmd_sender: Sender<IngestMsg>,
+) {
+    service.register(name, Stage::Ingress, |token| {
+        let topics = P::ingest_topics();
+        KafkaReceiver::new(
+            CONFIG.kafka.url.to_owned(),
+            consumer_group,
+            &topics.iter().map(String::as_str).collect::<Vec<_>>(),
+        )
+        .run_on(ProviderSinkTxKafkaAdapter::<P>::new(cmd_sender), token)
+    });
 }
@@ -0,0 +1,123 @@
+use common::io::provider::{LotId, LotProvider};
+use common::persistence::repo::LotImageRepo;
+use common::persistence::retry::DEFAULT_RETRY_BATCH_SIZE;
+use std::marker::PhantomData;
+use std::time::Duration;
+use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, instrument};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+/// Pause between batches while lots are due, so a backlog of retries reaches imgsync
+/// at no more than a batch per pause
+const BATCH_PAUSE: Duration = Duration::from_secs(10);
+
+/// Requests the images of lots of the provider again once a failed image of theirs is due
+/// for a retry. Images synced since are skipped by imgsync as unchanged, only failed ones
+/// are downloaded. Lots of providers without image sync are never claimed, as nothing
+/// would come back for them.
+pub struct ImageRetryQueue<P: LotProvider, R: LotImageRepo> {
+    repo: R,
+    response_sender: Sender<LotId>,
+    provider: PhantomData<fn() -> P>,
+}
+
+impl<P: LotProvider + 'static, R: LotImageRepo + 'static> ImageRetryQueue<P, R> {
+    pub fn new(repo: R) -> (Self, Receiver<LotId>) {
+        let (response_sender, response_receiver) = tokio::sync::mpsc::channel(32);
+        let queue = Self {
+            repo,
+            response_sender,
+            provider: PhantomData,
+        };
+        (queue, response_receiver)
+    }
+
+    pub fn run(self, cancellation_token: CancellationToken) -> oneshot::Receiver<()> {
+        let join_handle = tokio::spawn(self.run_blocking());
+
+        let (done_sender, done) = oneshot::channel();
+        tokio::spawn(async move {
+            cancellation_token.cancelled().await;
+            join_handle.abort();
+            let _ = done_sender.send(());
+        });
+        done
+    }
+
+    pub async fn run_blocking(self) {
+        loop {
+            // a full batch means more lots are due
+            let pause = match self.request_due().await >= DEFAULT_RETRY_BATCH_SIZE as usize {
+                true => BATCH_PAUSE,
+                false => POLL_INTERVAL,
+            };
+            tokio::time::sleep(pause).await;
+        }
+    }
+
+    /// Claims lots with due retries and requests their images, returns how many were claimed.
+    #[instrument(skip(self))]
+    async fn request_due(&self) -> usize {
+        let due = match self
+            .repo
+            .claim_image_retries(P::PROVIDER, DEFAULT_RETRY_BATCH_SIZE)
+            .await
+        {
+            Ok(due) => due,
+            Err(e) => {
+                error!(repo_error = ?e, "claiming image retries failed");
+                return 0;
+            }
+        };
+        if !due.is_empty() {
+            info!(lots = due.len(), "retrying failed lot images");
+        }
+        let claimed = due.len();
+        for id in due {
+            self.response_sender
+                .send(id)
+                .await
+                .expect("tokio mpsc channel - retry receiver is gone");
+        }
+        claimed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::io::copart::{Copart, ImageSyncStatus};
+    use common::persistence::models::copart::{NewLotImage, NewLotImages};
+    use common::persistence::testing::{fixtures, TestDb};
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_due_lots_are_requested_once() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        db.seed(vec![fixtures::lot_vehicle(1), fixtures::lot_vehicle(2)])
+            .await?;
+        let repo = db.repo();
+        for ln in [1, 2] {
+            repo.upsert_lot_images(
+                LotId::copart(ln),
+                NewLotImages(vec![NewLotImage {
+                    variants: vec![],
+                    sync_status: ImageSyncStatus::Failed.as_str().to_string(),
+                    sync_error: Some("upload".to_string()),
+                    ..fixtures::lot_image(ln, 1)
+                }]),
+            )
+            .await?;
+        }
+        // only the failed image of the first lot is due
+        db.expire_retries(&[1]).await?;
+
+        let (queue, mut receiver) = ImageRetryQueue::<Copart, _>::new(repo);
+        assert_eq!(queue.request_due().await, 1);
+        assert_eq!(receiver.try_recv()?, LotId::copart(1));
+        assert_eq!(queue.request_due().await, 0);
+        assert!(receiver.try_recv().is_err());
+        Ok(())
+    }
+}
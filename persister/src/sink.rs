@@ -0,0 +1,496 @@
+use common::io::error::GeneralError;
+use common::io::provider::{
+    AuctionResultResponse, IngestMsg, IngestResponse, LotId, LotSearchResponse, LotVehicle,
+    Provider, SyncedImagesResponse,
+};
+use common::persistence::models::copart::{
+    NewAuctionResult, NewLotImage, NewLotImages, NewLotPriceSnapshot, NewLotVehicles,
+};
+use common::persistence::repo::{LotImageRepo, LotPriceRepo, LotVehicleRepo, TakedownRepo};
+use common::vin;
+use futures::StreamExt;
+use std::sync::Arc;
+use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::{oneshot, Semaphore};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, instrument};
+
+pub struct ExternalSignaling {
+    pub cmd_sender: Sender<IngestMsg>,
+    /// Newly inserted lots of every provider
+    pub response_receiver: Receiver<LotId>,
+}
+
+pub struct PersisterSink<R: LotVehicleRepo + LotImageRepo + LotPriceRepo + TakedownRepo> {
+    cmd_receiver: Receiver<IngestMsg>,
+    msg_handler: Arc<SingleMsgHandler<R>>,
+    usage_permit: Arc<Semaphore>,
+}
+
+struct SingleMsgHandler<R: LotVehicleRepo + LotImageRepo + LotPriceRepo + TakedownRepo> {
+    repo: R,
+    response_sender: Sender<LotId>,
+}
+
+impl<R: LotVehicleRepo + LotImageRepo + LotPriceRepo + TakedownRepo> SingleMsgHandler<R> {
+    async fn handle_message(&self, msg: IngestMsg) {
+        let provider = msg.provider;
+        match msg.response {
+            IngestResponse::LotSearch(resp) => self.handle_lot_search(provider, resp).await,
+            IngestResponse::SyncedImages(resp) => self.handle_synced_images(provider, resp).await,
+            IngestResponse::AuctionResult(resp) => self.handle_auction_result(provider, resp).await,
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn handle_lot_search(
+        &self,
+        provider: Provider,
+        incoming_msg: Result<LotSearchResponse, GeneralError>,
+    ) {
+        match incoming_msg {
+            Ok(mut lsr) => {
+                if let Err(e) = self.drop_blocked(&mut lsr.response.0).await {
+                    // never ingest what may be a taken down vin
+                    error!(persister_error = ?e, "vin blocklist check failed");
+                    return;
+                }
+                self.upsert_lot_vehicles(provider, lsr).await
+            }
+            Err(e) => {
+                error!(producer_error = ?e, "lot search response in an error")
+            }
+        }
+    }
+
+    /// Removes listings of vins with an active takedown, so they are neither stored
+    /// nor get their images synced.
+    async fn drop_blocked(&self, listings: &mut Vec<LotVehicle>) -> Result<(), GeneralError> {
+        let vins = listings
+            .iter()
+            .filter_map(|l| l.vin.as_deref().map(vin::normalize))
+            .collect();
+        let blocked = self.repo.blocked_vins(vins).await?;
+        if blocked.is_empty() {
+            return Ok(());
+        }
+        listings.retain(|l| {
+            !l.vin
+                .as_deref()
+                .is_some_and(|v| blocked.contains(&vin::normalize(v)))
+        });
+        debug!("dropped listings of `{}` blocked vins", blocked.len());
+        Ok(())
+    }
+
+    /// Snapshots changed prices of all lots and requests images of newly inserted lots only.
+    async fn upsert_lot_vehicles(&self, provider: Provider, lsr: LotSearchResponse) {
+        let snapshots = NewLotPriceSnapshot::from_listings(provider, &lsr.response);
+        let new_lot_vehicles = NewLotVehicles::new(provider, lsr.response);
+        match self.repo.upsert_lot_vehicles(new_lot_vehicles).await {
+            Ok(ids) => {
+                if let Err(e) = self.repo.save_price_snapshots(snapshots).await {
+                    error!(persister_error = ?e, "save price snapshots failed")
+                }
+                futures::stream::iter(ids)
+                    .for_each(|id| async move {
+                        let _ = self.response_sender.send(id).await;
+                    })
+                    .await
+            }
+            Err(e) => error!(persister_error = ?e, "upsert lot vehicles failed"),
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn handle_synced_images(
+        &self,
+        provider: Provider,
+        incoming_msg: Result<SyncedImagesResponse, GeneralError>,
+    ) {
+        match incoming_msg {
+            Ok(synced_resp) => {
+                let new_lot_images: NewLotImages = NewLotImages(
+                    synced_resp
+                        .response
+                        .0
+                        .into_iter()
+                        .map(|i| NewLotImage {
+                            sequence_number: i.sequence_number,
+                            image_type: i.image_type,
+                            source: provider.to_string(),
+                            lot_vehicle_number: synced_resp.lot_number,
+                            source_url: i.source_url,
+                            perceptual_hash: i.perceptual_hash,
+                            source_etag: i.source_etag,
+                            source_size: i.source_size,
+                            sync_status: i.status.as_str().to_string(),
+                            sync_error: i.error.map(|e| e.as_str().to_string()),
+                            variants: i.variants.into_iter().map(Into::into).collect(),
+                        })
+                        .collect(),
+                );
+
+                let id = LotId::new(provider, synced_resp.lot_number);
+                match self.repo.lot_takedown(id).await {
+                    Ok(None) => {}
+                    Ok(Some(takedown_id)) => {
+                        // requested before the takedown, the next erasure deletes the
+                        // uploaded objects
+                        let keys = new_lot_images
+                            .0
+                            .into_iter()
+                            .flat_map(|image| image.variants.into_iter().map(|v| v.bucket_key))
+                            .collect();
+                        debug!("dropped synced images of a blocked lot");
+                        if let Err(e) = self.repo.queue_erasure(takedown_id, keys).await {
+                            error!(persister_error = ?e, "queue erasure failed")
+                        }
+                        return;
+                    }
+                    Err(e) => {
+                        error!(persister_error = ?e, "vin blocklist check failed");
+                        return;
+                    }
+                }
+
+                if let Err(e) = self.repo.upsert_lot_images(id, new_lot_images).await {
+                    error!(persister_error = ?e, "upsert lot images failed")
+                }
+            }
+            Err(e) => error!(producer_error = ?e, "lot image blobs response in an error"),
+        }
+    }
+
+    #[instrument(skip(self))]
+    async fn handle_auction_result(
+        &self,
+        provider: Provider,
+        incoming_msg: Result<AuctionResultResponse, GeneralError>,
+    ) {
+        match incoming_msg {
+            Ok(result) => {
+                match self
+                    .repo
+                    .lot_takedown(LotId::new(provider, result.lot_number))
+                    .await
+                {
+                    Ok(None) => {}
+                    Ok(Some(_)) => {
+                        debug!("dropped the auction result of a blocked lot");
+                        return;
+                    }
+                    Err(e) => {
+                        error!(persister_error = ?e, "vin blocklist check failed");
+                        return;
+                    }
+                }
+                let new_auction_result = NewAuctionResult::new(provider, result);
+                if let Err(e) = self.repo.save_auction_result(new_auction_result).await {
+                    error!(persister_error = ?e, "save auction result failed")
+                }
+            }
+            Err(e) => error!(producer_error = ?e, "auction result response in an error"),
+        }
+    }
+}
+
+impl<R> PersisterSink<R>
+where
+    R: LotVehicleRepo + LotImageRepo + LotPriceRepo + TakedownRepo + 'static,
+{
+    pub fn new(repo: R) -> (Self, ExternalSignaling) {
+        let (cmd_sender, cmd_receiver) = tokio::sync::mpsc::channel(32);
+        let (response_sender, response_receiver) = tokio::sync::mpsc::channel(32);
+        let external_signaling = ExternalSignaling {
+            cmd_sender,
+            response_receiver,
+        };
+        let msg_handler = Arc::new(SingleMsgHandler {
+            response_sender,
+            repo,
+        });
+        let sink = Self {
+            msg_handler,
+            cmd_receiver,
+            usage_permit: Arc::new(Semaphore::new(32)),
+        };
+        (sink, external_signaling)
+    }
+
+    pub fn run(self, cancellation_token: CancellationToken) -> oneshot::Receiver<()> {
+        let join_handle = tokio::spawn(self.run_blocking());
+
+        let (done_sender, done) = oneshot::channel();
+        tokio::spawn(async move {
+            cancellation_token.cancelled().await;
+            join_handle.abort();
+            let _ = done_sender.send(());
+        });
+        done
+    }
+
+    pub async fn run_blocking(mut self) {
+        while let Some(msg) = self.cmd_receiver.recv().await {
+            let _permit = unsafe {
+                self.usage_permit
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .unwrap_unchecked()
+            };
+
+            tokio::spawn({
+                let handler = Arc::clone(&self.msg_handler);
+                async move {
+                    handler.handle_message(msg).await;
+                    drop(_permit);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::io::provider::{
+        ImageSyncStatus, LotNumber, LotPrices, LotVehicleVector, SyncedImageVariant, SyncedImages,
+        SyncedImagesVector,
+    };
+    use common::persistence::models::takedown::TakedownState;
+    use common::persistence::testing::{fixtures, TestDb};
+    use std::time::Duration;
+
+    fn listing(ln: LotNumber, odometer: f64) -> LotVehicle {
+        LotVehicle {
+            lot_number: ln,
+            make: "TOYOTA".to_string(),
+            model: "CAMRY".to_string(),
+            year: 2018,
+            vehicle_type: "V".to_string(),
+            vin: Some(format!("4T1B11HK5JU{ln:06}")),
+            estimated_retail_value: 10000.0,
+            estimated_repair_cost: 2000.0,
+            odometer,
+            odometer_status: None,
+            engine_name: None,
+            engine_cylinders: None,
+            currency: "USD".to_string(),
+            sale_date: None,
+            main_damage: "FRONT END".to_string(),
+            other_damage: None,
+            country: "USA".to_string(),
+            state: "TX".to_string(),
+            transmission: None,
+            color: "SILVER".to_string(),
+            fuel_type: None,
+            drive_type: None,
+            keys_status: None,
+            trim: None,
+            yard: None,
+            prices: None,
+        }
+    }
+
+    fn lot_search(provider: Provider, listings: Vec<LotVehicle>) -> IngestMsg {
+        IngestMsg {
+            provider,
+            response: IngestResponse::LotSearch(Ok(LotSearchResponse {
+                page_number: 0,
+                response: LotVehicleVector(listings),
+            })),
+        }
+    }
+
+    async fn inserted_lots(receiver: &mut Receiver<LotId>) -> Vec<LotId> {
+        let mut ids = vec![];
+        while let Ok(Some(id)) =
+            tokio::time::timeout(Duration::from_millis(500), receiver.recv()).await
+        {
+            ids.push(id);
+        }
+        ids.sort();
+        ids
+    }
+
+    fn copart(lns: &[LotNumber]) -> Vec<LotId> {
+        lns.iter().copied().map(LotId::copart).collect()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_lot_images_requested_for_new_lots_only() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let db = TestDb::start().await?;
+        let (sink, mut sig) = PersisterSink::new(db.repo());
+        tokio::spawn(sink.run_blocking());
+
+        sig.cmd_sender
+            .send(lot_search(
+                Provider::Copart,
+                vec![listing(1, 100.0), listing(2, 100.0), listing(2, 200.0)],
+            ))
+            .await?;
+        assert_eq!(
+            inserted_lots(&mut sig.response_receiver).await,
+            copart(&[1, 2])
+        );
+
+        // re-seen lots, changed or not, do not trigger another image sync
+        sig.cmd_sender
+            .send(lot_search(
+                Provider::Copart,
+                vec![listing(1, 100.0), listing(2, 300.0), listing(3, 100.0)],
+            ))
+            .await?;
+        assert_eq!(
+            inserted_lots(&mut sig.response_receiver).await,
+            copart(&[3])
+        );
+
+        // the same lot number at another provider is a new lot
+        sig.cmd_sender
+            .send(lot_search(Provider::Stub, vec![listing(1, 100.0)]))
+            .await?;
+        assert_eq!(
+            inserted_lots(&mut sig.response_receiver).await,
+            vec![LotId::new(Provider::Stub, 1)]
+        );
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_blocked_vins_not_ingested() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        let repo = db.repo();
+        let blocked_vin = listing(2, 100.0).vin.unwrap();
+        let takedown = repo
+            .submit_takedown(fixtures::vin_takedown(&blocked_vin))
+            .await?;
+        for state in [TakedownState::Verified, TakedownState::Active] {
+            repo.transition_takedown(takedown.id, state, "support", None)
+                .await?;
+        }
+
+        let (sink, mut sig) = PersisterSink::new(db.repo());
+        tokio::spawn(sink.run_blocking());
+
+        // listings may carry the vin in lowercase
+        sig.cmd_sender
+            .send(lot_search(
+                Provider::Copart,
+                vec![
+                    listing(1, 100.0),
+                    LotVehicle {
+                        vin: Some(blocked_vin.to_lowercase()),
+                        ..listing(2, 100.0)
+                    },
+                ],
+            ))
+            .await?;
+        assert_eq!(
+            inserted_lots(&mut sig.response_receiver).await,
+            copart(&[1])
+        );
+        assert!(repo.lot_vehicle_by_id(LotId::copart(1)).await?.is_some());
+        assert!(repo.lot_vehicle_by_id(LotId::copart(2)).await?.is_none());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_blocked_lots_responses_dropped() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        db.seed(vec![fixtures::lot_vehicle(1)]).await?;
+        let repo = db.repo();
+        let vin = fixtures::lot_vehicle(1).vin.unwrap();
+        let takedown = repo.submit_takedown(fixtures::vin_takedown(&vin)).await?;
+        for state in [TakedownState::Verified, TakedownState::Active] {
+            repo.transition_takedown(takedown.id, state, "support", None)
+                .await?;
+        }
+
+        let (sink, sig) = PersisterSink::new(db.repo());
+        tokio::spawn(sink.run_blocking());
+
+        // requested before the takedown, responses arrive after it
+        sig.cmd_sender
+            .send(IngestMsg {
+                provider: Provider::Copart,
+                response: IngestResponse::SyncedImages(Ok(SyncedImagesResponse {
+                    lot_number: 1,
+                    response: SyncedImagesVector(vec![SyncedImages {
+                        source_url: Some("https://cs.copart.com/1/1/high_res.jpg".to_string()),
+                        sequence_number: 1,
+                        image_type: "IMAGE".to_string(),
+                        variants: vec![SyncedImageVariant {
+                            name: "w800".to_string(),
+                            format: "webp".to_string(),
+                            mime_type: "image/webp".to_string(),
+                            width: 800,
+                            height: 533,
+                            size: 1024,
+                            bucket_key: "sha256/a".to_string(),
+                            sha256: "a".to_string(),
+                        }],
+                        perceptual_hash: None,
+                        source_etag: None,
+                        source_size: None,
+                        status: ImageSyncStatus::Synced,
+                        error: None,
+                    }]),
+                    skipped: 0,
+                    fetched: 1,
+                })),
+            })
+            .await?;
+        sig.cmd_sender
+            .send(IngestMsg {
+                provider: Provider::Copart,
+                response: IngestResponse::AuctionResult(Ok(AuctionResultResponse {
+                    lot_number: 1,
+                    final_bid: 4200.0,
+                    buyer_country: None,
+                    buyer_state: None,
+                    min_met: true,
+                    sold_at: None,
+                })),
+            })
+            .await?;
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        assert!(repo.lot_images(LotId::copart(1)).await?.is_empty());
+        let details = repo.lot_vehicle_by_id(LotId::copart(1)).await?.unwrap();
+        assert!(details.auction_result.is_none());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_price_snapshots_of_seen_lots() -> Result<(), Box<dyn std::error::Error>> {
+        let db = TestDb::start().await?;
+        let (sink, mut sig) = PersisterSink::new(db.repo());
+        tokio::spawn(sink.run_blocking());
+        let priced = |current_bid| LotVehicle {
+            prices: Some(LotPrices {
+                current_bid,
+                buy_today_bid: 0.0,
+                high_bid: current_bid,
+                sale_status: "PURE SALE".to_string(),
+            }),
+            ..listing(1, 100.0)
+        };
+
+        // every sighting is snapshotted unless the prices did not change
+        for current_bid in [100.0, 100.0, 250.0] {
+            sig.cmd_sender
+                .send(lot_search(Provider::Copart, vec![priced(current_bid)]))
+                .await?;
+            inserted_lots(&mut sig.response_receiver).await;
+        }
+        let prices = db.repo().price_snapshots(LotId::copart(1)).await?.unwrap();
+        assert_eq!(
+            prices.iter().map(|p| p.current_bid).collect::<Vec<_>>(),
+            vec![100.0, 250.0]
+        );
+        Ok(())
+    }
+}
@@ -1,22 +1,20 @@
 use crate::proxy::ProxyChainServer;
-use common::logging::setup_logging;
-use std::sync::Arc;
-use tracing::info;
+use common::service::{Service, Stage};
+
+common::jemalloc!();
 
 mod proxy;
 
 #[tokio::main]
 async fn main() {
-    setup_logging("proxy");
-    info!("starting app");
-
-    let proxy_server_notifier = Arc::new(tokio::sync::Notify::new());
-    ProxyChainServer.run(8100, Arc::clone(&proxy_server_notifier));
-    info!("app started");
-    proxy_server_notifier.notified().await;
+    let mut service = Service::new("proxy").admin("0.0.0.0:6974");
 
-    tokio::signal::ctrl_c()
+    let token = service.token();
+    let proxy_done = ProxyChainServer
+        .run(8100, token.clone())
         .await
-        .expect("failed to listen for ctrl c event");
-    info!("exiting");
+        .expect("failed to start proxy chain server");
+    service.register_running("proxy server", Stage::Ingress, token, proxy_done);
+
+    service.run().await;
 }
@@ -9,9 +9,10 @@ use hyper::upgrade::Upgraded;
 use hyper::{Method, Request, Response};
 use hyper_util::rt::TokioIo;
 use std::net::SocketAddr;
-use std::sync::Arc;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info};
 
 type ServerBuilder = hyper::server::conn::http1::Builder;
@@ -35,25 +36,34 @@ pub trait ProxyChain {
 }
 
 impl ProxyChainServer {
-    pub fn run(self, port: u16, notifier: Arc<tokio::sync::Notify>) {
-        tokio::spawn(async move {
-            self.main_loop(port, notifier)
-                .await
-                .expect("Main loop failed");
-        });
-    }
-
-    async fn main_loop(
-        &self,
+    /// Binds the listener up front so a taken port fails the startup,
+    /// then accepts connections until the token is cancelled.
+    pub async fn run(
+        self,
         port: u16,
-        notifier: Arc<tokio::sync::Notify>,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+        cancellation_token: CancellationToken,
+    ) -> std::io::Result<oneshot::Receiver<()>> {
         let addr = SocketAddr::from(([0, 0, 0, 0], port));
-
         let listener = TcpListener::bind(addr).await?;
-        notifier.notify_waiters();
         info!("Proxy chain listening on: {}", addr);
 
+        let (done_sender, done) = oneshot::channel();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = cancellation_token.cancelled() => {
+                    info!("Proxy chain stopped accepting connections");
+                }
+                Err(e) = self.main_loop(listener) => {
+                    error!("Main loop failed: {}", e);
+                }
+            }
+            let _ = done_sender.send(());
+        });
+
+        Ok(done)
+    }
+
+    async fn main_loop(&self, listener: TcpListener) -> std::io::Result<()> {
         loop {
             let (stream, _) = listener.accept().await?;
             let io = TokioIo::new(stream);
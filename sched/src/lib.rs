@@ -3,7 +3,10 @@ pub mod copart;
 use async_trait::async_trait;
 use chrono::Utc;
 use std::collections::HashMap;
-use tracing::debug;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info};
 
 pub struct Scheduler {
     pub tasks: Vec<ScheduledTask>,
@@ -34,7 +37,29 @@ pub enum ScheduledTask {
 }
 
 impl Scheduler {
-    pub fn run_task(task: ScheduledTask, opts: Option<HashMap<String, String>>) {
+    /// Spawns every task and aborts them once the token is cancelled.
+    pub fn run(self, cancellation_token: CancellationToken) -> oneshot::Receiver<()> {
+        let (done_sender, done) = oneshot::channel();
+        let handles = self
+            .tasks
+            .into_iter()
+            .map(|task| Self::run_task(task, None))
+            .collect::<Vec<_>>();
+
+        tokio::spawn(async move {
+            cancellation_token.cancelled().await;
+            info!("aborting `{}` scheduled tasks", handles.len());
+            for handle in handles {
+                handle.abort();
+                let _ = handle.await;
+            }
+            let _ = done_sender.send(());
+        });
+
+        done
+    }
+
+    pub fn run_task(task: ScheduledTask, opts: Option<HashMap<String, String>>) -> JoinHandle<()> {
         match task {
             ScheduledTask::Interval { task, interval } => {
                 Self::spawn_interval_task(task, interval, opts)
@@ -50,7 +75,7 @@ impl Scheduler {
         task: Box<dyn Task>,
         interval: tokio::time::Duration,
         opts: Option<HashMap<String, String>>,
-    ) {
+    ) -> JoinHandle<()> {
         tokio::spawn({
             {
                 async move {
@@ -66,14 +91,14 @@ impl Scheduler {
                     }
                 }
             }
-        });
+        })
     }
 
     fn spawn_interval_deferred_task(
         task: Box<dyn Task>,
         interval: tokio::time::Duration,
         opts: Option<HashMap<String, String>>,
-    ) {
+    ) -> JoinHandle<()> {
         tokio::spawn({
             {
                 async move {
@@ -90,14 +115,14 @@ impl Scheduler {
                     }
                 }
             }
-        });
+        })
     }
 
     fn spawn_timed_task(
         task: Box<dyn Task>,
         when: chrono::NaiveDateTime,
         opts: Option<HashMap<String, String>>,
-    ) {
+    ) -> JoinHandle<()> {
         tokio::spawn(async move {
             let now = Utc::now().naive_utc();
             let delay = (when - now).to_std().unwrap_or(std::time::Duration::ZERO);
@@ -108,7 +133,7 @@ impl Scheduler {
                 when,
             );
             task.run(opts.as_ref()).await;
-        });
+        })
     }
 }
 
@@ -129,6 +154,7 @@ mod tests {
     use crate::{ScheduledTask, Scheduler, Task};
     use async_trait::async_trait;
     use std::collections::HashMap;
+    use tokio_util::sync::CancellationToken;
 
     struct NopTask {
         sender: tokio::sync::mpsc::Sender<()>,
@@ -174,4 +200,25 @@ mod tests {
         assert_eq!(rx1.recv().await, Some(()));
         assert_eq!(rx2.recv().await, Some(()));
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_stops_tasks_on_cancel() {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let token = CancellationToken::new();
+
+        let done = Scheduler {
+            tasks: vec![ScheduledTask::Interval {
+                task: Box::new(NopTask { sender: tx }),
+                interval: tokio::time::Duration::from_secs(3),
+            }],
+        }
+        .run(token.clone());
+        assert_eq!(rx.recv().await, Some(()));
+
+        token.cancel();
+        done.await.unwrap();
+
+        // the task owned the only sender, the channel closes once it is aborted
+        assert_eq!(rx.recv().await, None);
+    }
 }
@@ -1,32 +1,26 @@
-use common::logging::setup_logging;
+use common::service::{Service, Stage};
 use sched::copart::{CopartLoginRefreshTask, CopartLotSearchTask};
 use sched::{hours, minutes, ScheduledTask, Scheduler};
-use tracing::info;
+
+common::jemalloc!();
 
 #[tokio::main]
 async fn main() {
-    setup_logging("sched");
-    info!("starting app");
-
-    Scheduler::run_task(
-        ScheduledTask::Interval {
-            task: Box::new(CopartLotSearchTask::default()),
-            interval: hours(4),
-        },
-        None,
-    );
+    let mut service = Service::new("sched").admin("0.0.0.0:6973");
 
-    Scheduler::run_task(
-        ScheduledTask::IntervalDeferred {
-            task: Box::new(CopartLoginRefreshTask::default()),
-            interval: minutes(30),
-        },
-        None,
-    );
+    let scheduler = Scheduler {
+        tasks: vec![
+            ScheduledTask::Interval {
+                task: Box::new(CopartLotSearchTask::default()),
+                interval: hours(4),
+            },
+            ScheduledTask::IntervalDeferred {
+                task: Box::new(CopartLoginRefreshTask::default()),
+                interval: minutes(30),
+            },
+        ],
+    };
+    service.register("scheduler", Stage::Ingress, |token| scheduler.run(token));
 
-    info!("app started");
-    tokio::signal::ctrl_c()
-        .await
-        .expect("failed to listen for ctrl c event");
-    info!("exited");
+    service.run().await;
 }